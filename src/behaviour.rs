@@ -0,0 +1,1131 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use spacetraders_api::models::{Ship, ShipNavStatus, ShipRole};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::cache::{self, UniverseCache};
+use crate::client::SpaceTradersClient;
+use crate::condition::{self, Advice, CONDITION_WARNING};
+use crate::construction;
+use crate::contract::{self, Estimate};
+use crate::events::{self, EventKind};
+use crate::explore;
+use crate::fitting;
+use crate::impact::ImpactModel;
+use crate::ledger;
+use crate::mining::{self, MiningGroups};
+use crate::navigation::fly_to_for;
+use crate::rescue::{self, Mission, Stranding};
+use crate::trade::{self, TradePlan};
+use crate::watch;
+
+/// Automation a ship runs unattended under the daemon.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Subcommand)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Behaviour {
+    /// Fly between waypoints of one system in a loop
+    Patrol {
+        #[arg(required = true)]
+        waypoints: Vec<String>,
+        /// Index of the waypoint the ship is heading for.
+        #[arg(skip)]
+        #[serde(default)]
+        next: usize,
+    },
+    /// Chart waypoints and snapshot unvisited markets and shipyards with a probe
+    Explore {
+        /// Jump on to unexplored systems once this one is done
+        #[arg(long)]
+        #[serde(default)]
+        across_gates: bool,
+        /// Systems the probe has explored, so it doesn't jump back.
+        #[arg(skip)]
+        #[serde(default)]
+        visited: Vec<String>,
+        /// Set once there is nothing left to explore.
+        #[arg(skip)]
+        #[serde(default)]
+        finished: bool,
+    },
+    /// Park at a market and poll its prices
+    Station {
+        waypoint: String,
+        /// Minutes between polls
+        #[arg(long, default_value_t = DEFAULT_POLL_MINUTES)]
+        interval: u64,
+        #[arg(skip)]
+        #[serde(default)]
+        last_poll: Option<DateTime<Utc>>,
+    },
+    /// Extract at an asteroid field and leave full holds to haulers
+    Mine {
+        waypoint: String,
+        /// Set while the hold is full, so the wait is only logged once.
+        #[arg(skip)]
+        #[serde(default)]
+        waiting: bool,
+    },
+    /// Collect cargo from the miners at a field and sell it at the best markets
+    Haul {
+        waypoint: String,
+        /// Set while carrying a load away from the field.
+        #[arg(skip)]
+        #[serde(default)]
+        selling: bool,
+    },
+    /// Buy the materials a construction site needs and deliver them until it is complete
+    Construct {
+        waypoint: String,
+        /// Set once the site is complete.
+        #[arg(skip)]
+        #[serde(default)]
+        finished: bool,
+    },
+    /// Buy the goods an accepted contract wants, deliver them and fulfil it
+    Contract {
+        id: String,
+        /// Set once the contract is fulfilled or past its deadline.
+        #[arg(skip)]
+        #[serde(default)]
+        finished: bool,
+    },
+    /// Trade along a route, buying the mix of goods the optimizer picks at each stop
+    Trade {
+        #[arg(num_args = 2.., required = true)]
+        waypoints: Vec<String>,
+        /// Plan again from fresh prices and run the route again when it is done
+        #[arg(long)]
+        #[serde(default)]
+        repeat: bool,
+        /// Index of the stop the ship is heading for.
+        #[arg(skip)]
+        #[serde(default)]
+        next: usize,
+        /// Made when the ship sets off on the route.
+        #[arg(skip)]
+        #[serde(default)]
+        plan: Option<TradePlan>,
+        /// Set once the route is done, or no mix of goods would pay.
+        #[arg(skip)]
+        #[serde(default)]
+        finished: bool,
+    },
+}
+
+/// Minutes between market polls from a station unless told otherwise.
+pub const DEFAULT_POLL_MINUTES: u64 = 5;
+
+/// Ticks in a row a rescue mission may fail before it is dropped, so the
+/// ships it holds up are freed and a fresh plan can be made.
+const MAX_RESCUE_FAILURES: u32 = 5;
+
+impl fmt::Display for Behaviour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Patrol { waypoints, .. } => write!(f, "patrol {}", waypoints.join(" → ")),
+            Self::Explore {
+                across_gates,
+                finished,
+                ..
+            } => {
+                write!(f, "explore")?;
+                if *across_gates {
+                    write!(f, " across gates")?;
+                }
+                if *finished {
+                    write!(f, " (finished)")?;
+                }
+                Ok(())
+            }
+            Self::Station {
+                waypoint, interval, ..
+            } => write!(f, "station at {} every {} min", waypoint, interval),
+            Self::Mine { waypoint, .. } => write!(f, "mine at {}", waypoint),
+            Self::Haul { waypoint, .. } => write!(f, "haul from {}", waypoint),
+            Self::Construct { waypoint, finished } => {
+                write!(f, "construct {}", waypoint)?;
+                if *finished {
+                    write!(f, " (finished)")?;
+                }
+                Ok(())
+            }
+            Self::Contract { id, finished } => {
+                write!(f, "contract {}", id)?;
+                if *finished {
+                    write!(f, " (finished)")?;
+                }
+                Ok(())
+            }
+            Self::Trade {
+                waypoints,
+                repeat,
+                finished,
+                ..
+            } => {
+                write!(f, "trade {}", waypoints.join(" → "))?;
+                if *repeat {
+                    write!(f, " repeating")?;
+                }
+                if *finished {
+                    write!(f, " (finished)")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Behaviour {
+    /// Short name used to attribute ledger entries.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Patrol { .. } => "patrol",
+            Self::Explore { .. } => "explore",
+            Self::Station { .. } => "station",
+            Self::Mine { .. } => "mine",
+            Self::Haul { .. } => "haul",
+            Self::Construct { .. } => "construct",
+            Self::Contract { .. } => "contract",
+            Self::Trade { .. } => "trade",
+        }
+    }
+
+    /// Contract the behaviour works on, to attribute its ledger entries.
+    pub fn contract(&self) -> Option<&str> {
+        match self {
+            Self::Contract { id, .. } => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Whether the behaviour has run its course and leaves the ship idle.
+    pub fn is_finished(&self) -> bool {
+        match self {
+            Self::Explore { finished, .. }
+            | Self::Construct { finished, .. }
+            | Self::Contract { finished, .. }
+            | Self::Trade { finished, .. } => *finished,
+            Self::Patrol { .. } | Self::Station { .. } | Self::Mine { .. } | Self::Haul { .. } => {
+                false
+            }
+        }
+    }
+
+    /// Checks the behaviour suits the ship before it is assigned.
+    pub fn check(&self, ship: &Ship) -> Result<()> {
+        match self {
+            Self::Patrol { .. } | Self::Station { .. } => Ok(()),
+            // Probes fly without fuel, so the tour never needs a refuel stop
+            Self::Explore { .. } if ship.registration.role != ShipRole::Satellite => {
+                anyhow::bail!("{} is not a probe; explore needs a SATELLITE", ship.symbol)
+            }
+            Self::Explore { .. } => Ok(()),
+            Self::Mine { .. } => {
+                let laser = ship.mounts.iter().any(|mount| {
+                    fitting::symbol_name(&mount.symbol).starts_with("MOUNT_MINING_LASER")
+                });
+                if !laser {
+                    anyhow::bail!("{} has no mining laser", ship.symbol);
+                }
+                Ok(())
+            }
+            Self::Haul { .. }
+            | Self::Construct { .. }
+            | Self::Contract { .. }
+            | Self::Trade { .. }
+                if ship.cargo.capacity == 0 =>
+            {
+                anyhow::bail!("{} has no cargo hold", ship.symbol)
+            }
+            Self::Haul { .. }
+            | Self::Construct { .. }
+            | Self::Contract { .. }
+            | Self::Trade { .. } => Ok(()),
+        }
+    }
+
+    /// Symbols are upper-cased so behaviours can be typed in any case.
+    pub fn normalized(mut self) -> Self {
+        match &mut self {
+            Self::Patrol { waypoints, next } => {
+                for waypoint in waypoints.iter_mut() {
+                    *waypoint = waypoint.to_uppercase();
+                }
+                *next = 0;
+            }
+            Self::Explore {
+                visited, finished, ..
+            } => {
+                visited.clear();
+                *finished = false;
+            }
+            Self::Station {
+                waypoint,
+                last_poll,
+                ..
+            } => {
+                *waypoint = waypoint.to_uppercase();
+                *last_poll = None;
+            }
+            Self::Mine { waypoint, waiting } => {
+                *waypoint = waypoint.to_uppercase();
+                *waiting = false;
+            }
+            Self::Haul { waypoint, selling } => {
+                *waypoint = waypoint.to_uppercase();
+                *selling = false;
+            }
+            Self::Construct { waypoint, finished } => {
+                *waypoint = waypoint.to_uppercase();
+                *finished = false;
+            }
+            // Contract ids are case-sensitive
+            Self::Contract { finished, .. } => *finished = false,
+            Self::Trade {
+                waypoints,
+                next,
+                plan,
+                finished,
+                ..
+            } => {
+                for waypoint in waypoints.iter_mut() {
+                    *waypoint = waypoint.to_uppercase();
+                }
+                *next = 0;
+                *plan = None;
+                *finished = false;
+            }
+        }
+        self
+    }
+
+    /// Waypoints the behaviour will take the ship to after its current
+    /// destination, in order, for drawing planned routes.
+    pub fn planned_stops(&self, ship: &Ship, cache: &UniverseCache) -> Vec<String> {
+        match self {
+            Self::Patrol { waypoints, next } if !waypoints.is_empty() => {
+                // One full loop, back to where it is heading now
+                let start = *next % waypoints.len();
+                let mut stops = waypoints[start..].to_vec();
+                stops.extend_from_slice(&waypoints[..=start]);
+                stops
+            }
+            Self::Patrol { .. } => Vec::new(),
+            Self::Explore {
+                across_gates,
+                finished: false,
+                ..
+            } => explore::planned_tour(cache, ship, *across_gates),
+            Self::Trade {
+                waypoints,
+                next,
+                finished: false,
+                ..
+            } => waypoints.iter().skip(*next).cloned().collect(),
+            Self::Station { waypoint, .. }
+            | Self::Mine { waypoint, .. }
+            | Self::Haul { waypoint, .. }
+            | Self::Construct {
+                waypoint,
+                finished: false,
+            } => vec![waypoint.clone()],
+            // Finished behaviours go nowhere, and a contract's stops depend
+            // on prices the contract isn't fetched for here
+            Self::Explore { .. }
+            | Self::Trade { .. }
+            | Self::Construct { .. }
+            | Self::Contract { .. } => Vec::new(),
+        }
+    }
+
+    /// Issues the ship's next order. Only called for ships that are neither
+    /// in transit nor cooling down.
+    async fn step(
+        &mut self,
+        client: &SpaceTradersClient,
+        ship: &Ship,
+        cache: &mut UniverseCache,
+        fleet: &mut Fleet<'_>,
+    ) -> Result<()> {
+        match self {
+            Self::Mine { waypoint, waiting } => {
+                mining::mine(client, ship, waypoint, &fleet.groups, waiting).await
+            }
+            Self::Haul { waypoint, selling } => {
+                mining::haul(
+                    client,
+                    ship,
+                    cache,
+                    fleet.ships,
+                    waypoint,
+                    &mut fleet.groups,
+                    &fleet.model,
+                    selling,
+                )
+                .await
+            }
+            Self::Construct { finished: true, .. } => Ok(()),
+            Self::Construct { waypoint, finished } => {
+                if !construction::step(client, ship, cache, waypoint).await? {
+                    *finished = true;
+                    events::record(
+                        log::Level::Info,
+                        EventKind::Log,
+                        format!("Construction of {} is complete", waypoint),
+                    );
+                }
+                Ok(())
+            }
+            Self::Contract { finished: true, .. } => Ok(()),
+            Self::Contract { id, finished } => {
+                if !contract::step(client, ship, cache, id).await? {
+                    *finished = true;
+                }
+                Ok(())
+            }
+            Self::Trade { finished: true, .. } => Ok(()),
+            Self::Trade {
+                waypoints,
+                repeat,
+                next,
+                plan,
+                finished,
+            } => {
+                let current = match plan {
+                    Some(current) => current,
+                    None => {
+                        let credits = client.get_my_agent().await?.credits;
+                        let free = ship.cargo.capacity - ship.cargo.units;
+                        let model = fleet.model.get_or_init(ImpactModel::load);
+                        let made = trade::plan(cache, model, waypoints, free, credits);
+                        if made.lots.is_empty() {
+                            *finished = true;
+                            events::record(
+                                log::Level::Warn,
+                                EventKind::Log,
+                                format!(
+                                    "{} finds nothing worth trading along {}",
+                                    ship.symbol,
+                                    waypoints.join(" → ")
+                                ),
+                            );
+                            return Ok(());
+                        }
+                        events::record(
+                            log::Level::Info,
+                            EventKind::Log,
+                            format!(
+                                "{} trade plan: {} lots, expected profit {}",
+                                ship.symbol,
+                                made.lots.len(),
+                                made.profit()
+                            ),
+                        );
+                        plan.insert(made)
+                    }
+                };
+                let model = fleet.model.get_or_init(ImpactModel::load);
+                if trade::step(client, ship, cache, model, current, next).await? {
+                    events::record(
+                        log::Level::Info,
+                        EventKind::Log,
+                        format!("{} finished its trade run", ship.symbol),
+                    );
+                    if *repeat {
+                        *next = 0;
+                        *plan = None;
+                    } else {
+                        *finished = true;
+                    }
+                }
+                Ok(())
+            }
+            Self::Station {
+                waypoint,
+                interval,
+                last_poll,
+            } => {
+                if ship.nav.waypoint_symbol != *waypoint {
+                    return fly_to_for(client, ship, waypoint, "to its station").await;
+                }
+                let due = last_poll.is_none_or(|polled| {
+                    Utc::now() - polled >= Duration::minutes(*interval as i64)
+                });
+                if ship.nav.status != ShipNavStatus::InTransit && due {
+                    let market = client
+                        .get_market(cache::system_symbol(waypoint), waypoint)
+                        .await?;
+                    cache.markets.insert(waypoint.clone(), market);
+                    cache.save()?;
+                    *last_poll = Some(Utc::now());
+                }
+                Ok(())
+            }
+            Self::Explore { finished: true, .. } => Ok(()),
+            Self::Explore {
+                across_gates,
+                visited,
+                finished,
+            } => {
+                if !explore::step(client, ship, cache, *across_gates, visited).await? {
+                    *finished = true;
+                    events::record(
+                        log::Level::Info,
+                        EventKind::Log,
+                        format!("{} has nothing left to explore", ship.symbol),
+                    );
+                }
+                Ok(())
+            }
+            Self::Patrol { waypoints, next } => {
+                if waypoints.is_empty() {
+                    return Ok(());
+                }
+                if waypoints[*next % waypoints.len()] == ship.nav.waypoint_symbol {
+                    *next = (*next + 1) % waypoints.len();
+                }
+                let target = &waypoints[*next % waypoints.len()];
+                if *target == ship.nav.waypoint_symbol {
+                    return Ok(());
+                }
+                fly_to_for(client, ship, target, "on patrol").await
+            }
+        }
+    }
+}
+
+/// What a behaviour can see of the rest of the fleet during a tick.
+struct Fleet<'a> {
+    ships: &'a [Ship],
+    groups: MiningGroups,
+    /// Learned on first use, so the price history is read at most once a tick.
+    model: OnceLock<ImpactModel>,
+}
+
+impl<'a> Fleet<'a> {
+    fn new(ships: &'a [Ship], assignments: &BTreeMap<String, Behaviour>) -> Self {
+        let mut groups = MiningGroups::default();
+        for (ship, behaviour) in assignments {
+            match behaviour {
+                Behaviour::Mine { waypoint, .. } => {
+                    groups.miners.entry(waypoint.clone()).or_default()
+                }
+                Behaviour::Haul { waypoint, .. } => {
+                    groups.haulers.entry(waypoint.clone()).or_default()
+                }
+                _ => continue,
+            }
+            .push(ship.clone());
+        }
+        Self {
+            ships,
+            groups,
+            model: OnceLock::new(),
+        }
+    }
+}
+
+/// Whether a ship can take orders right now.
+pub fn is_idle(ship: &Ship, now: DateTime<Utc>) -> bool {
+    if ship.nav.status == ShipNavStatus::InTransit {
+        let arrived = DateTime::parse_from_rfc3339(&ship.nav.route.arrival)
+            .map(|arrival| arrival <= now)
+            .unwrap_or(true);
+        if !arrived {
+            return false;
+        }
+    }
+    ship.cooldown
+        .expiration
+        .as_deref()
+        .and_then(|expiration| DateTime::parse_from_rfc3339(expiration).ok())
+        .is_none_or(|expiration| expiration <= now)
+}
+
+/// Applies the entries `after` added, changed or removed relative to
+/// `before`, unless `live` has moved on from `before` for that key too.
+fn merge_entries<V: PartialEq>(
+    live: &mut BTreeMap<String, V>,
+    before: &BTreeMap<String, V>,
+    mut after: BTreeMap<String, V>,
+) {
+    for (key, old) in before {
+        if live.get(key) != Some(old) {
+            after.remove(key);
+        } else if !after.contains_key(key) {
+            live.remove(key);
+        }
+    }
+    for (key, value) in after {
+        if before.get(&key).is_some() || !live.contains_key(&key) {
+            live.insert(key, value);
+        }
+    }
+}
+
+/// Behaviours assigned to ships, persisted so they survive daemon restarts.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scheduler {
+    /// Assigned behaviour keyed by ship symbol.
+    pub assignments: BTreeMap<String, Behaviour>,
+    /// Whether worn ships are repaired when the advisor says it pays off.
+    pub auto_repair: bool,
+    /// Whether open contracts the evaluator rates profitable are accepted.
+    pub auto_contracts: bool,
+    /// Whether stranded ships are sent drifting or rescued without asking.
+    pub auto_rescue: bool,
+    /// Missions getting stranded ships back to fuel, keyed by the stranded
+    /// ship. Both it and any rescuer pause their behaviours meanwhile.
+    pub rescues: BTreeMap<String, Mission>,
+    /// Failures in a row of each rescue mission, keyed by the stranded ship.
+    #[serde(skip)]
+    rescue_failures: HashMap<String, u32>,
+    /// Ships known to be stranded, so each is only logged once.
+    #[serde(skip)]
+    stranded: Vec<String>,
+    /// When open contracts were last evaluated.
+    #[serde(skip)]
+    contracts_checked: Option<DateTime<Utc>>,
+    /// Contracts already turned down, so each is only logged once.
+    #[serde(skip)]
+    declined_contracts: Vec<String>,
+    /// Waypoint where each ship's repair options were last checked, so a
+    /// ship is only quoted once per visit.
+    #[serde(skip)]
+    repair_checked: HashMap<String, String>,
+    /// Minutes between polls when probes are parked at markets to keep
+    /// prices fresh; `None` leaves probes alone.
+    pub market_watch: Option<u64>,
+    /// Probes the market watch last placed, so buying or losing one
+    /// triggers a new plan.
+    watched: Vec<String>,
+}
+
+impl Scheduler {
+    fn path() -> PathBuf {
+        cache::data_dir().join("behaviours.json")
+    }
+
+    /// Loads saved assignments, starting empty if they are missing or unreadable.
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::create_dir_all(cache::data_dir())?;
+        fs::write(Self::path(), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Folds back the copy a tick worked on, which started out as `before`.
+    /// Whatever a control request changed meanwhile wins over the tick.
+    pub fn merge_tick(&mut self, before: &Scheduler, after: Scheduler) -> Result<()> {
+        self.merge(before, after);
+        self.save()
+    }
+
+    fn merge(&mut self, before: &Scheduler, after: Scheduler) {
+        merge_entries(
+            &mut self.assignments,
+            &before.assignments,
+            after.assignments,
+        );
+        merge_entries(&mut self.rescues, &before.rescues, after.rescues);
+        if self.contracts_checked == before.contracts_checked {
+            self.contracts_checked = after.contracts_checked;
+        }
+        if self.watched == before.watched {
+            self.watched = after.watched;
+        }
+        self.stranded = after.stranded;
+        self.rescue_failures = after.rescue_failures;
+        self.declined_contracts = after.declined_contracts;
+        self.repair_checked = after.repair_checked;
+    }
+
+    pub fn assign(&mut self, ship: String, behaviour: Behaviour) -> Result<()> {
+        self.assignments.insert(ship, behaviour.normalized());
+        self.save()
+    }
+
+    /// Removes a ship's behaviour, returning whether it had one.
+    pub fn unassign(&mut self, ship: &str) -> Result<bool> {
+        let removed = self.assignments.remove(ship).is_some();
+        self.save()?;
+        Ok(removed)
+    }
+
+    pub fn set_auto_repair(&mut self, enabled: bool) -> Result<()> {
+        self.auto_repair = enabled;
+        self.save()
+    }
+
+    pub fn set_auto_contracts(&mut self, enabled: bool) -> Result<()> {
+        self.auto_contracts = enabled;
+        self.contracts_checked = None;
+        self.save()
+    }
+
+    pub fn set_auto_rescue(&mut self, enabled: bool) -> Result<()> {
+        self.auto_rescue = enabled;
+        self.save()
+    }
+
+    /// Ships taken up by rescue missions, stranded or helping.
+    fn rescue_ships(&self) -> Vec<String> {
+        self.rescues
+            .iter()
+            .flat_map(|(ship, mission)| [Some(ship.as_str()), mission.rescuer()])
+            .flatten()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Plans and starts a mission to get `ship` back to fuel.
+    pub fn start_rescue(
+        &mut self,
+        ships: &[Ship],
+        cache: &UniverseCache,
+        ship: &str,
+    ) -> Result<Stranding> {
+        if let Some(mission) = self.rescues.get(ship) {
+            anyhow::bail!("{} is already being helped: {}", ship, mission);
+        }
+        let Some(found) = ships.iter().find(|s| s.symbol == ship) else {
+            anyhow::bail!("no ship named {}", ship);
+        };
+        let Some(stranding) = rescue::assess(cache, ships, found, &self.rescue_ships()) else {
+            anyhow::bail!(
+                "{} is not stranded, or no cached market in {} sells fuel",
+                ship,
+                found.nav.system_symbol
+            );
+        };
+        let Some(mission) = stranding.mission.clone() else {
+            anyhow::bail!("{} has no fuel to drift and no ship can bring it any", ship);
+        };
+        events::record(
+            log::Level::Info,
+            EventKind::Log,
+            format!("Rescuing {}: {}", ship, mission),
+        );
+        self.rescues.insert(ship.to_string(), mission);
+        self.save()?;
+        Ok(stranding)
+    }
+
+    /// Calls off the mission helping `ship`, returning whether there was one.
+    /// Both ships go back to their behaviours.
+    pub fn cancel_rescue(&mut self, ship: &str) -> Result<bool> {
+        let removed = self.rescues.remove(ship).is_some();
+        self.rescue_failures.remove(ship);
+        self.save()?;
+        Ok(removed)
+    }
+
+    /// Drops a rescue mission that cannot go on. The ship is forgotten as
+    /// stranded, so auto-rescue plans afresh if it still is.
+    fn abandon_rescue(&mut self, ship: &str, reason: String) {
+        events::record(
+            log::Level::Warn,
+            EventKind::Log,
+            format!("Gave up rescuing {}: {}", ship, reason),
+        );
+        self.rescues.remove(ship);
+        self.rescue_failures.remove(ship);
+        self.stranded.retain(|stranded| stranded != ship);
+    }
+
+    /// Logs ships that newly cannot reach fuel and, with auto-rescue on,
+    /// starts a mission for each.
+    fn check_stranded(&mut self, ships: &[Ship], cache: &UniverseCache, now: DateTime<Utc>) {
+        let busy = self.rescue_ships();
+        let strandings: Vec<Stranding> = ships
+            .iter()
+            .filter(|ship| is_idle(ship, now) && !busy.contains(&ship.symbol))
+            .filter_map(|ship| rescue::assess(cache, ships, ship, &busy))
+            .collect();
+        for stranding in &strandings {
+            if self.stranded.contains(&stranding.ship) {
+                continue;
+            }
+            events::record(
+                log::Level::Warn,
+                EventKind::Log,
+                format!(
+                    "{} is stranded at {} with {} fuel, {:.0} from fuel at {}",
+                    stranding.ship,
+                    stranding.waypoint,
+                    stranding.fuel,
+                    stranding.distance,
+                    stranding.market
+                ),
+            );
+            if self.auto_rescue {
+                if let Err(e) = self.start_rescue(ships, cache, &stranding.ship) {
+                    events::record_error(&format!("{} rescue", stranding.ship), &e);
+                }
+            }
+        }
+        self.stranded = strandings.into_iter().map(|s| s.ship).collect();
+    }
+
+    /// Turns the market watch on with a poll interval in minutes, or off,
+    /// which releases the probes it parked.
+    pub fn set_market_watch(&mut self, interval: Option<u64>) -> Result<()> {
+        self.market_watch = interval;
+        if interval.is_none() {
+            for probe in &self.watched {
+                if let Some(Behaviour::Station { .. }) = self.assignments.get(probe) {
+                    self.assignments.remove(probe);
+                }
+            }
+        }
+        // Plan afresh on the next tick, with the new interval
+        self.watched.clear();
+        self.save()
+    }
+
+    /// Parks idle probes at the most valuable markets whenever the set of
+    /// probes changes. Probes running another behaviour are left alone.
+    fn plan_market_watch(&mut self, ships: &[Ship], cache: &UniverseCache, interval: u64) {
+        let probes: Vec<&Ship> = ships
+            .iter()
+            .filter(|ship| ship.registration.role == ShipRole::Satellite)
+            .filter(|ship| {
+                matches!(
+                    self.assignments.get(&ship.symbol),
+                    None | Some(Behaviour::Station { .. })
+                )
+            })
+            .collect();
+        let symbols: Vec<String> = probes.iter().map(|probe| probe.symbol.clone()).collect();
+        if symbols == self.watched {
+            return;
+        }
+
+        let stations = watch::plan_stations(&probes, cache);
+        for probe in &symbols {
+            match stations.get(probe) {
+                Some(waypoint) => {
+                    let unchanged = matches!(
+                        self.assignments.get(probe),
+                        Some(Behaviour::Station { waypoint: current, interval: every, .. })
+                            if current == waypoint && *every == interval
+                    );
+                    if !unchanged {
+                        self.assignments.insert(
+                            probe.clone(),
+                            Behaviour::Station {
+                                waypoint: waypoint.clone(),
+                                interval,
+                                last_poll: None,
+                            },
+                        );
+                    }
+                }
+                None => {
+                    self.assignments.remove(probe);
+                }
+            }
+        }
+        events::record(
+            log::Level::Info,
+            EventKind::Log,
+            format!(
+                "Market watch parked {} of {} probes",
+                stations.len(),
+                symbols.len()
+            ),
+        );
+        self.watched = symbols;
+    }
+
+    /// Repairs a worn ship at its current waypoint when the advisor
+    /// recommends it, returning whether it did. Scrapping is only ever
+    /// suggested, never done.
+    async fn check_repair(&mut self, client: &SpaceTradersClient, ship: &Ship) -> Result<bool> {
+        if condition::lowest_condition(ship) >= CONDITION_WARNING
+            || self.repair_checked.get(&ship.symbol) == Some(&ship.nav.waypoint_symbol)
+        {
+            return Ok(false);
+        }
+        self.repair_checked
+            .insert(ship.symbol.clone(), ship.nav.waypoint_symbol.clone());
+
+        let assessment = condition::assess_ship(client, ship).await;
+        match assessment.advice {
+            Advice::Repair => {
+                if ship.nav.status == ShipNavStatus::InOrbit {
+                    client.dock_ship(&ship.symbol).await?;
+                }
+                let data = client.repair_ship(&ship.symbol).await?;
+                events::record(
+                    log::Level::Info,
+                    EventKind::Condition,
+                    format!(
+                        "{} repaired for {} credits: {}",
+                        ship.symbol, data.transaction.total_price, assessment.reason
+                    ),
+                );
+                return Ok(true);
+            }
+            Advice::Scrap => events::record(
+                log::Level::Warn,
+                EventKind::Condition,
+                format!(
+                    "{} should be scrapped for {} credits: {}",
+                    ship.symbol,
+                    assessment.scrap_value.unwrap_or_default(),
+                    assessment.reason
+                ),
+            ),
+            Advice::KeepRunning => {}
+        }
+        Ok(false)
+    }
+
+    /// Lets every idle ship with a behaviour take its next step. Failures are
+    /// logged and retried on the next tick rather than dropping the behaviour.
+    pub async fn tick(
+        &mut self,
+        client: &SpaceTradersClient,
+        ships: &[Ship],
+        cache: &mut UniverseCache,
+    ) -> Result<()> {
+        let now = Utc::now();
+        if let Some(interval) = self.market_watch {
+            self.plan_market_watch(ships, cache, interval);
+        }
+        // Repaired ships were docked, so their snapshot is stale until the next tick
+        let mut repaired = Vec::new();
+        if self.auto_repair {
+            for ship in ships.iter().filter(|ship| is_idle(ship, now)) {
+                match self.check_repair(client, ship).await {
+                    Ok(true) => repaired.push(ship.symbol.clone()),
+                    Ok(false) => {}
+                    Err(e) => events::record_error(&format!("{} repair", ship.symbol), &e),
+                }
+            }
+        }
+        let contracts_due = self
+            .contracts_checked
+            .is_none_or(|checked| now - checked >= Duration::minutes(contract::CHECK_MINUTES));
+        if self.auto_contracts && contracts_due {
+            self.contracts_checked = Some(now);
+            // Only ships with nothing else to do may take a contract on
+            let busy = self.rescue_ships();
+            let free: Vec<Ship> = ships
+                .iter()
+                .filter(|ship| {
+                    self.assignments
+                        .get(&ship.symbol)
+                        .is_none_or(Behaviour::is_finished)
+                        && !busy.contains(&ship.symbol)
+                })
+                .cloned()
+                .collect();
+            match contract::accept_profitable(client, cache, &free, &self.declined_contracts).await
+            {
+                Ok((declined, accepted)) => {
+                    self.declined_contracts = declined;
+                    if let Some(Estimate {
+                        contract,
+                        ship: Some(ship),
+                        ..
+                    }) = accepted
+                    {
+                        events::record(
+                            log::Level::Info,
+                            EventKind::Log,
+                            format!("{} will run contract {}", ship, contract),
+                        );
+                        self.assignments.insert(
+                            ship,
+                            Behaviour::Contract {
+                                id: contract,
+                                finished: false,
+                            },
+                        );
+                    }
+                }
+                Err(e) => events::record_error("Contract check", &e),
+            }
+        }
+
+        self.check_stranded(ships, cache, now);
+        let mut rescued = Vec::new();
+        let mut abandoned = Vec::new();
+        for (symbol, mission) in &self.rescues {
+            // A ship sold or lost since the mission started can't be waited for
+            let gone = [Some(symbol.as_str()), mission.rescuer()]
+                .into_iter()
+                .flatten()
+                .find(|ship| !ships.iter().any(|s| s.symbol == *ship));
+            if let Some(gone) = gone {
+                abandoned.push((
+                    symbol.clone(),
+                    format!("{} is no longer in the fleet", gone),
+                ));
+                continue;
+            }
+            ledger::attribute(symbol, Some(String::from("rescue")));
+            if let Some(rescuer) = mission.rescuer() {
+                ledger::attribute(rescuer, Some(String::from("rescue")));
+            }
+            let result = rescue::step(client, ships, symbol, mission).await;
+            ledger::attribute(symbol, None);
+            if let Some(rescuer) = mission.rescuer() {
+                ledger::attribute(rescuer, None);
+            }
+            match result {
+                Ok(true) => {
+                    events::record(
+                        log::Level::Info,
+                        EventKind::Log,
+                        format!("{} has fuel again", symbol),
+                    );
+                    rescued.push(symbol.clone());
+                }
+                Ok(false) => {
+                    self.rescue_failures.remove(symbol);
+                }
+                Err(e) => {
+                    events::record_error(&format!("{} rescue", symbol), &e);
+                    let failures = self.rescue_failures.entry(symbol.clone()).or_default();
+                    *failures += 1;
+                    if *failures >= MAX_RESCUE_FAILURES {
+                        abandoned.push((
+                            symbol.clone(),
+                            format!("failed {} times in a row", failures),
+                        ));
+                    }
+                }
+            }
+        }
+        for symbol in &rescued {
+            self.rescues.remove(symbol);
+            self.rescue_failures.remove(symbol);
+        }
+        for (symbol, reason) in abandoned {
+            self.abandon_rescue(&symbol, reason);
+        }
+
+        let busy = self.rescue_ships();
+        let mut fleet = Fleet::new(ships, &self.assignments);
+        for (symbol, behaviour) in self.assignments.iter_mut() {
+            let Some(ship) = ships.iter().find(|ship| ship.symbol == *symbol) else {
+                continue;
+            };
+            if !is_idle(ship, now) || busy.contains(symbol) || repaired.contains(symbol) {
+                continue;
+            }
+            ledger::attribute(symbol, Some(behaviour.name().to_string()));
+            ledger::attribute_contract(symbol, behaviour.contract().map(String::from));
+            let result = behaviour.step(client, ship, cache, &mut fleet).await;
+            ledger::attribute(symbol, None);
+            ledger::attribute_contract(symbol, None);
+            if let Err(e) = result {
+                events::record_error(&format!("{} {}", symbol, behaviour), &e);
+            }
+        }
+        self.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patrol(next: usize) -> Behaviour {
+        Behaviour::Patrol {
+            waypoints: vec![String::from("X1-A-1"), String::from("X1-A-2")],
+            next,
+        }
+    }
+
+    fn entries(values: &[(&str, i32)]) -> BTreeMap<String, i32> {
+        values
+            .iter()
+            .map(|(key, value)| (key.to_string(), *value))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_what_changed_live_over_what_the_tick_did() {
+        let before = entries(&[("changed", 1), ("removed", 1), ("raced", 1), ("dropped", 1)]);
+        // Meanwhile a request changed "raced", dropped "dropped" and added "added"
+        let mut live = entries(&[("changed", 1), ("removed", 1), ("raced", 5), ("added", 5)]);
+        let after = entries(&[
+            ("changed", 2),
+            ("raced", 2),
+            ("dropped", 2),
+            ("added", 2),
+            ("new", 2),
+        ]);
+        merge_entries(&mut live, &before, after);
+
+        assert_eq!(
+            live,
+            entries(&[("changed", 2), ("raced", 5), ("added", 5), ("new", 2)])
+        );
+    }
+
+    #[test]
+    fn merges_a_tick_into_a_scheduler_changed_meanwhile() {
+        let before = Scheduler {
+            assignments: [
+                (String::from("A"), patrol(0)),
+                (String::from("B"), patrol(0)),
+                (String::from("C"), patrol(0)),
+            ]
+            .into(),
+            contracts_checked: Some(Utc::now() - Duration::minutes(20)),
+            ..Scheduler::default()
+        };
+        let mut after = before.clone();
+        for behaviour in after.assignments.values_mut() {
+            *behaviour = patrol(1);
+        }
+        after.contracts_checked = Some(Utc::now());
+        after.stranded = vec![String::from("B")];
+        after.rescues.insert(
+            String::from("B"),
+            Mission::Drift {
+                market: String::from("X1-A-1"),
+            },
+        );
+
+        // While the tick ran, A was reassigned, C unassigned and contract
+        // automation switched on again
+        let mut live = before.clone();
+        live.assignments.insert(
+            String::from("A"),
+            Behaviour::Station {
+                waypoint: String::from("X1-A-1"),
+                interval: 5,
+                last_poll: None,
+            },
+        );
+        live.assignments.remove("C");
+        live.contracts_checked = None;
+        live.merge(&before, after);
+
+        assert!(matches!(live.assignments["A"], Behaviour::Station { .. }));
+        assert_eq!(live.assignments["B"], patrol(1));
+        assert!(!live.assignments.contains_key("C"));
+        assert!(live.rescues.contains_key("B"));
+        assert_eq!(live.stranded, vec![String::from("B")]);
+        assert_eq!(live.contracts_checked, None);
+    }
+}
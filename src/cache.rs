@@ -0,0 +1,217 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use spacetraders_api::models::{Construction, Market, Shipyard, System, Waypoint};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Directory holding everything the client persists between runs.
+pub fn data_dir() -> PathBuf {
+    std::env::var("SPACE_TRADERS_DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(".spacetraders"))
+}
+
+/// Returns the system part of a waypoint symbol, e.g. `X1-AB12` for `X1-AB12-C34`.
+pub fn system_symbol(waypoint_symbol: &str) -> &str {
+    match waypoint_symbol.rsplit_once('-') {
+        Some((system, _)) => system,
+        None => waypoint_symbol,
+    }
+}
+
+fn merge_changed<V: PartialEq>(
+    live: &mut HashMap<String, V>,
+    before: &HashMap<String, V>,
+    after: HashMap<String, V>,
+) {
+    for (key, value) in after {
+        if before.get(&key) != Some(&value) {
+            live.insert(key, value);
+        }
+    }
+}
+
+/// Universe data that only changes on a server reset. Kept on disk so the
+/// galaxy map doesn't need to page through every system on each start.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UniverseCache {
+    pub systems: Vec<System>,
+    /// Set once every system has been downloaded; until then `systems`
+    /// only holds the ones our scans have found.
+    pub systems_synced: bool,
+    /// Jump gate connections keyed by the gate's waypoint symbol.
+    pub jump_gates: HashMap<String, Vec<String>>,
+    /// Full waypoint details, including traits, keyed by system symbol.
+    pub waypoints: HashMap<String, Vec<Waypoint>>,
+    /// Last market seen at each marketplace, keyed by waypoint symbol.
+    pub markets: HashMap<String, Market>,
+    /// Last shipyard seen at each waypoint, for the mounts and modules its
+    /// ships come with.
+    pub shipyards: HashMap<String, Shipyard>,
+    /// Goods each manufactured good is made from, keyed by the good.
+    pub supply_chain: BTreeMap<String, Vec<String>>,
+    /// Last seen progress of each construction site, keyed by waypoint symbol.
+    pub constructions: HashMap<String, Construction>,
+    /// The file as this copy last read or wrote it, so saving only writes
+    /// what this process changed on top of what others saved meanwhile.
+    #[serde(skip)]
+    base: Option<Arc<UniverseCache>>,
+}
+
+impl UniverseCache {
+    fn path() -> PathBuf {
+        data_dir().join("universe.json")
+    }
+
+    fn read() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Loads the cache from disk, starting empty if it is missing or unreadable.
+    pub fn load() -> Self {
+        let mut cache = Self::read();
+        cache.base = Some(Arc::new(cache.clone()));
+        cache
+    }
+
+    /// Folds what this copy changed since it was loaded into what is on
+    /// disk now, so the daemon, the TUI and CLI commands can share the file
+    /// without overwriting each other. The file is only rewritten when the
+    /// merge changed it.
+    pub fn save(&mut self) -> Result<()> {
+        self.sync(true)
+    }
+
+    /// Takes in what other processes saved since this copy was loaded,
+    /// keeping this copy's own unsaved changes.
+    pub fn reload(&mut self) {
+        if let Err(e) = self.sync(false) {
+            log::warn!("Reloading the universe cache failed: {}", e);
+        }
+    }
+
+    fn sync(&mut self, write: bool) -> Result<()> {
+        let disk = Self::read();
+        let base = self.base.take();
+        let ours = std::mem::take(self);
+        let mut merged = match &base {
+            Some(base) => {
+                let mut merged = disk.clone();
+                merged.merge_changes(base, ours);
+                merged
+            }
+            // Never read from disk, so this copy is the whole story
+            None => ours,
+        };
+        if write && !merged.same_as(&disk) {
+            fs::create_dir_all(data_dir())?;
+            // Written aside and renamed, so readers never see half a file
+            let partial = Self::path().with_extension("json.partial");
+            fs::write(&partial, serde_json::to_string(&merged)?)?;
+            fs::rename(partial, Self::path())?;
+            merged.base = Some(Arc::new(merged.clone()));
+        } else {
+            merged.base = Some(Arc::new(disk));
+        }
+        *self = merged;
+        Ok(())
+    }
+
+    /// Whether two copies hold the same data, wherever they were loaded from.
+    pub fn same_as(&self, other: &UniverseCache) -> bool {
+        self.systems == other.systems
+            && self.systems_synced == other.systems_synced
+            && self.jump_gates == other.jump_gates
+            && self.waypoints == other.waypoints
+            && self.markets == other.markets
+            && self.shipyards == other.shipyards
+            && self.supply_chain == other.supply_chain
+            && self.constructions == other.constructions
+    }
+
+    /// Takes the entries `after` changed relative to `before`, leaving the
+    /// rest as they are, so a copy updated away from the lock can be folded
+    /// back without losing what others stored meanwhile.
+    pub fn merge_changes(&mut self, before: &UniverseCache, after: UniverseCache) {
+        merge_changed(&mut self.jump_gates, &before.jump_gates, after.jump_gates);
+        merge_changed(&mut self.waypoints, &before.waypoints, after.waypoints);
+        merge_changed(&mut self.markets, &before.markets, after.markets);
+        merge_changed(&mut self.shipyards, &before.shipyards, after.shipyards);
+        merge_changed(
+            &mut self.constructions,
+            &before.constructions,
+            after.constructions,
+        );
+        for (good, inputs) in after.supply_chain {
+            if before.supply_chain.get(&good) != Some(&inputs) {
+                self.supply_chain.insert(good, inputs);
+            }
+        }
+        if after.systems != before.systems {
+            self.systems = after.systems;
+        }
+        if after.systems_synced != before.systems_synced {
+            self.systems_synced = after.systems_synced;
+        }
+    }
+
+    pub fn system(&self, symbol: &str) -> Option<&System> {
+        self.systems.iter().find(|system| system.symbol == symbol)
+    }
+
+    /// Jump gate edges as pairs of system symbols, each listed once.
+    pub fn jump_gate_edges(&self) -> Vec<(&str, &str)> {
+        let mut edges = Vec::new();
+        for (gate, connections) in &self.jump_gates {
+            let from = system_symbol(gate);
+            for connection in connections {
+                let to = system_symbol(connection);
+                let edge = if from < to { (from, to) } else { (to, from) };
+                if !edges.contains(&edge) {
+                    edges.push(edge);
+                }
+            }
+        }
+        edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn market(symbol: &str) -> Market {
+        Market::new(symbol.to_string(), vec![], vec![], vec![])
+    }
+
+    #[test]
+    fn saves_from_several_copies_keep_each_others_changes() {
+        let dir = std::env::temp_dir().join(format!("universe-cache-{}", std::process::id()));
+        std::env::set_var("SPACE_TRADERS_DATA_DIR", &dir);
+
+        let mut daemon = UniverseCache::load();
+        let mut cli = UniverseCache::load();
+        let mut tui = UniverseCache::load();
+        daemon
+            .markets
+            .insert(String::from("X1-A-1"), market("X1-A-1"));
+        daemon.save().unwrap();
+        // The CLI copy predates the daemon's market, but saving keeps it
+        cli.markets.insert(String::from("X1-A-2"), market("X1-A-2"));
+        cli.save().unwrap();
+        assert_eq!(cli.markets.len(), 2);
+
+        // An attached TUI picks up both without saving
+        tui.reload();
+        assert_eq!(tui.markets.len(), 2);
+        assert_eq!(UniverseCache::read().markets.len(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,1823 @@
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use spacetraders_api::models::{
+    Agent, Ship, ShipNavStatus, ShipRole, TradeSymbol, WaypointModifierSymbol, WaypointTraitSymbol,
+    WaypointType,
+};
+use std::collections::BTreeMap;
+use unicode_width::UnicodeWidthStr;
+
+use crate::behaviour::{Behaviour, DEFAULT_POLL_MINUTES};
+use crate::cache::{self, UniverseCache};
+use crate::client::{ApiError, Progress, SpaceTradersClient};
+use crate::condition;
+use crate::construction;
+use crate::contract;
+use crate::fitting::{self, Loadout};
+use crate::fleet::{self, FleetCommand};
+use crate::impact::ImpactModel;
+use crate::ledger::{self, Grouping, Period};
+use crate::prices;
+use crate::rescue;
+use crate::rivals::{self, AgentSort, Rivals};
+use crate::roster::{self, GroupBy, Roster, ShipFilter, ShipSort};
+use crate::scan::{self, ScanKind};
+use crate::search::{self, WaypointQuery};
+use crate::supply;
+use crate::trade;
+
+/// Space Traders client. Without a subcommand the terminal UI starts.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Print machine-readable JSON instead of tables
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Go through a running daemon instead of calling the API directly
+    #[arg(long, global = true)]
+    pub attach: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Fleet overview
+    Ships {
+        #[command(subcommand)]
+        command: ShipsCommand,
+    },
+    /// Orders for a single ship
+    Ship {
+        #[command(subcommand)]
+        command: ShipCommand,
+    },
+    /// Market information
+    Market {
+        #[command(subcommand)]
+        command: MarketCommand,
+    },
+    /// Contract information
+    Contracts {
+        #[command(subcommand)]
+        command: ContractsCommand,
+    },
+    /// System information
+    Systems {
+        #[command(subcommand)]
+        command: SystemsCommand,
+    },
+    /// Credit transactions and profit and loss
+    Ledger {
+        #[command(subcommand)]
+        command: LedgerCommand,
+    },
+    /// Behaviours running in the daemon
+    Behaviours {
+        #[command(subcommand)]
+        command: BehavioursCommand,
+    },
+    /// Other agents and the rivals we watch
+    Agents {
+        #[command(subcommand)]
+        command: AgentsCommand,
+    },
+    /// Factions, their traits and whether they recruit
+    Factions {
+        #[command(subcommand)]
+        command: FactionsCommand,
+    },
+    /// Run fleet automation in the background with a local control API
+    Daemon,
+}
+
+#[derive(Subcommand)]
+pub enum ShipsCommand {
+    /// List all ships with location, fuel, cargo and local notes
+    List {
+        #[command(flatten)]
+        selection: Selection,
+        #[arg(long, value_enum, default_value_t = ShipSort::Symbol)]
+        sort: ShipSort,
+        /// Split the list into sections
+        #[arg(long, value_enum, default_value_t = GroupBy::None)]
+        group_by: GroupBy,
+    },
+    /// Give an order to several ships at once
+    Batch {
+        /// Ship to order; repeatable, and combined with the filters
+        #[arg(long = "ship")]
+        ships: Vec<String>,
+        #[command(flatten)]
+        selection: Selection,
+        #[command(subcommand)]
+        command: FleetCommand,
+    },
+}
+
+/// Which ships a fleet command covers. Unset filters match every ship.
+#[derive(Args)]
+pub struct Selection {
+    /// Only ships with this role, e.g. HAULER
+    #[arg(long, value_parser = parse_symbol::<ShipRole>)]
+    role: Option<ShipRole>,
+    /// Only ships in this nav status, e.g. IN_TRANSIT
+    #[arg(long, value_parser = parse_symbol::<ShipNavStatus>)]
+    status: Option<ShipNavStatus>,
+    /// Only ships in this system
+    #[arg(long)]
+    system: Option<String>,
+    /// Only ships in this local group
+    #[arg(long)]
+    group: Option<String>,
+    /// Only ships with this local tag
+    #[arg(long)]
+    tag: Option<String>,
+}
+
+impl Selection {
+    fn filter(self) -> ShipFilter {
+        ShipFilter {
+            role: self.role,
+            status: self.status,
+            system: self.system.map(|system| system.to_uppercase()),
+            group: self.group,
+            tag: self.tag,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum ShipCommand {
+    /// Fly a ship to a waypoint in its system
+    Navigate { ship: String, waypoint: String },
+    /// Dock a ship at its current waypoint
+    Dock { ship: String },
+    /// Move a ship into orbit
+    Orbit { ship: String },
+    /// Buy cargo at the ship's market
+    Buy {
+        ship: String,
+        #[arg(value_parser = parse_symbol::<TradeSymbol>)]
+        good: TradeSymbol,
+        units: i32,
+    },
+    /// Sell cargo at the ship's market
+    Sell {
+        ship: String,
+        #[arg(value_parser = parse_symbol::<TradeSymbol>)]
+        good: TradeSymbol,
+        units: i32,
+    },
+    /// Fill the ship's tank at its market
+    Refuel {
+        ship: String,
+        /// Fuel to add instead of filling up
+        #[arg(long)]
+        units: Option<i32>,
+        /// Refuel from FUEL in the ship's hold instead of the market
+        #[arg(long)]
+        from_cargo: bool,
+    },
+    /// Check whether the ship can reach fuel, and plan a drift or a rescue
+    Rescue {
+        ship: String,
+        /// Have the daemon carry out the plan
+        #[arg(long)]
+        start: bool,
+    },
+    /// Scan for systems, waypoints or other ships with the ship's sensors
+    Scan {
+        ship: String,
+        #[arg(value_enum)]
+        kind: ScanKind,
+    },
+    /// Show component condition and the repair advisor's recommendation
+    Condition { ship: String },
+    /// Repair the ship at its shipyard
+    Repair { ship: String },
+    /// Scrap the ship at its shipyard for credits
+    Scrap {
+        ship: String,
+        /// Confirm that the ship should be destroyed
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Plan a new loadout at the ship's shipyard, and apply it with --apply
+    Fitting {
+        ship: String,
+        /// Part to add, e.g. MOUNT_MINING_LASER_II; repeatable
+        #[arg(long)]
+        install: Vec<String>,
+        /// Part to take off; repeatable
+        #[arg(long)]
+        remove: Vec<String>,
+        /// Carry out the plan if it validates
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Hand a ship to a daemon behaviour
+    Assign {
+        ship: String,
+        #[command(subcommand)]
+        behaviour: Behaviour,
+    },
+    /// Stop a ship's daemon behaviour
+    Unassign { ship: String },
+    /// Give a ship a local nickname, or clear it when left out
+    Nickname { ship: String, name: Option<String> },
+    /// List a ship under a local group such as "X1 miners", or ungroup it
+    Group { ship: String, group: Option<String> },
+    /// Add local tags to a ship
+    Tag {
+        ship: String,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Remove local tags from a ship
+    Untag {
+        ship: String,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Set a ship's local notes, or clear them when left out
+    Note { ship: String, text: Option<String> },
+}
+
+#[derive(Subcommand)]
+pub enum MarketCommand {
+    /// Show a market's goods, and prices when one of our ships is present
+    Show { waypoint: String },
+    /// Show recorded prices at a market, newest first
+    History {
+        waypoint: String,
+        /// Only this good
+        #[arg(long, value_parser = parse_symbol::<TradeSymbol>)]
+        good: Option<TradeSymbol>,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Show what a good is made from and feeds, and which cached markets trade it
+    Chain {
+        #[arg(value_parser = parse_symbol::<TradeSymbol>)]
+        good: TradeSymbol,
+    },
+    /// Show how our own trades have moved prices and how fast they recover
+    Impact,
+    /// Plan the goods a ship should buy at each stop of a trade route
+    Plan {
+        ship: String,
+        /// Stops in order
+        #[arg(num_args = 2.., required = true)]
+        waypoints: Vec<String>,
+    },
+    /// Find the most profitable routes between two cached markets in a ship's system
+    Routes {
+        ship: String,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ContractsCommand {
+    /// List our contracts
+    List,
+    /// Accept a contract and collect its advance payment
+    Accept { id: String },
+    /// Fulfill a contract whose deliveries are complete
+    Fulfill { id: String },
+    /// Estimate a contract's sourcing cost, travel time and profit
+    Evaluate { id: String },
+}
+
+#[derive(Subcommand)]
+pub enum LedgerCommand {
+    /// Show the most recent transactions
+    List {
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Income and expenses per period and ship, behaviour or contract
+    Pnl {
+        #[arg(long, value_enum, default_value_t = Period::Day)]
+        period: Period,
+        #[arg(long, value_enum, default_value_t = Grouping::Ship)]
+        by: Grouping,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AgentsCommand {
+    /// List agents from the last synced directory
+    List {
+        #[arg(long, value_enum, default_value_t = AgentSort::Credits)]
+        sort: AgentSort,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Download the directory again first
+        #[arg(long)]
+        sync: bool,
+    },
+    /// Show an agent's public details
+    Show { agent: String },
+    /// Record an agent's credits over time
+    Watch { agent: String },
+    /// Stop recording an agent's credits
+    Unwatch { agent: String },
+    /// Credits and fleet size of a watched agent over time
+    History {
+        agent: String,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum FactionsCommand {
+    /// List factions with their headquarters and recruitment status
+    List,
+    /// Show a faction's description and traits
+    Show { faction: String },
+}
+
+#[derive(Subcommand)]
+pub enum SystemsCommand {
+    /// List the waypoints of a system
+    Waypoints {
+        system: String,
+        /// Only waypoints of this type, e.g. ASTEROID
+        #[arg(long = "type", value_parser = parse_symbol::<WaypointType>)]
+        waypoint_type: Option<WaypointType>,
+        /// Only waypoints with this trait, e.g. MARKETPLACE
+        #[arg(long = "trait", value_parser = parse_symbol::<WaypointTraitSymbol>)]
+        waypoint_trait: Option<WaypointTraitSymbol>,
+    },
+    /// Find waypoints by type, traits and modifiers, nearest to a ship first
+    Search {
+        /// System to search; all cached systems when left out
+        system: Option<String>,
+        #[arg(long = "type", value_parser = parse_symbol::<WaypointType>)]
+        waypoint_type: Option<WaypointType>,
+        /// Required trait; repeatable
+        #[arg(long = "trait", value_parser = parse_symbol::<WaypointTraitSymbol>)]
+        traits: Vec<WaypointTraitSymbol>,
+        /// Required modifier, e.g. UNSTABLE; repeatable
+        #[arg(long = "modifier", value_parser = parse_symbol::<WaypointModifierSymbol>)]
+        modifiers: Vec<WaypointModifierSymbol>,
+        /// Ship to measure distances from
+        #[arg(long)]
+        ship: Option<String>,
+    },
+    /// Progress of construction sites and where to buy their materials
+    Construction {
+        /// System to check; our headquarters' and ships' systems when left out
+        system: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BehavioursCommand {
+    /// List ships with a behaviour
+    List,
+    /// Repair worn ships at shipyards when the advisor recommends it
+    AutoRepair {
+        #[arg(value_enum)]
+        setting: Toggle,
+    },
+    /// Accept open contracts the evaluator rates profitable and achievable
+    AutoContracts {
+        #[arg(value_enum)]
+        setting: Toggle,
+    },
+    /// Send stranded ships drifting to fuel, or another ship to bring some
+    AutoRescue {
+        #[arg(value_enum)]
+        setting: Toggle,
+    },
+    /// Manage missions getting stranded ships back to fuel
+    Rescue {
+        #[command(subcommand)]
+        command: RescueCommand,
+    },
+    /// Park probes at the most valuable markets and keep their prices fresh
+    MarketWatch {
+        #[arg(value_enum)]
+        setting: Toggle,
+        /// Minutes between polls
+        #[arg(long, default_value_t = DEFAULT_POLL_MINUTES)]
+        interval: u64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RescueCommand {
+    /// Call off the mission helping a stranded ship
+    Cancel { ship: String },
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Toggle {
+    On,
+    Off,
+}
+
+/// Parses an API enum from its symbol, e.g. `MARKETPLACE`, in any case.
+fn parse_symbol<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_uppercase()))
+        .map_err(|_| format!("unknown symbol `{}`", value))
+}
+
+/// Exit code for a failed command. Game errors exit with their code's
+/// family, e.g. 42 for navigation errors 4200-4299; other HTTP errors exit
+/// with 4 or 5 for client and server errors; anything else exits with 1.
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    match error.downcast_ref::<ApiError>() {
+        Some(ApiError {
+            code: Some(code), ..
+        }) if *code >= 1000 => code / 100,
+        Some(api_error) => (api_error.status / 100) as i32,
+        None => 1,
+    }
+}
+
+/// Runs a subcommand and returns the process exit code.
+pub async fn run(client: &SpaceTradersClient, command: Command, json: bool) -> i32 {
+    match execute(client, command, json).await {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit_code(&e)
+        }
+    }
+}
+
+async fn execute(client: &SpaceTradersClient, command: Command, json: bool) -> Result<()> {
+    match command {
+        Command::Ships {
+            command:
+                ShipsCommand::List {
+                    selection,
+                    sort,
+                    group_by,
+                },
+        } => {
+            let ships = client.get_my_ships().await?;
+            let roster = Roster::load();
+            let behaviours = if client.is_attached() {
+                client.get_behaviours().await?
+            } else {
+                BTreeMap::new()
+            };
+            let sections = roster::arrange(&ships, &roster, &selection.filter(), sort, group_by);
+            let listed: Vec<&Ship> = sections
+                .iter()
+                .flat_map(|(_, indices)| indices.iter().map(|i| &ships[*i]))
+                .collect();
+            output(json, &listed, || {
+                for (section, indices) in &sections {
+                    if !section.is_empty() {
+                        println!("{} ({})", section, indices.len());
+                    }
+                    let rows = indices
+                        .iter()
+                        .map(|i| {
+                            let ship = &ships[*i];
+                            let notes = roster.notes(&ship.symbol);
+                            vec![
+                                ship.symbol.clone(),
+                                notes.nickname.unwrap_or_default(),
+                                ship.registration.role.to_string(),
+                                ship.nav.status.to_string(),
+                                ship.nav.waypoint_symbol.clone(),
+                                format!("{}/{}", ship.fuel.current, ship.fuel.capacity),
+                                format!("{}/{}", ship.cargo.units, ship.cargo.capacity),
+                                notes.group.unwrap_or_default(),
+                                notes.tags.join(","),
+                                behaviours
+                                    .get(&ship.symbol)
+                                    .map(|b| b.to_string())
+                                    .unwrap_or_default(),
+                            ]
+                        })
+                        .collect();
+                    print_table(
+                        &[
+                            "SHIP",
+                            "NAME",
+                            "ROLE",
+                            "STATUS",
+                            "WAYPOINT",
+                            "FUEL",
+                            "CARGO",
+                            "GROUP",
+                            "TAGS",
+                            "BEHAVIOUR",
+                        ],
+                        rows,
+                    );
+                }
+            })
+        }
+        Command::Ships {
+            command:
+                ShipsCommand::Batch {
+                    ships: symbols,
+                    selection,
+                    command,
+                },
+        } => {
+            let symbols: Vec<String> = symbols.iter().map(|s| s.to_uppercase()).collect();
+            let filter = selection.filter();
+            if symbols.is_empty() && filter == ShipFilter::default() {
+                anyhow::bail!("pick ships with --ship or a filter such as --group or --tag");
+            }
+            let roster = Roster::load();
+            let ships: Vec<Ship> = client
+                .get_my_ships()
+                .await?
+                .into_iter()
+                .filter(|ship| symbols.is_empty() || symbols.contains(&ship.symbol))
+                .filter(|ship| filter.matches(ship, &roster))
+                .collect();
+            if ships.is_empty() {
+                anyhow::bail!("no ships match");
+            }
+            let command = command.normalized();
+            let outcomes = fleet::run(client, &ships, &command).await;
+            output(json, &outcomes, || {
+                let rows = outcomes
+                    .iter()
+                    .map(|outcome| {
+                        vec![
+                            outcome.ship.clone(),
+                            if outcome.ok { "OK" } else { "FAILED" }.to_string(),
+                            outcome.message.clone(),
+                        ]
+                    })
+                    .collect();
+                print_table(&["SHIP", "RESULT", "DETAIL"], rows);
+            })
+        }
+        Command::Ship { command } => match command {
+            ShipCommand::Navigate { ship, waypoint } => {
+                let data = client
+                    .navigate_ship(&ship.to_uppercase(), &waypoint.to_uppercase())
+                    .await?;
+                output(json, &data, || {
+                    println!(
+                        "{} navigating to {}, arriving {} (fuel {}/{})",
+                        ship.to_uppercase(),
+                        data.nav.route.destination.symbol,
+                        data.nav.route.arrival,
+                        data.fuel.current,
+                        data.fuel.capacity
+                    );
+                })
+            }
+            ShipCommand::Dock { ship } => {
+                let nav = client.dock_ship(&ship.to_uppercase()).await?;
+                output(json, &nav, || {
+                    println!("{} docked at {}", ship.to_uppercase(), nav.waypoint_symbol)
+                })
+            }
+            ShipCommand::Orbit { ship } => {
+                let nav = client.orbit_ship(&ship.to_uppercase()).await?;
+                output(json, &nav, || {
+                    println!(
+                        "{} in orbit at {}",
+                        ship.to_uppercase(),
+                        nav.waypoint_symbol
+                    )
+                })
+            }
+            ShipCommand::Buy { ship, good, units } => {
+                let data = client
+                    .purchase_cargo(&ship.to_uppercase(), good, units)
+                    .await?;
+                prices::after_trading(client, &data.transaction.waypoint_symbol).await;
+                output(json, &data, || {
+                    println!(
+                        "Bought {} {} for {} credits ({} now)",
+                        data.transaction.units,
+                        data.transaction.trade_symbol,
+                        data.transaction.total_price,
+                        data.agent.credits
+                    )
+                })
+            }
+            ShipCommand::Sell { ship, good, units } => {
+                let data = client.sell_cargo(&ship.to_uppercase(), good, units).await?;
+                prices::after_trading(client, &data.transaction.waypoint_symbol).await;
+                output(json, &data, || {
+                    println!(
+                        "Sold {} {} for {} credits ({} now)",
+                        data.transaction.units,
+                        data.transaction.trade_symbol,
+                        data.transaction.total_price,
+                        data.agent.credits
+                    )
+                })
+            }
+            ShipCommand::Refuel {
+                ship,
+                units,
+                from_cargo,
+            } => {
+                let data = client
+                    .refuel_ship(&ship.to_uppercase(), units, from_cargo)
+                    .await?;
+                output(json, &data, || {
+                    println!(
+                        "Refueled to {}/{} for {} credits",
+                        data.fuel.current, data.fuel.capacity, data.transaction.total_price
+                    )
+                })
+            }
+            ShipCommand::Scan { ship, kind } => {
+                let symbol = ship.to_uppercase();
+                let ships = client.get_my_ships().await?;
+                let Some(ship) = ships.iter().find(|s| s.symbol == symbol) else {
+                    anyhow::bail!("no ship named {}", symbol);
+                };
+                let mut cache = UniverseCache::load();
+                let result = scan::scan(client, ship, kind, &mut cache).await?;
+                output(json, &result, || {
+                    println!("{} scan: {}", kind, result.summary);
+                    if !result.ships.is_empty() {
+                        let rows = result
+                            .ships
+                            .iter()
+                            .map(|scanned| {
+                                vec![
+                                    scanned.symbol.clone(),
+                                    scanned.registration.role.to_string(),
+                                    scanned.nav.waypoint_symbol.clone(),
+                                    scanned.nav.status.to_string(),
+                                    scanned
+                                        .frame
+                                        .as_ref()
+                                        .map(|frame| frame.symbol.clone())
+                                        .unwrap_or_default(),
+                                    scanned.engine.symbol.clone(),
+                                ]
+                            })
+                            .collect();
+                        print_table(
+                            &["SHIP", "ROLE", "WAYPOINT", "STATUS", "FRAME", "ENGINE"],
+                            rows,
+                        );
+                    }
+                    println!("Cooldown: {}s", result.cooldown.remaining_seconds);
+                })
+            }
+            ShipCommand::Rescue { ship, start } => {
+                let symbol = ship.to_uppercase();
+                let stranding = if start {
+                    client.start_rescue(&symbol).await?
+                } else {
+                    let ships = client.get_my_ships().await?;
+                    let Some(ship) = ships.iter().find(|s| s.symbol == symbol) else {
+                        anyhow::bail!("no ship named {}", symbol);
+                    };
+                    let cache = UniverseCache::load();
+                    let Some(stranding) = rescue::assess(&cache, &ships, ship, &[]) else {
+                        println!(
+                            "{} can reach fuel, or no cached market in {} sells it",
+                            symbol, ship.nav.system_symbol
+                        );
+                        return Ok(());
+                    };
+                    stranding
+                };
+                output(json, &stranding, || {
+                    let seconds = |seconds: Option<i64>| {
+                        seconds
+                            .map(|s| format!("{}s", s))
+                            .unwrap_or_else(|| String::from("-"))
+                    };
+                    println!(
+                        "{} is stranded at {} with {} fuel; nearest fuel at {}, {:.0} away",
+                        stranding.ship,
+                        stranding.waypoint,
+                        stranding.fuel,
+                        stranding.market,
+                        stranding.distance
+                    );
+                    println!("Drift: {}", seconds(stranding.drift_seconds));
+                    println!(
+                        "Rescue: {} ({} FUEL by {})",
+                        seconds(stranding.rescue_seconds),
+                        stranding.units,
+                        stranding.rescuer.as_deref().unwrap_or("no ship")
+                    );
+                    match &stranding.mission {
+                        Some(mission) if start => println!("Started: {}", mission),
+                        Some(mission) => println!("Plan: {} (--start to carry it out)", mission),
+                        None => println!("Plan: none, no fuel to drift and no ship to help"),
+                    }
+                })
+            }
+            ShipCommand::Condition { ship } => {
+                let symbol = ship.to_uppercase();
+                let ships = client.get_my_ships().await?;
+                let Some(ship) = ships.iter().find(|s| s.symbol == symbol) else {
+                    anyhow::bail!("no ship named {}", symbol);
+                };
+                let assessment = condition::assess_ship(client, ship).await;
+                output(json, &assessment, || {
+                    let rows = condition::components(ship)
+                        .iter()
+                        .map(|(component, condition, integrity)| {
+                            vec![
+                                component.to_string(),
+                                format!("{:.0}%", condition * 100.0),
+                                format!("{:.0}%", integrity * 100.0),
+                            ]
+                        })
+                        .collect();
+                    print_table(&["COMPONENT", "CONDITION", "INTEGRITY"], rows);
+                    println!();
+                    let quote = |credits: Option<i64>| {
+                        credits
+                            .map(|c| c.to_string())
+                            .unwrap_or_else(|| String::from("-"))
+                    };
+                    println!(
+                        "Repair: {}  Scrap: {}  Earning: {:.0}/h",
+                        quote(assessment.repair_cost),
+                        quote(assessment.scrap_value),
+                        assessment.earning_rate
+                    );
+                    println!("Advice: {} ({})", assessment.advice, assessment.reason);
+                })
+            }
+            ShipCommand::Repair { ship } => {
+                let data = client.repair_ship(&ship.to_uppercase()).await?;
+                output(json, &data, || {
+                    println!(
+                        "Repaired {} for {} credits ({} now)",
+                        data.ship.symbol, data.transaction.total_price, data.agent.credits
+                    )
+                })
+            }
+            ShipCommand::Scrap { ship, yes } => {
+                if !yes {
+                    anyhow::bail!("scrapping destroys {}; pass --yes to confirm", ship);
+                }
+                let data = client.scrap_ship(&ship.to_uppercase()).await?;
+                output(json, &data, || {
+                    println!(
+                        "Scrapped {} for {} credits ({} now)",
+                        data.transaction.ship_symbol,
+                        data.transaction.total_price,
+                        data.agent.credits
+                    )
+                })
+            }
+            ShipCommand::Fitting {
+                ship,
+                install,
+                remove,
+                apply,
+            } => {
+                let symbol = ship.to_uppercase();
+                let ships = client.get_my_ships().await?;
+                let Some(ship) = ships.iter().find(|s| s.symbol == symbol) else {
+                    anyhow::bail!("no ship named {}", symbol);
+                };
+                let waypoint = &ship.nav.waypoint_symbol;
+                let system = cache::system_symbol(waypoint);
+                let shipyard = client.get_shipyard(system, waypoint).await.ok();
+                let market = client.get_market(system, waypoint).await.ok();
+
+                let mut planned = Loadout::of(ship);
+                for part in &remove {
+                    if !planned.remove(&part.to_uppercase()) {
+                        anyhow::bail!("{} has no {} to remove", symbol, part);
+                    }
+                }
+                for part in &install {
+                    let part = part.to_uppercase();
+                    if fitting::PartKind::of(&part).is_none() {
+                        anyhow::bail!("{} is not a mount or module", part);
+                    }
+                    planned.add(&part);
+                }
+
+                let cache = UniverseCache::load();
+                let catalog =
+                    fitting::catalog(&ships, cache.shipyards.values().chain(shipyard.as_ref()));
+                let plan =
+                    fitting::plan(ship, planned, &catalog, market.as_ref(), shipyard.as_ref());
+                output(json, &plan, || {
+                    let budget = &plan.budget;
+                    print_table(
+                        &["", "USED", "AVAILABLE"],
+                        vec![
+                            vec![
+                                String::from("Power"),
+                                budget.power_used.to_string(),
+                                budget.power_output.to_string(),
+                            ],
+                            vec![
+                                String::from("Crew"),
+                                budget.crew_required.to_string(),
+                                budget.crew_capacity.to_string(),
+                            ],
+                            vec![
+                                String::from("Module slots"),
+                                budget.slots_used.to_string(),
+                                budget.module_slots.to_string(),
+                            ],
+                            vec![
+                                String::from("Mounting points"),
+                                budget.mounts_used.to_string(),
+                                budget.mounting_points.to_string(),
+                            ],
+                        ],
+                    );
+                    println!();
+                    for change in &plan.changes {
+                        println!("{}", change);
+                    }
+                    if let Some(quote) = &plan.quote {
+                        println!(
+                            "Cost: {} for parts + {} in fees = {} credits",
+                            quote.parts, quote.fees, quote.total
+                        );
+                    }
+                    for problem in &plan.problems {
+                        println!("problem: {}", problem);
+                    }
+                })?;
+
+                if apply {
+                    if !plan.problems.is_empty() {
+                        anyhow::bail!("plan has problems; nothing was changed");
+                    }
+                    fitting::apply(client, ship, &plan.changes).await?;
+                    if !json {
+                        println!("Applied {} changes to {}", plan.changes.len(), symbol);
+                    }
+                }
+                Ok(())
+            }
+            ShipCommand::Assign { ship, behaviour } => {
+                let ship = ship.to_uppercase();
+                let behaviour = behaviour.normalized();
+                client.assign_behaviour(&ship, behaviour.clone()).await?;
+                output(json, &behaviour, || {
+                    println!("{} assigned: {}", ship, behaviour)
+                })
+            }
+            ShipCommand::Unassign { ship } => {
+                let ship = ship.to_uppercase();
+                let removed = client.unassign_behaviour(&ship).await?;
+                output(json, &removed, || {
+                    if removed {
+                        println!("{} unassigned", ship);
+                    } else {
+                        println!("{} had no behaviour", ship);
+                    }
+                })
+            }
+            ShipCommand::Nickname { ship, name } => {
+                let ship = ship.to_uppercase();
+                Roster::load().edit(&ship, |notes| notes.nickname = name.clone())?;
+                output(json, &name, || match &name {
+                    Some(name) => println!("{} is now called {}", ship, name),
+                    None => println!("{} has no nickname", ship),
+                })
+            }
+            ShipCommand::Group { ship, group } => {
+                let ship = ship.to_uppercase();
+                Roster::load().edit(&ship, |notes| notes.group = group.clone())?;
+                output(json, &group, || match &group {
+                    Some(group) => println!("{} is in {}", ship, group),
+                    None => println!("{} is ungrouped", ship),
+                })
+            }
+            ShipCommand::Tag { ship, tags } => {
+                let ship = ship.to_uppercase();
+                let mut roster = Roster::load();
+                roster.edit(&ship, |notes| {
+                    for tag in tags {
+                        if !notes.tags.contains(&tag) {
+                            notes.tags.push(tag);
+                        }
+                    }
+                })?;
+                let notes = roster.notes(&ship);
+                output(json, &notes.tags, || {
+                    println!("{} tags: {}", ship, notes.tags.join(", "))
+                })
+            }
+            ShipCommand::Untag { ship, tags } => {
+                let ship = ship.to_uppercase();
+                let mut roster = Roster::load();
+                roster.edit(&ship, |notes| notes.tags.retain(|tag| !tags.contains(tag)))?;
+                let notes = roster.notes(&ship);
+                output(json, &notes.tags, || {
+                    println!("{} tags: {}", ship, notes.tags.join(", "))
+                })
+            }
+            ShipCommand::Note { ship, text } => {
+                let ship = ship.to_uppercase();
+                let text = text.unwrap_or_default();
+                Roster::load().edit(&ship, |notes| notes.notes = text.clone())?;
+                output(json, &text, || println!("Notes on {} saved", ship))
+            }
+        },
+        Command::Market {
+            command: MarketCommand::Show { waypoint },
+        } => {
+            let waypoint = waypoint.to_uppercase();
+            let market = client
+                .get_market(cache::system_symbol(&waypoint), &waypoint)
+                .await?;
+            let mut cache = UniverseCache::load();
+            cache.markets.insert(waypoint, market.clone());
+            cache.save()?;
+            output(json, &market, || match &market.trade_goods {
+                Some(goods) => {
+                    let rows = goods
+                        .iter()
+                        .map(|good| {
+                            vec![
+                                good.symbol.to_string(),
+                                format!("{:?}", good.r#type).to_uppercase(),
+                                good.purchase_price.to_string(),
+                                good.sell_price.to_string(),
+                                good.trade_volume.to_string(),
+                                good.supply.to_string(),
+                                good.activity.map(|a| a.to_string()).unwrap_or_default(),
+                            ]
+                        })
+                        .collect();
+                    print_table(
+                        &[
+                            "GOOD", "TYPE", "BUY", "SELL", "VOLUME", "SUPPLY", "ACTIVITY",
+                        ],
+                        rows,
+                    );
+                }
+                None => {
+                    // Prices are only visible with a ship at the market
+                    let rows =
+                        market
+                            .exports
+                            .iter()
+                            .map(|good| vec![good.symbol.to_string(), String::from("EXPORT")])
+                            .chain(
+                                market.imports.iter().map(|good| {
+                                    vec![good.symbol.to_string(), String::from("IMPORT")]
+                                }),
+                            )
+                            .chain(market.exchange.iter().map(|good| {
+                                vec![good.symbol.to_string(), String::from("EXCHANGE")]
+                            }))
+                            .collect();
+                    print_table(&["GOOD", "TYPE"], rows);
+                }
+            })
+        }
+        Command::Market {
+            command: MarketCommand::Impact,
+        } => {
+            let cells = ImpactModel::load().cells();
+            output(json, &cells, || {
+                if cells.is_empty() {
+                    println!(
+                        "No measured trades yet; assuming 5% per batch and a 60 min half-life"
+                    );
+                    return;
+                }
+                let rows = cells
+                    .iter()
+                    .map(|cell| {
+                        vec![
+                            cell.side.to_string(),
+                            cell.supply.to_string(),
+                            cell.activity.map_or("-".to_string(), |a| a.to_string()),
+                            format!("{:.1}%", cell.impact * 100.0),
+                            cell.samples.to_string(),
+                            cell.half_life
+                                .map_or("-".to_string(), |minutes| format!("{:.0} min", minutes)),
+                            cell.recoveries.to_string(),
+                        ]
+                    })
+                    .collect();
+                print_table(
+                    &[
+                        "SIDE",
+                        "SUPPLY",
+                        "ACTIVITY",
+                        "PER BATCH",
+                        "TRADES",
+                        "HALF-LIFE",
+                        "RECOVERIES",
+                    ],
+                    rows,
+                );
+            })
+        }
+        Command::Market {
+            command: MarketCommand::Plan { ship, waypoints },
+        } => {
+            let symbol = ship.to_uppercase();
+            let ships = client.get_my_ships().await?;
+            let Some(ship) = ships.iter().find(|s| s.symbol == symbol) else {
+                anyhow::bail!("no ship named {}", symbol);
+            };
+            let route: Vec<String> = waypoints.iter().map(|w| w.to_uppercase()).collect();
+            let credits = client.get_my_agent().await?.credits;
+            let plan = trade::plan(
+                &UniverseCache::load(),
+                &ImpactModel::load(),
+                &route,
+                ship.cargo.capacity - ship.cargo.units,
+                credits,
+            );
+            output(json, &plan, || {
+                let rows = plan
+                    .lots
+                    .iter()
+                    .map(|lot| {
+                        vec![
+                            lot.good.to_string(),
+                            lot.units.to_string(),
+                            plan.route[lot.buy_at].clone(),
+                            plan.route[lot.sell_at].clone(),
+                            lot.cost.to_string(),
+                            lot.revenue.to_string(),
+                            (lot.revenue - lot.cost).to_string(),
+                        ]
+                    })
+                    .collect();
+                print_table(
+                    &[
+                        "GOOD", "UNITS", "BUY AT", "SELL AT", "COST", "REVENUE", "PROFIT",
+                    ],
+                    rows,
+                );
+                println!("profit: {}", plan.profit());
+            })
+        }
+        Command::Market {
+            command: MarketCommand::Routes { ship, limit },
+        } => {
+            let symbol = ship.to_uppercase();
+            let ships = client.get_my_ships().await?;
+            let Some(ship) = ships.iter().find(|s| s.symbol == symbol) else {
+                anyhow::bail!("no ship named {}", symbol);
+            };
+            let credits = client.get_my_agent().await?.credits;
+            let routes = trade::find_routes(
+                &UniverseCache::load(),
+                &ImpactModel::load(),
+                &ship.nav.system_symbol,
+                ship.cargo.capacity - ship.cargo.units,
+                credits,
+                limit,
+            );
+            output(json, &routes, || {
+                let rows = routes
+                    .iter()
+                    .map(|plan| {
+                        let goods: Vec<String> = plan
+                            .lots
+                            .iter()
+                            .map(|lot| format!("{} {}", lot.units, lot.good))
+                            .collect();
+                        vec![
+                            plan.route[0].clone(),
+                            plan.route[1].clone(),
+                            goods.join(", "),
+                            plan.cost().to_string(),
+                            plan.profit().to_string(),
+                        ]
+                    })
+                    .collect();
+                print_table(&["FROM", "TO", "GOODS", "COST", "PROFIT"], rows);
+            })
+        }
+        Command::Market {
+            command:
+                MarketCommand::History {
+                    waypoint,
+                    good,
+                    limit,
+                },
+        } => {
+            let waypoint = waypoint.to_uppercase();
+            let good = good.map(|good| good.to_string());
+            let samples: Vec<prices::PriceSample> = prices::load()
+                .into_iter()
+                .rev()
+                .filter(|sample| sample.waypoint == waypoint)
+                .filter(|sample| good.as_ref().is_none_or(|good| sample.good == *good))
+                .take(limit)
+                .collect();
+            output(json, &samples, || {
+                let rows = samples
+                    .iter()
+                    .map(|sample| {
+                        vec![
+                            sample
+                                .time
+                                .with_timezone(&chrono::Local)
+                                .format("%Y-%m-%d %H:%M")
+                                .to_string(),
+                            sample.good.clone(),
+                            sample.purchase_price.to_string(),
+                            sample.sell_price.to_string(),
+                            sample.trade_volume.to_string(),
+                            sample.supply.to_string(),
+                            sample.activity.map(|a| a.to_string()).unwrap_or_default(),
+                        ]
+                    })
+                    .collect();
+                print_table(
+                    &[
+                        "TIME", "GOOD", "BUY", "SELL", "VOLUME", "SUPPLY", "ACTIVITY",
+                    ],
+                    rows,
+                );
+            })
+        }
+        Command::Market {
+            command: MarketCommand::Chain { good },
+        } => {
+            let mut cache = UniverseCache::load();
+            supply::cache_supply_chain(client, &mut cache).await?;
+            let report = supply::report(&cache.supply_chain, &cache.markets, &good.to_string());
+            output(json, &report, || {
+                let none = || String::from("nothing");
+                let list = |goods: &[String]| {
+                    if goods.is_empty() {
+                        none()
+                    } else {
+                        goods.join(", ")
+                    }
+                };
+                println!("Made from: {}", list(&report.inputs));
+                println!("Feeds: {}", list(&report.feeds));
+                println!();
+                if report.markets.is_empty() {
+                    println!("No cached market trades {}", report.good);
+                } else {
+                    let rows = report
+                        .markets
+                        .iter()
+                        .map(|listing| {
+                            vec![
+                                listing.waypoint.clone(),
+                                listing.role.to_string(),
+                                listing.supply.map(|s| s.to_string()).unwrap_or_default(),
+                                listing.activity.map(|a| a.to_string()).unwrap_or_default(),
+                                listing
+                                    .purchase_price
+                                    .map(|p| p.to_string())
+                                    .unwrap_or_default(),
+                                listing
+                                    .sell_price
+                                    .map(|p| p.to_string())
+                                    .unwrap_or_default(),
+                            ]
+                        })
+                        .collect();
+                    print_table(
+                        &["WAYPOINT", "TRADES", "SUPPLY", "ACTIVITY", "BUY", "SELL"],
+                        rows,
+                    );
+                }
+                for bottleneck in &report.bottlenecks {
+                    println!(
+                        "bottleneck: {} is short of {} ({})",
+                        bottleneck.waypoint, bottleneck.input, bottleneck.supply
+                    );
+                }
+            })
+        }
+        Command::Contracts {
+            command: ContractsCommand::Accept { id },
+        } => {
+            let data = client.accept_contract(&id).await?;
+            output(json, &data, || {
+                println!(
+                    "Accepted {}, received {} credits",
+                    data.contract.id, data.contract.terms.payment.on_accepted
+                )
+            })
+        }
+        Command::Contracts {
+            command: ContractsCommand::Fulfill { id },
+        } => {
+            let data = client.fulfill_contract(&id).await?;
+            output(json, &data, || {
+                println!(
+                    "Fulfilled {}, received {} credits",
+                    data.contract.id, data.contract.terms.payment.on_fulfilled
+                )
+            })
+        }
+        Command::Contracts {
+            command: ContractsCommand::Evaluate { id },
+        } => {
+            let contracts = client.get_contracts().await?;
+            let Some(found) = contracts.iter().find(|c| c.id == id) else {
+                anyhow::bail!("no contract with id {}", id);
+            };
+            let ships = client.get_my_ships().await?;
+            let estimate = contract::evaluate(&UniverseCache::load(), &ships, found);
+            output(json, &estimate, || {
+                let rows = estimate
+                    .deliveries
+                    .iter()
+                    .map(|delivery| {
+                        vec![
+                            delivery.good.clone(),
+                            delivery.remaining.to_string(),
+                            delivery.destination.clone(),
+                            delivery.market.clone().unwrap_or_else(|| "-".to_string()),
+                            delivery.price.map_or("-".to_string(), |p| p.to_string()),
+                            delivery.cost.map_or("-".to_string(), |c| c.to_string()),
+                        ]
+                    })
+                    .collect();
+                print_table(
+                    &["GOOD", "UNITS", "DELIVER TO", "BUY AT", "PRICE", "COST"],
+                    rows,
+                );
+                println!("payment: {}", estimate.payment);
+                println!(
+                    "cost: {}{}",
+                    estimate.cost,
+                    if estimate.priced {
+                        ""
+                    } else {
+                        " (some goods unpriced)"
+                    }
+                );
+                println!("profit: {}", estimate.profit());
+                match (&estimate.ship, estimate.travel_seconds) {
+                    (Some(ship), Some(seconds)) => println!(
+                        "travel: {} min with {} in {} trip(s), deadline {} ({})",
+                        seconds / 60,
+                        ship,
+                        estimate.trips,
+                        found.terms.deadline,
+                        if estimate.achievable == Some(true) {
+                            "achievable"
+                        } else {
+                            "out of reach"
+                        }
+                    ),
+                    _ => println!("travel: no hauler in the destination's system"),
+                }
+                println!("verdict: {}", estimate.verdict());
+            })
+        }
+        Command::Contracts {
+            command: ContractsCommand::List,
+        } => {
+            let contracts = client.get_contracts().await?;
+            let ships = client.get_my_ships().await?;
+            let cache = UniverseCache::load();
+            output(json, &contracts, || {
+                let rows = contracts
+                    .iter()
+                    .map(|contract| {
+                        let status = if contract.fulfilled {
+                            "FULFILLED"
+                        } else if contract.accepted {
+                            "ACCEPTED"
+                        } else {
+                            "OPEN"
+                        };
+                        let deliveries = contract
+                            .terms
+                            .deliver
+                            .iter()
+                            .flatten()
+                            .map(|good| {
+                                format!(
+                                    "{} {}/{} to {}",
+                                    good.trade_symbol,
+                                    good.units_fulfilled,
+                                    good.units_required,
+                                    good.destination_symbol
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        vec![
+                            contract.id.clone(),
+                            contract.faction_symbol.clone(),
+                            status.to_string(),
+                            (contract.terms.payment.on_accepted
+                                + contract.terms.payment.on_fulfilled)
+                                .to_string(),
+                            contract.terms.deadline.clone(),
+                            deliveries,
+                            if contract.fulfilled {
+                                "-".to_string()
+                            } else {
+                                estimate_cell(&contract::evaluate(&cache, &ships, contract))
+                            },
+                        ]
+                    })
+                    .collect();
+                print_table(
+                    &[
+                        "ID", "FACTION", "STATUS", "PAYMENT", "DEADLINE", "DELIVER", "ESTIMATE",
+                    ],
+                    rows,
+                );
+            })
+        }
+        Command::Systems {
+            command:
+                SystemsCommand::Waypoints {
+                    system,
+                    waypoint_type,
+                    waypoint_trait,
+                },
+        } => {
+            let waypoints = client
+                .get_system_waypoints(&system.to_uppercase(), waypoint_type, waypoint_trait)
+                .await?;
+            output(json, &waypoints, || {
+                let rows = waypoints
+                    .iter()
+                    .map(|waypoint| {
+                        vec![
+                            waypoint.symbol.clone(),
+                            waypoint.r#type.to_string(),
+                            format!("{},{}", waypoint.x, waypoint.y),
+                            waypoint
+                                .traits
+                                .iter()
+                                .map(|t| t.symbol.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        ]
+                    })
+                    .collect();
+                print_table(&["WAYPOINT", "TYPE", "POSITION", "TRAITS"], rows);
+            })
+        }
+        Command::Systems {
+            command:
+                SystemsCommand::Search {
+                    system,
+                    waypoint_type,
+                    traits,
+                    modifiers,
+                    ship,
+                },
+        } => {
+            let mut cache = UniverseCache::load();
+            let system = system.map(|system| system.to_uppercase());
+            if let Some(system) = &system {
+                search::cache_waypoints(client, &mut cache, system).await?;
+            }
+            let ship = match ship {
+                Some(symbol) => {
+                    let symbol = symbol.to_uppercase();
+                    let ships = client.get_my_ships().await?;
+                    let Some(ship) = ships.into_iter().find(|s| s.symbol == symbol) else {
+                        anyhow::bail!("no ship named {}", symbol);
+                    };
+                    Some(ship)
+                }
+                None => None,
+            };
+            let query = WaypointQuery {
+                waypoint_type,
+                traits,
+                modifiers,
+            };
+            let matches = search::search(&cache, system.as_deref(), &query, ship.as_ref());
+            output(json, &matches, || {
+                let rows = matches
+                    .iter()
+                    .map(|found| {
+                        let distance = match (found.distance, found.system_distance) {
+                            (Some(distance), _) => format!("{:.0}", distance),
+                            (None, Some(distance)) => format!("{:.0} (system)", distance),
+                            (None, None) => String::from("-"),
+                        };
+                        vec![
+                            found.waypoint.symbol.clone(),
+                            found.waypoint.r#type.to_string(),
+                            distance,
+                            found
+                                .waypoint
+                                .traits
+                                .iter()
+                                .map(|t| t.symbol.to_string())
+                                .chain(
+                                    found
+                                        .waypoint
+                                        .modifiers
+                                        .iter()
+                                        .flatten()
+                                        .map(|m| m.symbol.to_string()),
+                                )
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        ]
+                    })
+                    .collect();
+                print_table(&["WAYPOINT", "TYPE", "DISTANCE", "TRAITS"], rows);
+            })
+        }
+        Command::Systems {
+            command: SystemsCommand::Construction { system },
+        } => {
+            let mut cache = UniverseCache::load();
+            let systems = match system {
+                Some(system) => vec![system.to_uppercase()],
+                None => {
+                    let agent = client.get_my_agent().await?;
+                    let ships = client.get_my_ships().await?;
+                    construction::our_systems(Some(&agent), &ships)
+                }
+            };
+            construction::cache_constructions(client, &mut cache, &systems).await?;
+            let reports = construction::reports(&cache, &systems);
+            output(json, &reports, || {
+                if reports.is_empty() {
+                    println!("Nothing under construction in {}", systems.join(", "));
+                }
+                for report in &reports {
+                    let site = &report.construction;
+                    println!(
+                        "{}{}",
+                        site.symbol,
+                        if site.is_complete { " (complete)" } else { "" }
+                    );
+                    let rows = site
+                        .materials
+                        .iter()
+                        .map(|material| {
+                            let source = report
+                                .sources
+                                .iter()
+                                .find(|source| source.good == material.trade_symbol);
+                            vec![
+                                material.trade_symbol.to_string(),
+                                format!("{}/{}", material.fulfilled, material.required),
+                                source
+                                    .and_then(|source| source.market.clone())
+                                    .unwrap_or_default(),
+                                source
+                                    .and_then(|source| source.price)
+                                    .map(|price| price.to_string())
+                                    .unwrap_or_default(),
+                                source
+                                    .and_then(|source| source.cost)
+                                    .map(|cost| cost.to_string())
+                                    .unwrap_or_default(),
+                            ]
+                        })
+                        .collect();
+                    print_table(&["MATERIAL", "DELIVERED", "BUY AT", "PRICE", "COST"], rows);
+                    println!("Known cost to finish: {}", report.known_cost());
+                    println!();
+                }
+            })
+        }
+        Command::Ledger {
+            command: LedgerCommand::List { limit },
+        } => {
+            let entries = ledger::load();
+            let recent = &entries[entries.len().saturating_sub(limit)..];
+            output(json, &recent, || {
+                let rows = recent
+                    .iter()
+                    .filter(|entry| entry.kind != ledger::EntryKind::Balance)
+                    .map(|entry| {
+                        vec![
+                            entry
+                                .time
+                                .with_timezone(&chrono::Local)
+                                .format("%Y-%m-%d %H:%M")
+                                .to_string(),
+                            entry.kind.to_string(),
+                            entry.amount.to_string(),
+                            entry.ship.clone().unwrap_or_default(),
+                            entry
+                                .item
+                                .clone()
+                                .or_else(|| entry.contract.clone())
+                                .unwrap_or_default(),
+                            entry.behaviour.clone().unwrap_or_default(),
+                        ]
+                    })
+                    .collect();
+                print_table(
+                    &["TIME", "KIND", "CREDITS", "SHIP", "ITEM", "BEHAVIOUR"],
+                    rows,
+                );
+            })
+        }
+        Command::Ledger {
+            command: LedgerCommand::Pnl { period, by },
+        } => {
+            let report = ledger::profit_and_loss(&ledger::load(), period, by);
+            output(json, &report, || {
+                let rows = report
+                    .iter()
+                    .map(|row| {
+                        vec![
+                            period.label(row.period),
+                            row.group.clone().unwrap_or_else(|| String::from("(none)")),
+                            row.income.to_string(),
+                            row.expenses.to_string(),
+                            row.net().to_string(),
+                        ]
+                    })
+                    .collect();
+                print_table(&["PERIOD", by.label(), "INCOME", "EXPENSES", "NET"], rows);
+            })
+        }
+        Command::Agents {
+            command: AgentsCommand::List { sort, limit, sync },
+        } => {
+            let mut rivals = Rivals::load();
+            if sync || rivals.agents.is_empty() {
+                rivals.agents = client.get_all_agents(&Progress::default()).await?;
+                rivals.save()?;
+            }
+            let agents: Vec<&Agent> = rivals::sorted(&rivals.agents, sort)
+                .into_iter()
+                .take(limit)
+                .collect();
+            output(json, &agents, || {
+                let rows = agents
+                    .iter()
+                    .map(|agent| {
+                        vec![
+                            if rivals.is_watched(&agent.symbol) {
+                                String::from("*")
+                            } else {
+                                String::new()
+                            },
+                            agent.symbol.clone(),
+                            agent.credits.to_string(),
+                            agent.ship_count.to_string(),
+                            agent.headquarters.clone(),
+                            agent.starting_faction.clone(),
+                        ]
+                    })
+                    .collect();
+                print_table(&["", "AGENT", "CREDITS", "SHIPS", "HQ", "FACTION"], rows);
+            })
+        }
+        Command::Agents {
+            command: AgentsCommand::Show { agent },
+        } => {
+            let agent = client.get_agent(&agent.to_uppercase()).await?;
+            let mut rivals = Rivals::load();
+            rivals.update(&agent);
+            rivals.save()?;
+            output(json, &agent, || {
+                println!("Agent: {}", agent.symbol);
+                println!("Credits: {}", agent.credits);
+                println!("Ships: {}", agent.ship_count);
+                println!("HQ: {}", agent.headquarters);
+                println!("Faction: {}", agent.starting_faction);
+                if rivals.is_watched(&agent.symbol) {
+                    println!("Watched");
+                }
+            })
+        }
+        Command::Agents {
+            command: AgentsCommand::Watch { agent },
+        } => {
+            // Fetched first so a typo isn't watched
+            let agent = client.get_agent(&agent.to_uppercase()).await?;
+            rivals::record(std::slice::from_ref(&agent))?;
+            let mut rivals = Rivals::load();
+            rivals.update(&agent);
+            rivals.set_watched(&agent.symbol, true)?;
+            output(json, &agent.symbol, || {
+                println!(
+                    "Watching {}; the daemon records their credits every {} minutes",
+                    agent.symbol,
+                    rivals::POLL_MINUTES
+                )
+            })
+        }
+        Command::Agents {
+            command: AgentsCommand::Unwatch { agent },
+        } => {
+            let agent = agent.to_uppercase();
+            let mut rivals = Rivals::load();
+            if !rivals.is_watched(&agent) {
+                anyhow::bail!("{} is not watched", agent);
+            }
+            rivals.set_watched(&agent, false)?;
+            output(json, &agent, || println!("Stopped watching {}", agent))
+        }
+        Command::Agents {
+            command: AgentsCommand::History { agent, limit },
+        } => {
+            let agent = agent.to_uppercase();
+            let samples: Vec<rivals::RivalSample> = rivals::history()
+                .into_iter()
+                .rev()
+                .filter(|sample| sample.agent == agent)
+                .take(limit)
+                .collect();
+            output(json, &samples, || {
+                let rows = samples
+                    .iter()
+                    .map(|sample| {
+                        vec![
+                            sample
+                                .time
+                                .with_timezone(&chrono::Local)
+                                .format("%Y-%m-%d %H:%M")
+                                .to_string(),
+                            sample.credits.to_string(),
+                            sample.ship_count.to_string(),
+                        ]
+                    })
+                    .collect();
+                print_table(&["TIME", "CREDITS", "SHIPS"], rows);
+            })
+        }
+        Command::Factions {
+            command: FactionsCommand::List,
+        } => {
+            let factions = client.get_factions().await?;
+            output(json, &factions, || {
+                let rows = factions
+                    .iter()
+                    .map(|faction| {
+                        vec![
+                            faction.symbol.to_string(),
+                            faction.name.clone(),
+                            faction.headquarters.clone().unwrap_or_default(),
+                            if faction.is_recruiting {
+                                String::from("yes")
+                            } else {
+                                String::from("no")
+                            },
+                        ]
+                    })
+                    .collect();
+                print_table(&["FACTION", "NAME", "HQ", "RECRUITING"], rows);
+            })
+        }
+        Command::Factions {
+            command: FactionsCommand::Show { faction },
+        } => {
+            let faction = client.get_faction(&faction.to_uppercase()).await?;
+            output(json, &faction, || {
+                println!("{} ({})", faction.name, faction.symbol);
+                println!("{}", faction.description);
+                println!("HQ: {}", faction.headquarters.as_deref().unwrap_or("none"));
+                println!(
+                    "Recruiting: {}",
+                    if faction.is_recruiting { "yes" } else { "no" }
+                );
+                println!();
+                for faction_trait in &faction.traits {
+                    println!("{}: {}", faction_trait.name, faction_trait.description);
+                }
+            })
+        }
+        Command::Behaviours {
+            command: BehavioursCommand::List,
+        } => {
+            let behaviours = client.get_behaviours().await?;
+            output(json, &behaviours, || {
+                let rows = behaviours
+                    .iter()
+                    .map(|(ship, behaviour)| vec![ship.clone(), behaviour.to_string()])
+                    .collect();
+                print_table(&["SHIP", "BEHAVIOUR"], rows);
+            })
+        }
+        Command::Behaviours {
+            command: BehavioursCommand::AutoRepair { setting },
+        } => {
+            let enabled = matches!(setting, Toggle::On);
+            client.set_auto_repair(enabled).await?;
+            output(json, &enabled, || {
+                println!(
+                    "Automatic repairs {}",
+                    if enabled { "enabled" } else { "disabled" }
+                )
+            })
+        }
+        Command::Behaviours {
+            command: BehavioursCommand::AutoContracts { setting },
+        } => {
+            let enabled = matches!(setting, Toggle::On);
+            client.set_auto_contracts(enabled).await?;
+            output(json, &enabled, || {
+                println!(
+                    "Automatic contracts {}",
+                    if enabled { "enabled" } else { "disabled" }
+                )
+            })
+        }
+        Command::Behaviours {
+            command: BehavioursCommand::AutoRescue { setting },
+        } => {
+            let enabled = matches!(setting, Toggle::On);
+            client.set_auto_rescue(enabled).await?;
+            output(json, &enabled, || {
+                println!(
+                    "Automatic rescues {}",
+                    if enabled { "enabled" } else { "disabled" }
+                )
+            })
+        }
+        Command::Behaviours {
+            command:
+                BehavioursCommand::Rescue {
+                    command: RescueCommand::Cancel { ship },
+                },
+        } => {
+            let symbol = ship.to_uppercase();
+            let cancelled = client.cancel_rescue(&symbol).await?;
+            output(json, &cancelled, || {
+                if cancelled {
+                    println!("Rescue of {} cancelled", symbol);
+                } else {
+                    println!("{} is not being rescued", symbol);
+                }
+            })
+        }
+        Command::Behaviours {
+            command: BehavioursCommand::MarketWatch { setting, interval },
+        } => {
+            let interval = matches!(setting, Toggle::On).then_some(interval);
+            client.set_market_watch(interval).await?;
+            output(json, &interval, || match interval {
+                Some(minutes) => println!("Market watch enabled, polling every {} min", minutes),
+                None => println!("Market watch disabled"),
+            })
+        }
+        Command::Daemon => unreachable!("the daemon is started from main"),
+    }
+}
+
+/// Profit and verdict of an open contract for the contracts table.
+fn estimate_cell(estimate: &contract::Estimate) -> String {
+    match estimate.travel_seconds {
+        Some(seconds) => format!(
+            "{} {:+} in {}m",
+            estimate.verdict(),
+            estimate.profit(),
+            seconds / 60
+        ),
+        None => format!("{} {:+}", estimate.verdict(), estimate.profit()),
+    }
+}
+
+/// Prints `value` as JSON, or calls `human` to print it for people.
+fn output<T: Serialize>(json: bool, value: &T, human: impl FnOnce()) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(value)?);
+    } else {
+        human();
+    }
+    Ok(())
+}
+
+fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.width()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+
+    let format_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - cell.width())))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", format_row(headers.to_vec()));
+    for row in &rows {
+        println!("{}", format_row(row.iter().map(String::as_str).collect()));
+    }
+}
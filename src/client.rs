@@ -1,41 +1,1430 @@
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use spacetraders_api::apis::agents_api;
 use spacetraders_api::apis::configuration::Configuration;
+use spacetraders_api::apis::contracts_api;
+use spacetraders_api::apis::factions_api;
 use spacetraders_api::apis::fleet_api;
 use spacetraders_api::apis::global_api;
 use spacetraders_api::apis::systems_api;
+use spacetraders_api::apis::{Error, ResponseContent};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
 
+use crate::behaviour::Behaviour;
+use crate::cache;
+use crate::daemon::{self, Request};
+use crate::events::{self, EventKind};
+use crate::ledger::{self, EntryKind, LedgerEntry};
+use crate::prices;
+use crate::rescue::Stranding;
+
+/// An error response from the SpaceTraders API, carrying the game's error code.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiError {
+    pub status: u16,
+    pub code: Option<i32>,
+    pub message: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "{} (code {})", self.message, code),
+            None => write!(f, "{} (status {})", self.message, self.status),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    error: ErrorDetails,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetails {
+    message: String,
+    code: Option<i32>,
+}
+
+/// Converts a generated client error, unpacking the game's error body when there is one.
+fn api_error<T>(error: Error<T>) -> anyhow::Error
+where
+    T: fmt::Debug + Send + Sync + 'static,
+{
+    match error {
+        Error::ResponseError(response) => {
+            let status = response.status.as_u16();
+            match serde_json::from_str::<ErrorBody>(&response.content) {
+                Ok(body) => ApiError {
+                    status,
+                    code: body.error.code,
+                    message: body.error.message,
+                }
+                .into(),
+                Err(_) => ApiError {
+                    status,
+                    code: None,
+                    message: response.content,
+                }
+                .into(),
+            }
+        }
+        other => other.into(),
+    }
+}
+
+/// Writes a ledger entry; a failure to record must not fail the call that made money.
+fn book(entry: LedgerEntry) {
+    if matches!(
+        entry.kind,
+        EntryKind::Purchase | EntryKind::Sale | EntryKind::Refuel
+    ) {
+        if let Some(waypoint) = &entry.waypoint {
+            prices::traded_at(waypoint);
+        }
+    }
+    if let Err(e) = ledger::record(entry) {
+        events::record_error("Recording ledger entry failed", &e);
+    }
+}
+
+/// Logs wear and damage reported by actions such as flying and mining.
+fn record_condition_events(
+    ship_symbol: &str,
+    condition_events: &[spacetraders_api::models::ShipConditionEvent],
+) {
+    for event in condition_events {
+        events::record(
+            log::Level::Warn,
+            EventKind::Condition,
+            format!(
+                "{} {}: {}",
+                ship_symbol,
+                format!("{:?}", event.component).to_lowercase(),
+                event.name
+            ),
+        );
+    }
+}
+
+/// Module changes report the same fields as mount changes under another type.
+fn module_entry(
+    transaction: &spacetraders_api::models::InstallShipModule201ResponseDataTransaction,
+    balance: i64,
+) -> LedgerEntry {
+    LedgerEntry::modification(
+        &spacetraders_api::models::ShipModificationTransaction::new(
+            transaction.waypoint_symbol.clone(),
+            transaction.ship_symbol.clone(),
+            transaction.trade_symbol.clone(),
+            transaction.total_price,
+            transaction.timestamp.clone(),
+        ),
+        balance,
+    )
+}
+
+/// Sustained request rate the server allows each agent.
+const REQUEST_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Spaces out API calls so concurrent callers queue up rather than being
+/// turned away with 429s.
+struct RateLimiter {
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new() -> Self {
+        Self {
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits for the next free slot.
+    async fn wait(&self) {
+        let slot = {
+            let mut next = self.next.lock().await;
+            let slot = (*next).max(Instant::now());
+            *next = slot + REQUEST_INTERVAL;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Pages fetched so far by a paged download, shared with whoever waits on
+/// it. Clones count the same download.
+#[derive(Clone, Default)]
+pub struct Progress {
+    pages: Arc<AtomicI32>,
+    total: Arc<AtomicI32>,
+}
+
+impl Progress {
+    /// Pages fetched, and pages in all once the first page has said.
+    pub fn get(&self) -> (i32, Option<i32>) {
+        let total = self.total.load(Ordering::Relaxed);
+        (
+            self.pages.load(Ordering::Relaxed),
+            (total > 0).then_some(total),
+        )
+    }
+
+    fn page_done(&self, items: i32, page_size: i32) {
+        self.pages.fetch_add(1, Ordering::Relaxed);
+        self.total
+            .store((items + page_size - 1) / page_size, Ordering::Relaxed);
+    }
+}
+
+/// Clones share one rate limiter, so they can run calls side by side.
+#[derive(Clone)]
 pub struct SpaceTradersClient {
     config: Configuration,
+    /// Daemon socket that calls go through instead of the API, when attached.
+    daemon: Option<PathBuf>,
+    limiter: Arc<RateLimiter>,
 }
 
 impl SpaceTradersClient {
     pub fn new(api_token: String) -> Self {
         let mut config = Configuration::new();
         config.bearer_access_token = Some(api_token);
-        Self { config }
+        Self {
+            config,
+            daemon: None,
+            limiter: Arc::new(RateLimiter::new()),
+        }
+    }
+
+    /// A thin client that leaves every call to the daemon listening on `socket`.
+    pub fn attach(socket: PathBuf) -> Self {
+        Self {
+            config: Configuration::new(),
+            daemon: Some(socket),
+            limiter: Arc::new(RateLimiter::new()),
+        }
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.daemon.is_some()
+    }
+
+    /// The daemon socket, or an error explaining that behaviours need one.
+    fn require_daemon(&self) -> Result<&PathBuf> {
+        self.daemon.as_ref().ok_or_else(|| {
+            anyhow::anyhow!(
+                "behaviours run in the daemon; start `spacetraders daemon` and pass --attach"
+            )
+        })
     }
 
     pub async fn get_status(&self) -> Result<spacetraders_api::models::GetStatus200Response> {
-        Ok(global_api::get_status(&self.config).await?)
+        if let Some(socket) = &self.daemon {
+            return daemon::call(socket, Request::Status).await;
+        }
+        self.limiter.wait().await;
+        global_api::get_status(&self.config)
+            .await
+            .map_err(api_error)
     }
 
     pub async fn get_my_agent(&self) -> Result<spacetraders_api::models::Agent> {
-        let response = agents_api::get_my_agent(&self.config).await?;
+        if let Some(socket) = &self.daemon {
+            return daemon::call(socket, Request::Agent).await;
+        }
+        self.limiter.wait().await;
+        let response = agents_api::get_my_agent(&self.config)
+            .await
+            .map_err(api_error)?;
+        if let Err(e) = ledger::record_balance(response.data.credits) {
+            events::record_error("Recording balance failed", &e);
+        }
         Ok(*response.data)
     }
 
-    pub async fn get_my_ships(&self) -> Result<Vec<spacetraders_api::models::Ship>> {
-        let response = fleet_api::get_my_ships(&self.config, None, None).await?;
+    /// Another agent's public details.
+    pub async fn get_agent(&self, agent_symbol: &str) -> Result<spacetraders_api::models::Agent> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::PublicAgent {
+                    symbol: agent_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = agents_api::get_agent(&self.config, agent_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    /// Pages through every agent in the universe.
+    pub async fn get_all_agents(
+        &self,
+        progress: &Progress,
+    ) -> Result<Vec<spacetraders_api::models::Agent>> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(socket, Request::AllAgents).await;
+        }
+        const PAGE_SIZE: i32 = 20;
+        let mut agents = Vec::new();
+        let mut page = 1;
+        loop {
+            self.limiter.wait().await;
+            let response = agents_api::get_agents(&self.config, Some(page), Some(PAGE_SIZE))
+                .await
+                .map_err(api_error)?;
+            agents.extend(response.data);
+            progress.page_done(response.meta.total, PAGE_SIZE);
+            if page * PAGE_SIZE >= response.meta.total {
+                return Ok(agents);
+            }
+            page += 1;
+        }
+    }
+
+    /// Pages through every faction.
+    pub async fn get_factions(&self) -> Result<Vec<spacetraders_api::models::Faction>> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(socket, Request::Factions).await;
+        }
+        const PAGE_SIZE: i32 = 20;
+        let mut factions = Vec::new();
+        let mut page = 1;
+        loop {
+            self.limiter.wait().await;
+            let response = factions_api::get_factions(&self.config, Some(page), Some(PAGE_SIZE))
+                .await
+                .map_err(api_error)?;
+            factions.extend(response.data);
+            if page * PAGE_SIZE >= response.meta.total {
+                return Ok(factions);
+            }
+            page += 1;
+        }
+    }
+
+    pub async fn get_faction(
+        &self,
+        faction_symbol: &str,
+    ) -> Result<spacetraders_api::models::Faction> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Faction {
+                    symbol: faction_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = factions_api::get_faction(&self.config, faction_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    pub async fn get_contracts(&self) -> Result<Vec<spacetraders_api::models::Contract>> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(socket, Request::Contracts).await;
+        }
+        self.limiter.wait().await;
+        let response = contracts_api::get_contracts(&self.config, None, None)
+            .await
+            .map_err(api_error)?;
         Ok(response.data)
     }
 
+    /// Pages through every ship in the fleet.
+    pub async fn get_my_ships(&self) -> Result<Vec<spacetraders_api::models::Ship>> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(socket, Request::Ships).await;
+        }
+        const PAGE_SIZE: i32 = 20;
+        let mut ships = Vec::new();
+        let mut page = 1;
+        loop {
+            self.limiter.wait().await;
+            let response = fleet_api::get_my_ships(&self.config, Some(page), Some(PAGE_SIZE))
+                .await
+                .map_err(api_error)?;
+            ships.extend(response.data);
+            if page * PAGE_SIZE >= response.meta.total {
+                return Ok(ships);
+            }
+            page += 1;
+        }
+    }
+
     pub async fn get_systems(
         &self,
         page: Option<i32>,
         limit: Option<i32>,
     ) -> Result<Vec<spacetraders_api::models::System>> {
-        let response = systems_api::get_systems(&self.config, page, limit).await?;
+        if let Some(socket) = &self.daemon {
+            return daemon::call(socket, Request::Systems { page, limit }).await;
+        }
+        self.limiter.wait().await;
+        let response = systems_api::get_systems(&self.config, page, limit)
+            .await
+            .map_err(api_error)?;
         Ok(response.data)
     }
+
+    /// Pages through every system in the universe.
+    pub async fn get_all_systems(
+        &self,
+        progress: &Progress,
+    ) -> Result<Vec<spacetraders_api::models::System>> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(socket, Request::AllSystems).await;
+        }
+        const PAGE_SIZE: i32 = 20;
+        let mut systems = Vec::new();
+        let mut page = 1;
+        loop {
+            self.limiter.wait().await;
+            let response = systems_api::get_systems(&self.config, Some(page), Some(PAGE_SIZE))
+                .await
+                .map_err(api_error)?;
+            systems.extend(response.data);
+            progress.page_done(response.meta.total, PAGE_SIZE);
+            if page * PAGE_SIZE >= response.meta.total {
+                return Ok(systems);
+            }
+            page += 1;
+        }
+    }
+
+    /// Pages through the waypoints of a system, optionally filtered by type and trait.
+    pub async fn get_system_waypoints(
+        &self,
+        system_symbol: &str,
+        waypoint_type: Option<spacetraders_api::models::WaypointType>,
+        waypoint_trait: Option<spacetraders_api::models::WaypointTraitSymbol>,
+    ) -> Result<Vec<spacetraders_api::models::Waypoint>> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::SystemWaypoints {
+                    system: system_symbol.to_string(),
+                    waypoint_type,
+                    waypoint_trait,
+                },
+            )
+            .await;
+        }
+        const PAGE_SIZE: i32 = 20;
+        let waypoint_trait = waypoint_trait.map(|t| t.to_string());
+        let mut waypoints = Vec::new();
+        let mut page = 1;
+        loop {
+            self.limiter.wait().await;
+            let response = systems_api::get_system_waypoints(
+                &self.config,
+                system_symbol,
+                Some(page),
+                Some(PAGE_SIZE),
+                waypoint_type,
+                waypoint_trait.as_deref(),
+            )
+            .await
+            .map_err(api_error)?;
+            waypoints.extend(response.data);
+            if page * PAGE_SIZE >= response.meta.total {
+                return Ok(waypoints);
+            }
+            page += 1;
+        }
+    }
+
+    pub async fn get_waypoint(
+        &self,
+        system_symbol: &str,
+        waypoint_symbol: &str,
+    ) -> Result<spacetraders_api::models::Waypoint> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Waypoint {
+                    system: system_symbol.to_string(),
+                    waypoint: waypoint_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = systems_api::get_waypoint(&self.config, system_symbol, waypoint_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    pub async fn get_jump_gate(
+        &self,
+        system_symbol: &str,
+        waypoint_symbol: &str,
+    ) -> Result<spacetraders_api::models::JumpGate> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::JumpGate {
+                    system: system_symbol.to_string(),
+                    waypoint: waypoint_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = systems_api::get_jump_gate(&self.config, system_symbol, waypoint_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    /// Materials a waypoint under construction still needs.
+    pub async fn get_construction(
+        &self,
+        system_symbol: &str,
+        waypoint_symbol: &str,
+    ) -> Result<spacetraders_api::models::Construction> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Construction {
+                    system: system_symbol.to_string(),
+                    waypoint: waypoint_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = systems_api::get_construction(&self.config, system_symbol, waypoint_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    pub async fn get_market(
+        &self,
+        system_symbol: &str,
+        waypoint_symbol: &str,
+    ) -> Result<spacetraders_api::models::Market> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Market {
+                    system: system_symbol.to_string(),
+                    waypoint: waypoint_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = systems_api::get_market(&self.config, system_symbol, waypoint_symbol)
+            .await
+            .map_err(api_error)?;
+        if let Err(e) = prices::record(&response.data) {
+            events::record_error("Recording prices failed", &e);
+        }
+        Ok(*response.data)
+    }
+
+    /// Like `get_market`, but records the prices even if they look unchanged
+    /// since the last poll, for a sample taken right before trading.
+    pub async fn sample_market(
+        &self,
+        system_symbol: &str,
+        waypoint_symbol: &str,
+    ) -> Result<spacetraders_api::models::Market> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::SampleMarket {
+                    system: system_symbol.to_string(),
+                    waypoint: waypoint_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        prices::traded_at(waypoint_symbol);
+        self.get_market(system_symbol, waypoint_symbol).await
+    }
+
+    /// Goods each manufactured good is made from, keyed by the good. The
+    /// generated model has no map for this response, so it is read by hand.
+    pub async fn get_supply_chain(&self) -> Result<BTreeMap<String, Vec<String>>> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(socket, Request::SupplyChain).await;
+        }
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct SupplyChain {
+            export_to_import_map: BTreeMap<String, Vec<String>>,
+        }
+        #[derive(Deserialize)]
+        struct Body {
+            data: SupplyChain,
+        }
+
+        let mut request = self
+            .config
+            .client
+            .get(format!("{}/market/supply-chain", self.config.base_path));
+        if let Some(user_agent) = &self.config.user_agent {
+            request = request.header("user-agent", user_agent);
+        }
+        self.limiter.wait().await;
+        let response = request
+            .send()
+            .await
+            .map_err(|e| api_error(Error::<()>::Reqwest(e)))?;
+        let status = response.status();
+        let content = response
+            .text()
+            .await
+            .map_err(|e| api_error(Error::<()>::Reqwest(e)))?;
+        if !status.is_success() {
+            return Err(api_error(Error::<()>::ResponseError(ResponseContent {
+                status,
+                content,
+                entity: None,
+            })));
+        }
+        Ok(serde_json::from_str::<Body>(&content)?
+            .data
+            .export_to_import_map)
+    }
+
+    pub async fn get_shipyard(
+        &self,
+        system_symbol: &str,
+        waypoint_symbol: &str,
+    ) -> Result<spacetraders_api::models::Shipyard> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Shipyard {
+                    system: system_symbol.to_string(),
+                    waypoint: waypoint_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = systems_api::get_shipyard(&self.config, system_symbol, waypoint_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    pub async fn purchase_ship(
+        &self,
+        ship_type: spacetraders_api::models::ShipType,
+        waypoint_symbol: &str,
+    ) -> Result<spacetraders_api::models::PurchaseShip201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::PurchaseShip {
+                    ship_type,
+                    waypoint: waypoint_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::PurchaseShipRequest::new(
+            ship_type,
+            waypoint_symbol.to_string(),
+        );
+        self.limiter.wait().await;
+        let response = fleet_api::purchase_ship(&self.config, Some(request))
+            .await
+            .map_err(api_error)?;
+        book(LedgerEntry::shipyard(
+            &response.data.transaction,
+            response.data.agent.credits,
+        ));
+        Ok(*response.data)
+    }
+
+    pub async fn navigate_ship(
+        &self,
+        ship_symbol: &str,
+        waypoint_symbol: &str,
+    ) -> Result<spacetraders_api::models::NavigateShip200ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Navigate {
+                    ship: ship_symbol.to_string(),
+                    waypoint: waypoint_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        let request =
+            spacetraders_api::models::NavigateShipRequest::new(waypoint_symbol.to_string());
+        self.limiter.wait().await;
+        let response = fleet_api::navigate_ship(&self.config, ship_symbol, Some(request))
+            .await
+            .map_err(api_error)?;
+        record_condition_events(ship_symbol, &response.data.events);
+        Ok(*response.data)
+    }
+
+    pub async fn dock_ship(&self, ship_symbol: &str) -> Result<spacetraders_api::models::ShipNav> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Dock {
+                    ship: ship_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = fleet_api::dock_ship(&self.config, ship_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data.nav)
+    }
+
+    pub async fn orbit_ship(&self, ship_symbol: &str) -> Result<spacetraders_api::models::ShipNav> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Orbit {
+                    ship: ship_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = fleet_api::orbit_ship(&self.config, ship_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data.nav)
+    }
+
+    /// Sets how the ship flies: DRIFT uses almost no fuel but is slow.
+    pub async fn set_flight_mode(
+        &self,
+        ship_symbol: &str,
+        mode: spacetraders_api::models::ShipNavFlightMode,
+    ) -> Result<spacetraders_api::models::PatchShipNav200ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::FlightMode {
+                    ship: ship_symbol.to_string(),
+                    mode,
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::PatchShipNavRequest {
+            flight_mode: Some(mode),
+        };
+        self.limiter.wait().await;
+        let response = fleet_api::patch_ship_nav(&self.config, ship_symbol, Some(request))
+            .await
+            .map_err(api_error)?;
+        record_condition_events(ship_symbol, &response.data.events);
+        Ok(*response.data)
+    }
+
+    pub async fn purchase_cargo(
+        &self,
+        ship_symbol: &str,
+        trade_symbol: spacetraders_api::models::TradeSymbol,
+        units: i32,
+    ) -> Result<spacetraders_api::models::SellCargo201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::PurchaseCargo {
+                    ship: ship_symbol.to_string(),
+                    good: trade_symbol,
+                    units,
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::PurchaseCargoRequest::new(trade_symbol, units);
+        self.limiter.wait().await;
+        let response = fleet_api::purchase_cargo(&self.config, ship_symbol, Some(request))
+            .await
+            .map_err(api_error)?;
+        book(LedgerEntry::market(
+            &response.data.transaction,
+            response.data.agent.credits,
+            false,
+        ));
+        Ok(*response.data)
+    }
+
+    /// Dumps cargo into space.
+    pub async fn jettison_cargo(
+        &self,
+        ship_symbol: &str,
+        trade_symbol: spacetraders_api::models::TradeSymbol,
+        units: i32,
+    ) -> Result<spacetraders_api::models::Jettison200ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Jettison {
+                    ship: ship_symbol.to_string(),
+                    good: trade_symbol,
+                    units,
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::JettisonRequest::new(trade_symbol, units);
+        self.limiter.wait().await;
+        let response = fleet_api::jettison(&self.config, ship_symbol, Some(request))
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    /// Mines the asteroid the ship is orbiting.
+    pub async fn extract_resources(
+        &self,
+        ship_symbol: &str,
+    ) -> Result<spacetraders_api::models::ExtractResources201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Extract {
+                    ship: ship_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = fleet_api::extract_resources(&self.config, ship_symbol, None)
+            .await
+            .map_err(api_error)?;
+        record_condition_events(ship_symbol, &response.data.events);
+        Ok(*response.data)
+    }
+
+    /// Moves cargo to another ship at the same waypoint, returning the
+    /// sender's cargo afterwards.
+    pub async fn transfer_cargo(
+        &self,
+        ship_symbol: &str,
+        to_ship_symbol: &str,
+        trade_symbol: spacetraders_api::models::TradeSymbol,
+        units: i32,
+    ) -> Result<spacetraders_api::models::ShipCargo> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Transfer {
+                    ship: ship_symbol.to_string(),
+                    to: to_ship_symbol.to_string(),
+                    good: trade_symbol,
+                    units,
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::TransferCargoRequest::new(
+            trade_symbol,
+            units,
+            to_ship_symbol.to_string(),
+        );
+        self.limiter.wait().await;
+        let response = fleet_api::transfer_cargo(&self.config, ship_symbol, Some(request))
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data.cargo)
+    }
+
+    pub async fn sell_cargo(
+        &self,
+        ship_symbol: &str,
+        trade_symbol: spacetraders_api::models::TradeSymbol,
+        units: i32,
+    ) -> Result<spacetraders_api::models::SellCargo201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::SellCargo {
+                    ship: ship_symbol.to_string(),
+                    good: trade_symbol,
+                    units,
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::SellCargoRequest::new(trade_symbol, units);
+        self.limiter.wait().await;
+        let response = fleet_api::sell_cargo(&self.config, ship_symbol, Some(request))
+            .await
+            .map_err(api_error)?;
+        book(LedgerEntry::market(
+            &response.data.transaction,
+            response.data.agent.credits,
+            false,
+        ));
+        Ok(*response.data)
+    }
+
+    /// Fills the ship's tank, or adds `units` of fuel, from the market at
+    /// its waypoint or with `from_cargo` from FUEL in its own hold.
+    pub async fn refuel_ship(
+        &self,
+        ship_symbol: &str,
+        units: Option<i32>,
+        from_cargo: bool,
+    ) -> Result<spacetraders_api::models::RefuelShip200ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Refuel {
+                    ship: ship_symbol.to_string(),
+                    units,
+                    from_cargo,
+                },
+            )
+            .await;
+        }
+        let mut request = spacetraders_api::models::RefuelShipRequest::new();
+        request.units = units;
+        request.from_cargo = from_cargo.then_some(true);
+        self.limiter.wait().await;
+        let response = fleet_api::refuel_ship(&self.config, ship_symbol, Some(request))
+            .await
+            .map_err(api_error)?;
+        book(LedgerEntry::market(
+            &response.data.transaction,
+            response.data.agent.credits,
+            true,
+        ));
+        Ok(*response.data)
+    }
+
+    /// Jumps to a connected system's gate, paying for the jump in antimatter.
+    pub async fn jump_ship(
+        &self,
+        ship_symbol: &str,
+        waypoint_symbol: &str,
+    ) -> Result<spacetraders_api::models::JumpShip200ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Jump {
+                    ship: ship_symbol.to_string(),
+                    waypoint: waypoint_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::JumpShipRequest::new(waypoint_symbol.to_string());
+        self.limiter.wait().await;
+        let response = fleet_api::jump_ship(&self.config, ship_symbol, Some(request))
+            .await
+            .map_err(api_error)?;
+        book(LedgerEntry::market(
+            &response.data.transaction,
+            response.data.agent.credits,
+            false,
+        ));
+        Ok(*response.data)
+    }
+
+    /// Scans for systems around the ship with its sensors.
+    pub async fn scan_systems(
+        &self,
+        ship_symbol: &str,
+    ) -> Result<spacetraders_api::models::CreateShipSystemScan201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::ScanSystems {
+                    ship: ship_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = fleet_api::create_ship_system_scan(&self.config, ship_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    /// Scans the waypoints in range of the ship's sensors.
+    pub async fn scan_waypoints(
+        &self,
+        ship_symbol: &str,
+    ) -> Result<spacetraders_api::models::CreateShipWaypointScan201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::ScanWaypoints {
+                    ship: ship_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = fleet_api::create_ship_waypoint_scan(&self.config, ship_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    /// Scans for other ships in range of the ship's sensors.
+    pub async fn scan_ships(
+        &self,
+        ship_symbol: &str,
+    ) -> Result<spacetraders_api::models::CreateShipShipScan201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::ScanShips {
+                    ship: ship_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = fleet_api::create_ship_ship_scan(&self.config, ship_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    /// Charts the uncharted waypoint the ship is at, revealing its traits.
+    pub async fn create_chart(
+        &self,
+        ship_symbol: &str,
+    ) -> Result<spacetraders_api::models::CreateChart201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Chart {
+                    ship: ship_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = fleet_api::create_chart(&self.config, ship_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    /// Delivers cargo to the construction site the ship is docked at.
+    pub async fn supply_construction(
+        &self,
+        ship_symbol: &str,
+        waypoint_symbol: &str,
+        trade_symbol: spacetraders_api::models::TradeSymbol,
+        units: i32,
+    ) -> Result<spacetraders_api::models::SupplyConstruction201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::SupplyConstruction {
+                    ship: ship_symbol.to_string(),
+                    waypoint: waypoint_symbol.to_string(),
+                    good: trade_symbol,
+                    units,
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::SupplyConstructionRequest::new(
+            ship_symbol.to_string(),
+            trade_symbol.to_string(),
+            units,
+        );
+        self.limiter.wait().await;
+        let response = systems_api::supply_construction(
+            &self.config,
+            cache::system_symbol(waypoint_symbol),
+            waypoint_symbol,
+            Some(request),
+        )
+        .await
+        .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    pub async fn accept_contract(
+        &self,
+        contract_id: &str,
+    ) -> Result<spacetraders_api::models::AcceptContract200ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::AcceptContract {
+                    id: contract_id.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = contracts_api::accept_contract(&self.config, contract_id)
+            .await
+            .map_err(api_error)?;
+        book(LedgerEntry::contract(
+            EntryKind::ContractAccepted,
+            contract_id,
+            response.data.contract.terms.payment.on_accepted,
+            response.data.agent.credits,
+        ));
+        Ok(*response.data)
+    }
+
+    /// Delivers cargo towards a contract at the waypoint the ship is docked at.
+    pub async fn deliver_contract(
+        &self,
+        contract_id: &str,
+        ship_symbol: &str,
+        trade_symbol: spacetraders_api::models::TradeSymbol,
+        units: i32,
+    ) -> Result<spacetraders_api::models::DeliverContract200ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::DeliverContract {
+                    id: contract_id.to_string(),
+                    ship: ship_symbol.to_string(),
+                    good: trade_symbol,
+                    units,
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::DeliverContractRequest::new(
+            ship_symbol.to_string(),
+            trade_symbol.to_string(),
+            units,
+        );
+        self.limiter.wait().await;
+        let response = contracts_api::deliver_contract(&self.config, contract_id, Some(request))
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data)
+    }
+
+    pub async fn fulfill_contract(
+        &self,
+        contract_id: &str,
+    ) -> Result<spacetraders_api::models::AcceptContract200ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::FulfillContract {
+                    id: contract_id.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = contracts_api::fulfill_contract(&self.config, contract_id)
+            .await
+            .map_err(api_error)?;
+        book(LedgerEntry::contract(
+            EntryKind::ContractFulfilled,
+            contract_id,
+            response.data.contract.terms.payment.on_fulfilled,
+            response.data.agent.credits,
+        ));
+        Ok(*response.data)
+    }
+
+    /// What repairing the ship at its current shipyard would cost.
+    pub async fn get_repair_quote(
+        &self,
+        ship_symbol: &str,
+    ) -> Result<spacetraders_api::models::RepairTransaction> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::RepairQuote {
+                    ship: ship_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = fleet_api::get_repair_ship(&self.config, ship_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data.transaction)
+    }
+
+    /// What scrapping the ship at its current shipyard would pay.
+    pub async fn get_scrap_quote(
+        &self,
+        ship_symbol: &str,
+    ) -> Result<spacetraders_api::models::ScrapTransaction> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::ScrapQuote {
+                    ship: ship_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = fleet_api::get_scrap_ship(&self.config, ship_symbol)
+            .await
+            .map_err(api_error)?;
+        Ok(*response.data.transaction)
+    }
+
+    pub async fn repair_ship(
+        &self,
+        ship_symbol: &str,
+    ) -> Result<spacetraders_api::models::RepairShip200ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Repair {
+                    ship: ship_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = fleet_api::repair_ship(&self.config, ship_symbol)
+            .await
+            .map_err(api_error)?;
+        book(LedgerEntry::repair(
+            &response.data.transaction,
+            response.data.agent.credits,
+        ));
+        Ok(*response.data)
+    }
+
+    pub async fn scrap_ship(
+        &self,
+        ship_symbol: &str,
+    ) -> Result<spacetraders_api::models::ScrapShip200ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::Scrap {
+                    ship: ship_symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        self.limiter.wait().await;
+        let response = fleet_api::scrap_ship(&self.config, ship_symbol)
+            .await
+            .map_err(api_error)?;
+        book(LedgerEntry::scrap(
+            &response.data.transaction,
+            response.data.agent.credits,
+        ));
+        Ok(*response.data)
+    }
+
+    pub async fn install_mount(
+        &self,
+        ship_symbol: &str,
+        symbol: &str,
+    ) -> Result<spacetraders_api::models::InstallMount201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::InstallMount {
+                    ship: ship_symbol.to_string(),
+                    part: symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::InstallMountRequest::new(symbol.to_string());
+        self.limiter.wait().await;
+        let response = fleet_api::install_mount(&self.config, ship_symbol, Some(request))
+            .await
+            .map_err(api_error)?;
+        book(LedgerEntry::modification(
+            &response.data.transaction,
+            response.data.agent.credits,
+        ));
+        Ok(*response.data)
+    }
+
+    pub async fn remove_mount(
+        &self,
+        ship_symbol: &str,
+        symbol: &str,
+    ) -> Result<spacetraders_api::models::RemoveMount201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::RemoveMount {
+                    ship: ship_symbol.to_string(),
+                    part: symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::RemoveMountRequest::new(symbol.to_string());
+        self.limiter.wait().await;
+        let response = fleet_api::remove_mount(&self.config, ship_symbol, Some(request))
+            .await
+            .map_err(api_error)?;
+        book(LedgerEntry::modification(
+            &response.data.transaction,
+            response.data.agent.credits,
+        ));
+        Ok(*response.data)
+    }
+
+    pub async fn install_module(
+        &self,
+        ship_symbol: &str,
+        symbol: &str,
+    ) -> Result<spacetraders_api::models::InstallShipModule201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::InstallModule {
+                    ship: ship_symbol.to_string(),
+                    part: symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::InstallShipModuleRequest::new(symbol.to_string());
+        self.limiter.wait().await;
+        let response = fleet_api::install_ship_module(&self.config, ship_symbol, Some(request))
+            .await
+            .map_err(api_error)?;
+        book(module_entry(
+            &response.data.transaction,
+            response.data.agent.credits,
+        ));
+        Ok(*response.data)
+    }
+
+    pub async fn remove_module(
+        &self,
+        ship_symbol: &str,
+        symbol: &str,
+    ) -> Result<spacetraders_api::models::InstallShipModule201ResponseData> {
+        if let Some(socket) = &self.daemon {
+            return daemon::call(
+                socket,
+                Request::RemoveModule {
+                    ship: ship_symbol.to_string(),
+                    part: symbol.to_string(),
+                },
+            )
+            .await;
+        }
+        let request = spacetraders_api::models::RemoveShipModuleRequest::new(symbol.to_string());
+        self.limiter.wait().await;
+        let response = fleet_api::remove_ship_module(&self.config, ship_symbol, Some(request))
+            .await
+            .map_err(api_error)?;
+        book(module_entry(
+            &response.data.transaction,
+            response.data.agent.credits,
+        ));
+        Ok(*response.data)
+    }
+
+    /// Turns the daemon's automatic repairs at shipyards on or off.
+    pub async fn set_auto_repair(&self, enabled: bool) -> Result<()> {
+        daemon::call(self.require_daemon()?, Request::SetAutoRepair { enabled }).await
+    }
+
+    /// Turns the daemon's automatic acceptance of profitable contracts on or off.
+    pub async fn set_auto_contracts(&self, enabled: bool) -> Result<()> {
+        daemon::call(
+            self.require_daemon()?,
+            Request::SetAutoContracts { enabled },
+        )
+        .await
+    }
+
+    /// Turns the daemon's automatic rescue of stranded ships on or off.
+    pub async fn set_auto_rescue(&self, enabled: bool) -> Result<()> {
+        daemon::call(self.require_daemon()?, Request::SetAutoRescue { enabled }).await
+    }
+
+    /// Has the daemon get a stranded ship to fuel, returning the plan.
+    pub async fn start_rescue(&self, ship_symbol: &str) -> Result<Stranding> {
+        daemon::call(
+            self.require_daemon()?,
+            Request::Rescue {
+                ship: ship_symbol.to_string(),
+            },
+        )
+        .await
+    }
+
+    /// Has the daemon call off the mission helping a stranded ship,
+    /// returning whether there was one.
+    pub async fn cancel_rescue(&self, ship_symbol: &str) -> Result<bool> {
+        daemon::call(
+            self.require_daemon()?,
+            Request::CancelRescue {
+                ship: ship_symbol.to_string(),
+            },
+        )
+        .await
+    }
+
+    /// Parks probes at markets and polls them every `interval` minutes, or
+    /// stops with `None`.
+    pub async fn set_market_watch(&self, interval: Option<u64>) -> Result<()> {
+        daemon::call(self.require_daemon()?, Request::SetMarketWatch { interval }).await
+    }
+
+    /// Behaviours assigned in the daemon, keyed by ship symbol.
+    pub async fn get_behaviours(&self) -> Result<BTreeMap<String, Behaviour>> {
+        daemon::call(self.require_daemon()?, Request::Behaviours).await
+    }
+
+    pub async fn assign_behaviour(&self, ship_symbol: &str, behaviour: Behaviour) -> Result<()> {
+        daemon::call(
+            self.require_daemon()?,
+            Request::Assign {
+                ship: ship_symbol.to_string(),
+                behaviour,
+            },
+        )
+        .await
+    }
+
+    /// Stops a ship's behaviour, returning whether it had one.
+    pub async fn unassign_behaviour(&self, ship_symbol: &str) -> Result<bool> {
+        daemon::call(
+            self.require_daemon()?,
+            Request::Unassign {
+                ship: ship_symbol.to_string(),
+            },
+        )
+        .await
+    }
 }
@@ -0,0 +1,254 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use spacetraders_api::models::Ship;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::cache;
+use crate::client::SpaceTradersClient;
+use crate::ledger::{self, EntryKind, LedgerEntry};
+
+/// Condition below which a component is worth a warning and a repair check.
+pub const CONDITION_WARNING: f64 = 0.5;
+/// Condition below which a component is close to failing.
+pub const CONDITION_CRITICAL: f64 = 0.2;
+
+/// Longest a repair may take to pay for itself from the ship's earnings.
+const REPAIR_PAYBACK_HOURS: f64 = 24.0;
+/// Ledger window a ship's earning rate is measured over.
+const EARNING_WINDOW: Duration = Duration::hours(24);
+/// Condition samples kept per ship.
+const MAX_SAMPLES: usize = 200;
+
+/// Frame, reactor and engine as `(name, condition, integrity)`.
+pub fn components(ship: &Ship) -> [(&'static str, f64, f64); 3] {
+    [
+        ("FRAME", ship.frame.condition, ship.frame.integrity),
+        ("REACTOR", ship.reactor.condition, ship.reactor.integrity),
+        ("ENGINE", ship.engine.condition, ship.engine.integrity),
+    ]
+}
+
+/// Condition of the ship's most worn component.
+pub fn lowest_condition(ship: &Ship) -> f64 {
+    components(ship)
+        .iter()
+        .map(|(_, condition, _)| *condition)
+        .fold(1.0, f64::min)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConditionSample {
+    pub time: DateTime<Utc>,
+    pub frame: f64,
+    pub reactor: f64,
+    pub engine: f64,
+}
+
+impl ConditionSample {
+    fn lowest(&self) -> f64 {
+        self.frame.min(self.reactor).min(self.engine)
+    }
+}
+
+/// Condition history per ship, kept on disk to measure wear over time.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WearLog {
+    pub ships: BTreeMap<String, Vec<ConditionSample>>,
+}
+
+impl WearLog {
+    fn path() -> PathBuf {
+        cache::data_dir().join("wear.json")
+    }
+
+    /// Loads the history, starting empty if it is missing or unreadable.
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::create_dir_all(cache::data_dir())?;
+        fs::write(Self::path(), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Adds a sample for every ship whose condition changed and saves if any did.
+    pub fn record(&mut self, ships: &[Ship]) -> Result<()> {
+        let mut changed = false;
+        for ship in ships {
+            let samples = self.ships.entry(ship.symbol.clone()).or_default();
+            let sample = ConditionSample {
+                time: Utc::now(),
+                frame: ship.frame.condition,
+                reactor: ship.reactor.condition,
+                engine: ship.engine.condition,
+            };
+            let unchanged = samples.last().is_some_and(|last| {
+                last.frame == sample.frame
+                    && last.reactor == sample.reactor
+                    && last.engine == sample.engine
+            });
+            if !unchanged {
+                samples.push(sample);
+                if samples.len() > MAX_SAMPLES {
+                    samples.remove(0);
+                }
+                changed = true;
+            }
+        }
+        if changed {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Average loss of the lowest component condition per day, if the history
+    /// spans long enough to tell.
+    pub fn wear_per_day(&self, ship: &str) -> Option<f64> {
+        let samples = self.ships.get(ship)?;
+        let (first, last) = (samples.first()?, samples.last()?);
+        let days = (last.time - first.time).num_seconds() as f64 / 86_400.0;
+        if days < 1.0 / 24.0 {
+            return None;
+        }
+        Some((first.lowest() - last.lowest()).max(0.0) / days)
+    }
+}
+
+/// Net credits per hour a ship earned over the recent ledger window. Buying,
+/// repairing and refitting the ship are capital costs, not running costs.
+pub fn earning_rate(entries: &[LedgerEntry], ship: &str) -> f64 {
+    let since = Utc::now() - EARNING_WINDOW;
+    let net: i64 = entries
+        .iter()
+        .filter(|entry| entry.time >= since && entry.ship.as_deref() == Some(ship))
+        .filter(|entry| {
+            !matches!(
+                entry.kind,
+                EntryKind::ShipPurchase | EntryKind::Repair | EntryKind::Modification
+            )
+        })
+        .map(|entry| entry.amount)
+        .sum();
+    net as f64 / EARNING_WINDOW.num_hours() as f64
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Advice {
+    KeepRunning,
+    Repair,
+    Scrap,
+}
+
+impl fmt::Display for Advice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::KeepRunning => write!(f, "KEEP RUNNING"),
+            Self::Repair => write!(f, "REPAIR"),
+            Self::Scrap => write!(f, "SCRAP"),
+        }
+    }
+}
+
+/// The repair advisor's verdict on one ship and what it was based on.
+#[derive(Clone, Debug, Serialize)]
+pub struct Assessment {
+    pub ship: String,
+    pub advice: Advice,
+    pub reason: String,
+    pub condition: f64,
+    pub repair_cost: Option<i64>,
+    pub scrap_value: Option<i64>,
+    /// Net credits per hour over the last day.
+    pub earning_rate: f64,
+}
+
+/// Weighs a repair quote against what the ship earns: repair when it pays
+/// for itself within a day, scrap a critically worn ship when it doesn't,
+/// otherwise keep running.
+pub fn assess(
+    ship: &Ship,
+    repair_cost: Option<i64>,
+    scrap_value: Option<i64>,
+    earning_rate: f64,
+) -> Assessment {
+    let condition = lowest_condition(ship);
+    let payback_hours = repair_cost
+        .filter(|_| earning_rate > 0.0)
+        .map(|cost| cost as f64 / earning_rate);
+
+    let (advice, reason) = if condition >= CONDITION_WARNING {
+        (
+            Advice::KeepRunning,
+            format!("condition {:.0}% is fine", condition * 100.0),
+        )
+    } else if repair_cost.is_none() {
+        (
+            Advice::KeepRunning,
+            String::from("no repair quote here; dock at a shipyard to get one"),
+        )
+    } else if let Some(hours) = payback_hours.filter(|h| *h <= REPAIR_PAYBACK_HOURS) {
+        (
+            Advice::Repair,
+            format!("repair pays for itself in {:.1} hours", hours),
+        )
+    } else if condition < CONDITION_CRITICAL && scrap_value.is_some() {
+        let payback = match payback_hours {
+            Some(hours) => format!("repair would take {:.0} hours to pay back", hours),
+            None => String::from("ship earns nothing to pay for a repair"),
+        };
+        (
+            Advice::Scrap,
+            format!("condition is critical and {}", payback),
+        )
+    } else if condition < CONDITION_CRITICAL {
+        (
+            Advice::Repair,
+            String::from("condition is critical and the ship can't be scrapped here"),
+        )
+    } else {
+        (
+            Advice::KeepRunning,
+            match payback_hours {
+                Some(hours) => format!("repair would take {:.0} hours to pay back", hours),
+                None => String::from("ship earns nothing to pay for a repair yet"),
+            },
+        )
+    };
+
+    Assessment {
+        ship: ship.symbol.clone(),
+        advice,
+        reason,
+        condition,
+        repair_cost,
+        scrap_value,
+        earning_rate,
+    }
+}
+
+/// Fetches repair and scrap quotes and assesses the ship. Quotes are only
+/// available at a shipyard, so a missing quote is not an error.
+pub async fn assess_ship(client: &SpaceTradersClient, ship: &Ship) -> Assessment {
+    let repair_cost = client
+        .get_repair_quote(&ship.symbol)
+        .await
+        .ok()
+        .map(|quote| quote.total_price as i64);
+    let scrap_value = client
+        .get_scrap_quote(&ship.symbol)
+        .await
+        .ok()
+        .map(|quote| quote.total_price as i64);
+    let rate = earning_rate(&ledger::load(), &ship.symbol);
+    assess(ship, repair_cost, scrap_value, rate)
+}
@@ -0,0 +1,385 @@
+use anyhow::Result;
+use serde::Serialize;
+use spacetraders_api::models::{
+    market_trade_good, Agent, Construction, ConstructionMaterial, Ship, ShipNavStatus, TradeSymbol,
+    Waypoint,
+};
+
+use crate::cache::{self, UniverseCache};
+use crate::client::SpaceTradersClient;
+use crate::events::{self, EventKind};
+use crate::navigation::fly_to;
+use crate::prices;
+use crate::search;
+
+/// Units of a material still to be delivered.
+pub fn remaining(material: &ConstructionMaterial) -> i32 {
+    (material.required - material.fulfilled).max(0)
+}
+
+/// Where a material still owed to a site can be bought. Prices are only
+/// known for markets one of our ships has visited.
+#[derive(Clone, Debug, Serialize)]
+pub struct Source {
+    pub good: TradeSymbol,
+    pub remaining: i32,
+    pub market: Option<String>,
+    pub price: Option<i32>,
+    pub cost: Option<i64>,
+}
+
+/// A construction site's progress with the plan for finishing it.
+#[derive(Clone, Debug, Serialize)]
+pub struct SiteReport {
+    pub construction: Construction,
+    pub sources: Vec<Source>,
+}
+
+impl SiteReport {
+    /// Cost of the materials still needed, counting only priced ones.
+    pub fn known_cost(&self) -> i64 {
+        self.sources.iter().filter_map(|source| source.cost).sum()
+    }
+}
+
+/// Systems worth watching for construction: our headquarters' and every
+/// system one of our ships is in.
+pub fn our_systems(agent: Option<&Agent>, ships: &[Ship]) -> Vec<String> {
+    let mut systems: Vec<String> = agent
+        .map(|agent| cache::system_symbol(&agent.headquarters).to_string())
+        .into_iter()
+        .chain(ships.iter().map(|ship| ship.nav.system_symbol.clone()))
+        .collect();
+    systems.sort();
+    systems.dedup();
+    systems
+}
+
+/// Cached waypoints in `systems` that are still under construction.
+pub fn sites<'a>(cache: &'a UniverseCache, systems: &[String]) -> Vec<&'a Waypoint> {
+    systems
+        .iter()
+        .filter_map(|system| cache.waypoints.get(system))
+        .flatten()
+        .filter(|waypoint| waypoint.is_under_construction)
+        .collect()
+}
+
+/// Refetches the waypoints of a site's system, since the cached ones still
+/// list it as under construction once it is finished.
+async fn refresh_sites(
+    client: &SpaceTradersClient,
+    cache: &mut UniverseCache,
+    site: &str,
+) -> Result<()> {
+    let system = cache::system_symbol(site);
+    let waypoints = client.get_system_waypoints(system, None, None).await?;
+    cache.waypoints.insert(system.to_string(), waypoints);
+    Ok(())
+}
+
+/// Fetches the progress of every construction site in `systems`, dropping
+/// finished ones from the site list.
+pub async fn cache_constructions(
+    client: &SpaceTradersClient,
+    cache: &mut UniverseCache,
+    systems: &[String],
+) -> Result<()> {
+    for system in systems {
+        search::cache_waypoints(client, cache, system).await?;
+    }
+    let symbols: Vec<String> = sites(cache, systems)
+        .iter()
+        .map(|waypoint| waypoint.symbol.clone())
+        .collect();
+    let mut finished = Vec::new();
+    for symbol in symbols {
+        let construction = client
+            .get_construction(cache::system_symbol(&symbol), &symbol)
+            .await?;
+        if construction.is_complete {
+            finished.push(symbol.clone());
+        }
+        cache.constructions.insert(symbol, construction);
+    }
+    finished.dedup_by(|a, b| cache::system_symbol(a) == cache::system_symbol(b));
+    for site in finished {
+        refresh_sites(client, cache, &site).await?;
+    }
+    cache.save()
+}
+
+/// The cheapest cached market exporting or exchanging `good`, preferring
+/// markets in `system`. Markets without prices rank after those with them.
+pub fn cheapest_source(
+    cache: &UniverseCache,
+    good: TradeSymbol,
+    system: &str,
+) -> Option<(String, Option<i32>)> {
+    cache
+        .markets
+        .values()
+        .filter_map(|market| {
+            let price = market.trade_goods.as_ref().and_then(|goods| {
+                goods
+                    .iter()
+                    .find(|g| g.symbol == good && g.r#type != market_trade_good::Type::Import)
+                    .map(|g| g.purchase_price)
+            });
+            let listed = market
+                .exports
+                .iter()
+                .chain(&market.exchange)
+                .any(|g| g.symbol == good);
+            (price.is_some() || listed).then(|| (market.symbol.clone(), price))
+        })
+        .min_by_key(|(symbol, price)| {
+            (
+                cache::system_symbol(symbol) != system,
+                price.is_none(),
+                price.unwrap_or_default(),
+                symbol.clone(),
+            )
+        })
+}
+
+/// Where to buy each material the site still needs and what it will cost.
+pub fn sourcing_plan(cache: &UniverseCache, construction: &Construction) -> Vec<Source> {
+    let system = cache::system_symbol(&construction.symbol);
+    construction
+        .materials
+        .iter()
+        .filter(|material| remaining(material) > 0)
+        .map(|material| {
+            let source = cheapest_source(cache, material.trade_symbol, system);
+            let price = source.as_ref().and_then(|(_, price)| *price);
+            Source {
+                good: material.trade_symbol,
+                remaining: remaining(material),
+                market: source.map(|(market, _)| market),
+                price,
+                cost: price.map(|price| price as i64 * remaining(material) as i64),
+            }
+        })
+        .collect()
+}
+
+/// Progress and sourcing plans for the cached sites in `systems`.
+pub fn reports(cache: &UniverseCache, systems: &[String]) -> Vec<SiteReport> {
+    sites(cache, systems)
+        .iter()
+        .filter_map(|waypoint| cache.constructions.get(&waypoint.symbol))
+        .map(|construction| SiteReport {
+            construction: construction.clone(),
+            sources: sourcing_plan(cache, construction),
+        })
+        .collect()
+}
+
+/// Fetches the site's latest progress into the cache.
+async fn fetch_construction(
+    client: &SpaceTradersClient,
+    cache: &mut UniverseCache,
+    site: &str,
+) -> Result<Construction> {
+    let construction = client
+        .get_construction(cache::system_symbol(site), site)
+        .await?;
+    cache
+        .constructions
+        .insert(site.to_string(), construction.clone());
+    if construction.is_complete {
+        refresh_sites(client, cache, site).await?;
+    }
+    cache.save()?;
+    Ok(construction)
+}
+
+/// Delivers what the ship carries that the site needs.
+async fn deliver(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    cache: &mut UniverseCache,
+    construction: &Construction,
+) -> Result<()> {
+    if ship.nav.status == ShipNavStatus::InOrbit {
+        client.dock_ship(&ship.symbol).await?;
+    }
+    let mut construction = construction.clone();
+    for item in &ship.cargo.inventory {
+        let Some(material) = construction
+            .materials
+            .iter()
+            .find(|m| m.trade_symbol == item.symbol)
+        else {
+            continue;
+        };
+        let units = item.units.min(remaining(material));
+        if units == 0 {
+            continue;
+        }
+        let data = client
+            .supply_construction(&ship.symbol, &construction.symbol, item.symbol, units)
+            .await?;
+        construction = *data.construction;
+        events::record(
+            log::Level::Info,
+            EventKind::Log,
+            format!(
+                "{} delivered {} {} to {}",
+                ship.symbol, units, item.symbol, construction.symbol
+            ),
+        );
+    }
+    if construction.is_complete {
+        refresh_sites(client, cache, &construction.symbol).await?;
+    }
+    cache
+        .constructions
+        .insert(construction.symbol.clone(), construction);
+    cache.save()
+}
+
+/// Buys as much of `good` as the hold takes, is `wanted` and the agent can
+/// afford, in lots no larger than the market's trade volume, refuelling
+/// while docked. Prices come from a fresh look at the market rather than
+/// the cache. The purpose is only for the log.
+pub async fn buy(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    good: TradeSymbol,
+    wanted: i32,
+    purpose: &str,
+) -> Result<()> {
+    let waypoint = &ship.nav.waypoint_symbol;
+    let market = prices::before_trading(client, waypoint).await?;
+    let goods = market.trade_goods.unwrap_or_default();
+    let Some(listing) = goods.iter().find(|g| g.symbol == good) else {
+        anyhow::bail!("{} doesn't sell {}", waypoint, good);
+    };
+    if ship.nav.status == ShipNavStatus::InOrbit {
+        client.dock_ship(&ship.symbol).await?;
+    }
+    if ship.fuel.current < ship.fuel.capacity && goods.iter().any(|g| g.symbol == TradeSymbol::Fuel)
+    {
+        client.refuel_ship(&ship.symbol, None, false).await?;
+    }
+    let volume = listing.trade_volume.max(1);
+    let mut price = listing.purchase_price.max(1) as i64;
+    let mut credits = client.get_my_agent().await?.credits;
+    let mut remaining = wanted.min(ship.cargo.capacity - ship.cargo.units);
+    let mut bought = 0;
+    while remaining > 0 {
+        let units = remaining.min(volume).min((credits / price) as i32);
+        if units <= 0 {
+            break;
+        }
+        let data = client.purchase_cargo(&ship.symbol, good, units).await?;
+        credits = data.agent.credits;
+        price = data.transaction.price_per_unit.max(1) as i64;
+        remaining -= units;
+        bought += units;
+    }
+    if bought == 0 {
+        anyhow::bail!(
+            "can't afford {} at {} ({} credits each)",
+            good,
+            waypoint,
+            price
+        );
+    }
+    prices::after_trading(client, waypoint).await;
+    events::record(
+        log::Level::Info,
+        EventKind::Log,
+        format!(
+            "{} bought {} {} at {} for {}",
+            ship.symbol, bought, good, waypoint, purpose
+        ),
+    );
+    Ok(())
+}
+
+/// One step of the construction behaviour: deliver any needed cargo to the
+/// site, otherwise fetch the next material from its cheapest market.
+/// Returns false once the site is complete.
+pub async fn step(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    cache: &mut UniverseCache,
+    site: &str,
+) -> Result<bool> {
+    if ship.nav.status == ShipNavStatus::InTransit {
+        return Ok(true);
+    }
+    let mut construction = match cache.constructions.get(site) {
+        Some(construction) => construction.clone(),
+        None => fetch_construction(client, cache, site).await?,
+    };
+    if construction.is_complete {
+        return Ok(false);
+    }
+
+    let carrying = ship.cargo.inventory.iter().any(|item| {
+        construction
+            .materials
+            .iter()
+            .any(|m| m.trade_symbol == item.symbol && remaining(m) > 0)
+    });
+    let free = ship.cargo.capacity - ship.cargo.units;
+    if carrying && (free == 0 || ship.nav.waypoint_symbol == site) {
+        if ship.nav.waypoint_symbol != site {
+            fly_to(client, ship, site).await?;
+            return Ok(true);
+        }
+        deliver(client, ship, cache, &construction).await?;
+        return Ok(true);
+    }
+    if free == 0 {
+        anyhow::bail!(
+            "{}'s hold is full of cargo {} doesn't need; sell or jettison it",
+            ship.symbol,
+            site
+        );
+    }
+    if !carrying {
+        // Setting out to buy, so check what others have delivered meanwhile
+        construction = fetch_construction(client, cache, site).await?;
+        if construction.is_complete {
+            return Ok(false);
+        }
+    }
+
+    let carried = |good: TradeSymbol| {
+        ship.cargo
+            .inventory
+            .iter()
+            .filter(|item| item.symbol == good)
+            .map(|item| item.units)
+            .sum::<i32>()
+    };
+    let next = sourcing_plan(cache, &construction)
+        .into_iter()
+        .find(|source| source.market.is_some() && source.remaining > carried(source.good));
+    let Some(source) = next else {
+        if carrying {
+            fly_to(client, ship, site).await?;
+            return Ok(true);
+        }
+        anyhow::bail!("no known market sells the materials {} needs", site);
+    };
+    let market = source.market.unwrap();
+    if ship.nav.waypoint_symbol != market {
+        fly_to(client, ship, &market).await?;
+        return Ok(true);
+    }
+    buy(
+        client,
+        ship,
+        source.good,
+        source.remaining - carried(source.good),
+        "construction",
+    )
+    .await?;
+    Ok(true)
+}
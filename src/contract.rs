@@ -0,0 +1,625 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use spacetraders_api::models::{
+    Contract, ContractDeliverGood, Ship, ShipNavStatus, ShipRole, TradeSymbol,
+};
+
+use crate::cache::{self, UniverseCache};
+use crate::client::SpaceTradersClient;
+use crate::construction;
+use crate::events::{self, EventKind};
+use crate::explore;
+use crate::navigation::{
+    distance, flight_seconds, fly_to, position, FLIGHT_OVERHEAD, FUEL_PER_UNIT,
+};
+
+/// Minutes between the daemon's looks at new contracts.
+pub const CHECK_MINUTES: i64 = 10;
+
+/// Where one good owed to a contract can be bought and what it will cost.
+#[derive(Clone, Debug, Serialize)]
+pub struct Delivery {
+    pub good: String,
+    pub destination: String,
+    pub remaining: i32,
+    pub market: Option<String>,
+    pub price: Option<i32>,
+    pub cost: Option<i64>,
+}
+
+/// What a contract is worth to us, from cached prices and the ship best
+/// placed to run it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Estimate {
+    pub contract: String,
+    pub payment: i64,
+    pub deliveries: Vec<Delivery>,
+    /// Cost of the goods and fuel, counting only what has a known price.
+    pub cost: i64,
+    /// Whether every good has a known price, so `cost` is complete.
+    pub priced: bool,
+    pub ship: Option<String>,
+    pub trips: i32,
+    pub travel_seconds: Option<i64>,
+    /// Whether the deliveries can be made before the deadline, if a ship
+    /// can run them at all.
+    pub achievable: Option<bool>,
+}
+
+impl Estimate {
+    pub fn profit(&self) -> i64 {
+        self.payment - self.cost
+    }
+
+    /// Accept only when every cost is known, it pays and there is time.
+    pub fn worth_accepting(&self) -> bool {
+        self.priced && self.profit() > 0 && self.achievable == Some(true)
+    }
+
+    /// One word for tables: ACCEPT, DECLINE or UNKNOWN when prices or a
+    /// ship are missing.
+    pub fn verdict(&self) -> &'static str {
+        if self.worth_accepting() {
+            "ACCEPT"
+        } else if self.profit() <= 0 || self.achievable == Some(false) {
+            "DECLINE"
+        } else {
+            "UNKNOWN"
+        }
+    }
+}
+
+/// Length of a path through `points`, in order.
+fn path_length(points: &[(i32, i32)]) -> f64 {
+    points.windows(2).map(|leg| distance(leg[0], leg[1])).sum()
+}
+
+/// Waypoints with their positions, if all are cached and in `system`.
+fn located(
+    cache: &UniverseCache,
+    symbols: &[String],
+    system: &str,
+) -> Option<Vec<(String, (i32, i32))>> {
+    symbols
+        .iter()
+        .map(|symbol| {
+            (cache::system_symbol(symbol) == system)
+                .then(|| Some((symbol.clone(), position(cache, symbol)?)))?
+        })
+        .collect()
+}
+
+/// Distance a ship in `system` flies to buy at `markets` and deliver to
+/// each of `destinations` in `trips` loads: the first from where it is, the
+/// rest from the last drop-off. Stop order comes from the route planner.
+fn route_distance(
+    cache: &UniverseCache,
+    system: &str,
+    start: (i32, i32),
+    markets: &[String],
+    destinations: &[String],
+    trips: i32,
+) -> Option<f64> {
+    let markets = located(cache, markets, system)?;
+    let destinations = located(cache, destinations, system)?;
+    let point = |stops: &[(String, (i32, i32))], symbol: &String| {
+        stops
+            .iter()
+            .find(|(s, _)| s == symbol)
+            .map(|(_, point)| *point)
+    };
+    // One load: buy along a tour of the markets, then drop off along a tour
+    // of the destinations
+    let load = |from: (i32, i32)| {
+        let mut points = vec![from];
+        for stop in explore::plan_tour(from, &markets, None) {
+            points.extend(point(&markets, &stop));
+        }
+        let bought = *points.last().unwrap();
+        for stop in explore::plan_tour(bought, &destinations, None) {
+            points.extend(point(&destinations, &stop));
+        }
+        (path_length(&points), *points.last().unwrap())
+    };
+    let (first, end) = load(start);
+    let (again, _) = load(end);
+    Some(first + again * (trips - 1).max(0) as f64)
+}
+
+/// The cheapest known FUEL price in the cache.
+fn fuel_price(cache: &UniverseCache) -> Option<i32> {
+    cache
+        .markets
+        .values()
+        .filter_map(|market| market.trade_goods.as_ref())
+        .flatten()
+        .filter(|good| good.symbol == TradeSymbol::Fuel)
+        .map(|good| good.purchase_price)
+        .min()
+}
+
+/// Parses a good's symbol as contracts spell it.
+fn trade_symbol(symbol: &str) -> Option<TradeSymbol> {
+    serde_json::from_value(serde_json::Value::String(symbol.to_string())).ok()
+}
+
+fn deliveries(cache: &UniverseCache, contract: &Contract) -> Vec<Delivery> {
+    contract
+        .terms
+        .deliver
+        .iter()
+        .flatten()
+        .filter(|good| good.units_required > good.units_fulfilled)
+        .map(|good| {
+            let remaining = good.units_required - good.units_fulfilled;
+            let source = trade_symbol(&good.trade_symbol).and_then(|symbol| {
+                construction::cheapest_source(
+                    cache,
+                    symbol,
+                    cache::system_symbol(&good.destination_symbol),
+                )
+            });
+            let price = source.as_ref().and_then(|(_, price)| *price);
+            Delivery {
+                good: good.trade_symbol.clone(),
+                destination: good.destination_symbol.clone(),
+                remaining,
+                market: source.map(|(market, _)| market),
+                price,
+                cost: price.map(|price| price as i64 * remaining as i64),
+            }
+        })
+        .collect()
+}
+
+/// Estimates the cost, travel time and profit of a contract. The ship is
+/// the hauler in the destinations' system that would finish soonest;
+/// ships elsewhere would need jumps the estimate can't price. For the same
+/// reason a contract is only routed when its destinations and markets share
+/// one system; otherwise it gets no ship, `achievable` is `None` and it is
+/// never accepted automatically. `ships` should only hold ships free to take
+/// the contract on.
+pub fn evaluate(cache: &UniverseCache, ships: &[Ship], contract: &Contract) -> Estimate {
+    let deliveries = deliveries(cache, contract);
+    let units: i32 = deliveries.iter().map(|d| d.remaining).sum();
+    let mut markets: Vec<String> = deliveries.iter().filter_map(|d| d.market.clone()).collect();
+    markets.sort();
+    markets.dedup();
+    let mut destinations: Vec<String> = deliveries.iter().map(|d| d.destination.clone()).collect();
+    destinations.sort();
+    destinations.dedup();
+
+    let system = destinations
+        .first()
+        .map(|first| cache::system_symbol(first))
+        .filter(|system| {
+            destinations
+                .iter()
+                .all(|d| cache::system_symbol(d) == *system)
+        });
+    let run = system.and_then(|system| {
+        ships
+            .iter()
+            .filter(|ship| {
+                ship.cargo.capacity > 0
+                    && ship.registration.role != ShipRole::Satellite
+                    && ship.nav.system_symbol == system
+            })
+            .filter_map(|ship| {
+                let start = position(cache, &ship.nav.waypoint_symbol)?;
+                let trips = (units + ship.cargo.capacity - 1) / ship.cargo.capacity;
+                let distance =
+                    route_distance(cache, system, start, &markets, &destinations, trips)?;
+                // Every stop is a flight, and the overhead adds up over trips
+                let flights = (markets.len() + destinations.len()) as i32 * trips.max(1);
+                let seconds = flight_seconds(distance, ship.engine.speed)
+                    + FLIGHT_OVERHEAD * (flights - 1) as f64;
+                Some((ship.symbol.clone(), trips, distance, seconds as i64))
+            })
+            .min_by_key(|(_, _, _, seconds)| *seconds)
+    });
+
+    let goods_cost: i64 = deliveries.iter().filter_map(|d| d.cost).sum();
+    let fuel_cost = match (&run, fuel_price(cache)) {
+        (Some((_, _, distance, _)), Some(price)) => {
+            (distance / FUEL_PER_UNIT).ceil() as i64 * price as i64
+        }
+        _ => 0,
+    };
+    let achievable = run.as_ref().map(|(_, _, _, seconds)| {
+        DateTime::parse_from_rfc3339(&contract.terms.deadline)
+            .is_ok_and(|deadline| Utc::now() + chrono::Duration::seconds(*seconds) < deadline)
+    });
+    let payment = &contract.terms.payment;
+    Estimate {
+        contract: contract.id.clone(),
+        payment: payment.on_accepted as i64 + payment.on_fulfilled as i64,
+        priced: deliveries.iter().all(|d| d.cost.is_some()),
+        deliveries,
+        cost: goods_cost + fuel_cost,
+        ship: run.as_ref().map(|(ship, ..)| ship.clone()),
+        trips: run.as_ref().map_or(0, |(_, trips, ..)| *trips),
+        travel_seconds: run.map(|(.., seconds)| seconds),
+        achievable,
+    }
+}
+
+/// Whether a contract can still be accepted.
+pub fn is_open(contract: &Contract, now: DateTime<Utc>) -> bool {
+    !contract.accepted
+        && contract
+            .deadline_to_accept
+            .as_deref()
+            .unwrap_or(&contract.expiration)
+            .parse::<DateTime<Utc>>()
+            .is_ok_and(|deadline| deadline > now)
+}
+
+/// Whether an accepted contract can still be fulfilled.
+fn is_under_way(contract: &Contract, now: DateTime<Utc>) -> bool {
+    contract.accepted
+        && !contract.fulfilled
+        && DateTime::parse_from_rfc3339(&contract.terms.deadline)
+            .is_ok_and(|deadline| deadline > now)
+}
+
+/// Accepts the first open contract worth taking, unless one is already
+/// under way. Returns the contracts turned down, so each is only logged
+/// once by the caller, and the estimate for the one accepted, naming the
+/// ship to run it.
+pub async fn accept_profitable(
+    client: &SpaceTradersClient,
+    cache: &UniverseCache,
+    ships: &[Ship],
+    declined: &[String],
+) -> Result<(Vec<String>, Option<Estimate>)> {
+    let contracts = client.get_contracts().await?;
+    let now = Utc::now();
+    if contracts.iter().any(|c| is_under_way(c, now)) {
+        return Ok((declined.to_vec(), None));
+    }
+    let mut turned_down = Vec::new();
+    for contract in contracts.iter().filter(|c| is_open(c, now)) {
+        let estimate = evaluate(cache, ships, contract);
+        if estimate.worth_accepting() {
+            client.accept_contract(&contract.id).await?;
+            events::record(
+                log::Level::Info,
+                EventKind::Log,
+                format!(
+                    "Accepted contract {}: est. profit {} with {} in {} min",
+                    contract.id,
+                    estimate.profit(),
+                    estimate.ship.as_deref().unwrap_or_default(),
+                    estimate.travel_seconds.unwrap_or_default() / 60
+                ),
+            );
+            return Ok((turned_down, Some(estimate)));
+        }
+        if !declined.contains(&contract.id) {
+            events::record(
+                log::Level::Info,
+                EventKind::Log,
+                format!(
+                    "Not accepting contract {} ({}): est. profit {}{}",
+                    contract.id,
+                    estimate.verdict(),
+                    estimate.profit(),
+                    match estimate.achievable {
+                        Some(false) => ", deadline out of reach",
+                        None => ", no hauler can run it within one system",
+                        Some(true) if !estimate.priced => ", some goods unpriced",
+                        Some(true) => "",
+                    }
+                ),
+            );
+        }
+        turned_down.push(contract.id.clone());
+    }
+    Ok((turned_down, None))
+}
+
+/// Docks and hands over the carried goods the contract still wants here.
+async fn deliver(client: &SpaceTradersClient, ship: &Ship, contract: &Contract) -> Result<()> {
+    if ship.nav.status == ShipNavStatus::InOrbit {
+        client.dock_ship(&ship.symbol).await?;
+    }
+    let mut contract = contract.clone();
+    for item in &ship.cargo.inventory {
+        let owed = contract.terms.deliver.iter().flatten().find(|good| {
+            good.destination_symbol == ship.nav.waypoint_symbol
+                && good.trade_symbol == item.symbol.to_string()
+        });
+        let units = owed.map_or(0, |good| {
+            item.units.min(good.units_required - good.units_fulfilled)
+        });
+        if units <= 0 {
+            continue;
+        }
+        let data = client
+            .deliver_contract(&contract.id, &ship.symbol, item.symbol, units)
+            .await?;
+        contract = *data.contract;
+        events::record(
+            log::Level::Info,
+            EventKind::Log,
+            format!(
+                "{} delivered {} {} for contract {}",
+                ship.symbol, units, item.symbol, contract.id
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// One step of the contract behaviour: deliver carried goods where they are
+/// owed, otherwise buy the next good at its cheapest market, and fulfil the
+/// contract once everything is in. Returns false once the contract is
+/// fulfilled or past its deadline.
+pub async fn step(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    cache: &UniverseCache,
+    id: &str,
+) -> Result<bool> {
+    if ship.nav.status == ShipNavStatus::InTransit {
+        return Ok(true);
+    }
+    let contracts = client.get_contracts().await?;
+    let Some(contract) = contracts.into_iter().find(|c| c.id == id) else {
+        anyhow::bail!("no contract with id {}", id);
+    };
+    if contract.fulfilled {
+        return Ok(false);
+    }
+    if !contract.accepted {
+        anyhow::bail!("contract {} has not been accepted", id);
+    }
+    if !is_under_way(&contract, Utc::now()) {
+        events::record(
+            log::Level::Warn,
+            EventKind::Log,
+            format!("Contract {} passed its deadline undelivered", id),
+        );
+        return Ok(false);
+    }
+
+    let owed: Vec<&ContractDeliverGood> = contract
+        .terms
+        .deliver
+        .iter()
+        .flatten()
+        .filter(|good| good.units_required > good.units_fulfilled)
+        .collect();
+    if owed.is_empty() {
+        let data = client.fulfill_contract(id).await?;
+        events::record(
+            log::Level::Info,
+            EventKind::Log,
+            format!(
+                "Fulfilled contract {}, received {} credits",
+                id, data.contract.terms.payment.on_fulfilled
+            ),
+        );
+        return Ok(false);
+    }
+
+    let carried = |good: &str| {
+        ship.cargo
+            .inventory
+            .iter()
+            .filter(|item| item.symbol.to_string() == good)
+            .map(|item| item.units)
+            .sum::<i32>()
+    };
+    let carrying: Vec<&&ContractDeliverGood> = owed
+        .iter()
+        .filter(|good| carried(&good.trade_symbol) > 0)
+        .collect();
+    if carrying
+        .iter()
+        .any(|good| good.destination_symbol == ship.nav.waypoint_symbol)
+    {
+        deliver(client, ship, &contract).await?;
+        return Ok(true);
+    }
+
+    let free = ship.cargo.capacity - ship.cargo.units;
+    let next = deliveries(cache, &contract)
+        .into_iter()
+        .find(|d| d.market.is_some() && d.remaining > carried(&d.good));
+    if let Some(next) = next.filter(|_| free > 0) {
+        let market = next.market.unwrap();
+        if ship.nav.waypoint_symbol != market {
+            fly_to(client, ship, &market).await?;
+            return Ok(true);
+        }
+        let Some(good) = trade_symbol(&next.good) else {
+            anyhow::bail!("unknown good {} in contract {}", next.good, id);
+        };
+        let purpose = format!("contract {}", id);
+        construction::buy(
+            client,
+            ship,
+            good,
+            next.remaining - carried(&next.good),
+            &purpose,
+        )
+        .await?;
+        return Ok(true);
+    }
+    if let Some(good) = carrying.first() {
+        fly_to(client, ship, &good.destination_symbol).await?;
+        return Ok(true);
+    }
+    if free == 0 {
+        anyhow::bail!(
+            "{}'s hold is full of cargo contract {} doesn't need; sell or jettison it",
+            ship.symbol,
+            id
+        );
+    }
+    anyhow::bail!("no known market sells the goods contract {} needs", id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetraders_api::models::{
+        market_trade_good, ContractDeliverGood, ContractPayment, ContractTerms, Market,
+        MarketTradeGood, SupplyLevel, Waypoint, WaypointType,
+    };
+
+    /// A cache with a waypoint at each point, and at each named market a
+    /// listing of `good` at `price`. FUEL costs 2 everywhere listed.
+    fn cache(
+        waypoints: &[(&str, (i32, i32))],
+        markets: &[(&str, TradeSymbol, i32)],
+    ) -> UniverseCache {
+        let mut cache = UniverseCache::default();
+        for (symbol, (x, y)) in waypoints {
+            let system = cache::system_symbol(symbol).to_string();
+            cache
+                .waypoints
+                .entry(system.clone())
+                .or_default()
+                .push(Waypoint::new(
+                    symbol.to_string(),
+                    WaypointType::Planet,
+                    system,
+                    *x,
+                    *y,
+                    vec![],
+                    vec![],
+                    false,
+                ));
+        }
+        for (symbol, good, price) in markets {
+            let listing = |good, price| {
+                MarketTradeGood::new(
+                    good,
+                    market_trade_good::Type::Export,
+                    10,
+                    SupplyLevel::Moderate,
+                    price,
+                    price / 2,
+                )
+            };
+            let mut market = Market::new(symbol.to_string(), vec![], vec![], vec![]);
+            market.trade_goods = Some(vec![listing(*good, *price), listing(TradeSymbol::Fuel, 2)]);
+            cache.markets.insert(symbol.to_string(), market);
+        }
+        cache
+    }
+
+    fn hauler(symbol: &str, waypoint: &str, capacity: i32) -> Ship {
+        let mut ship = Ship {
+            symbol: symbol.to_string(),
+            ..Ship::default()
+        };
+        ship.nav.waypoint_symbol = waypoint.to_string();
+        ship.nav.system_symbol = cache::system_symbol(waypoint).to_string();
+        ship.registration.role = ShipRole::Hauler;
+        ship.cargo.capacity = capacity;
+        ship.engine.speed = 10;
+        ship
+    }
+
+    /// A contract paying 10,000 for each `(good, destination, units)`, due
+    /// in a day.
+    fn contract(deliver: &[(&str, &str, i32)]) -> Contract {
+        let deadline = (Utc::now() + chrono::Duration::days(1)).to_rfc3339();
+        let mut terms = ContractTerms::new(deadline.clone(), ContractPayment::new(2_000, 8_000));
+        terms.deliver = Some(
+            deliver
+                .iter()
+                .map(|(good, destination, units)| {
+                    ContractDeliverGood::new(good.to_string(), destination.to_string(), *units, 0)
+                })
+                .collect(),
+        );
+        Contract::new(
+            String::from("C1"),
+            String::from("COSMIC"),
+            spacetraders_api::models::contract::Type::Procurement,
+            terms,
+            false,
+            false,
+            deadline,
+        )
+    }
+
+    #[test]
+    fn prices_goods_and_fuel_with_the_nearest_hauler() {
+        let cache = cache(
+            &[
+                ("X1-A-M", (0, 0)),
+                ("X1-A-D", (100, 0)),
+                ("X1-A-NEAR", (10, 0)),
+                ("X1-A-FAR", (-300, 0)),
+            ],
+            &[("X1-A-M", TradeSymbol::Iron, 50)],
+        );
+        let ships = [
+            hauler("FAR", "X1-A-FAR", 40),
+            hauler("NEAR", "X1-A-NEAR", 40),
+        ];
+        let estimate = evaluate(&cache, &ships, &contract(&[("IRON", "X1-A-D", 60)]));
+
+        assert_eq!(estimate.ship.as_deref(), Some("NEAR"));
+        assert_eq!(estimate.trips, 2);
+        assert!(estimate.priced);
+        // 10 out to the market and 100 across, then 200 back and forth
+        let fuel = (310.0 / FUEL_PER_UNIT).ceil() as i64 * 2;
+        assert_eq!(estimate.cost, 60 * 50 + fuel);
+        assert_eq!(estimate.profit(), 10_000 - estimate.cost);
+        assert_eq!(estimate.achievable, Some(true));
+        assert!(estimate.worth_accepting());
+    }
+
+    #[test]
+    fn ignores_ships_in_other_systems_and_satellites() {
+        let cache = cache(
+            &[("X1-A-M", (0, 0)), ("X1-A-D", (10, 0)), ("X1-B-H", (0, 0))],
+            &[("X1-A-M", TradeSymbol::Iron, 50)],
+        );
+        let mut probe = hauler("PROBE", "X1-A-M", 10);
+        probe.registration.role = ShipRole::Satellite;
+        let ships = [hauler("AWAY", "X1-B-H", 40), probe];
+        let estimate = evaluate(&cache, &ships, &contract(&[("IRON", "X1-A-D", 10)]));
+
+        assert_eq!(estimate.ship, None);
+        assert_eq!(estimate.achievable, None);
+        assert_eq!(estimate.verdict(), "UNKNOWN");
+    }
+
+    #[test]
+    fn leaves_contracts_spanning_systems_unrouted() {
+        let cache = cache(
+            &[
+                ("X1-A-M", (0, 0)),
+                ("X1-A-D", (10, 0)),
+                ("X1-B-D", (10, 0)),
+                ("X1-A-H", (5, 0)),
+                ("X1-B-H", (5, 0)),
+            ],
+            &[
+                ("X1-A-M", TradeSymbol::Iron, 5),
+                ("X1-B-D", TradeSymbol::Copper, 5),
+            ],
+        );
+        let ships = [hauler("A", "X1-A-H", 40), hauler("B", "X1-B-H", 40)];
+        let estimate = evaluate(
+            &cache,
+            &ships,
+            &contract(&[("IRON", "X1-A-D", 10), ("COPPER", "X1-B-D", 10)]),
+        );
+
+        assert!(estimate.priced);
+        assert!(estimate.profit() > 0);
+        assert_eq!(estimate.ship, None);
+        assert_eq!(estimate.achievable, None);
+        assert!(!estimate.worth_accepting());
+    }
+}
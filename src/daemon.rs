@@ -0,0 +1,626 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use spacetraders_api::models::{
+    ShipNavFlightMode, ShipType, TradeSymbol, WaypointTraitSymbol, WaypointType,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::Mutex;
+
+use crate::behaviour::{Behaviour, Scheduler};
+use crate::cache::{self, UniverseCache};
+use crate::client::{ApiError, Progress, SpaceTradersClient};
+use crate::condition::WearLog;
+use crate::events::{self, EventMonitor};
+use crate::rivals;
+
+/// How often the scheduler looks for idle ships.
+const TICK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Minutes between contract fetches for deadline alerts.
+const CONTRACT_POLL_MINUTES: i64 = 1;
+
+/// Socket the daemon listens on and attached clients connect to.
+pub fn socket_path() -> PathBuf {
+    cache::data_dir().join("daemon.sock")
+}
+
+/// A call to the daemon, sent as one line of JSON. API calls mirror the
+/// `SpaceTradersClient` methods so an attached client behaves like a direct one.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Request {
+    Status,
+    Agent,
+    Contracts,
+    Ships,
+    Systems {
+        page: Option<i32>,
+        limit: Option<i32>,
+    },
+    AllSystems,
+    PublicAgent {
+        symbol: String,
+    },
+    AllAgents,
+    Factions,
+    Faction {
+        symbol: String,
+    },
+    SystemWaypoints {
+        system: String,
+        waypoint_type: Option<WaypointType>,
+        waypoint_trait: Option<WaypointTraitSymbol>,
+    },
+    Waypoint {
+        system: String,
+        waypoint: String,
+    },
+    JumpGate {
+        system: String,
+        waypoint: String,
+    },
+    Construction {
+        system: String,
+        waypoint: String,
+    },
+    Market {
+        system: String,
+        waypoint: String,
+    },
+    SampleMarket {
+        system: String,
+        waypoint: String,
+    },
+    Shipyard {
+        system: String,
+        waypoint: String,
+    },
+    SupplyChain,
+    PurchaseShip {
+        ship_type: ShipType,
+        waypoint: String,
+    },
+    Navigate {
+        ship: String,
+        waypoint: String,
+    },
+    Dock {
+        ship: String,
+    },
+    Orbit {
+        ship: String,
+    },
+    FlightMode {
+        ship: String,
+        mode: ShipNavFlightMode,
+    },
+    PurchaseCargo {
+        ship: String,
+        good: TradeSymbol,
+        units: i32,
+    },
+    Extract {
+        ship: String,
+    },
+    Transfer {
+        ship: String,
+        to: String,
+        good: TradeSymbol,
+        units: i32,
+    },
+    SellCargo {
+        ship: String,
+        good: TradeSymbol,
+        units: i32,
+    },
+    Jettison {
+        ship: String,
+        good: TradeSymbol,
+        units: i32,
+    },
+    Refuel {
+        ship: String,
+        #[serde(default)]
+        units: Option<i32>,
+        #[serde(default)]
+        from_cargo: bool,
+    },
+    Jump {
+        ship: String,
+        waypoint: String,
+    },
+    Chart {
+        ship: String,
+    },
+    ScanSystems {
+        ship: String,
+    },
+    ScanWaypoints {
+        ship: String,
+    },
+    ScanShips {
+        ship: String,
+    },
+    SupplyConstruction {
+        ship: String,
+        waypoint: String,
+        good: TradeSymbol,
+        units: i32,
+    },
+    AcceptContract {
+        id: String,
+    },
+    DeliverContract {
+        id: String,
+        ship: String,
+        good: TradeSymbol,
+        units: i32,
+    },
+    FulfillContract {
+        id: String,
+    },
+    RepairQuote {
+        ship: String,
+    },
+    ScrapQuote {
+        ship: String,
+    },
+    Repair {
+        ship: String,
+    },
+    Scrap {
+        ship: String,
+    },
+    InstallMount {
+        ship: String,
+        part: String,
+    },
+    RemoveMount {
+        ship: String,
+        part: String,
+    },
+    InstallModule {
+        ship: String,
+        part: String,
+    },
+    RemoveModule {
+        ship: String,
+        part: String,
+    },
+    SetAutoRepair {
+        enabled: bool,
+    },
+    SetAutoContracts {
+        enabled: bool,
+    },
+    SetAutoRescue {
+        enabled: bool,
+    },
+    Rescue {
+        ship: String,
+    },
+    CancelRescue {
+        ship: String,
+    },
+    SetMarketWatch {
+        interval: Option<u64>,
+    },
+    Behaviours,
+    Assign {
+        ship: String,
+        behaviour: Behaviour,
+    },
+    Unassign {
+        ship: String,
+    },
+}
+
+/// Why a request failed, keeping API errors intact so attached clients
+/// still see the game's error codes.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Failure {
+    Api(ApiError),
+    Other(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    Ok(Value),
+    Error(Failure),
+}
+
+/// Sends one request to the daemon at `socket` and decodes its reply.
+pub async fn call<T: DeserializeOwned>(socket: &Path, request: Request) -> Result<T> {
+    let stream = UnixStream::connect(socket).await.map_err(|e| {
+        anyhow::anyhow!(
+            "cannot reach daemon at {} ({}); start it with `spacetraders daemon`",
+            socket.display(),
+            e
+        )
+    })?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut line = serde_json::to_string(&request)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+
+    let mut reply = String::new();
+    BufReader::new(reader).read_line(&mut reply).await?;
+    match serde_json::from_str(&reply)? {
+        Response::Ok(value) => Ok(serde_json::from_value(value)?),
+        Response::Error(Failure::Api(error)) => Err(error.into()),
+        Response::Error(Failure::Other(message)) => Err(anyhow::anyhow!(message)),
+    }
+}
+
+/// State shared by the scheduler and every control connection.
+struct Daemon {
+    client: SpaceTradersClient,
+    cache: Mutex<UniverseCache>,
+    scheduler: Mutex<Scheduler>,
+}
+
+impl Daemon {
+    async fn handle(&self, request: Request) -> Result<Value> {
+        let client = &self.client;
+        let value = match request {
+            Request::Status => serde_json::to_value(client.get_status().await?)?,
+            Request::Agent => serde_json::to_value(client.get_my_agent().await?)?,
+            Request::Contracts => serde_json::to_value(client.get_contracts().await?)?,
+            Request::Ships => serde_json::to_value(client.get_my_ships().await?)?,
+            Request::Systems { page, limit } => {
+                serde_json::to_value(client.get_systems(page, limit).await?)?
+            }
+            Request::PublicAgent { symbol } => {
+                serde_json::to_value(client.get_agent(&symbol).await?)?
+            }
+            Request::AllAgents => {
+                serde_json::to_value(client.get_all_agents(&Progress::default()).await?)?
+            }
+            Request::Factions => serde_json::to_value(client.get_factions().await?)?,
+            Request::Faction { symbol } => {
+                serde_json::to_value(client.get_faction(&symbol).await?)?
+            }
+            Request::AllSystems => {
+                let cached = {
+                    let cache = self.cache.lock().await;
+                    cache.systems_synced.then(|| cache.systems.clone())
+                };
+                match cached {
+                    Some(systems) => serde_json::to_value(systems)?,
+                    None => {
+                        // Download without the lock; it takes minutes
+                        let systems = client.get_all_systems(&Progress::default()).await?;
+                        let mut cache = self.cache.lock().await;
+                        cache.systems = systems;
+                        cache.systems_synced = true;
+                        cache.save()?;
+                        serde_json::to_value(&cache.systems)?
+                    }
+                }
+            }
+            Request::SystemWaypoints {
+                system,
+                waypoint_type,
+                waypoint_trait,
+            } => serde_json::to_value(
+                client
+                    .get_system_waypoints(&system, waypoint_type, waypoint_trait)
+                    .await?,
+            )?,
+            Request::Waypoint { system, waypoint } => {
+                serde_json::to_value(client.get_waypoint(&system, &waypoint).await?)?
+            }
+            Request::JumpGate { system, waypoint } => {
+                serde_json::to_value(client.get_jump_gate(&system, &waypoint).await?)?
+            }
+            Request::Construction { system, waypoint } => {
+                serde_json::to_value(client.get_construction(&system, &waypoint).await?)?
+            }
+            Request::Market { system, waypoint } => {
+                let market = client.get_market(&system, &waypoint).await?;
+                let mut cache = self.cache.lock().await;
+                cache.markets.insert(waypoint, market.clone());
+                cache.save()?;
+                serde_json::to_value(market)?
+            }
+            Request::SampleMarket { system, waypoint } => {
+                let market = client.sample_market(&system, &waypoint).await?;
+                let mut cache = self.cache.lock().await;
+                cache.markets.insert(waypoint, market.clone());
+                cache.save()?;
+                serde_json::to_value(market)?
+            }
+            Request::Shipyard { system, waypoint } => {
+                serde_json::to_value(client.get_shipyard(&system, &waypoint).await?)?
+            }
+            Request::SupplyChain => serde_json::to_value(client.get_supply_chain().await?)?,
+            Request::PurchaseShip {
+                ship_type,
+                waypoint,
+            } => serde_json::to_value(client.purchase_ship(ship_type, &waypoint).await?)?,
+            Request::Navigate { ship, waypoint } => {
+                serde_json::to_value(client.navigate_ship(&ship, &waypoint).await?)?
+            }
+            Request::Dock { ship } => serde_json::to_value(client.dock_ship(&ship).await?)?,
+            Request::Orbit { ship } => serde_json::to_value(client.orbit_ship(&ship).await?)?,
+            Request::FlightMode { ship, mode } => {
+                serde_json::to_value(client.set_flight_mode(&ship, mode).await?)?
+            }
+            Request::PurchaseCargo { ship, good, units } => {
+                serde_json::to_value(client.purchase_cargo(&ship, good, units).await?)?
+            }
+            Request::Extract { ship } => {
+                serde_json::to_value(client.extract_resources(&ship).await?)?
+            }
+            Request::Transfer {
+                ship,
+                to,
+                good,
+                units,
+            } => serde_json::to_value(client.transfer_cargo(&ship, &to, good, units).await?)?,
+            Request::SellCargo { ship, good, units } => {
+                serde_json::to_value(client.sell_cargo(&ship, good, units).await?)?
+            }
+            Request::Jettison { ship, good, units } => {
+                serde_json::to_value(client.jettison_cargo(&ship, good, units).await?)?
+            }
+            Request::Refuel {
+                ship,
+                units,
+                from_cargo,
+            } => serde_json::to_value(client.refuel_ship(&ship, units, from_cargo).await?)?,
+            Request::Jump { ship, waypoint } => {
+                serde_json::to_value(client.jump_ship(&ship, &waypoint).await?)?
+            }
+            Request::Chart { ship } => serde_json::to_value(client.create_chart(&ship).await?)?,
+            Request::ScanSystems { ship } => {
+                serde_json::to_value(client.scan_systems(&ship).await?)?
+            }
+            Request::ScanWaypoints { ship } => {
+                serde_json::to_value(client.scan_waypoints(&ship).await?)?
+            }
+            Request::ScanShips { ship } => serde_json::to_value(client.scan_ships(&ship).await?)?,
+            Request::SupplyConstruction {
+                ship,
+                waypoint,
+                good,
+                units,
+            } => serde_json::to_value(
+                client
+                    .supply_construction(&ship, &waypoint, good, units)
+                    .await?,
+            )?,
+            Request::AcceptContract { id } => {
+                serde_json::to_value(client.accept_contract(&id).await?)?
+            }
+            Request::DeliverContract {
+                id,
+                ship,
+                good,
+                units,
+            } => serde_json::to_value(client.deliver_contract(&id, &ship, good, units).await?)?,
+            Request::FulfillContract { id } => {
+                serde_json::to_value(client.fulfill_contract(&id).await?)?
+            }
+            Request::RepairQuote { ship } => {
+                serde_json::to_value(client.get_repair_quote(&ship).await?)?
+            }
+            Request::ScrapQuote { ship } => {
+                serde_json::to_value(client.get_scrap_quote(&ship).await?)?
+            }
+            Request::Repair { ship } => serde_json::to_value(client.repair_ship(&ship).await?)?,
+            Request::Scrap { ship } => serde_json::to_value(client.scrap_ship(&ship).await?)?,
+            Request::InstallMount { ship, part } => {
+                serde_json::to_value(client.install_mount(&ship, &part).await?)?
+            }
+            Request::RemoveMount { ship, part } => {
+                serde_json::to_value(client.remove_mount(&ship, &part).await?)?
+            }
+            Request::InstallModule { ship, part } => {
+                serde_json::to_value(client.install_module(&ship, &part).await?)?
+            }
+            Request::RemoveModule { ship, part } => {
+                serde_json::to_value(client.remove_module(&ship, &part).await?)?
+            }
+            Request::SetAutoRepair { enabled } => {
+                info!("Automatic repairs {}", if enabled { "on" } else { "off" });
+                self.scheduler.lock().await.set_auto_repair(enabled)?;
+                Value::Null
+            }
+            Request::SetAutoContracts { enabled } => {
+                info!("Automatic contracts {}", if enabled { "on" } else { "off" });
+                self.scheduler.lock().await.set_auto_contracts(enabled)?;
+                Value::Null
+            }
+            Request::SetAutoRescue { enabled } => {
+                info!("Automatic rescues {}", if enabled { "on" } else { "off" });
+                self.scheduler.lock().await.set_auto_rescue(enabled)?;
+                Value::Null
+            }
+            Request::Rescue { ship } => {
+                let ships = client.get_my_ships().await?;
+                let mut scheduler = self.scheduler.lock().await;
+                let cache = self.cache.lock().await;
+                let stranding = scheduler.start_rescue(&ships, &cache, &ship)?;
+                serde_json::to_value(stranding)?
+            }
+            Request::CancelRescue { ship } => {
+                let cancelled = self.scheduler.lock().await.cancel_rescue(&ship)?;
+                if cancelled {
+                    info!("Rescue of {} cancelled", ship);
+                }
+                serde_json::to_value(cancelled)?
+            }
+            Request::SetMarketWatch { interval } => {
+                match interval {
+                    Some(minutes) => info!("Market watch on, polling every {} min", minutes),
+                    None => info!("Market watch off"),
+                }
+                self.scheduler.lock().await.set_market_watch(interval)?;
+                Value::Null
+            }
+            Request::Behaviours => serde_json::to_value(&self.scheduler.lock().await.assignments)?,
+            Request::Assign { ship, behaviour } => {
+                let ships = client.get_my_ships().await?;
+                let Some(found) = ships.iter().find(|s| s.symbol == ship) else {
+                    anyhow::bail!("no ship named {}", ship);
+                };
+                behaviour.check(found)?;
+                info!("{} assigned: {}", ship, behaviour);
+                self.scheduler.lock().await.assign(ship, behaviour)?;
+                Value::Null
+            }
+            Request::Unassign { ship } => {
+                info!("{} unassigned", ship);
+                Value::Bool(self.scheduler.lock().await.unassign(&ship)?)
+            }
+        };
+        Ok(value)
+    }
+
+    /// Answers requests on one connection until the client hangs up.
+    async fn serve(&self, stream: UnixStream) -> Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => match self.handle(request).await {
+                    Ok(value) => Response::Ok(value),
+                    Err(e) => match e.downcast::<ApiError>() {
+                        Ok(api_error) => Response::Error(Failure::Api(api_error)),
+                        Err(e) => Response::Error(Failure::Other(e.to_string())),
+                    },
+                },
+                Err(e) => Response::Error(Failure::Other(format!("invalid request: {}", e))),
+            };
+            let mut reply = serde_json::to_string(&response)?;
+            reply.push('\n');
+            writer.write_all(reply.as_bytes()).await?;
+        }
+        Ok(())
+    }
+
+    /// Steps assigned behaviours forever, using fresh fleet data each tick.
+    /// Fleet alerts and wear are tracked here too, so they are logged while
+    /// no TUI is open.
+    async fn schedule(&self) {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        let mut monitor = EventMonitor::new();
+        let mut wear = WearLog::load();
+        let mut rivals_polled: Option<DateTime<Utc>> = None;
+        let mut contracts = Vec::new();
+        let mut contracts_polled: Option<DateTime<Utc>> = None;
+        loop {
+            interval.tick().await;
+            let now = Utc::now();
+            if rivals_polled.is_none_or(|polled| {
+                now - polled >= chrono::Duration::minutes(rivals::POLL_MINUTES)
+            }) {
+                rivals_polled = Some(now);
+                if let Err(e) = rivals::poll_watched(&self.client).await {
+                    events::record_error("Polling watched agents failed", &e);
+                }
+            }
+            let ships = match self.client.get_my_ships().await {
+                Ok(ships) => ships,
+                Err(e) => {
+                    events::record_error("Fetching ships failed", &e);
+                    continue;
+                }
+            };
+            if contracts_polled.is_none_or(|polled| {
+                now - polled >= chrono::Duration::minutes(CONTRACT_POLL_MINUTES)
+            }) {
+                contracts_polled = Some(now);
+                match self.client.get_contracts().await {
+                    Ok(fetched) => contracts = fetched,
+                    Err(e) => events::record_error("Fetching contracts failed", &e),
+                }
+            }
+            monitor.check(&ships, &contracts);
+            if let Err(e) = wear.record(&ships) {
+                events::record_error("Saving wear log failed", &e);
+            }
+            // Work on copies so control requests aren't held up for the
+            // whole tick, then fold the results back in
+            let scheduler = self.scheduler.lock().await.clone();
+            let cache = self.cache.lock().await.clone();
+            let (mut stepped, mut updated) = (scheduler.clone(), cache.clone());
+            if let Err(e) = stepped.tick(&self.client, &ships, &mut updated).await {
+                events::record_error("Saving behaviours failed", &e);
+            }
+            let mut live = self.scheduler.lock().await;
+            if let Err(e) = live.merge_tick(&scheduler, stepped) {
+                events::record_error("Saving behaviours failed", &e);
+            }
+            if !updated.same_as(&cache) {
+                let mut live = self.cache.lock().await;
+                live.merge_changes(&cache, updated);
+                if let Err(e) = live.save() {
+                    events::record_error("Saving cache failed", &e);
+                }
+            }
+        }
+    }
+}
+
+/// Runs the daemon until interrupted: the behaviour scheduler plus the
+/// control API on `socket_path()`.
+pub async fn run(client: SpaceTradersClient) -> Result<()> {
+    let socket = socket_path();
+    if socket.exists() {
+        if UnixStream::connect(&socket).await.is_ok() {
+            anyhow::bail!("a daemon is already listening on {}", socket.display());
+        }
+        // Left behind by a daemon that didn't shut down cleanly
+        fs::remove_file(&socket)?;
+    }
+    fs::create_dir_all(cache::data_dir())?;
+    let listener = UnixListener::bind(&socket)?;
+
+    let daemon = Arc::new(Daemon {
+        client,
+        cache: Mutex::new(UniverseCache::load()),
+        scheduler: Mutex::new(Scheduler::load()),
+    });
+    info!("Daemon listening on {}", socket.display());
+    println!(
+        "Daemon listening on {}; events are logged to {}",
+        socket.display(),
+        cache::data_dir().join("events.log").display()
+    );
+
+    let scheduler = Arc::clone(&daemon);
+    tokio::spawn(async move { scheduler.schedule().await });
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted?;
+                let daemon = Arc::clone(&daemon);
+                tokio::spawn(async move {
+                    if let Err(e) = daemon.serve(stream).await {
+                        events::record_error("Control connection failed", &e);
+                    }
+                });
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    info!("Daemon shutting down");
+    fs::remove_file(&socket)?;
+    Ok(())
+}
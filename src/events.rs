@@ -0,0 +1,358 @@
+use chrono::{DateTime, Local, Utc};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use spacetraders_api::models::{Contract, Ship, ShipNavStatus};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+
+use crate::cache;
+use crate::condition::{self, CONDITION_CRITICAL, CONDITION_WARNING};
+
+/// Events kept in memory for the log pane; older ones are only in the log file.
+const MAX_EVENTS: usize = 500;
+
+/// Size at which the log file is moved aside to `events.log.1` and a new
+/// one started, so a long-running daemon doesn't fill the disk.
+const MAX_LOG_BYTES: u64 = 10 * 1024 * 1024;
+
+/// How long before a contract deadline we start warning about it.
+const CONTRACT_DEADLINE_WARNING: chrono::Duration = chrono::Duration::hours(2);
+
+/// How long after an arrival or cooldown it is still reported, and its
+/// key remembered. Older ones are dropped so the daemon's memory of fired
+/// events stays bounded.
+const FIRED_RETENTION: chrono::Duration = chrono::Duration::hours(1);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EventKind {
+    Log,
+    ShipArrived,
+    CooldownFinished,
+    ContractDeadline,
+    CargoFull,
+    Condition,
+    ApiError(Option<i32>),
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Log => write!(f, "LOG"),
+            Self::ShipArrived => write!(f, "ARRIVED"),
+            Self::CooldownFinished => write!(f, "COOLDOWN"),
+            Self::ContractDeadline => write!(f, "DEADLINE"),
+            Self::CargoFull => write!(f, "CARGO"),
+            Self::Condition => write!(f, "CONDITION"),
+            Self::ApiError(Some(code)) => write!(f, "API {}", code),
+            Self::ApiError(None) => write!(f, "API"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub kind: EventKind,
+    pub message: String,
+}
+
+static EVENTS: Mutex<VecDeque<Event>> = Mutex::new(VecDeque::new());
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+static LOGGER: EventLogger = EventLogger;
+
+/// `log` backend that feeds the in-app event log instead of writing to the
+/// terminal underneath the TUI.
+struct EventLogger;
+
+impl Log for EventLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self::record(record.level(), EventKind::Log, record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+fn open_log() -> std::io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(cache::data_dir().join("events.log"))
+}
+
+/// Moves a full log file aside, replacing the previous one, and starts a
+/// new one.
+fn rotate(file: &mut File) -> std::io::Result<()> {
+    if file.metadata()?.len() < MAX_LOG_BYTES {
+        return Ok(());
+    }
+    let path = cache::data_dir().join("events.log");
+    fs::rename(&path, path.with_extension("log.1"))?;
+    *file = open_log()?;
+    Ok(())
+}
+
+/// Installs the event logger and opens the persisted log file. The level
+/// comes from `RUST_LOG` when it is a plain level name, otherwise `info`.
+pub fn init() -> anyhow::Result<()> {
+    fs::create_dir_all(cache::data_dir())?;
+    let mut file = open_log()?;
+    rotate(&mut file)?;
+    *LOG_FILE.lock().unwrap() = Some(file);
+
+    let level = std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|level| level.parse::<LevelFilter>().ok())
+        .unwrap_or(LevelFilter::Info);
+    log::set_logger(&LOGGER).map_err(|e| anyhow::anyhow!(e))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Adds an event to the log pane and appends it to the log file.
+pub fn record(level: Level, kind: EventKind, message: String) {
+    let event = Event {
+        time: Local::now(),
+        level,
+        kind,
+        message,
+    };
+
+    if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+        let _ = writeln!(
+            file,
+            "{} {:<5} [{}] {}",
+            event.time.to_rfc3339(),
+            event.level,
+            event.kind,
+            event.message
+        );
+        let _ = rotate(file);
+    }
+
+    let mut events = EVENTS.lock().unwrap();
+    if events.len() == MAX_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(event);
+}
+
+/// Records an error, tagging it with the API error code when it came from the game server.
+pub fn record_error(context: &str, error: &anyhow::Error) {
+    let kind = match error.downcast_ref::<crate::client::ApiError>() {
+        Some(api_error) => EventKind::ApiError(api_error.code),
+        None => EventKind::Log,
+    };
+    record(Level::Error, kind, format!("{}: {}", context, error));
+}
+
+/// Events at `level` or more severe, oldest first.
+pub fn recent(level: Level) -> Vec<Event> {
+    EVENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|event| event.level <= level)
+        .cloned()
+        .collect()
+}
+
+/// Watches fleet and contract data for changes worth an event. Each event
+/// fires once; anything that already happened before startup is ignored.
+pub struct EventMonitor {
+    started: DateTime<Utc>,
+    /// Keys of fired events, with when each can be forgotten; `None` keeps
+    /// it until the condition clears.
+    fired: HashMap<String, Option<DateTime<Utc>>>,
+}
+
+impl Default for EventMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventMonitor {
+    pub fn new() -> Self {
+        Self {
+            started: Utc::now(),
+            fired: HashMap::new(),
+        }
+    }
+
+    fn fire_once(
+        &mut self,
+        key: String,
+        forget_at: Option<DateTime<Utc>>,
+        level: Level,
+        kind: EventKind,
+        message: String,
+    ) {
+        if self.fired.insert(key, forget_at).is_none() {
+            record(level, kind, message);
+        }
+    }
+
+    /// When `timestamp` is, if it falls between startup and `now` and is
+    /// recent enough to report.
+    fn passed_since_start(&self, timestamp: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(timestamp)
+            .ok()
+            .map(|time| time.with_timezone(&Utc))
+            .filter(|time| *time > self.started && *time <= now && now - *time < FIRED_RETENTION)
+    }
+
+    pub fn check(&mut self, ships: &[Ship], contracts: &[Contract]) {
+        let now = Utc::now();
+        self.fired
+            .retain(|_, forget_at| forget_at.is_none_or(|forget_at| forget_at > now));
+
+        for ship in ships {
+            let route = &ship.nav.route;
+            let arrived = self.passed_since_start(&route.arrival, now);
+            if let Some(arrival) = arrived.filter(|_| ship.nav.status == ShipNavStatus::InTransit) {
+                self.fire_once(
+                    format!("arrived:{}:{}", ship.symbol, route.arrival),
+                    Some(arrival + FIRED_RETENTION),
+                    Level::Info,
+                    EventKind::ShipArrived,
+                    format!("{} arrived at {}", ship.symbol, route.destination.symbol),
+                );
+            }
+
+            if let Some(expiration) = &ship.cooldown.expiration {
+                if let Some(expired) = self.passed_since_start(expiration, now) {
+                    self.fire_once(
+                        format!("cooldown:{}:{}", ship.symbol, expiration),
+                        Some(expired + FIRED_RETENTION),
+                        Level::Info,
+                        EventKind::CooldownFinished,
+                        format!("{} cooldown finished", ship.symbol),
+                    );
+                }
+            }
+
+            let cargo_key = format!("cargo:{}", ship.symbol);
+            if ship.cargo.capacity > 0 && ship.cargo.units >= ship.cargo.capacity {
+                self.fire_once(
+                    cargo_key,
+                    None,
+                    Level::Warn,
+                    EventKind::CargoFull,
+                    format!(
+                        "{} cargo hold full ({}/{})",
+                        ship.symbol, ship.cargo.units, ship.cargo.capacity
+                    ),
+                );
+            } else {
+                // Allow the warning again the next time the hold fills up
+                self.fired.remove(&cargo_key);
+            }
+
+            for (component, condition, _) in condition::components(ship) {
+                for (threshold, level) in [
+                    (CONDITION_WARNING, Level::Warn),
+                    (CONDITION_CRITICAL, Level::Error),
+                ] {
+                    let key = format!("condition:{}:{}:{}", ship.symbol, component, threshold);
+                    if condition < threshold {
+                        self.fire_once(
+                            key,
+                            None,
+                            level,
+                            EventKind::Condition,
+                            format!(
+                                "{} {} condition at {:.0}%",
+                                ship.symbol,
+                                component.to_lowercase(),
+                                condition * 100.0
+                            ),
+                        );
+                    } else {
+                        // Warn again if it wears down after a repair
+                        self.fired.remove(&key);
+                    }
+                }
+            }
+        }
+
+        for contract in contracts
+            .iter()
+            .filter(|contract| contract.accepted && !contract.fulfilled)
+        {
+            let Ok(deadline) = DateTime::parse_from_rfc3339(&contract.terms.deadline) else {
+                continue;
+            };
+            let remaining = deadline.with_timezone(&Utc) - now;
+            // Expired contracts can no longer be fulfilled, so there is nothing to warn about
+            if remaining > chrono::Duration::zero() && remaining < CONTRACT_DEADLINE_WARNING {
+                self.fire_once(
+                    format!("deadline:{}", contract.id),
+                    Some(deadline.with_timezone(&Utc)),
+                    Level::Warn,
+                    EventKind::ContractDeadline,
+                    format!(
+                        "Contract {} deadline in {} minutes",
+                        contract.id,
+                        remaining.num_minutes()
+                    ),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arriving(minutes_ago: i64) -> Ship {
+        let mut ship = Ship {
+            symbol: String::from("SHIP-1"),
+            ..Ship::default()
+        };
+        ship.nav.status = ShipNavStatus::InTransit;
+        ship.nav.route.arrival = (Utc::now() - chrono::Duration::minutes(minutes_ago)).to_rfc3339();
+        ship
+    }
+
+    fn arrivals(monitor: &EventMonitor) -> usize {
+        monitor
+            .fired
+            .keys()
+            .filter(|key| key.starts_with("arrived:"))
+            .count()
+    }
+
+    #[test]
+    fn forgets_events_once_they_are_old() {
+        let mut monitor = EventMonitor::new();
+        monitor.started = Utc::now() - chrono::Duration::days(1);
+
+        let ship = arriving(2);
+        monitor.check(std::slice::from_ref(&ship), &[]);
+        assert_eq!(arrivals(&monitor), 1);
+        // Seen again, the same arrival doesn't add a key
+        monitor.check(&[ship], &[]);
+        assert_eq!(arrivals(&monitor), 1);
+
+        // Past the retention, old keys go and old arrivals aren't reported
+        for forget_at in monitor.fired.values_mut().flatten() {
+            *forget_at = Utc::now() - chrono::Duration::seconds(1);
+        }
+        monitor.check(&[arriving(120)], &[]);
+        assert_eq!(arrivals(&monitor), 0);
+    }
+}
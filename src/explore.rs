@@ -0,0 +1,305 @@
+use anyhow::Result;
+use spacetraders_api::models::{Ship, ShipNavStatus, Waypoint, WaypointTraitSymbol, WaypointType};
+
+use crate::cache::{self, UniverseCache};
+use crate::client::SpaceTradersClient;
+use crate::events::{self, EventKind};
+use crate::navigation::{distance, fly_to_for};
+use crate::search;
+
+fn has_trait(waypoint: &Waypoint, symbol: WaypointTraitSymbol) -> bool {
+    waypoint.traits.iter().any(|t| t.symbol == symbol)
+}
+
+/// Whether a probe still has something to learn at a waypoint: it is
+/// uncharted, or it is a marketplace or shipyard no ship has seen prices at.
+pub fn needs_visit(cache: &UniverseCache, waypoint: &Waypoint) -> bool {
+    has_trait(waypoint, WaypointTraitSymbol::Uncharted)
+        || has_trait(waypoint, WaypointTraitSymbol::Marketplace)
+            && cache
+                .markets
+                .get(&waypoint.symbol)
+                .is_none_or(|market| market.trade_goods.is_none())
+        || has_trait(waypoint, WaypointTraitSymbol::Shipyard)
+            && cache
+                .shipyards
+                .get(&waypoint.symbol)
+                .is_none_or(|shipyard| shipyard.ships.is_none())
+}
+
+/// Orders `stops` into a short path from `start`, ending at `end` if given.
+/// Flight time grows with distance, so the shortest path is also the
+/// quickest. Built nearest-neighbour first, then improved with 2-opt.
+pub fn plan_tour(
+    start: (i32, i32),
+    stops: &[(String, (i32, i32))],
+    end: Option<(i32, i32)>,
+) -> Vec<String> {
+    let mut remaining: Vec<&(String, (i32, i32))> = stops.iter().collect();
+    let mut path = Vec::new();
+    let mut position = start;
+    while !remaining.is_empty() {
+        let nearest = (0..remaining.len())
+            .min_by(|a, b| {
+                distance(position, remaining[*a].1).total_cmp(&distance(position, remaining[*b].1))
+            })
+            .unwrap();
+        let stop = remaining.swap_remove(nearest);
+        position = stop.1;
+        path.push(stop);
+    }
+
+    let point = |i: usize, path: &[&(String, (i32, i32))]| -> Option<(i32, i32)> {
+        match i {
+            0 => Some(start),
+            i if i <= path.len() => Some(path[i - 1].1),
+            _ => end,
+        }
+    };
+    let leg = |a: Option<(i32, i32)>, b: Option<(i32, i32)>| match (a, b) {
+        (Some(a), Some(b)) => distance(a, b),
+        _ => 0.0,
+    };
+    // Positions 1..=len are stops; reversing stops i..=j only changes the
+    // legs into i and out of j.
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 1..=path.len() {
+            for j in i + 1..=path.len() {
+                let before = leg(point(i - 1, &path), point(i, &path))
+                    + leg(point(j, &path), point(j + 1, &path));
+                let after = leg(point(i - 1, &path), point(j, &path))
+                    + leg(point(i, &path), point(j + 1, &path));
+                if after + 1e-9 < before {
+                    path[i - 1..j].reverse();
+                    improved = true;
+                }
+            }
+        }
+    }
+    path.into_iter().map(|(symbol, _)| symbol.clone()).collect()
+}
+
+/// Waypoints other than `here` that still need a visit, with their positions.
+fn stops(cache: &UniverseCache, waypoints: &[Waypoint], here: &str) -> Vec<(String, (i32, i32))> {
+    waypoints
+        .iter()
+        .filter(|w| w.symbol != here && needs_visit(cache, w))
+        .map(|w| (w.symbol.clone(), (w.x, w.y)))
+        .collect()
+}
+
+/// The rest of the tour the explore behaviour would fly from the ship's
+/// destination, from cached waypoints, for drawing on the system map.
+pub fn planned_tour(cache: &UniverseCache, ship: &Ship, across_gates: bool) -> Vec<String> {
+    let Some(waypoints) = cache.waypoints.get(&ship.nav.system_symbol) else {
+        return Vec::new();
+    };
+    let gate = waypoints
+        .iter()
+        .find(|w| across_gates && w.r#type == WaypointType::JumpGate);
+    let position = (ship.nav.route.destination.x, ship.nav.route.destination.y);
+    let mut tour = plan_tour(
+        position,
+        &stops(cache, waypoints, &ship.nav.waypoint_symbol),
+        gate.map(|gate| (gate.x, gate.y)),
+    );
+    tour.extend(gate.map(|gate| gate.symbol.clone()));
+    tour
+}
+
+/// Charts the waypoint the ship is at if needed and snapshots its market
+/// and shipyard into the cache.
+async fn survey(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    cache: &mut UniverseCache,
+    mut waypoint: Waypoint,
+) -> Result<()> {
+    if has_trait(&waypoint, WaypointTraitSymbol::Uncharted) {
+        // Someone else may have charted it since we cached it
+        match client.create_chart(&ship.symbol).await {
+            Ok(data) => {
+                events::record(
+                    log::Level::Info,
+                    EventKind::Log,
+                    format!("{} charted {}", ship.symbol, waypoint.symbol),
+                );
+                waypoint = *data.waypoint;
+            }
+            Err(e) => {
+                events::record_error(&format!("{} chart", ship.symbol), &e);
+                waypoint = client
+                    .get_waypoint(&waypoint.system_symbol, &waypoint.symbol)
+                    .await?;
+            }
+        }
+        if let Some(cached) = cache
+            .waypoints
+            .get_mut(&waypoint.system_symbol)
+            .and_then(|waypoints| waypoints.iter_mut().find(|w| w.symbol == waypoint.symbol))
+        {
+            *cached = waypoint.clone();
+        }
+    }
+    if has_trait(&waypoint, WaypointTraitSymbol::Marketplace) {
+        let market = client
+            .get_market(&waypoint.system_symbol, &waypoint.symbol)
+            .await?;
+        cache.markets.insert(waypoint.symbol.clone(), market);
+    }
+    if has_trait(&waypoint, WaypointTraitSymbol::Shipyard) {
+        let shipyard = client
+            .get_shipyard(&waypoint.system_symbol, &waypoint.symbol)
+            .await?;
+        cache.shipyards.insert(waypoint.symbol.clone(), shipyard);
+    }
+    cache.save()
+}
+
+/// The system's jump gate and the gate of the first connected system not
+/// explored yet.
+async fn next_jump(
+    client: &SpaceTradersClient,
+    cache: &mut UniverseCache,
+    system: &str,
+    visited: &[String],
+) -> Result<Option<(Waypoint, String)>> {
+    let Some(gate) = cache
+        .waypoints
+        .get(system)
+        .and_then(|waypoints| {
+            waypoints
+                .iter()
+                .find(|w| w.r#type == WaypointType::JumpGate)
+        })
+        .cloned()
+    else {
+        return Ok(None);
+    };
+    if !cache.jump_gates.contains_key(&gate.symbol) {
+        let jump_gate = client.get_jump_gate(system, &gate.symbol).await?;
+        cache
+            .jump_gates
+            .insert(gate.symbol.clone(), jump_gate.connections);
+        cache.save()?;
+    }
+    let next = cache.jump_gates[&gate.symbol]
+        .iter()
+        .find(|connection| {
+            !visited
+                .iter()
+                .any(|v| v == cache::system_symbol(connection))
+        })
+        .cloned();
+    Ok(next.map(|next| (gate, next)))
+}
+
+/// One step of the explore behaviour: survey where the ship is, then head
+/// for the next stop of the shortest tour of what is left to see. With
+/// `across_gates` the tour ends at the jump gate and the ship jumps on to
+/// a system it hasn't explored. Returns false once there is nothing left.
+pub async fn step(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    cache: &mut UniverseCache,
+    across_gates: bool,
+    visited: &mut Vec<String>,
+) -> Result<bool> {
+    let system = ship.nav.system_symbol.clone();
+    search::cache_waypoints(client, cache, &system).await?;
+    if !visited.contains(&system) {
+        visited.push(system.clone());
+    }
+
+    let here = cache.waypoints[&system]
+        .iter()
+        .find(|w| w.symbol == ship.nav.waypoint_symbol)
+        .cloned();
+    if let Some(here) = here.filter(|w| needs_visit(cache, w)) {
+        if ship.nav.status == ShipNavStatus::InTransit {
+            // Arrived, but the server still reports the flight
+            return Ok(true);
+        }
+        survey(client, ship, cache, here).await?;
+    }
+
+    let stops = stops(cache, &cache.waypoints[&system], &ship.nav.waypoint_symbol);
+    let jump = if across_gates {
+        next_jump(client, cache, &system, visited).await?
+    } else {
+        None
+    };
+
+    let position = (ship.nav.route.destination.x, ship.nav.route.destination.y);
+    let tour = plan_tour(
+        position,
+        &stops,
+        jump.as_ref().map(|(gate, _)| (gate.x, gate.y)),
+    );
+    let target = match (tour.first(), &jump) {
+        (Some(stop), _) => stop.clone(),
+        (None, Some((gate, next))) if gate.symbol == ship.nav.waypoint_symbol => {
+            if ship.nav.status == ShipNavStatus::Docked {
+                client.orbit_ship(&ship.symbol).await?;
+            }
+            client.jump_ship(&ship.symbol, next).await?;
+            events::record(
+                log::Level::Info,
+                EventKind::Log,
+                format!("{} jumped to {}", ship.symbol, cache::system_symbol(next)),
+            );
+            return Ok(true);
+        }
+        (None, Some((gate, _))) => gate.symbol.clone(),
+        (None, None) => return Ok(false),
+    };
+
+    let purpose = format!("to explore, {} stops left", tour.len().max(1));
+    fly_to_for(client, ship, &target, &purpose).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stops(points: &[(&str, (i32, i32))]) -> Vec<(String, (i32, i32))> {
+        points
+            .iter()
+            .map(|(symbol, point)| (symbol.to_string(), *point))
+            .collect()
+    }
+
+    #[test]
+    fn visits_stops_along_a_line_in_order() {
+        let stops = stops(&[("C", (30, 0)), ("A", (10, 0)), ("B", (20, 0))]);
+        assert_eq!(plan_tour((0, 0), &stops, None), vec!["A", "B", "C"]);
+        assert!(plan_tour((0, 0), &[], Some((5, 5))).is_empty());
+    }
+
+    #[test]
+    fn saves_the_stop_nearest_the_end_for_last() {
+        // Nearest neighbour alone would finish at D, far from the gate at
+        // (50, 0)
+        let stops = stops(&[("A", (-10, 0)), ("D", (0, -30)), ("B", (20, 0))]);
+        assert_eq!(plan_tour((0, 0), &stops, None), vec!["A", "B", "D"]);
+        assert_eq!(
+            plan_tour((0, 0), &stops, Some((50, 0))),
+            vec!["A", "D", "B"]
+        );
+    }
+
+    #[test]
+    fn improves_on_nearest_neighbour() {
+        let stops = stops(&[
+            ("A", (3, -10)),
+            ("B", (6, -3)),
+            ("C", (4, 5)),
+            ("D", (7, -3)),
+        ]);
+        // Nearest neighbour alone goes C, B, D, A, a little further
+        assert_eq!(plan_tour((0, 0), &stops, None), vec!["C", "D", "B", "A"]);
+    }
+}
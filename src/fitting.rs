@@ -0,0 +1,573 @@
+use anyhow::Result;
+use serde::Serialize;
+use spacetraders_api::models::{
+    Market, Ship, ShipCargo, ShipModule, ShipMount, ShipNavStatus, ShipRequirements, Shipyard,
+    TradeSymbol,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::client::SpaceTradersClient;
+use crate::prices;
+
+/// The API's symbol for a generated enum, e.g. `MOUNT_MINING_LASER_I`.
+pub fn symbol_name<T: Serialize>(symbol: &T) -> String {
+    serde_json::to_value(symbol)
+        .ok()
+        .and_then(|value| value.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PartKind {
+    Mount,
+    Module,
+}
+
+impl PartKind {
+    /// Mounts and modules are traded as goods named after their kind.
+    pub fn of(symbol: &str) -> Option<Self> {
+        if symbol.starts_with("MOUNT_") {
+            Some(Self::Mount)
+        } else if symbol.starts_with("MODULE_") {
+            Some(Self::Module)
+        } else {
+            None
+        }
+    }
+}
+
+/// A mount or module with the requirements it places on a ship.
+#[derive(Clone, Debug, Serialize)]
+pub struct Part {
+    pub symbol: String,
+    pub name: String,
+    pub kind: PartKind,
+    pub requirements: ShipRequirements,
+    /// Crew berths the part adds, for crew quarters.
+    pub crew_capacity: i32,
+}
+
+impl Part {
+    pub fn mount(mount: &ShipMount) -> Self {
+        Self {
+            symbol: symbol_name(&mount.symbol),
+            name: mount.name.clone(),
+            kind: PartKind::Mount,
+            requirements: (*mount.requirements).clone(),
+            crew_capacity: 0,
+        }
+    }
+
+    pub fn module(module: &ShipModule) -> Self {
+        let symbol = symbol_name(&module.symbol);
+        let crew_capacity = if symbol.starts_with("MODULE_CREW_QUARTERS") {
+            module.capacity.unwrap_or(0)
+        } else {
+            0
+        };
+        Self {
+            symbol,
+            name: module.name.clone(),
+            kind: PartKind::Module,
+            requirements: (*module.requirements).clone(),
+            crew_capacity,
+        }
+    }
+}
+
+/// Every part whose requirements we know, from our own ships and from the
+/// ships on offer at shipyards we've seen. Markets only list prices.
+pub fn catalog<'a>(
+    ships: &[Ship],
+    shipyards: impl IntoIterator<Item = &'a Shipyard>,
+) -> BTreeMap<String, Part> {
+    let mut parts = BTreeMap::new();
+    let listed = shipyards
+        .into_iter()
+        .flat_map(|shipyard| shipyard.ships.iter().flatten())
+        .map(|listing| (&listing.mounts, &listing.modules));
+    let owned = ships.iter().map(|ship| (&ship.mounts, &ship.modules));
+    for (mounts, modules) in owned.chain(listed) {
+        for mount in mounts {
+            let part = Part::mount(mount);
+            parts.entry(part.symbol.clone()).or_insert(part);
+        }
+        for module in modules {
+            let part = Part::module(module);
+            parts.entry(part.symbol.clone()).or_insert(part);
+        }
+    }
+    parts
+}
+
+/// The mounts and modules a ship carries, or should carry, by symbol.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Loadout {
+    pub mounts: Vec<String>,
+    pub modules: Vec<String>,
+}
+
+impl Loadout {
+    pub fn of(ship: &Ship) -> Self {
+        Self {
+            mounts: ship.mounts.iter().map(|m| symbol_name(&m.symbol)).collect(),
+            modules: ship
+                .modules
+                .iter()
+                .map(|m| symbol_name(&m.symbol))
+                .collect(),
+        }
+    }
+
+    pub fn parts(&self) -> impl Iterator<Item = &String> {
+        self.mounts.iter().chain(&self.modules)
+    }
+
+    pub fn add(&mut self, symbol: &str) {
+        match PartKind::of(symbol) {
+            Some(PartKind::Mount) => self.mounts.push(symbol.to_string()),
+            Some(PartKind::Module) => self.modules.push(symbol.to_string()),
+            None => {}
+        }
+    }
+
+    /// Removes one copy of `symbol`, returning whether there was one.
+    pub fn remove(&mut self, symbol: &str) -> bool {
+        for list in [&mut self.mounts, &mut self.modules] {
+            if let Some(i) = list.iter().position(|s| s == symbol) {
+                list.remove(i);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Power, crew and slot use of a loadout against what the ship provides.
+#[derive(Clone, Debug, Serialize)]
+pub struct Budget {
+    pub power_used: i32,
+    pub power_output: i32,
+    pub crew_required: i32,
+    pub crew_capacity: i32,
+    pub slots_used: i32,
+    pub module_slots: i32,
+    pub mounts_used: i32,
+    pub mounting_points: i32,
+    /// Planned parts we have no requirements for.
+    pub unknown_parts: Vec<String>,
+}
+
+impl Budget {
+    /// Requirements the loadout breaks; empty when it can be installed.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.power_used > self.power_output {
+            problems.push(format!(
+                "needs {} power but the reactor outputs {}",
+                self.power_used, self.power_output
+            ));
+        }
+        if self.crew_required > self.crew_capacity {
+            problems.push(format!(
+                "needs {} crew but there are berths for {}",
+                self.crew_required, self.crew_capacity
+            ));
+        }
+        if self.slots_used > self.module_slots {
+            problems.push(format!(
+                "needs {} module slots but the frame has {}",
+                self.slots_used, self.module_slots
+            ));
+        }
+        if self.mounts_used > self.mounting_points {
+            problems.push(format!(
+                "needs {} mounting points but the frame has {}",
+                self.mounts_used, self.mounting_points
+            ));
+        }
+        for symbol in &self.unknown_parts {
+            problems.push(format!("requirements of {} are unknown", symbol));
+        }
+        problems
+    }
+}
+
+pub fn budget(ship: &Ship, planned: &Loadout, catalog: &BTreeMap<String, Part>) -> Budget {
+    let mut unknown_parts = Vec::new();
+    let mut lookup = |symbol: &String| match catalog.get(symbol) {
+        Some(part) => Some(part),
+        None => {
+            unknown_parts.push(symbol.clone());
+            None
+        }
+    };
+    let planned_parts: Vec<&Part> = planned.parts().filter_map(&mut lookup).collect();
+    let current = Loadout::of(ship);
+    let current_parts: Vec<&Part> = current.parts().filter_map(|s| catalog.get(s)).collect();
+
+    let sum = |parts: &[&Part], value: &dyn Fn(&Part) -> i32| -> i32 {
+        parts.iter().map(|part| value(part)).sum()
+    };
+    let power = |part: &Part| part.requirements.power.unwrap_or(0);
+    let crew = |part: &Part| part.requirements.crew.unwrap_or(0);
+    let berths = |part: &Part| part.crew_capacity;
+    let slots = |part: &Part| match part.kind {
+        PartKind::Module => part.requirements.slots.unwrap_or(1),
+        PartKind::Mount => 0,
+    };
+
+    // The server reports crew for the current fit, so adjust it by the
+    // difference between current and planned parts.
+    Budget {
+        power_used: ship.frame.requirements.power.unwrap_or(0)
+            + ship.engine.requirements.power.unwrap_or(0)
+            + sum(&planned_parts, &power),
+        power_output: ship.reactor.power_output,
+        crew_required: ship.crew.required - sum(&current_parts, &crew) + sum(&planned_parts, &crew),
+        crew_capacity: ship.crew.capacity - sum(&current_parts, &berths)
+            + sum(&planned_parts, &berths),
+        slots_used: sum(&planned_parts, &slots),
+        module_slots: ship.frame.module_slots,
+        mounts_used: planned.mounts.len() as i32,
+        mounting_points: ship.frame.mounting_points,
+        unknown_parts,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Action {
+    Remove,
+    Install,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Change {
+    pub action: Action,
+    pub part: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.action {
+            Action::Remove => write!(f, "remove {}", self.part),
+            Action::Install => write!(f, "install {}", self.part),
+        }
+    }
+}
+
+/// Steps from the current loadout to the planned one, removals first so
+/// slots and power are free before anything is installed.
+pub fn changes(current: &Loadout, planned: &Loadout) -> Vec<Change> {
+    let mut removals = Vec::new();
+    let mut remaining = planned.clone();
+    for symbol in current.parts() {
+        if !remaining.remove(symbol) {
+            removals.push(Change {
+                action: Action::Remove,
+                part: symbol.clone(),
+            });
+        }
+    }
+    let installs = remaining.parts().map(|symbol| Change {
+        action: Action::Install,
+        part: symbol.clone(),
+    });
+    removals.into_iter().chain(installs).collect()
+}
+
+/// What a set of changes costs at the ship's current waypoint.
+#[derive(Clone, Debug, Serialize)]
+pub struct Quote {
+    pub parts: i64,
+    pub fees: i64,
+    pub total: i64,
+}
+
+/// Prices the changes: parts not already in the hold are bought at the
+/// market, and the shipyard charges its fee for every install and removal.
+pub fn quote(
+    changes: &[Change],
+    cargo: &ShipCargo,
+    market: Option<&Market>,
+    shipyard: &Shipyard,
+) -> Result<Quote, String> {
+    let mut in_hold: BTreeMap<String, i32> = cargo
+        .inventory
+        .iter()
+        .map(|item| (item.symbol.to_string(), item.units))
+        .collect();
+    let mut parts = 0;
+    for change in changes {
+        match change.action {
+            Action::Remove => *in_hold.entry(change.part.clone()).or_default() += 1,
+            Action::Install => {
+                if let Some(units) = in_hold.get_mut(&change.part).filter(|units| **units > 0) {
+                    *units -= 1;
+                    continue;
+                }
+                let price = market
+                    .and_then(|market| market.trade_goods.as_ref())
+                    .and_then(|goods| goods.iter().find(|g| g.symbol.to_string() == change.part))
+                    .map(|good| good.purchase_price as i64)
+                    .ok_or_else(|| format!("{} is not for sale here", change.part))?;
+                parts += price;
+            }
+        }
+    }
+    let fees = changes.len() as i64 * shipyard.modifications_fee as i64;
+    Ok(Quote {
+        parts,
+        fees,
+        total: parts + fees,
+    })
+}
+
+/// A planned loadout with everything needed to judge it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Plan {
+    pub planned: Loadout,
+    pub budget: Budget,
+    pub changes: Vec<Change>,
+    pub quote: Option<Quote>,
+    /// Reasons the plan can't be applied; empty when it can.
+    pub problems: Vec<String>,
+}
+
+pub fn plan(
+    ship: &Ship,
+    planned: Loadout,
+    catalog: &BTreeMap<String, Part>,
+    market: Option<&Market>,
+    shipyard: Option<&Shipyard>,
+) -> Plan {
+    let budget = budget(ship, &planned, catalog);
+    let changes = changes(&Loadout::of(ship), &planned);
+    let mut problems = budget.problems();
+    let quote = match shipyard {
+        Some(shipyard) => match quote(&changes, &ship.cargo, market, shipyard) {
+            Ok(quote) => Some(quote),
+            Err(problem) => {
+                problems.push(problem);
+                None
+            }
+        },
+        None => {
+            problems.push(format!("no shipyard at {}", ship.nav.waypoint_symbol));
+            None
+        }
+    };
+    Plan {
+        planned,
+        budget,
+        changes,
+        quote,
+        problems,
+    }
+}
+
+/// Carries out the changes in order, buying parts that aren't in the hold.
+/// Stops at the first failure; earlier changes stay applied.
+pub async fn apply(client: &SpaceTradersClient, ship: &Ship, changes: &[Change]) -> Result<()> {
+    let mut in_hold: BTreeMap<String, i32> = ship
+        .cargo
+        .inventory
+        .iter()
+        .map(|item| (item.symbol.to_string(), item.units))
+        .collect();
+    if ship.nav.status != ShipNavStatus::Docked {
+        client.dock_ship(&ship.symbol).await?;
+    }
+    let waypoint = &ship.nav.waypoint_symbol;
+    let mut bought = false;
+    for change in changes {
+        let kind = PartKind::of(&change.part)
+            .ok_or_else(|| anyhow::anyhow!("{} is not a mount or module", change.part))?;
+        match change.action {
+            Action::Remove => {
+                match kind {
+                    PartKind::Mount => {
+                        client.remove_mount(&ship.symbol, &change.part).await?;
+                    }
+                    PartKind::Module => {
+                        client.remove_module(&ship.symbol, &change.part).await?;
+                    }
+                }
+                *in_hold.entry(change.part.clone()).or_default() += 1;
+            }
+            Action::Install => {
+                match in_hold.get_mut(&change.part).filter(|units| **units > 0) {
+                    Some(units) => *units -= 1,
+                    None => {
+                        let good: TradeSymbol =
+                            serde_json::from_value(serde_json::Value::String(change.part.clone()))?;
+                        if !bought {
+                            prices::before_trading(client, waypoint).await?;
+                            bought = true;
+                        }
+                        client.purchase_cargo(&ship.symbol, good, 1).await?;
+                    }
+                }
+                match kind {
+                    PartKind::Mount => {
+                        client.install_mount(&ship.symbol, &change.part).await?;
+                    }
+                    PartKind::Module => {
+                        client.install_module(&ship.symbol, &change.part).await?;
+                    }
+                }
+            }
+        }
+    }
+    if bought {
+        prices::after_trading(client, waypoint).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetraders_api::models::{ship_module, ship_mount};
+
+    fn requirements(power: i32, crew: i32, slots: Option<i32>) -> ShipRequirements {
+        ShipRequirements {
+            power: Some(power),
+            crew: Some(crew),
+            slots,
+        }
+    }
+
+    /// A catalog entry: symbol, power, crew, module slots and berths.
+    fn part(symbol: &str, power: i32, crew: i32, slots: Option<i32>, berths: i32) -> Part {
+        Part {
+            symbol: symbol.to_string(),
+            name: symbol.to_string(),
+            kind: PartKind::of(symbol).unwrap(),
+            requirements: requirements(power, crew, slots),
+            crew_capacity: berths,
+        }
+    }
+
+    fn catalog() -> BTreeMap<String, Part> {
+        [
+            part("MOUNT_MINING_LASER_I", 1, 1, None, 0),
+            part("MOUNT_GAS_SIPHON_I", 2, 2, None, 0),
+            part("MODULE_CARGO_HOLD_I", 1, 0, Some(1), 0),
+            part("MODULE_CREW_QUARTERS_I", 1, 0, Some(1), 20),
+        ]
+        .into_iter()
+        .map(|part| (part.symbol.clone(), part))
+        .collect()
+    }
+
+    /// A ship with a mining laser and a cargo hold, a reactor putting out
+    /// 10 power and a frame with two module slots and two mounting points.
+    fn ship() -> Ship {
+        let mut ship = Ship {
+            mounts: vec![ShipMount::new(
+                ship_mount::Symbol::MountMiningLaserI,
+                String::new(),
+                requirements(1, 1, None),
+            )],
+            modules: vec![ShipModule::new(
+                ship_module::Symbol::ModuleCargoHoldI,
+                String::new(),
+                String::new(),
+                requirements(1, 0, Some(1)),
+            )],
+            ..Ship::default()
+        };
+        ship.frame.requirements.power = Some(1);
+        ship.frame.module_slots = 2;
+        ship.frame.mounting_points = 2;
+        ship.engine.requirements.power = Some(1);
+        ship.reactor.power_output = 10;
+        ship.crew.required = 5;
+        ship.crew.capacity = 10;
+        ship
+    }
+
+    fn loadout(mounts: &[&str], modules: &[&str]) -> Loadout {
+        Loadout {
+            mounts: mounts.iter().map(|s| s.to_string()).collect(),
+            modules: modules.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn budgets_the_planned_loadout_against_the_ship() {
+        let planned = loadout(
+            &["MOUNT_GAS_SIPHON_I", "MOUNT_GAS_SIPHON_I"],
+            &["MODULE_CARGO_HOLD_I", "MODULE_CREW_QUARTERS_I"],
+        );
+        let budget = budget(&ship(), &planned, &catalog());
+
+        assert_eq!(budget.power_used, 8);
+        // The laser's crew leaves, the siphons' joins
+        assert_eq!(budget.crew_required, 8);
+        assert_eq!(budget.crew_capacity, 30);
+        assert_eq!(budget.slots_used, 2);
+        assert_eq!(budget.mounts_used, 2);
+        assert!(budget.problems().is_empty());
+    }
+
+    #[test]
+    fn reports_what_the_loadout_breaks() {
+        let planned = loadout(
+            &[
+                "MOUNT_GAS_SIPHON_I",
+                "MOUNT_GAS_SIPHON_I",
+                "MOUNT_SURVEYOR_II",
+            ],
+            &["MODULE_CARGO_HOLD_I"],
+        );
+        let mut ship = ship();
+        ship.reactor.power_output = 6;
+        let budget = budget(&ship, &planned, &catalog());
+
+        assert_eq!(budget.unknown_parts, vec!["MOUNT_SURVEYOR_II"]);
+        assert_eq!(
+            budget.problems(),
+            vec![
+                "needs 7 power but the reactor outputs 6",
+                "needs 3 mounting points but the frame has 2",
+                "requirements of MOUNT_SURVEYOR_II are unknown",
+            ]
+        );
+    }
+
+    #[test]
+    fn removes_before_installing_and_keeps_shared_parts() {
+        let current = loadout(
+            &[
+                "MOUNT_MINING_LASER_I",
+                "MOUNT_MINING_LASER_I",
+                "MOUNT_GAS_SIPHON_I",
+            ],
+            &["MODULE_CARGO_HOLD_I"],
+        );
+        let planned = loadout(
+            &["MOUNT_MINING_LASER_I", "MOUNT_SURVEYOR_I"],
+            &["MODULE_CARGO_HOLD_I", "MODULE_CREW_QUARTERS_I"],
+        );
+        let steps: Vec<String> = changes(&current, &planned)
+            .iter()
+            .map(Change::to_string)
+            .collect();
+
+        assert_eq!(
+            steps,
+            vec![
+                "remove MOUNT_MINING_LASER_I",
+                "remove MOUNT_GAS_SIPHON_I",
+                "install MOUNT_SURVEYOR_I",
+                "install MODULE_CREW_QUARTERS_I",
+            ]
+        );
+        assert!(changes(&planned, &planned).is_empty());
+    }
+}
@@ -0,0 +1,237 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use spacetraders_api::models::{Ship, ShipNavStatus};
+use std::fmt;
+use tokio::task::JoinSet;
+
+use crate::behaviour::Behaviour;
+use crate::client::SpaceTradersClient;
+use crate::events::{self, EventKind};
+use crate::prices;
+
+/// An order given to several ships at once.
+#[derive(Clone, Debug, PartialEq, Subcommand)]
+pub enum FleetCommand {
+    /// Fly every ship to a waypoint in its system
+    Navigate { waypoint: String },
+    /// Dock every ship at its current waypoint
+    Dock,
+    /// Move every ship into orbit
+    Orbit,
+    /// Fill every ship's tank at its market
+    Refuel,
+    /// Sell every ship's cargo that its current market buys
+    Sell,
+    /// Hand every ship to a daemon behaviour
+    Assign {
+        #[command(subcommand)]
+        behaviour: Behaviour,
+    },
+}
+
+impl fmt::Display for FleetCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Navigate { waypoint } => write!(f, "navigate to {}", waypoint),
+            Self::Dock => write!(f, "dock"),
+            Self::Orbit => write!(f, "orbit"),
+            Self::Refuel => write!(f, "refuel"),
+            Self::Sell => write!(f, "sell"),
+            Self::Assign { behaviour } => write!(f, "assign {}", behaviour),
+        }
+    }
+}
+
+#[derive(Parser)]
+#[command(no_binary_name = true)]
+struct Typed {
+    #[command(subcommand)]
+    command: FleetCommand,
+}
+
+impl FleetCommand {
+    /// Parses an order typed as on the command line, e.g. `dock` or
+    /// `assign mine X1-AB12-EF5`.
+    pub fn parse(words: &[&str]) -> Option<Self> {
+        Typed::try_parse_from(words)
+            .ok()
+            .map(|typed| typed.command.normalized())
+    }
+
+    /// Upper-cases symbols so they can be typed in any case.
+    pub fn normalized(self) -> Self {
+        match self {
+            Self::Navigate { waypoint } => Self::Navigate {
+                waypoint: waypoint.to_uppercase(),
+            },
+            Self::Assign { behaviour } => Self::Assign {
+                behaviour: behaviour.normalized(),
+            },
+            other => other,
+        }
+    }
+}
+
+/// How one ship fared.
+#[derive(Clone, Debug, Serialize)]
+pub struct Outcome {
+    pub ship: String,
+    pub ok: bool,
+    pub message: String,
+}
+
+async fn sell_all(client: &SpaceTradersClient, ship: &Ship) -> Result<String> {
+    if ship.cargo.inventory.is_empty() {
+        return Ok(String::from("hold already empty"));
+    }
+    let waypoint = &ship.nav.waypoint_symbol;
+    let market = prices::before_trading(client, waypoint).await?;
+    let goods = market.trade_goods.unwrap_or_default();
+    if ship.nav.status == ShipNavStatus::InOrbit {
+        client.dock_ship(&ship.symbol).await?;
+    }
+    let mut earned = 0;
+    let mut unsold = Vec::new();
+    for item in &ship.cargo.inventory {
+        let Some(good) = goods.iter().find(|g| g.symbol == item.symbol) else {
+            unsold.push(item.symbol.to_string());
+            continue;
+        };
+        let mut remaining = item.units;
+        while remaining > 0 {
+            let units = remaining.min(good.trade_volume.max(1));
+            let data = client.sell_cargo(&ship.symbol, item.symbol, units).await?;
+            earned += data.transaction.total_price;
+            remaining -= units;
+        }
+    }
+    prices::after_trading(client, waypoint).await;
+    Ok(if unsold.is_empty() {
+        format!("sold for {}", earned)
+    } else {
+        format!("sold for {}, {} not bought here", earned, unsold.join(", "))
+    })
+}
+
+async fn execute(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    command: &FleetCommand,
+) -> Result<String> {
+    match command {
+        FleetCommand::Navigate { waypoint } => {
+            if ship.nav.status == ShipNavStatus::Docked {
+                client.orbit_ship(&ship.symbol).await?;
+            }
+            let data = client.navigate_ship(&ship.symbol, waypoint).await?;
+            Ok(format!("arriving {}", data.nav.route.arrival))
+        }
+        FleetCommand::Dock => match ship.nav.status {
+            ShipNavStatus::Docked => Ok(String::from("already docked")),
+            _ => {
+                let nav = client.dock_ship(&ship.symbol).await?;
+                Ok(format!("docked at {}", nav.waypoint_symbol))
+            }
+        },
+        FleetCommand::Orbit => match ship.nav.status {
+            ShipNavStatus::InOrbit => Ok(String::from("already in orbit")),
+            _ => {
+                let nav = client.orbit_ship(&ship.symbol).await?;
+                Ok(format!("in orbit at {}", nav.waypoint_symbol))
+            }
+        },
+        FleetCommand::Refuel => {
+            if ship.fuel.current >= ship.fuel.capacity {
+                return Ok(String::from("tank already full"));
+            }
+            if ship.nav.status == ShipNavStatus::InOrbit {
+                client.dock_ship(&ship.symbol).await?;
+            }
+            let data = client.refuel_ship(&ship.symbol, None, false).await?;
+            Ok(format!(
+                "refuelled to {}/{} for {}",
+                data.fuel.current, data.fuel.capacity, data.transaction.total_price
+            ))
+        }
+        FleetCommand::Sell => sell_all(client, ship).await,
+        FleetCommand::Assign { behaviour } => {
+            behaviour.check(ship)?;
+            client
+                .assign_behaviour(&ship.symbol, behaviour.clone())
+                .await?;
+            Ok(format!("assigned {}", behaviour))
+        }
+    }
+}
+
+/// Gives `command` to every ship at once. Calls queue on the client's rate
+/// limiter; each ship's result is returned, in ship order, and summarized
+/// in the event log.
+pub async fn run(
+    client: &SpaceTradersClient,
+    ships: &[Ship],
+    command: &FleetCommand,
+) -> Vec<Outcome> {
+    let mut tasks = JoinSet::new();
+    for ship in ships {
+        let client = client.clone();
+        let ship = ship.clone();
+        let command = command.clone();
+        tasks.spawn(async move {
+            let result = execute(&client, &ship, &command).await;
+            Outcome {
+                ship: ship.symbol,
+                ok: result.is_ok(),
+                message: match result {
+                    Ok(message) => message,
+                    Err(e) => e.to_string(),
+                },
+            }
+        });
+    }
+    let mut outcomes = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok(outcome) => outcomes.push(outcome),
+            Err(e) => events::record(
+                log::Level::Error,
+                EventKind::Log,
+                format!("Fleet {} task failed: {}", command, e),
+            ),
+        }
+    }
+    outcomes.sort_by(|a, b| a.ship.cmp(&b.ship));
+    record(command, &outcomes);
+    outcomes
+}
+
+/// Logs each failure and a one-line summary of the batch.
+fn record(command: &FleetCommand, outcomes: &[Outcome]) {
+    let failed: Vec<&Outcome> = outcomes.iter().filter(|outcome| !outcome.ok).collect();
+    for outcome in &failed {
+        events::record(
+            log::Level::Warn,
+            EventKind::Log,
+            format!(
+                "Fleet {}: {} failed: {}",
+                command, outcome.ship, outcome.message
+            ),
+        );
+    }
+    events::record(
+        if failed.is_empty() {
+            log::Level::Info
+        } else {
+            log::Level::Warn
+        },
+        EventKind::Log,
+        format!(
+            "Fleet {} on {} ships: {} ok, {} failed",
+            command,
+            outcomes.len(),
+            outcomes.len() - failed.len(),
+            failed.len()
+        ),
+    );
+}
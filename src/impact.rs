@@ -0,0 +1,478 @@
+use chrono::Duration;
+use serde::Serialize;
+use spacetraders_api::models::{ActivityLevel, MarketTradeGood, SupplyLevel};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::ledger::{self, EntryKind, LedgerEntry};
+use crate::prices::{self, PriceSample};
+
+/// Share a price moves against us per `trade_volume` batch until our own
+/// trades say otherwise.
+const DEFAULT_IMPACT: f64 = 0.05;
+
+/// Minutes for half of a price move to wear off until observed.
+const DEFAULT_HALF_LIFE: f64 = 60.0;
+
+/// Observations needed before a group's own figure is trusted over the
+/// broader one it falls back to.
+const MIN_SAMPLES: usize = 3;
+
+/// Longest gap between the snapshots around a trade for it to be measured.
+const MAX_GAP_MINUTES: i64 = 30;
+
+/// Longest wait after a trade for its recovery to be measured.
+const MAX_RECOVERY_HOURS: i64 = 24;
+
+/// Which way we traded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Buy => write!(f, "BUY"),
+            Self::Sell => write!(f, "SELL"),
+        }
+    }
+}
+
+/// The market conditions a price move was seen under.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Conditions {
+    side: Side,
+    supply: SupplyLevel,
+    activity: Option<ActivityLevel>,
+}
+
+/// What the model has learned for one side, supply and activity level.
+#[derive(Clone, Debug, Serialize)]
+pub struct Cell {
+    pub side: Side,
+    pub supply: SupplyLevel,
+    pub activity: Option<ActivityLevel>,
+    /// Mean share the price moved against us per `trade_volume` batch.
+    pub impact: f64,
+    pub samples: usize,
+    /// Median minutes for half of the move to wear off.
+    pub half_life: Option<f64>,
+    pub recoveries: usize,
+}
+
+/// How prices answer our own trades, learned from the ledger and the
+/// price history.
+#[derive(Default)]
+pub struct ImpactModel {
+    /// Share moved per batch under each set of conditions.
+    impacts: Vec<(Conditions, f64)>,
+    /// Half-lives in minutes under each set of conditions.
+    half_lives: Vec<(Conditions, f64)>,
+}
+
+fn side_of(entry: &LedgerEntry) -> Option<Side> {
+    match entry.kind {
+        EntryKind::Purchase | EntryKind::Refuel => Some(Side::Buy),
+        EntryKind::Sale => Some(Side::Sell),
+        _ => None,
+    }
+}
+
+/// The price we pay or get, depending on the side.
+fn price(sample: &PriceSample, side: Side) -> f64 {
+    match side {
+        Side::Buy => sample.purchase_price as f64,
+        Side::Sell => sample.sell_price as f64,
+    }
+}
+
+/// How far the price moved against us between two snapshots, as a share
+/// of the first.
+fn moved(before: &PriceSample, after: &PriceSample, side: Side) -> f64 {
+    let (before, after) = (price(before, side), price(after, side));
+    if before <= 0.0 {
+        return 0.0;
+    }
+    match side {
+        Side::Buy => (after - before) / before,
+        Side::Sell => (before - after) / before,
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    values[values.len() / 2]
+}
+
+impl ImpactModel {
+    /// Learns from every pair of consecutive snapshots of a good at a
+    /// market with only our buying or only our selling between them: the
+    /// move per batch traded, and from the snapshot after, how quickly
+    /// the move wore off while we left the market alone.
+    pub fn learn(samples: &[PriceSample], entries: &[LedgerEntry]) -> Self {
+        let mut series: BTreeMap<(&str, &str), Vec<&PriceSample>> = BTreeMap::new();
+        for sample in samples {
+            series
+                .entry((&sample.waypoint, &sample.good))
+                .or_default()
+                .push(sample);
+        }
+        let mut trades: BTreeMap<(&str, &str), Vec<&LedgerEntry>> = BTreeMap::new();
+        for entry in entries.iter().filter(|entry| side_of(entry).is_some()) {
+            if let (Some(waypoint), Some(item), Some(_)) =
+                (&entry.waypoint, &entry.item, entry.units)
+            {
+                trades.entry((waypoint, item)).or_default().push(entry);
+            }
+        }
+
+        let mut model = Self::default();
+        for (key, snapshots) in &series {
+            let Some(ours) = trades.get(key) else {
+                continue;
+            };
+            let between = |from: &PriceSample, to: &PriceSample| {
+                ours.iter()
+                    .filter(|entry| entry.time > from.time && entry.time <= to.time)
+                    .collect::<Vec<_>>()
+            };
+            for (i, pair) in snapshots.windows(2).enumerate() {
+                let (before, after) = (pair[0], pair[1]);
+                if after.time - before.time > Duration::minutes(MAX_GAP_MINUTES) {
+                    continue;
+                }
+                let traded = between(before, after);
+                let Some(side) = traded.first().and_then(|entry| side_of(entry)) else {
+                    continue;
+                };
+                if traded.iter().any(|entry| side_of(entry) != Some(side)) {
+                    continue;
+                }
+                let units: i32 = traded.iter().filter_map(|entry| entry.units).sum();
+                let batches = units as f64 / before.trade_volume.max(1) as f64;
+                if batches <= 0.0 {
+                    continue;
+                }
+                let conditions = Conditions {
+                    side,
+                    supply: before.supply,
+                    activity: before.activity,
+                };
+                let shift = moved(before, after, side);
+                model.impacts.push((conditions, shift / batches));
+
+                // The next snapshot without our trades in between shows the recovery
+                let Some(later) = snapshots.get(i + 2) else {
+                    continue;
+                };
+                let minutes = (later.time - after.time).num_seconds() as f64 / 60.0;
+                if shift <= 0.0
+                    || minutes <= 0.0
+                    || later.time - after.time > Duration::hours(MAX_RECOVERY_HOURS)
+                    || !between(after, later).is_empty()
+                {
+                    continue;
+                }
+                let recovered = (shift - moved(before, later, side)) / shift;
+                if recovered > 0.0 {
+                    // A full recovery only bounds the half-life, so count it as most of one
+                    let recovered = recovered.min(0.95);
+                    let half_life = minutes * 2f64.ln() / -(1.0 - recovered).ln();
+                    model.half_lives.push((conditions, half_life));
+                }
+            }
+        }
+        model
+    }
+
+    /// Learns from the recorded ledger and price history.
+    pub fn load() -> Self {
+        Self::learn(&prices::load(), &ledger::load())
+    }
+
+    /// Values seen under the most specific conditions with enough of them:
+    /// the same side, supply and activity, then the same side and supply,
+    /// then the same side.
+    fn matching(
+        values: &[(Conditions, f64)],
+        side: Side,
+        supply: SupplyLevel,
+        activity: Option<ActivityLevel>,
+    ) -> Vec<f64> {
+        let levels: [&dyn Fn(&Conditions) -> bool; 3] = [
+            &|c| c.side == side && c.supply == supply && c.activity == activity,
+            &|c| c.side == side && c.supply == supply,
+            &|c| c.side == side,
+        ];
+        levels
+            .iter()
+            .map(|level| {
+                values
+                    .iter()
+                    .filter(|(conditions, _)| level(conditions))
+                    .map(|(_, value)| *value)
+                    .collect::<Vec<_>>()
+            })
+            .find(|found| found.len() >= MIN_SAMPLES)
+            .unwrap_or_default()
+    }
+
+    /// Share the price moves against us per `trade_volume` batch.
+    pub fn impact(&self, side: Side, supply: SupplyLevel, activity: Option<ActivityLevel>) -> f64 {
+        let found = Self::matching(&self.impacts, side, supply, activity);
+        if found.is_empty() {
+            DEFAULT_IMPACT
+        } else {
+            mean(&found).clamp(0.0, 0.5)
+        }
+    }
+
+    /// Minutes for half of a move to wear off.
+    pub fn half_life(
+        &self,
+        side: Side,
+        supply: SupplyLevel,
+        activity: Option<ActivityLevel>,
+    ) -> f64 {
+        let mut found = Self::matching(&self.half_lives, side, supply, activity);
+        if found.is_empty() {
+            DEFAULT_HALF_LIFE
+        } else {
+            median(&mut found)
+        }
+    }
+
+    /// Expected price of the next unit of `good` once `traded` units have
+    /// changed hands on this visit.
+    pub fn price(&self, good: &MarketTradeGood, traded: i32, side: Side) -> f64 {
+        let batches = (traded / good.trade_volume.max(1)) as f64;
+        let impact = self.impact(side, good.supply, good.activity);
+        match side {
+            Side::Buy => good.purchase_price as f64 * (1.0 + impact).powf(batches),
+            Side::Sell => good.sell_price as f64 * (1.0 - impact).powf(batches),
+        }
+    }
+
+    /// Expected total for trading `units` of `good` in one visit.
+    pub fn proceeds(&self, good: &MarketTradeGood, units: i32, side: Side) -> f64 {
+        let volume = good.trade_volume.max(1);
+        (0..units)
+            .step_by(volume as usize)
+            .map(|traded| self.price(good, traded, side) * volume.min(units - traded) as f64)
+            .sum()
+    }
+
+    /// What has been learned for each set of conditions, for display.
+    pub fn cells(&self) -> Vec<Cell> {
+        let mut grouped: BTreeMap<Conditions, (Vec<f64>, Vec<f64>)> = BTreeMap::new();
+        for (conditions, impact) in &self.impacts {
+            grouped.entry(*conditions).or_default().0.push(*impact);
+        }
+        for (conditions, half_life) in &self.half_lives {
+            grouped.entry(*conditions).or_default().1.push(*half_life);
+        }
+        grouped
+            .into_iter()
+            .map(|(conditions, (impacts, mut half_lives))| Cell {
+                side: conditions.side,
+                supply: conditions.supply,
+                activity: conditions.activity,
+                impact: if impacts.is_empty() {
+                    0.0
+                } else {
+                    mean(&impacts)
+                },
+                samples: impacts.len(),
+                half_life: (!half_lives.is_empty()).then(|| median(&mut half_lives)),
+                recoveries: half_lives.len(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use spacetraders_api::models::{market_trade_good, TradeSymbol};
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        "2026-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap() + Duration::minutes(minutes)
+    }
+
+    /// IRON at `waypoint`, traded in batches of 10.
+    fn sample(
+        waypoint: &str,
+        minutes: i64,
+        price: i32,
+        supply: SupplyLevel,
+        activity: Option<ActivityLevel>,
+    ) -> PriceSample {
+        PriceSample {
+            time: at(minutes),
+            waypoint: waypoint.to_string(),
+            good: String::from("IRON"),
+            purchase_price: price,
+            sell_price: price,
+            trade_volume: 10,
+            supply,
+            activity,
+        }
+    }
+
+    fn trade(waypoint: &str, minutes: i64, kind: EntryKind, units: i32) -> LedgerEntry {
+        LedgerEntry {
+            time: at(minutes),
+            kind,
+            amount: 0,
+            balance: None,
+            ship: Some(String::from("SHIP-1")),
+            behaviour: None,
+            contract: None,
+            item: Some(String::from("IRON")),
+            units: Some(units),
+            waypoint: Some(waypoint.to_string()),
+        }
+    }
+
+    /// `rounds` sales of one batch at `waypoint`, each knocking the price
+    /// from 100 down to `after`, ten minutes apart.
+    fn sales(
+        waypoint: &str,
+        rounds: i64,
+        after: i32,
+        supply: SupplyLevel,
+        activity: Option<ActivityLevel>,
+    ) -> (Vec<PriceSample>, Vec<LedgerEntry>) {
+        let mut samples = Vec::new();
+        let mut entries = Vec::new();
+        for round in 0..rounds {
+            let start = round * 10;
+            samples.push(sample(waypoint, start, 100, supply, activity));
+            entries.push(trade(waypoint, start + 1, EntryKind::Sale, 10));
+            samples.push(sample(waypoint, start + 2, after, supply, activity));
+        }
+        (samples, entries)
+    }
+
+    #[test]
+    fn falls_back_to_defaults_without_trades() {
+        let model = ImpactModel::learn(&[], &[]);
+        assert_eq!(
+            model.impact(Side::Sell, SupplyLevel::Moderate, None),
+            DEFAULT_IMPACT
+        );
+        assert_eq!(
+            model.half_life(Side::Buy, SupplyLevel::Moderate, None),
+            DEFAULT_HALF_LIFE
+        );
+    }
+
+    #[test]
+    fn needs_enough_samples_to_trust_them() {
+        let (samples, entries) = sales(
+            "X1-A-1",
+            MIN_SAMPLES as i64 - 1,
+            90,
+            SupplyLevel::Moderate,
+            None,
+        );
+        let model = ImpactModel::learn(&samples, &entries);
+        assert_eq!(
+            model.impact(Side::Sell, SupplyLevel::Moderate, None),
+            DEFAULT_IMPACT
+        );
+
+        let (samples, entries) = sales(
+            "X1-A-1",
+            MIN_SAMPLES as i64,
+            90,
+            SupplyLevel::Moderate,
+            None,
+        );
+        let model = ImpactModel::learn(&samples, &entries);
+        let impact = model.impact(Side::Sell, SupplyLevel::Moderate, None);
+        assert!((impact - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn falls_back_from_activity_to_supply_to_side() {
+        let growing = Some(ActivityLevel::Growing);
+        let (mut samples, mut entries) = sales("X1-A-1", 3, 90, SupplyLevel::Moderate, growing);
+        let (more_samples, more_entries) = sales("X1-A-2", 3, 80, SupplyLevel::High, growing);
+        samples.extend(more_samples);
+        entries.extend(more_entries);
+        let model = ImpactModel::learn(&samples, &entries);
+
+        let impact = |supply, activity| model.impact(Side::Sell, supply, activity);
+        // Exact conditions, then the same supply at any activity
+        assert!((impact(SupplyLevel::Moderate, growing) - 0.1).abs() < 1e-9);
+        assert!((impact(SupplyLevel::High, Some(ActivityLevel::Strong)) - 0.2).abs() < 1e-9);
+        // No sales at this supply, so every sale counts
+        assert!((impact(SupplyLevel::Scarce, None) - 0.15).abs() < 1e-9);
+        // Nothing learned about buying
+        assert_eq!(
+            model.impact(Side::Buy, SupplyLevel::Moderate, growing),
+            DEFAULT_IMPACT
+        );
+    }
+
+    #[test]
+    fn skips_windows_with_buying_and_selling() {
+        let (samples, mut entries) = sales("X1-A-1", 3, 90, SupplyLevel::Moderate, None);
+        for round in 0..3 {
+            entries.push(trade("X1-A-1", round * 10 + 1, EntryKind::Purchase, 10));
+        }
+        let model = ImpactModel::learn(&samples, &entries);
+        assert!(model.impacts.is_empty());
+    }
+
+    #[test]
+    fn measures_recovery_from_the_next_quiet_snapshot() {
+        let mut samples = Vec::new();
+        let mut entries = Vec::new();
+        for round in 0..3 {
+            let start = round * 100;
+            samples.push(sample("X1-A-1", start, 100, SupplyLevel::Moderate, None));
+            entries.push(trade("X1-A-1", start + 1, EntryKind::Sale, 10));
+            samples.push(sample("X1-A-1", start + 2, 80, SupplyLevel::Moderate, None));
+            // Half the drop has worn off half an hour later
+            samples.push(sample(
+                "X1-A-1",
+                start + 32,
+                90,
+                SupplyLevel::Moderate,
+                None,
+            ));
+        }
+        let model = ImpactModel::learn(&samples, &entries);
+        assert!((model.impact(Side::Sell, SupplyLevel::Moderate, None) - 0.2).abs() < 1e-9);
+        let half_life = model.half_life(Side::Sell, SupplyLevel::Moderate, None);
+        assert!((half_life - 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn proceeds_move_the_price_batch_by_batch() {
+        let model = ImpactModel::default();
+        let good = MarketTradeGood::new(
+            TradeSymbol::Iron,
+            market_trade_good::Type::Exchange,
+            10,
+            SupplyLevel::Moderate,
+            100,
+            100,
+        );
+        // Two full batches and a half one, each 5% worse than the last
+        let sold = model.proceeds(&good, 25, Side::Sell);
+        assert!((sold - (1000.0 + 950.0 + 5.0 * 90.25)).abs() < 1e-9);
+        let bought = model.proceeds(&good, 25, Side::Buy);
+        assert!((bought - (1000.0 + 1050.0 + 5.0 * 110.25)).abs() < 1e-9);
+    }
+}
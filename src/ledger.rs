@@ -0,0 +1,483 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, TimeZone, Timelike, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use spacetraders_api::models::{
+    market_transaction, MarketTransaction, RepairTransaction, ScrapTransaction,
+    ShipModificationTransaction, ShipyardTransaction,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::cache;
+
+/// What moved credits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EntryKind {
+    Purchase,
+    Sale,
+    Refuel,
+    ShipPurchase,
+    Repair,
+    Scrap,
+    Modification,
+    ContractAccepted,
+    ContractFulfilled,
+    /// Credits observed on the agent without a known transaction, e.g.
+    /// spending by another client. Only carries a balance.
+    Balance,
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Purchase => write!(f, "PURCHASE"),
+            Self::Sale => write!(f, "SALE"),
+            Self::Refuel => write!(f, "REFUEL"),
+            Self::ShipPurchase => write!(f, "SHIP_PURCHASE"),
+            Self::Repair => write!(f, "REPAIR"),
+            Self::Scrap => write!(f, "SCRAP"),
+            Self::Modification => write!(f, "MODIFICATION"),
+            Self::ContractAccepted => write!(f, "CONTRACT_ACCEPTED"),
+            Self::ContractFulfilled => write!(f, "CONTRACT_FULFILLED"),
+            Self::Balance => write!(f, "BALANCE"),
+        }
+    }
+}
+
+/// One credit-affecting event.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub time: DateTime<Utc>,
+    pub kind: EntryKind,
+    /// Change in credits: income is positive, spending negative.
+    pub amount: i64,
+    /// Agent credits afterwards, when the response reported them.
+    pub balance: Option<i64>,
+    pub ship: Option<String>,
+    /// Behaviour that was driving the ship, if any.
+    pub behaviour: Option<String>,
+    pub contract: Option<String>,
+    /// What changed hands, e.g. a trade good or ship type.
+    pub item: Option<String>,
+    pub units: Option<i32>,
+    pub waypoint: Option<String>,
+}
+
+impl LedgerEntry {
+    fn new(kind: EntryKind, amount: i64, balance: Option<i64>) -> Self {
+        Self {
+            time: Utc::now(),
+            kind,
+            amount,
+            balance,
+            ship: None,
+            behaviour: None,
+            contract: None,
+            item: None,
+            units: None,
+            waypoint: None,
+        }
+    }
+
+    /// A cargo trade or, with `refuel`, a fuel purchase.
+    pub fn market(transaction: &MarketTransaction, balance: i64, refuel: bool) -> Self {
+        let total = transaction.total_price as i64;
+        let (kind, amount) = match transaction.r#type {
+            _ if refuel => (EntryKind::Refuel, -total),
+            market_transaction::Type::Purchase => (EntryKind::Purchase, -total),
+            market_transaction::Type::Sell => (EntryKind::Sale, total),
+        };
+        Self {
+            time: parse_time(&transaction.timestamp),
+            ship: Some(transaction.ship_symbol.clone()),
+            item: Some(transaction.trade_symbol.clone()),
+            units: Some(transaction.units),
+            waypoint: Some(transaction.waypoint_symbol.clone()),
+            ..Self::new(kind, amount, Some(balance))
+        }
+    }
+
+    pub fn shipyard(transaction: &ShipyardTransaction, balance: i64) -> Self {
+        Self {
+            time: parse_time(&transaction.timestamp),
+            ship: Some(transaction.ship_symbol.clone()),
+            item: Some(transaction.ship_type.clone()),
+            waypoint: Some(transaction.waypoint_symbol.clone()),
+            ..Self::new(
+                EntryKind::ShipPurchase,
+                -(transaction.price as i64),
+                Some(balance),
+            )
+        }
+    }
+
+    pub fn repair(transaction: &RepairTransaction, balance: i64) -> Self {
+        Self {
+            time: parse_time(&transaction.timestamp),
+            ship: Some(transaction.ship_symbol.clone()),
+            waypoint: Some(transaction.waypoint_symbol.clone()),
+            ..Self::new(
+                EntryKind::Repair,
+                -(transaction.total_price as i64),
+                Some(balance),
+            )
+        }
+    }
+
+    pub fn scrap(transaction: &ScrapTransaction, balance: i64) -> Self {
+        Self {
+            time: parse_time(&transaction.timestamp),
+            ship: Some(transaction.ship_symbol.clone()),
+            waypoint: Some(transaction.waypoint_symbol.clone()),
+            ..Self::new(
+                EntryKind::Scrap,
+                transaction.total_price as i64,
+                Some(balance),
+            )
+        }
+    }
+
+    /// The shipyard's fee for installing or removing a mount or module.
+    pub fn modification(transaction: &ShipModificationTransaction, balance: i64) -> Self {
+        Self {
+            time: parse_time(&transaction.timestamp),
+            ship: Some(transaction.ship_symbol.clone()),
+            item: Some(transaction.trade_symbol.clone()),
+            waypoint: Some(transaction.waypoint_symbol.clone()),
+            ..Self::new(
+                EntryKind::Modification,
+                -(transaction.total_price as i64),
+                Some(balance),
+            )
+        }
+    }
+
+    pub fn contract(kind: EntryKind, contract_id: &str, payment: i32, balance: i64) -> Self {
+        Self {
+            contract: Some(contract_id.to_string()),
+            ..Self::new(kind, payment as i64, Some(balance))
+        }
+    }
+}
+
+fn parse_time(timestamp: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+/// Behaviour currently driving each ship, for attributing its transactions.
+static BEHAVIOURS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+/// Contract each ship is currently working on.
+static CONTRACTS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+/// Last balance written, so agent refreshes only add a point when it changed.
+static LAST_BALANCE: Mutex<Option<i64>> = Mutex::new(None);
+
+fn path() -> PathBuf {
+    cache::data_dir().join("ledger.jsonl")
+}
+
+/// Marks `ship`'s transactions as made by `behaviour` until cleared with `None`.
+pub fn attribute(ship: &str, behaviour: Option<String>) {
+    let mut behaviours = BEHAVIOURS.lock().unwrap();
+    match behaviour {
+        Some(behaviour) => behaviours.insert(ship.to_string(), behaviour),
+        None => behaviours.remove(ship),
+    };
+}
+
+/// Marks `ship`'s transactions as spent on `contract` until cleared with `None`.
+pub fn attribute_contract(ship: &str, contract: Option<String>) {
+    let mut contracts = CONTRACTS.lock().unwrap();
+    match contract {
+        Some(contract) => contracts.insert(ship.to_string(), contract),
+        None => contracts.remove(ship),
+    };
+}
+
+/// Appends an entry to the ledger file.
+pub fn record(mut entry: LedgerEntry) -> Result<()> {
+    if let Some(ship) = &entry.ship {
+        if entry.behaviour.is_none() {
+            entry.behaviour = BEHAVIOURS.lock().unwrap().get(ship).cloned();
+        }
+        if entry.contract.is_none() {
+            entry.contract = CONTRACTS.lock().unwrap().get(ship).cloned();
+        }
+    }
+    if entry.balance.is_some() {
+        *LAST_BALANCE.lock().unwrap() = entry.balance;
+    }
+
+    fs::create_dir_all(cache::data_dir())?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path())?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+    Ok(())
+}
+
+/// Records the agent's credits when they differ from the last known balance,
+/// so the credits chart also covers changes made outside this client.
+pub fn record_balance(credits: i64) -> Result<()> {
+    let last = {
+        let mut last = LAST_BALANCE.lock().unwrap();
+        if last.is_none() {
+            *last = load().iter().rev().find_map(|entry| entry.balance);
+        }
+        *last
+    };
+    if last == Some(credits) {
+        return Ok(());
+    }
+    record(LedgerEntry::new(EntryKind::Balance, 0, Some(credits)))
+}
+
+/// Every entry, oldest first. Unreadable lines are skipped.
+pub fn load() -> Vec<LedgerEntry> {
+    fs::read_to_string(path())
+        .map(|contents| {
+            contents
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Length of a profit and loss reporting period.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Period {
+    Hour,
+    #[default]
+    Day,
+}
+
+impl Period {
+    /// Start of the local period containing `time`.
+    fn start(self, time: DateTime<Utc>) -> DateTime<Local> {
+        let local = time.with_timezone(&Local);
+        let naive = match self {
+            Self::Hour => local.date_naive().and_hms_opt(local.hour(), 0, 0),
+            Self::Day => local.date_naive().and_hms_opt(0, 0, 0),
+        }
+        .unwrap();
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .unwrap_or(local)
+    }
+
+    pub fn label(self, start: DateTime<Local>) -> String {
+        match self {
+            Self::Hour => start.format("%Y-%m-%d %H:00").to_string(),
+            Self::Day => start.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// What profit and loss rows are split by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Grouping {
+    #[default]
+    Ship,
+    Behaviour,
+    Contract,
+}
+
+impl Grouping {
+    fn key(self, entry: &LedgerEntry) -> Option<String> {
+        match self {
+            Self::Ship => entry.ship.clone(),
+            Self::Behaviour => entry.behaviour.clone(),
+            Self::Contract => entry.contract.clone(),
+        }
+    }
+
+    /// Column heading for the group.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Ship => "SHIP",
+            Self::Behaviour => "BEHAVIOUR",
+            Self::Contract => "CONTRACT",
+        }
+    }
+}
+
+/// Income and spending of one group over one period.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProfitAndLoss {
+    pub period: DateTime<Local>,
+    /// Ship symbol, behaviour or contract id depending on the grouping, or
+    /// `None` for entries without one, e.g. manual trades.
+    pub group: Option<String>,
+    pub income: i64,
+    pub expenses: i64,
+}
+
+impl ProfitAndLoss {
+    pub fn net(&self) -> i64 {
+        self.income - self.expenses
+    }
+}
+
+/// Groups transactions by period and `grouping`, most recent period first.
+pub fn profit_and_loss(
+    entries: &[LedgerEntry],
+    period: Period,
+    grouping: Grouping,
+) -> Vec<ProfitAndLoss> {
+    let mut rows: BTreeMap<(DateTime<Local>, Option<String>), ProfitAndLoss> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| e.kind != EntryKind::Balance) {
+        let start = period.start(entry.time);
+        let group = grouping.key(entry);
+        let row = rows
+            .entry((start, group.clone()))
+            .or_insert_with(|| ProfitAndLoss {
+                period: start,
+                group,
+                income: 0,
+                expenses: 0,
+            });
+        if entry.amount >= 0 {
+            row.income += entry.amount;
+        } else {
+            row.expenses -= entry.amount;
+        }
+    }
+
+    let mut rows: Vec<ProfitAndLoss> = rows.into_values().collect();
+    rows.sort_by(|a, b| b.period.cmp(&a.period).then_with(|| a.group.cmp(&b.group)));
+    rows
+}
+
+/// Known balances over time, oldest first.
+pub fn credit_history(entries: &[LedgerEntry]) -> Vec<(DateTime<Utc>, i64)> {
+    entries
+        .iter()
+        .filter_map(|entry| Some((entry.time, entry.balance?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        kind: EntryKind,
+        amount: i64,
+        ship: &str,
+        behaviour: Option<&str>,
+        contract: Option<&str>,
+    ) -> LedgerEntry {
+        LedgerEntry {
+            ship: Some(ship.to_string()),
+            behaviour: behaviour.map(String::from),
+            contract: contract.map(String::from),
+            ..LedgerEntry::new(kind, amount, None)
+        }
+    }
+
+    fn totals(report: &[ProfitAndLoss]) -> Vec<(Option<&str>, i64, i64)> {
+        report
+            .iter()
+            .map(|row| (row.group.as_deref(), row.income, row.expenses))
+            .collect()
+    }
+
+    fn at(time: &str, entry: LedgerEntry) -> LedgerEntry {
+        LedgerEntry {
+            time: time.parse().unwrap(),
+            ..entry
+        }
+    }
+
+    #[test]
+    fn sums_each_ship_per_period_latest_first() {
+        let entries = vec![
+            at(
+                "2026-01-01T10:01:00Z",
+                entry(EntryKind::Purchase, -300, "S-1", None, None),
+            ),
+            at(
+                "2026-01-01T10:05:00Z",
+                entry(EntryKind::Sale, 500, "S-1", None, None),
+            ),
+            at(
+                "2026-01-01T10:05:00Z",
+                entry(EntryKind::Refuel, -20, "S-2", None, None),
+            ),
+            at(
+                "2026-01-01T12:01:00Z",
+                entry(EntryKind::Sale, 100, "S-1", None, None),
+            ),
+            at(
+                "2026-01-01T12:02:00Z",
+                LedgerEntry::new(EntryKind::Balance, 0, Some(5000)),
+            ),
+        ];
+        let report = profit_and_loss(&entries, Period::Hour, Grouping::Ship);
+
+        assert_eq!(
+            totals(&report),
+            vec![
+                (Some("S-1"), 100, 0),
+                (Some("S-1"), 500, 300),
+                (Some("S-2"), 0, 20),
+            ]
+        );
+        assert!(report[0].period > report[1].period);
+        assert_eq!(report[1].period, report[2].period);
+        assert_eq!(report[1].net(), 200);
+    }
+
+    #[test]
+    fn splits_profit_and_loss_per_behaviour_and_contract() {
+        let entries = vec![
+            entry(
+                EntryKind::Purchase,
+                -300,
+                "S-1",
+                Some("contract"),
+                Some("C1"),
+            ),
+            entry(EntryKind::Refuel, -50, "S-1", Some("contract"), Some("C1")),
+            LedgerEntry::contract(EntryKind::ContractFulfilled, "C1", 1000, 0),
+            entry(EntryKind::Purchase, -200, "S-2", Some("trade"), None),
+            entry(EntryKind::Sale, 500, "S-2", Some("trade"), None),
+            entry(EntryKind::Sale, 80, "S-1", None, None),
+            LedgerEntry::new(EntryKind::Balance, 0, Some(5000)),
+        ];
+
+        let by_behaviour = profit_and_loss(&entries, Period::Day, Grouping::Behaviour);
+        assert_eq!(
+            totals(&by_behaviour),
+            vec![
+                (None, 1080, 0),
+                (Some("contract"), 0, 350),
+                (Some("trade"), 500, 200),
+            ]
+        );
+
+        let by_contract = profit_and_loss(&entries, Period::Day, Grouping::Contract);
+        assert_eq!(
+            totals(&by_contract),
+            vec![(None, 580, 200), (Some("C1"), 1000, 350)]
+        );
+        assert_eq!(by_contract[1].net(), 650);
+
+        let by_ship = profit_and_loss(&entries, Period::Day, Grouping::Ship);
+        assert_eq!(
+            totals(&by_ship),
+            vec![
+                (None, 1000, 0),
+                (Some("S-1"), 80, 350),
+                (Some("S-2"), 500, 200)
+            ]
+        );
+    }
+}
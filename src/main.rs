@@ -1,12 +1,40 @@
 use anyhow::Result;
+use clap::Parser;
 use colored::*;
 use console::Term;
 use dotenv::dotenv;
 use log::{error, info};
 
+mod behaviour;
+mod cache;
+mod cli;
 mod client;
+mod condition;
+mod construction;
+mod contract;
+mod daemon;
+mod events;
+mod explore;
+mod fitting;
+mod fleet;
+mod impact;
+mod ledger;
+mod mining;
+mod navigation;
+mod palette;
+mod prices;
+mod rescue;
+mod rivals;
+mod roster;
+mod scan;
+mod search;
+mod standings;
+mod supply;
+mod trade;
 mod ui;
+mod watch;
 
+use cli::{Cli, Command};
 use client::SpaceTradersClient;
 use ui::{run_app, App};
 
@@ -15,7 +43,26 @@ fn print_banner() {
     println!("{}", "=========================".bright_cyan());
 }
 
-async fn startup_sequence() -> Result<String> {
+fn load_api_token() -> Result<String> {
+    match std::env::var("SPACE_TRADERS_API_TOKEN") {
+        Ok(token) => Ok(token),
+        Err(_) => {
+            error!("❌ Failed to load API token!");
+            anyhow::bail!("SPACE_TRADERS_API_TOKEN must be set in .env file");
+        }
+    }
+}
+
+/// A client that talks to the daemon when attached, otherwise to the API.
+fn connect(attach: bool) -> Result<SpaceTradersClient> {
+    if attach {
+        Ok(SpaceTradersClient::attach(daemon::socket_path()))
+    } else {
+        Ok(SpaceTradersClient::new(load_api_token()?))
+    }
+}
+
+async fn startup_sequence(attach: bool) -> Result<SpaceTradersClient> {
     let term = Term::stdout();
     term.clear_screen()?;
     print_banner();
@@ -24,39 +71,45 @@ async fn startup_sequence() -> Result<String> {
     info!("Loading environment variables...");
     dotenv().ok();
 
-    // Initialize logging
+    // Initialize logging into the in-app event log
+    events::init()?;
     info!("Initializing logging system...");
-    env_logger::init();
 
-    // Load API token
+    // Load API token, unless the daemon makes the calls for us
     info!("Loading API credentials...");
-    let api_token = match std::env::var("SPACE_TRADERS_API_TOKEN") {
-        Ok(token) => {
-            info!("✅ System initialized successfully!");
-            token
-        }
-        Err(_) => {
-            error!("❌ Failed to load API token!");
-            anyhow::bail!("SPACE_TRADERS_API_TOKEN must be set in .env file");
-        }
-    };
+    let client = connect(attach)?;
+    info!("✅ System initialized successfully!");
 
     println!("\n{}", "System Status:".yellow().bold());
     println!("├─ {} {}", "Environment:".blue(), "✓".green());
     println!("├─ {} {}", "Logging:".blue(), "✓".green());
-    println!("└─ {} {}", "API Token:".blue(), "✓".green());
+    if attach {
+        println!("└─ {} {}", "Daemon:".blue(), "attached".green());
+    } else {
+        println!("└─ {} {}", "API Token:".blue(), "✓".green());
+    }
     println!();
 
-    Ok(api_token)
+    Ok(client)
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let api_token = startup_sequence().await?;
-    info!("Space Traders API client ready for commands!");
+    let cli = Cli::parse();
+
+    // Subcommands run headless and exit without starting the TUI
+    if let Some(command) = cli.command {
+        dotenv().ok();
+        events::init()?;
+        if let Command::Daemon = command {
+            return daemon::run(SpaceTradersClient::new(load_api_token()?)).await;
+        }
+        let client = connect(cli.attach)?;
+        std::process::exit(cli::run(&client, command, cli.json).await);
+    }
 
-    // Create client
-    let client = SpaceTradersClient::new(api_token);
+    let client = startup_sequence(cli.attach).await?;
+    info!("Space Traders API client ready for commands!");
 
     // Create and run the app
     let mut app = App::new(client);
@@ -0,0 +1,301 @@
+use anyhow::Result;
+use spacetraders_api::models::{Ship, ShipCargo, ShipNavStatus, TradeSymbol};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use crate::cache::{self, UniverseCache};
+use crate::client::SpaceTradersClient;
+use crate::events::{self, EventKind};
+use crate::impact::{ImpactModel, Side};
+use crate::navigation::fly_to;
+use crate::prices;
+
+/// Share of its hold a hauler fills before leaving the field to sell.
+const HAUL_DEPART_FILL: f64 = 0.9;
+
+/// Ships mining at and hauling from each asteroid field, built from the
+/// assignments every tick so miners and haulers can rely on each other.
+#[derive(Default)]
+pub struct MiningGroups {
+    pub miners: BTreeMap<String, Vec<String>>,
+    pub haulers: BTreeMap<String, Vec<String>>,
+    /// Units haulers have taken from each miner this tick, keyed by miner
+    /// and good, which the tick's ship snapshots don't show yet.
+    pub taken: HashMap<(String, TradeSymbol), i32>,
+}
+
+/// Extracts at the field until the hold is full, then waits there for a
+/// hauler to empty it.
+pub async fn mine(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    field: &str,
+    groups: &MiningGroups,
+    waiting: &mut bool,
+) -> Result<()> {
+    if ship.nav.waypoint_symbol != field {
+        return fly_to(client, ship, field).await;
+    }
+    if ship.nav.status == ShipNavStatus::InTransit {
+        return Ok(());
+    }
+    if ship.nav.status == ShipNavStatus::Docked {
+        client.orbit_ship(&ship.symbol).await?;
+    }
+
+    if ship.cargo.units >= ship.cargo.capacity {
+        if !*waiting {
+            *waiting = true;
+            if groups.haulers.contains_key(field) {
+                events::record(
+                    log::Level::Info,
+                    EventKind::Log,
+                    format!("{} is full, waiting for a hauler", ship.symbol),
+                );
+            } else {
+                events::record(
+                    log::Level::Warn,
+                    EventKind::Log,
+                    format!("{} is full and no hauler serves {}", ship.symbol, field),
+                );
+            }
+        }
+        return Ok(());
+    }
+    *waiting = false;
+
+    let data = client.extract_resources(&ship.symbol).await?;
+    let extracted = &data.extraction.r#yield;
+    events::record(
+        log::Level::Debug,
+        EventKind::Log,
+        format!(
+            "{} extracted {} {}",
+            ship.symbol, extracted.units, extracted.symbol
+        ),
+    );
+    Ok(())
+}
+
+/// The cached market in `system` expected to pay the most for the cargo,
+/// allowing for our sales pushing its prices down batch by batch.
+fn best_market(
+    cache: &UniverseCache,
+    model: &ImpactModel,
+    system: &str,
+    cargo: &ShipCargo,
+) -> Option<String> {
+    cache
+        .markets
+        .values()
+        .filter(|market| cache::system_symbol(&market.symbol) == system)
+        .filter_map(|market| {
+            let goods = market.trade_goods.as_ref()?;
+            let value: f64 = cargo
+                .inventory
+                .iter()
+                .filter_map(|item| {
+                    let good = goods.iter().find(|g| g.symbol == item.symbol)?;
+                    Some(model.proceeds(good, item.units, Side::Sell))
+                })
+                .sum();
+            (value > 0.0).then_some((market.symbol.clone(), value))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(symbol, _)| symbol)
+}
+
+/// The best first-batch price for `good` at another cached market in the
+/// ship's system.
+fn best_elsewhere(cache: &UniverseCache, ship: &Ship, good: TradeSymbol) -> Option<i32> {
+    cache
+        .markets
+        .values()
+        .filter(|market| {
+            market.symbol != ship.nav.waypoint_symbol
+                && cache::system_symbol(&market.symbol) == ship.nav.system_symbol
+        })
+        .filter_map(|market| market.trade_goods.as_ref())
+        .flatten()
+        .filter(|g| g.symbol == good)
+        .map(|g| g.sell_price)
+        .max()
+}
+
+/// Docks, refuels if the market sells fuel, and sells the cargo the
+/// market buys in lots no larger than its trade volume. After the first
+/// lot of each good, selling stops once the impact model expects another
+/// market in the system to pay more, so big loads are split between them.
+async fn sell_cargo(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    cache: &mut UniverseCache,
+    model: &ImpactModel,
+) -> Result<()> {
+    let waypoint = &ship.nav.waypoint_symbol;
+    let goods = prices::before_trading(client, waypoint)
+        .await?
+        .trade_goods
+        .unwrap_or_default();
+    if ship.nav.status == ShipNavStatus::InOrbit {
+        client.dock_ship(&ship.symbol).await?;
+    }
+    if ship.fuel.current < ship.fuel.capacity
+        && goods.iter().any(|good| good.symbol == TradeSymbol::Fuel)
+    {
+        client.refuel_ship(&ship.symbol, None, false).await?;
+    }
+
+    let mut earned = 0;
+    let mut kept = Vec::new();
+    for item in &ship.cargo.inventory {
+        let Some(good) = goods.iter().find(|g| g.symbol == item.symbol) else {
+            continue;
+        };
+        let elsewhere = best_elsewhere(cache, ship, item.symbol);
+        let mut sold = 0;
+        while sold < item.units {
+            let expected = model.price(good, sold, Side::Sell);
+            if sold > 0 && elsewhere.is_some_and(|price| price as f64 > expected) {
+                kept.push(format!("{} {}", item.units - sold, item.symbol));
+                break;
+            }
+            let units = (item.units - sold).min(good.trade_volume.max(1));
+            let data = client.sell_cargo(&ship.symbol, item.symbol, units).await?;
+            earned += data.transaction.total_price;
+            sold += units;
+        }
+    }
+    events::record(
+        log::Level::Info,
+        EventKind::Log,
+        if kept.is_empty() {
+            format!("{} sold cargo at {} for {}", ship.symbol, waypoint, earned)
+        } else {
+            format!(
+                "{} sold cargo at {} for {}, keeping {} for better prices elsewhere",
+                ship.symbol,
+                waypoint,
+                earned,
+                kept.join(", ")
+            )
+        },
+    );
+
+    // Our sales moved the prices, so look again before choosing the next stop
+    let market = client
+        .get_market(cache::system_symbol(waypoint), waypoint)
+        .await?;
+    cache.markets.insert(waypoint.clone(), market);
+    cache.save()
+}
+
+/// Jettisons cargo no cached market in the ship's system buys, so the hold
+/// isn't carried back and forth for ever.
+async fn jettison_unsellable(client: &SpaceTradersClient, ship: &Ship) -> Result<()> {
+    for item in &ship.cargo.inventory {
+        client
+            .jettison_cargo(&ship.symbol, item.symbol, item.units)
+            .await?;
+        events::record(
+            log::Level::Warn,
+            EventKind::Log,
+            format!(
+                "{} jettisoned {} {}: no known market in {} buys it",
+                ship.symbol, item.units, item.symbol, ship.nav.system_symbol
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// Waits at the field taking cargo from full miners, and once its own hold
+/// is nearly full, carries it to the best markets and comes back. Cargo no
+/// known market buys is jettisoned rather than kept.
+#[allow(clippy::too_many_arguments)]
+pub async fn haul(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    cache: &mut UniverseCache,
+    ships: &[Ship],
+    field: &str,
+    groups: &mut MiningGroups,
+    model: &OnceLock<ImpactModel>,
+    selling: &mut bool,
+) -> Result<()> {
+    if ship.nav.status == ShipNavStatus::InTransit {
+        return Ok(());
+    }
+
+    if *selling {
+        if ship.cargo.inventory.is_empty() {
+            *selling = false;
+            return fly_to(client, ship, field).await;
+        }
+        let model = model.get_or_init(ImpactModel::load);
+        let Some(market) = best_market(cache, model, &ship.nav.system_symbol, &ship.cargo) else {
+            // Whatever is left has no buyer, so drop it and go back to work
+            jettison_unsellable(client, ship).await?;
+            *selling = false;
+            return fly_to(client, ship, field).await;
+        };
+        if market != ship.nav.waypoint_symbol {
+            return fly_to(client, ship, &market).await;
+        }
+        return sell_cargo(client, ship, cache, model).await;
+    }
+
+    if ship.nav.waypoint_symbol != field {
+        return fly_to(client, ship, field).await;
+    }
+    if ship.nav.status == ShipNavStatus::Docked {
+        client.orbit_ship(&ship.symbol).await?;
+    }
+
+    let mut free = ship.cargo.capacity - ship.cargo.units;
+    let miners = groups.miners.get(field).into_iter().flatten();
+    let full_miners: Vec<&Ship> = miners
+        .filter_map(|symbol| ships.iter().find(|s| s.symbol == *symbol))
+        .filter(|miner| {
+            miner.nav.waypoint_symbol == field
+                && miner.nav.status == ShipNavStatus::InOrbit
+                && miner.cargo.units >= miner.cargo.capacity
+        })
+        .collect();
+    for miner in full_miners {
+        let mut took = false;
+        for item in &miner.cargo.inventory {
+            if free == 0 {
+                break;
+            }
+            let taken = groups
+                .taken
+                .entry((miner.symbol.clone(), item.symbol))
+                .or_default();
+            let units = (item.units - *taken).min(free);
+            if units <= 0 {
+                continue;
+            }
+            client
+                .transfer_cargo(&miner.symbol, &ship.symbol, item.symbol, units)
+                .await?;
+            *taken += units;
+            free -= units;
+            took = true;
+        }
+        if !took {
+            continue;
+        }
+        events::record(
+            log::Level::Info,
+            EventKind::Log,
+            format!("{} took cargo from {}", ship.symbol, miner.symbol),
+        );
+    }
+
+    let loaded = ship.cargo.capacity - free;
+    if loaded as f64 >= ship.cargo.capacity as f64 * HAUL_DEPART_FILL {
+        *selling = true;
+    }
+    Ok(())
+}
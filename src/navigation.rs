@@ -0,0 +1,73 @@
+use anyhow::Result;
+use spacetraders_api::models::{Ship, ShipNavStatus};
+
+use crate::cache::{self, UniverseCache};
+use crate::client::SpaceTradersClient;
+use crate::events::{self, EventKind};
+
+/// Seconds per unit of distance at engine speed 1 in CRUISE mode.
+const CRUISE_MULTIPLIER: f64 = 25.0;
+
+/// Seconds every flight takes regardless of distance.
+pub const FLIGHT_OVERHEAD: f64 = 15.0;
+
+/// Fuel in one unit of FUEL cargo bought at a market.
+pub const FUEL_PER_UNIT: f64 = 100.0;
+
+/// Straight-line distance between two points in a system.
+pub fn distance(a: (i32, i32), b: (i32, i32)) -> f64 {
+    ((a.0 - b.0) as f64).hypot((a.1 - b.1) as f64)
+}
+
+/// Position of a waypoint within its system, if its system is cached.
+pub fn position(cache: &UniverseCache, waypoint: &str) -> Option<(i32, i32)> {
+    cache
+        .waypoints
+        .get(cache::system_symbol(waypoint))?
+        .iter()
+        .find(|w| w.symbol == waypoint)
+        .map(|w| (w.x, w.y))
+}
+
+/// Flight time in CRUISE mode, as the server computes it.
+pub fn flight_seconds(distance: f64, speed: i32) -> f64 {
+    FLIGHT_OVERHEAD + distance.max(1.0) * CRUISE_MULTIPLIER / speed.max(1) as f64
+}
+
+/// Undocks if needed and sends the ship to `waypoint`, logging its arrival time.
+pub async fn fly_to(client: &SpaceTradersClient, ship: &Ship, waypoint: &str) -> Result<()> {
+    navigate(client, ship, waypoint, None).await
+}
+
+/// Like [`fly_to`], with what the ship is going there for in the log, e.g.
+/// "on patrol".
+pub async fn fly_to_for(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    waypoint: &str,
+    purpose: &str,
+) -> Result<()> {
+    navigate(client, ship, waypoint, Some(purpose)).await
+}
+
+async fn navigate(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    waypoint: &str,
+    purpose: Option<&str>,
+) -> Result<()> {
+    if ship.nav.status == ShipNavStatus::Docked {
+        client.orbit_ship(&ship.symbol).await?;
+    }
+    let data = client.navigate_ship(&ship.symbol, waypoint).await?;
+    let purpose = purpose.map(|p| format!(" {}", p)).unwrap_or_default();
+    events::record(
+        log::Level::Info,
+        EventKind::Log,
+        format!(
+            "{} heading to {}{}, arriving {}",
+            ship.symbol, waypoint, purpose, data.nav.route.arrival
+        ),
+    );
+    Ok(())
+}
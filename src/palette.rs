@@ -0,0 +1,386 @@
+use ratatui::widgets::ListState;
+use spacetraders_api::models::{Ship, TradeSymbol, WaypointTraitSymbol};
+use std::fmt;
+use tui_input::Input;
+
+use crate::cache::UniverseCache;
+use crate::fleet::FleetCommand;
+
+/// Most matches listed at once; the rest are reachable by typing more.
+const MAX_MATCHES: usize = 50;
+
+/// What selecting a palette entry leads to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaletteTarget {
+    Ship(String),
+    System(String),
+    Waypoint(String),
+    /// Narrows the palette to cached waypoints with this trait.
+    WaypointTrait(WaypointTraitSymbol),
+    /// Narrows the palette to cached markets trading this good.
+    TradeGood(TradeSymbol),
+    Command(PaletteCommand),
+}
+
+/// Ship orders and roster edits that can be typed into the palette.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PaletteCommand {
+    Navigate {
+        ship: String,
+        waypoint: String,
+    },
+    Dock {
+        ship: String,
+    },
+    Orbit {
+        ship: String,
+    },
+    Nickname {
+        ship: String,
+        name: Option<String>,
+    },
+    Group {
+        ship: String,
+        group: Option<String>,
+    },
+    Tag {
+        ship: String,
+        tag: String,
+    },
+    Untag {
+        ship: String,
+        tag: String,
+    },
+    Note {
+        ship: String,
+        text: String,
+    },
+    /// An order for every ship marked on the Ships tab.
+    Fleet(FleetCommand),
+}
+
+impl PaletteCommand {
+    /// Parses `navigate SHIP to WAYPOINT`, `dock SHIP`, `orbit SHIP` and
+    /// the roster edits `nickname`, `group`, `tag`, `untag` and `note`,
+    /// and `fleet ORDER` for the marked ships.
+    /// Symbols are upper-cased so they can be typed in any case; names,
+    /// tags and notes are kept as typed.
+    pub fn parse(input: &str) -> Option<Result<Self, &'static str>> {
+        let typed: Vec<&str> = input.split_whitespace().collect();
+        let words: Vec<String> = typed.iter().map(|word| word.to_uppercase()).collect();
+        // Everything after the ship symbol, as typed
+        let rest = typed
+            .get(2..)
+            .map(|rest| rest.join(" "))
+            .unwrap_or_default();
+        let command = match words.first()?.as_str() {
+            "NAVIGATE" => match words.as_slice() {
+                [_, ship, to, waypoint] if to == "TO" => Ok(Self::Navigate {
+                    ship: ship.clone(),
+                    waypoint: waypoint.clone(),
+                }),
+                _ => Err("usage: navigate SHIP to WAYPOINT"),
+            },
+            "DOCK" => match words.as_slice() {
+                [_, ship] => Ok(Self::Dock { ship: ship.clone() }),
+                _ => Err("usage: dock SHIP"),
+            },
+            "ORBIT" => match words.as_slice() {
+                [_, ship] => Ok(Self::Orbit { ship: ship.clone() }),
+                _ => Err("usage: orbit SHIP"),
+            },
+            "NICKNAME" => match words.as_slice() {
+                [_, ship, ..] => Ok(Self::Nickname {
+                    ship: ship.clone(),
+                    name: (!rest.is_empty()).then_some(rest),
+                }),
+                _ => Err("usage: nickname SHIP [NAME]"),
+            },
+            "GROUP" => match words.as_slice() {
+                [_, ship, ..] => Ok(Self::Group {
+                    ship: ship.clone(),
+                    group: (!rest.is_empty()).then_some(rest),
+                }),
+                _ => Err("usage: group SHIP [GROUP]"),
+            },
+            "TAG" => match words.as_slice() {
+                [_, ship, _] => Ok(Self::Tag {
+                    ship: ship.clone(),
+                    tag: rest,
+                }),
+                _ => Err("usage: tag SHIP TAG"),
+            },
+            "UNTAG" => match words.as_slice() {
+                [_, ship, _] => Ok(Self::Untag {
+                    ship: ship.clone(),
+                    tag: rest,
+                }),
+                _ => Err("usage: untag SHIP TAG"),
+            },
+            "NOTE" => match words.as_slice() {
+                [_, ship, ..] => Ok(Self::Note {
+                    ship: ship.clone(),
+                    text: rest,
+                }),
+                _ => Err("usage: note SHIP [TEXT]"),
+            },
+            "FLEET" => FleetCommand::parse(&typed[1..]).map(Self::Fleet).ok_or(
+                "usage: fleet navigate WAYPOINT | dock | orbit | refuel | sell | assign BEHAVIOUR",
+            ),
+            _ => return None,
+        };
+        Some(command)
+    }
+}
+
+impl fmt::Display for PaletteCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Navigate { ship, waypoint } => write!(f, "navigate {} to {}", ship, waypoint),
+            Self::Dock { ship } => write!(f, "dock {}", ship),
+            Self::Orbit { ship } => write!(f, "orbit {}", ship),
+            Self::Nickname { ship, name } => {
+                write!(
+                    f,
+                    "nickname {} {}",
+                    ship,
+                    name.as_deref().unwrap_or_default()
+                )
+            }
+            Self::Group { ship, group } => {
+                write!(f, "group {} {}", ship, group.as_deref().unwrap_or_default())
+            }
+            Self::Tag { ship, tag } => write!(f, "tag {} {}", ship, tag),
+            Self::Untag { ship, tag } => write!(f, "untag {} {}", ship, tag),
+            Self::Note { ship, text } => write!(f, "note {} {}", ship, text),
+            Self::Fleet(command) => write!(f, "fleet {}", command),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PaletteItem {
+    pub label: String,
+    pub kind: &'static str,
+    pub target: PaletteTarget,
+}
+
+#[derive(Default)]
+pub struct Palette {
+    pub input: Input,
+    pub items: Vec<PaletteItem>,
+    pub state: ListState,
+    /// Explanation shown instead of matches, e.g. a command usage hint.
+    pub hint: Option<String>,
+}
+
+impl Palette {
+    pub fn selected_item(&self) -> Option<&PaletteItem> {
+        self.items.get(self.state.selected()?)
+    }
+
+    /// Replaces the listed entries and selects the first.
+    pub fn set_items(&mut self, items: Vec<PaletteItem>) {
+        self.state
+            .select(if items.is_empty() { None } else { Some(0) });
+        self.items = items;
+    }
+
+    pub fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i + 1 < self.items.len() => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(0) | None => self.items.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.state.select(Some(i));
+    }
+
+    /// Rebuilds the entries for the current input.
+    pub fn update(&mut self, ships: &[Ship], cache: &UniverseCache) {
+        self.hint = None;
+        let query = self.input.value().trim().to_string();
+        match PaletteCommand::parse(&query) {
+            Some(Ok(command)) => self.set_items(vec![PaletteItem {
+                label: command.to_string(),
+                kind: "RUN",
+                target: PaletteTarget::Command(command),
+            }]),
+            Some(Err(usage)) => {
+                self.hint = Some(usage.to_string());
+                self.set_items(Vec::new());
+            }
+            None => self.set_items(search(&query, ships, cache)),
+        }
+    }
+}
+
+/// Scores `candidate` against `query` as a case-insensitive subsequence
+/// match. Consecutive characters and matches at the start of a symbol
+/// segment score higher; `None` means the query doesn't match at all.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous_match: Option<usize> = None;
+
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let q = q.to_ascii_uppercase();
+        let found =
+            (position..candidate.len()).find(|&i| candidate[i].to_ascii_uppercase() == q)?;
+
+        score += 1;
+        if found == 0 || matches!(candidate[found - 1], '-' | '_' | ' ') {
+            score += 8;
+        }
+        match previous_match {
+            Some(previous) if previous + 1 == found => score += 5,
+            Some(previous) => score -= (found - previous - 1).min(5) as i32,
+            None => score -= found.min(10) as i32,
+        }
+
+        previous_match = Some(found);
+        position = found + 1;
+    }
+
+    // Prefer shorter candidates when scores are otherwise equal
+    Some(score * 100 - candidate.len() as i32)
+}
+
+/// Collects everything the palette can jump to from the fleet and the
+/// universe cache, best matches first.
+pub fn search(query: &str, ships: &[Ship], cache: &UniverseCache) -> Vec<PaletteItem> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut scored: Vec<(i32, PaletteItem)> = Vec::new();
+    let mut consider = |label: &str, kind: &'static str, target: &dyn Fn() -> PaletteTarget| {
+        if let Some(score) = fuzzy_score(query, label) {
+            scored.push((
+                score,
+                PaletteItem {
+                    label: label.to_string(),
+                    kind,
+                    target: target(),
+                },
+            ));
+        }
+    };
+
+    for ship in ships {
+        consider(&ship.symbol, "SHIP", &|| {
+            PaletteTarget::Ship(ship.symbol.clone())
+        });
+    }
+
+    for system in &cache.systems {
+        consider(&system.symbol, "SYSTEM", &|| {
+            PaletteTarget::System(system.symbol.clone())
+        });
+        for waypoint in &system.waypoints {
+            consider(&waypoint.symbol, "WAYPOINT", &|| {
+                PaletteTarget::Waypoint(waypoint.symbol.clone())
+            });
+        }
+    }
+
+    let mut traits: Vec<WaypointTraitSymbol> = cache
+        .waypoints
+        .values()
+        .flatten()
+        .flat_map(|waypoint| waypoint.traits.iter().map(|t| t.symbol))
+        .collect();
+    traits.sort();
+    traits.dedup();
+    for symbol in traits {
+        consider(&symbol.to_string(), "TRAIT", &|| {
+            PaletteTarget::WaypointTrait(symbol)
+        });
+    }
+
+    let mut goods: Vec<TradeSymbol> = cache
+        .markets
+        .values()
+        .flat_map(|market| {
+            market
+                .imports
+                .iter()
+                .chain(&market.exports)
+                .chain(&market.exchange)
+                .map(|good| good.symbol)
+        })
+        .collect();
+    goods.sort();
+    goods.dedup();
+    for symbol in goods {
+        consider(&symbol.to_string(), "GOOD", &|| {
+            PaletteTarget::TradeGood(symbol)
+        });
+    }
+
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored
+        .into_iter()
+        .take(MAX_MATCHES)
+        .map(|(_, item)| item)
+        .collect()
+}
+
+/// Cached waypoints carrying `symbol`, for drilling into a trait entry.
+pub fn waypoints_with_trait(
+    cache: &UniverseCache,
+    symbol: WaypointTraitSymbol,
+) -> Vec<PaletteItem> {
+    let mut items: Vec<PaletteItem> = cache
+        .waypoints
+        .values()
+        .flatten()
+        .filter(|waypoint| waypoint.traits.iter().any(|t| t.symbol == symbol))
+        .map(|waypoint| PaletteItem {
+            label: waypoint.symbol.clone(),
+            kind: "WAYPOINT",
+            target: PaletteTarget::Waypoint(waypoint.symbol.clone()),
+        })
+        .collect();
+    items.sort_by(|a, b| a.label.cmp(&b.label));
+    items
+}
+
+/// Cached markets importing, exporting or exchanging `symbol`.
+pub fn markets_trading(cache: &UniverseCache, symbol: TradeSymbol) -> Vec<PaletteItem> {
+    let mut items: Vec<PaletteItem> = cache
+        .markets
+        .values()
+        .filter_map(|market| {
+            let kind = if market.exports.iter().any(|g| g.symbol == symbol) {
+                "EXPORT"
+            } else if market.imports.iter().any(|g| g.symbol == symbol) {
+                "IMPORT"
+            } else if market.exchange.iter().any(|g| g.symbol == symbol) {
+                "EXCHANGE"
+            } else {
+                return None;
+            };
+            Some(PaletteItem {
+                label: market.symbol.clone(),
+                kind,
+                target: PaletteTarget::Waypoint(market.symbol.clone()),
+            })
+        })
+        .collect();
+    items.sort_by(|a, b| a.label.cmp(&b.label));
+    items
+}
@@ -0,0 +1,110 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use spacetraders_api::models::{ActivityLevel, Market, MarketTradeGood, SupplyLevel};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::cache;
+use crate::client::SpaceTradersClient;
+use crate::events;
+
+/// One good's prices at one market at one time.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PriceSample {
+    pub time: DateTime<Utc>,
+    pub waypoint: String,
+    pub good: String,
+    pub purchase_price: i32,
+    pub sell_price: i32,
+    pub trade_volume: i32,
+    pub supply: SupplyLevel,
+    pub activity: Option<ActivityLevel>,
+}
+
+/// Goods last written per market, so polling an unchanged market adds nothing.
+static LAST_RECORDED: Mutex<Option<HashMap<String, Vec<MarketTradeGood>>>> = Mutex::new(None);
+
+fn path() -> PathBuf {
+    cache::data_dir().join("prices.jsonl")
+}
+
+/// Appends the market's prices to the history. Markets seen without a ship
+/// present carry no prices and are skipped.
+pub fn record(market: &Market) -> Result<()> {
+    let Some(goods) = &market.trade_goods else {
+        return Ok(());
+    };
+    {
+        let mut last = LAST_RECORDED.lock().unwrap();
+        let last = last.get_or_insert_with(HashMap::new);
+        if last.get(&market.symbol) == Some(goods) {
+            return Ok(());
+        }
+        last.insert(market.symbol.clone(), goods.clone());
+    }
+
+    fs::create_dir_all(cache::data_dir())?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path())?;
+    let time = Utc::now();
+    for good in goods {
+        let sample = PriceSample {
+            time,
+            waypoint: market.symbol.clone(),
+            good: good.symbol.to_string(),
+            purchase_price: good.purchase_price,
+            sell_price: good.sell_price,
+            trade_volume: good.trade_volume,
+            supply: good.supply,
+            activity: good.activity,
+        };
+        writeln!(file, "{}", serde_json::to_string(&sample)?)?;
+    }
+    Ok(())
+}
+
+/// Lets the market's next snapshot through even if its prices look
+/// unchanged, so each of our trades is followed by an "after" sample for
+/// the impact model, including trades that didn't move the price.
+pub fn traded_at(waypoint: &str) {
+    if let Some(last) = LAST_RECORDED.lock().unwrap().as_mut() {
+        last.remove(waypoint);
+    }
+}
+
+/// Fetches the market right before one of our trades, recording it even if
+/// nothing changed since the last poll, so the impact model has a "before"
+/// sample close enough to pair with the one after. The market is returned
+/// for pricing the trade.
+pub async fn before_trading(client: &SpaceTradersClient, waypoint: &str) -> Result<Market> {
+    client
+        .sample_market(cache::system_symbol(waypoint), waypoint)
+        .await
+}
+
+/// Fetches the market straight after one of our trades so the history
+/// holds the prices the trade left behind. A failure only costs the impact
+/// model a sample, so it is logged rather than returned.
+pub async fn after_trading(client: &SpaceTradersClient, waypoint: &str) {
+    if let Err(e) = client
+        .get_market(cache::system_symbol(waypoint), waypoint)
+        .await
+    {
+        events::record_error(&format!("Snapshot of {} after trading", waypoint), &e);
+    }
+}
+
+/// Every sample, oldest first. Unreadable lines are skipped.
+pub fn load() -> Vec<PriceSample> {
+    fs::read_to_string(path())
+        .map(|contents| {
+            contents
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,494 @@
+use anyhow::Result;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use spacetraders_api::models::{Ship, ShipNavFlightMode, ShipNavStatus, TradeSymbol};
+use std::fmt;
+
+use crate::behaviour;
+use crate::cache::{self, UniverseCache};
+use crate::client::SpaceTradersClient;
+use crate::events::{self, EventKind};
+use crate::navigation::{
+    distance, flight_seconds, fly_to, position, FLIGHT_OVERHEAD, FUEL_PER_UNIT,
+};
+use crate::prices;
+
+/// Seconds per unit of distance at engine speed 1 in DRIFT mode.
+const DRIFT_MULTIPLIER: f64 = 250.0;
+
+/// Fuel a DRIFT flight burns, whatever the distance.
+const DRIFT_FUEL: i32 = 1;
+
+/// How a stranded ship gets back to fuel.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Mission {
+    /// The ship drifts to `market` on its last fuel and fills up there.
+    Drift { market: String },
+    /// `rescuer` buys `units` of FUEL at `market`, flies them over and
+    /// hands them to the stranded ship, which refuels from its hold.
+    Rescue {
+        rescuer: String,
+        market: String,
+        units: i32,
+    },
+}
+
+impl Mission {
+    /// The ship helping out, if any.
+    pub fn rescuer(&self) -> Option<&str> {
+        match self {
+            Self::Drift { .. } => None,
+            Self::Rescue { rescuer, .. } => Some(rescuer),
+        }
+    }
+}
+
+impl fmt::Display for Mission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Drift { market } => write!(f, "drift to {}", market),
+            Self::Rescue {
+                rescuer,
+                market,
+                units,
+            } => write!(f, "{} brings {} FUEL from {}", rescuer, units, market),
+        }
+    }
+}
+
+/// A ship that cannot reach any market selling fuel at CRUISE, and the
+/// ways out.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Stranding {
+    pub ship: String,
+    pub waypoint: String,
+    pub fuel: i32,
+    /// Nearest cached market selling fuel.
+    pub market: String,
+    pub distance: f64,
+    /// How long drifting there takes, if there is fuel enough to drift.
+    pub drift_seconds: Option<i64>,
+    /// Best placed ship to bring fuel, and how long until it arrives.
+    pub rescuer: Option<String>,
+    pub rescue_seconds: Option<i64>,
+    /// FUEL cargo needed to reach the market at CRUISE.
+    pub units: i32,
+    /// The faster of the two, or `None` when neither is possible.
+    pub mission: Option<Mission>,
+}
+
+/// Fuel a CRUISE flight over `distance` burns.
+fn cruise_fuel(distance: f64) -> i32 {
+    if distance > 0.0 {
+        (distance.round() as i32).max(1)
+    } else {
+        0
+    }
+}
+
+fn drift_seconds(distance: f64, speed: i32) -> f64 {
+    FLIGHT_OVERHEAD + distance.max(1.0) * DRIFT_MULTIPLIER / speed.max(1) as f64
+}
+
+/// Whether the ship can fly `distance` at CRUISE, counting ships that
+/// burn no fuel.
+fn can_cruise(ship: &Ship, distance: f64) -> bool {
+    ship.fuel.capacity == 0 || cruise_fuel(distance) <= ship.fuel.current
+}
+
+/// Cached markets in `system` known to sell fuel, with their positions.
+pub fn fuel_markets(cache: &UniverseCache, system: &str) -> Vec<(String, (i32, i32))> {
+    cache
+        .markets
+        .values()
+        .filter(|market| cache::system_symbol(&market.symbol) == system)
+        .filter(|market| {
+            let listed = market.exports.iter().chain(&market.exchange);
+            listed
+                .map(|good| good.symbol)
+                .chain(market.trade_goods.iter().flatten().map(|good| good.symbol))
+                .any(|symbol| symbol == TradeSymbol::Fuel)
+        })
+        .filter_map(|market| Some((market.symbol.clone(), position(cache, &market.symbol)?)))
+        .collect()
+}
+
+/// Units of FUEL in the ship's hold.
+fn fuel_cargo(ship: &Ship) -> i32 {
+    ship.cargo
+        .inventory
+        .iter()
+        .filter(|item| item.symbol == TradeSymbol::Fuel)
+        .map(|item| item.units)
+        .sum()
+}
+
+/// The ship in the same system that can bring `units` of FUEL from the
+/// market at `market` to `target` soonest, and how long it takes. Ships
+/// in `busy` are already helping elsewhere.
+fn best_rescuer<'a>(
+    cache: &UniverseCache,
+    ships: &'a [Ship],
+    stranded: &Ship,
+    market: (i32, i32),
+    target: (i32, i32),
+    units: i32,
+    busy: &[String],
+) -> Option<(&'a Ship, f64)> {
+    ships
+        .iter()
+        .filter(|ship| ship.symbol != stranded.symbol && !busy.contains(&ship.symbol))
+        .filter(|ship| ship.nav.system_symbol == stranded.nav.system_symbol)
+        .filter(|ship| ship.nav.status != ShipNavStatus::InTransit)
+        .filter(|ship| ship.cargo.capacity - ship.cargo.units + fuel_cargo(ship) >= units)
+        .filter_map(|ship| {
+            let here = position(cache, &ship.nav.waypoint_symbol)?;
+            let speed = ship.engine.speed;
+            let seconds = if fuel_cargo(ship) >= units {
+                let leg = distance(here, target);
+                can_cruise(ship, leg).then(|| flight_seconds(leg, speed))?
+            } else {
+                let (to_market, to_target) = (distance(here, market), distance(market, target));
+                // The rescuer fills its own tank at the market too
+                let reachable = can_cruise(ship, to_market)
+                    && (ship.fuel.capacity == 0 || cruise_fuel(to_target) <= ship.fuel.capacity);
+                let via_market =
+                    flight_seconds(to_market, speed) + flight_seconds(to_target, speed);
+                reachable.then_some(via_market)?
+            };
+            Some((ship, seconds))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Whether `ship` is stranded: it burns fuel, is not in transit, and no
+/// cached fuel market in its system is within CRUISE range. Returns the
+/// plan for getting it out, or `None` if it is fine or no fuel market is
+/// known. Ships in `busy` are not picked as rescuers.
+pub fn assess(
+    cache: &UniverseCache,
+    ships: &[Ship],
+    ship: &Ship,
+    busy: &[String],
+) -> Option<Stranding> {
+    if ship.fuel.capacity == 0 || ship.nav.status == ShipNavStatus::InTransit {
+        return None;
+    }
+    let here = position(cache, &ship.nav.waypoint_symbol)?;
+    let markets = fuel_markets(cache, &ship.nav.system_symbol);
+    let (market, at_market, distance) = markets
+        .iter()
+        .map(|(symbol, point)| (symbol, *point, distance(here, *point)))
+        .min_by(|a, b| a.2.total_cmp(&b.2))?;
+    if *market == ship.nav.waypoint_symbol || can_cruise(ship, distance) {
+        return None;
+    }
+
+    let needed = (cruise_fuel(distance) - ship.fuel.current).max(1) as f64;
+    let free = ship.cargo.capacity - ship.cargo.units;
+    let units = ((needed / FUEL_PER_UNIT).ceil() as i32).min(free);
+    let drift =
+        (ship.fuel.current >= DRIFT_FUEL).then(|| drift_seconds(distance, ship.engine.speed));
+    let rescue = (units > 0)
+        .then(|| best_rescuer(cache, ships, ship, at_market, here, units, busy))
+        .flatten();
+
+    let mission = match (drift, &rescue) {
+        (Some(drift), Some((_, rescue))) if drift <= *rescue => Some(Mission::Drift {
+            market: market.clone(),
+        }),
+        (_, Some((rescuer, _))) => Some(Mission::Rescue {
+            rescuer: rescuer.symbol.clone(),
+            market: market.clone(),
+            units,
+        }),
+        (Some(_), None) => Some(Mission::Drift {
+            market: market.clone(),
+        }),
+        (None, None) => None,
+    };
+    Some(Stranding {
+        ship: ship.symbol.clone(),
+        waypoint: ship.nav.waypoint_symbol.clone(),
+        fuel: ship.fuel.current,
+        market: market.clone(),
+        distance,
+        drift_seconds: drift.map(|seconds| seconds.round() as i64),
+        rescuer: rescue.as_ref().map(|(rescuer, _)| rescuer.symbol.clone()),
+        rescue_seconds: rescue.map(|(_, seconds)| seconds.round() as i64),
+        units,
+        mission,
+    })
+}
+
+/// Drifts the ship to the market, then puts it back to CRUISE and fills
+/// the tank. Returns whether it is done.
+async fn drift(client: &SpaceTradersClient, ship: &Ship, market: &str) -> Result<bool> {
+    if ship.nav.waypoint_symbol != market {
+        if ship.nav.flight_mode != ShipNavFlightMode::Drift {
+            if ship.nav.status == ShipNavStatus::Docked {
+                client.orbit_ship(&ship.symbol).await?;
+            }
+            client
+                .set_flight_mode(&ship.symbol, ShipNavFlightMode::Drift)
+                .await?;
+        }
+        let data = client.navigate_ship(&ship.symbol, market).await?;
+        events::record(
+            log::Level::Info,
+            EventKind::Log,
+            format!(
+                "{} drifting to {} for fuel, arriving {}",
+                ship.symbol, market, data.nav.route.arrival
+            ),
+        );
+        return Ok(false);
+    }
+    if ship.nav.flight_mode != ShipNavFlightMode::Cruise {
+        client
+            .set_flight_mode(&ship.symbol, ShipNavFlightMode::Cruise)
+            .await?;
+    }
+    if ship.nav.status == ShipNavStatus::InOrbit {
+        client.dock_ship(&ship.symbol).await?;
+    }
+    client.refuel_ship(&ship.symbol, None, false).await?;
+    Ok(true)
+}
+
+/// Moves the rescue along one order at a time: the rescuer fetches the
+/// fuel and flies it over, then the stranded ship refuels from its hold.
+/// Returns whether it is done.
+async fn rescue(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    rescuer: &Ship,
+    market: &str,
+    units: i32,
+) -> Result<bool> {
+    let now = Utc::now();
+    let carried = fuel_cargo(ship);
+    if carried > 0 {
+        if !behaviour::is_idle(ship, now) {
+            return Ok(false);
+        }
+        if ship.nav.status == ShipNavStatus::InOrbit {
+            client.dock_ship(&ship.symbol).await?;
+        }
+        let room = ship.fuel.capacity - ship.fuel.current;
+        let fuel = room.min(carried * FUEL_PER_UNIT as i32);
+        let data = client.refuel_ship(&ship.symbol, Some(fuel), true).await?;
+        events::record(
+            log::Level::Info,
+            EventKind::Log,
+            format!(
+                "{} refuelled from cargo to {}/{}",
+                ship.symbol, data.fuel.current, data.fuel.capacity
+            ),
+        );
+        return Ok(true);
+    }
+    if !behaviour::is_idle(rescuer, now) {
+        return Ok(false);
+    }
+
+    let loaded = fuel_cargo(rescuer);
+    if loaded >= units {
+        if rescuer.nav.waypoint_symbol != ship.nav.waypoint_symbol {
+            fly_to(client, rescuer, &ship.nav.waypoint_symbol).await?;
+            return Ok(false);
+        }
+        // Transfers need both ships docked or both in orbit
+        if rescuer.nav.status != ship.nav.status {
+            match ship.nav.status {
+                ShipNavStatus::Docked => client.dock_ship(&rescuer.symbol).await?,
+                _ => client.orbit_ship(&rescuer.symbol).await?,
+            };
+        }
+        client
+            .transfer_cargo(&rescuer.symbol, &ship.symbol, TradeSymbol::Fuel, units)
+            .await?;
+        events::record(
+            log::Level::Info,
+            EventKind::Log,
+            format!(
+                "{} handed {} FUEL to {}",
+                rescuer.symbol, units, ship.symbol
+            ),
+        );
+        return Ok(false);
+    }
+
+    if rescuer.nav.waypoint_symbol != market {
+        fly_to(client, rescuer, market).await?;
+        return Ok(false);
+    }
+    if rescuer.nav.status == ShipNavStatus::InOrbit {
+        client.dock_ship(&rescuer.symbol).await?;
+    }
+    prices::before_trading(client, market).await?;
+    if rescuer.fuel.current < rescuer.fuel.capacity {
+        client.refuel_ship(&rescuer.symbol, None, false).await?;
+    }
+    client
+        .purchase_cargo(&rescuer.symbol, TradeSymbol::Fuel, units - loaded)
+        .await?;
+    prices::after_trading(client, market).await;
+    Ok(false)
+}
+
+/// Issues the next order of `mission` for the stranded ship `symbol`.
+/// Returns whether the ship has fuel again.
+pub async fn step(
+    client: &SpaceTradersClient,
+    ships: &[Ship],
+    symbol: &str,
+    mission: &Mission,
+) -> Result<bool> {
+    let Some(ship) = ships.iter().find(|ship| ship.symbol == symbol) else {
+        anyhow::bail!("{} is no longer in the fleet", symbol);
+    };
+    match mission {
+        Mission::Drift { market } => {
+            if !behaviour::is_idle(ship, Utc::now()) {
+                return Ok(false);
+            }
+            drift(client, ship, market).await
+        }
+        Mission::Rescue {
+            rescuer,
+            market,
+            units,
+        } => {
+            let Some(rescuer) = ships.iter().find(|ship| ship.symbol == *rescuer) else {
+                anyhow::bail!("{} is no longer in the fleet", rescuer);
+            };
+            rescue(client, ship, rescuer, market, *units).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetraders_api::models::{
+        market_trade_good, Market, MarketTradeGood, SupplyLevel, Waypoint, WaypointType,
+    };
+
+    /// A cache with a waypoint at each point and a fuel market at `X1-A-M`.
+    fn cache(waypoints: &[(&str, (i32, i32))]) -> UniverseCache {
+        let mut cache = UniverseCache::default();
+        for (symbol, (x, y)) in waypoints {
+            cache
+                .waypoints
+                .entry(String::from("X1-A"))
+                .or_default()
+                .push(Waypoint::new(
+                    symbol.to_string(),
+                    WaypointType::Planet,
+                    String::from("X1-A"),
+                    *x,
+                    *y,
+                    vec![],
+                    vec![],
+                    false,
+                ));
+        }
+        let mut market = Market::new(String::from("X1-A-M"), vec![], vec![], vec![]);
+        market.trade_goods = Some(vec![MarketTradeGood::new(
+            TradeSymbol::Fuel,
+            market_trade_good::Type::Exchange,
+            100,
+            SupplyLevel::Moderate,
+            70,
+            60,
+        )]);
+        cache.markets.insert(market.symbol.clone(), market);
+        cache
+    }
+
+    fn ship(symbol: &str, waypoint: &str, fuel: i32, tank: i32) -> Ship {
+        let mut ship = Ship {
+            symbol: symbol.to_string(),
+            ..Ship::default()
+        };
+        ship.nav.waypoint_symbol = waypoint.to_string();
+        ship.nav.system_symbol = String::from("X1-A");
+        ship.nav.status = ShipNavStatus::InOrbit;
+        ship.fuel.current = fuel;
+        ship.fuel.capacity = tank;
+        ship.cargo.capacity = 40;
+        ship.engine.speed = 10;
+        ship
+    }
+
+    fn waypoints() -> UniverseCache {
+        cache(&[
+            ("X1-A-M", (0, 0)),
+            ("X1-A-S", (500, 0)),
+            ("X1-A-DRY", (0, 10)),
+            ("X1-A-R", (-100, 0)),
+        ])
+    }
+
+    #[test]
+    fn leaves_ships_that_can_reach_fuel_alone() {
+        let stranded = ship("S", "X1-A-S", 500, 600);
+        assert!(assess(&waypoints(), &[], &stranded, &[]).is_none());
+    }
+
+    #[test]
+    fn skips_rescuers_without_fuel_to_reach_the_market() {
+        let stranded = ship("S", "X1-A-S", 10, 600);
+        let ships = [
+            stranded.clone(),
+            ship("DRY", "X1-A-DRY", 0, 600),
+            ship("R", "X1-A-R", 200, 600),
+        ];
+        let stranding = assess(&waypoints(), &ships, &stranded, &[]).unwrap();
+
+        // 490 fuel short is 5 units of FUEL cargo
+        assert_eq!(stranding.units, 5);
+        assert_eq!(stranding.rescuer.as_deref(), Some("R"));
+        assert_eq!(
+            stranding.mission,
+            Some(Mission::Rescue {
+                rescuer: String::from("R"),
+                market: String::from("X1-A-M"),
+                units: 5,
+            })
+        );
+        assert!(stranding.rescue_seconds < stranding.drift_seconds);
+    }
+
+    #[test]
+    fn drifts_when_no_rescuer_can_help() {
+        let stranded = ship("S", "X1-A-S", 10, 600);
+        let ships = [
+            stranded.clone(),
+            ship("DRY", "X1-A-DRY", 0, 600),
+            ship("R", "X1-A-R", 200, 600),
+        ];
+        let busy = [String::from("R")];
+        let stranding = assess(&waypoints(), &ships, &stranded, &busy).unwrap();
+
+        assert_eq!(stranding.rescuer, None);
+        assert_eq!(
+            stranding.mission,
+            Some(Mission::Drift {
+                market: String::from("X1-A-M")
+            })
+        );
+    }
+
+    #[test]
+    fn finds_no_way_out_for_an_empty_tank_without_rescuers() {
+        let stranded = ship("S", "X1-A-S", 0, 600);
+        let ships = [stranded.clone(), ship("DRY", "X1-A-DRY", 0, 600)];
+        let stranding = assess(&waypoints(), &ships, &stranded, &[]).unwrap();
+
+        assert_eq!(stranding.drift_seconds, None);
+        assert_eq!(stranding.mission, None);
+    }
+}
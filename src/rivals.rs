@@ -0,0 +1,196 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use spacetraders_api::models::Agent;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::cache;
+use crate::client::SpaceTradersClient;
+use crate::events;
+
+/// Minutes between the daemon's polls of watched agents.
+pub const POLL_MINUTES: i64 = 15;
+
+/// How the agent browser is ordered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum AgentSort {
+    #[default]
+    Credits,
+    Ships,
+    Symbol,
+    Faction,
+}
+
+impl AgentSort {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Credits => Self::Ships,
+            Self::Ships => Self::Symbol,
+            Self::Symbol => Self::Faction,
+            Self::Faction => Self::Credits,
+        }
+    }
+}
+
+impl fmt::Display for AgentSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Credits => write!(f, "credits"),
+            Self::Ships => write!(f, "ships"),
+            Self::Symbol => write!(f, "symbol"),
+            Self::Faction => write!(f, "faction"),
+        }
+    }
+}
+
+/// Agents in browsing order: richest and largest fleets first, names and
+/// factions alphabetically.
+pub fn sorted(agents: &[Agent], sort: AgentSort) -> Vec<&Agent> {
+    let mut sorted: Vec<&Agent> = agents.iter().collect();
+    match sort {
+        AgentSort::Credits => sorted.sort_by_key(|agent| std::cmp::Reverse(agent.credits)),
+        AgentSort::Ships => sorted.sort_by_key(|agent| std::cmp::Reverse(agent.ship_count)),
+        AgentSort::Symbol => sorted.sort_by(|a, b| a.symbol.cmp(&b.symbol)),
+        AgentSort::Faction => sorted.sort_by(|a, b| {
+            a.starting_faction
+                .cmp(&b.starting_faction)
+                .then_with(|| b.credits.cmp(&a.credits))
+        }),
+    }
+    sorted
+}
+
+/// The last synced agent directory and the agents we keep an eye on.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rivals {
+    pub agents: Vec<Agent>,
+    pub watched: Vec<String>,
+}
+
+impl Rivals {
+    fn path() -> PathBuf {
+        cache::data_dir().join("rivals.json")
+    }
+
+    /// Loads the directory and watch list, starting empty if they are
+    /// missing or unreadable.
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::create_dir_all(cache::data_dir())?;
+        fs::write(Self::path(), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn is_watched(&self, agent: &str) -> bool {
+        self.watched.iter().any(|watched| watched == agent)
+    }
+
+    pub fn set_watched(&mut self, agent: &str, watched: bool) -> Result<()> {
+        self.watched.retain(|symbol| symbol != agent);
+        if watched {
+            self.watched.push(agent.to_string());
+        }
+        self.save()
+    }
+
+    /// Replaces an agent's directory entry with fresher details.
+    pub fn update(&mut self, agent: &Agent) {
+        match self.agents.iter_mut().find(|a| a.symbol == agent.symbol) {
+            Some(existing) => *existing = agent.clone(),
+            None => self.agents.push(agent.clone()),
+        }
+    }
+}
+
+/// One agent's public standing at one time.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RivalSample {
+    pub time: DateTime<Utc>,
+    pub agent: String,
+    pub credits: i64,
+    pub ship_count: i32,
+}
+
+fn history_path() -> PathBuf {
+    cache::data_dir().join("rivals.jsonl")
+}
+
+/// Every sample, oldest first. Unreadable lines are skipped.
+pub fn history() -> Vec<RivalSample> {
+    fs::read_to_string(history_path())
+        .map(|contents| {
+            contents
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Appends a sample for each agent whose credits or fleet changed since
+/// its last one.
+pub fn record(agents: &[Agent]) -> Result<()> {
+    let history = history();
+    let changed: Vec<&Agent> = agents
+        .iter()
+        .filter(|agent| {
+            history
+                .iter()
+                .rev()
+                .find(|sample| sample.agent == agent.symbol)
+                .is_none_or(|last| {
+                    last.credits != agent.credits || last.ship_count != agent.ship_count
+                })
+        })
+        .collect();
+    if changed.is_empty() {
+        return Ok(());
+    }
+    fs::create_dir_all(cache::data_dir())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_path())?;
+    let time = Utc::now();
+    for agent in changed {
+        let sample = RivalSample {
+            time,
+            agent: agent.symbol.clone(),
+            credits: agent.credits,
+            ship_count: agent.ship_count,
+        };
+        writeln!(file, "{}", serde_json::to_string(&sample)?)?;
+    }
+    Ok(())
+}
+
+/// Fetches every watched agent, records their standing and refreshes
+/// their directory entries. An agent that can't be fetched is logged and
+/// skipped until the next poll.
+pub async fn poll_watched(client: &SpaceTradersClient) -> Result<()> {
+    let mut agents = Vec::new();
+    for symbol in Rivals::load().watched {
+        match client.get_agent(&symbol).await {
+            Ok(agent) => agents.push(agent),
+            Err(e) => events::record_error(&format!("Polling agent {}", symbol), &e),
+        }
+    }
+    record(&agents)?;
+    // Reloaded so a watch list change made while fetching isn't lost
+    let mut rivals = Rivals::load();
+    for agent in &agents {
+        rivals.update(agent);
+    }
+    rivals.save()
+}
@@ -0,0 +1,339 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use spacetraders_api::models::{Ship, ShipNavStatus, ShipRole};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::cache;
+use crate::scan;
+
+/// What we keep locally about one of our ships.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShipNotes {
+    pub nickname: Option<String>,
+    /// Group the ship is listed under, e.g. "X1 miners".
+    pub group: Option<String>,
+    pub tags: Vec<String>,
+    pub notes: String,
+}
+
+impl ShipNotes {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Local notes on the fleet, keyed by ship symbol.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Roster {
+    pub ships: BTreeMap<String, ShipNotes>,
+}
+
+impl Roster {
+    fn path() -> PathBuf {
+        cache::data_dir().join("roster.json")
+    }
+
+    /// Loads the roster, starting empty if it is missing or unreadable.
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::create_dir_all(cache::data_dir())?;
+        fs::write(Self::path(), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn notes(&self, ship: &str) -> ShipNotes {
+        self.ships.get(ship).cloned().unwrap_or_default()
+    }
+
+    /// Changes a ship's notes and saves, forgetting ships left with none.
+    pub fn edit(&mut self, ship: &str, change: impl FnOnce(&mut ShipNotes)) -> Result<()> {
+        let notes = self.ships.entry(ship.to_string()).or_default();
+        change(notes);
+        if notes.is_empty() {
+            self.ships.remove(ship);
+        }
+        self.save()
+    }
+
+    /// Every group in use, alphabetically.
+    pub fn groups(&self) -> Vec<String> {
+        let mut groups: Vec<String> = self
+            .ships
+            .values()
+            .filter_map(|notes| notes.group.clone())
+            .collect();
+        groups.sort();
+        groups.dedup();
+        groups
+    }
+
+    /// Every tag in use, alphabetically.
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self
+            .ships
+            .values()
+            .flat_map(|notes| notes.tags.clone())
+            .collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+}
+
+/// How the fleet list is ordered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ShipSort {
+    #[default]
+    Symbol,
+    /// Fullest holds first
+    Cargo,
+    /// Emptiest tanks first
+    Fuel,
+    /// Ships ready soonest first
+    Cooldown,
+    /// Ships arriving soonest first, then those not in transit
+    Arrival,
+}
+
+impl ShipSort {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Symbol => Self::Cargo,
+            Self::Cargo => Self::Fuel,
+            Self::Fuel => Self::Cooldown,
+            Self::Cooldown => Self::Arrival,
+            Self::Arrival => Self::Symbol,
+        }
+    }
+}
+
+impl fmt::Display for ShipSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Symbol => write!(f, "symbol"),
+            Self::Cargo => write!(f, "cargo"),
+            Self::Fuel => write!(f, "fuel"),
+            Self::Cooldown => write!(f, "cooldown"),
+            Self::Arrival => write!(f, "arrival"),
+        }
+    }
+}
+
+/// What the fleet list is split into sections by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    #[default]
+    None,
+    Group,
+    Role,
+    Status,
+    System,
+}
+
+impl GroupBy {
+    pub fn next(self) -> Self {
+        match self {
+            Self::None => Self::Group,
+            Self::Group => Self::Role,
+            Self::Role => Self::Status,
+            Self::Status => Self::System,
+            Self::System => Self::None,
+        }
+    }
+
+    fn key(self, ship: &Ship, roster: &Roster) -> String {
+        match self {
+            Self::None => String::new(),
+            Self::Group => roster
+                .ships
+                .get(&ship.symbol)
+                .and_then(|notes| notes.group.clone())
+                .unwrap_or_else(|| String::from("ungrouped")),
+            Self::Role => ship.registration.role.to_string(),
+            Self::Status => ship.nav.status.to_string(),
+            Self::System => ship.nav.system_symbol.clone(),
+        }
+    }
+}
+
+impl fmt::Display for GroupBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Group => write!(f, "group"),
+            Self::Role => write!(f, "role"),
+            Self::Status => write!(f, "status"),
+            Self::System => write!(f, "system"),
+        }
+    }
+}
+
+/// Which ships the fleet list shows. Unset fields match every ship.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShipFilter {
+    pub role: Option<ShipRole>,
+    pub status: Option<ShipNavStatus>,
+    pub system: Option<String>,
+    pub group: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl ShipFilter {
+    pub fn matches(&self, ship: &Ship, roster: &Roster) -> bool {
+        self.role.is_none_or(|role| ship.registration.role == role)
+            && self.status.is_none_or(|status| ship.nav.status == status)
+            && self
+                .system
+                .as_ref()
+                .is_none_or(|system| ship.nav.system_symbol == *system)
+            && self.group.as_ref().is_none_or(|group| {
+                roster
+                    .ships
+                    .get(&ship.symbol)
+                    .is_some_and(|notes| notes.group.as_ref() == Some(group))
+            })
+            && self.tag.as_ref().is_none_or(|tag| {
+                roster
+                    .ships
+                    .get(&ship.symbol)
+                    .is_some_and(|notes| notes.tags.contains(tag))
+            })
+    }
+}
+
+impl fmt::Display for ShipFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(role) = self.role {
+            parts.push(format!("role {}", role));
+        }
+        if let Some(status) = self.status {
+            parts.push(format!("status {}", status));
+        }
+        if let Some(system) = &self.system {
+            parts.push(format!("system {}", system));
+        }
+        if let Some(group) = &self.group {
+            parts.push(format!("group {}", group));
+        }
+        if let Some(tag) = &self.tag {
+            parts.push(format!("tag {}", tag));
+        }
+        if parts.is_empty() {
+            write!(f, "all")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+fn fill(units: i32, capacity: i32) -> f64 {
+    if capacity > 0 {
+        units as f64 / capacity as f64
+    } else {
+        0.0
+    }
+}
+
+fn arrival(ship: &Ship) -> Option<DateTime<Utc>> {
+    if ship.nav.status != ShipNavStatus::InTransit {
+        return None;
+    }
+    DateTime::parse_from_rfc3339(&ship.nav.route.arrival)
+        .ok()
+        .map(|arrival| arrival.with_timezone(&Utc))
+}
+
+/// The ships passing `filter`, split into sections by `group_by` and sorted
+/// within each. Sections are alphabetical; with no grouping there is one
+/// section with an empty name. Ships are indices into `ships`.
+pub fn arrange(
+    ships: &[Ship],
+    roster: &Roster,
+    filter: &ShipFilter,
+    sort: ShipSort,
+    group_by: GroupBy,
+) -> Vec<(String, Vec<usize>)> {
+    let now = Utc::now();
+    let mut sections: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, ship) in ships.iter().enumerate() {
+        if filter.matches(ship, roster) {
+            sections
+                .entry(group_by.key(ship, roster))
+                .or_default()
+                .push(i);
+        }
+    }
+    for indices in sections.values_mut() {
+        match sort {
+            ShipSort::Symbol => indices.sort_by(|a, b| ships[*a].symbol.cmp(&ships[*b].symbol)),
+            ShipSort::Cargo => indices.sort_by(|a, b| {
+                let (a, b) = (&ships[*a].cargo, &ships[*b].cargo);
+                fill(b.units, b.capacity).total_cmp(&fill(a.units, a.capacity))
+            }),
+            ShipSort::Fuel => indices.sort_by(|a, b| {
+                // Ships that burn no fuel never need it, so they go last
+                let level = |ship: &Ship| match ship.fuel.capacity {
+                    0 => 1.0,
+                    capacity => fill(ship.fuel.current, capacity),
+                };
+                level(&ships[*a]).total_cmp(&level(&ships[*b]))
+            }),
+            ShipSort::Cooldown => indices
+                .sort_by_key(|i| scan::cooldown_remaining(&ships[*i], now).unwrap_or_default()),
+            ShipSort::Arrival => indices.sort_by_key(|i| {
+                let arrival = arrival(&ships[*i]);
+                (arrival.is_none(), arrival)
+            }),
+        }
+    }
+    sections.into_iter().collect()
+}
+
+/// The part of the filter the Ships tab's filter keys step through.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FilterField {
+    #[default]
+    Role,
+    Status,
+    System,
+    Group,
+    Tag,
+}
+
+impl FilterField {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Role => Self::Status,
+            Self::Status => Self::System,
+            Self::System => Self::Group,
+            Self::Group => Self::Tag,
+            Self::Tag => Self::Role,
+        }
+    }
+}
+
+impl fmt::Display for FilterField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Role => write!(f, "role"),
+            Self::Status => write!(f, "status"),
+            Self::System => write!(f, "system"),
+            Self::Group => write!(f, "group"),
+            Self::Tag => write!(f, "tag"),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use spacetraders_api::models::{
+    Cooldown, ScannedShip, ScannedSystem, ScannedWaypoint, Ship, System, Waypoint,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::cache::{self, UniverseCache};
+use crate::client::SpaceTradersClient;
+use crate::fitting;
+use crate::search;
+
+/// What a ship's sensors look for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ScanKind {
+    Systems,
+    Waypoints,
+    Ships,
+}
+
+impl fmt::Display for ScanKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Systems => write!(f, "system"),
+            Self::Waypoints => write!(f, "waypoint"),
+            Self::Ships => write!(f, "ship"),
+        }
+    }
+}
+
+/// Seconds left on a ship's reactor cooldown, if any.
+pub fn cooldown_remaining(ship: &Ship, now: DateTime<Utc>) -> Option<i64> {
+    let expiration = ship.cooldown.expiration.as_deref()?;
+    let expiration = DateTime::parse_from_rfc3339(expiration).ok()?;
+    let remaining = (expiration.with_timezone(&Utc) - now).num_seconds();
+    (remaining > 0).then_some(remaining)
+}
+
+/// Checks the ship carries a sensor array and isn't cooling down.
+pub fn check(ship: &Ship) -> Result<()> {
+    let sensors = ship
+        .mounts
+        .iter()
+        .any(|mount| fitting::symbol_name(&mount.symbol).starts_with("MOUNT_SENSOR_ARRAY"));
+    if !sensors {
+        anyhow::bail!("{} has no sensor array", ship.symbol);
+    }
+    if let Some(remaining) = cooldown_remaining(ship, Utc::now()) {
+        anyhow::bail!("{} is cooling down for {}s", ship.symbol, remaining);
+    }
+    Ok(())
+}
+
+/// Adds scanned systems the cache doesn't know yet. Returns how many were new.
+pub fn merge_systems(cache: &mut UniverseCache, scanned: &[ScannedSystem]) -> usize {
+    let mut added = 0;
+    for system in scanned {
+        if cache.system(&system.symbol).is_none() {
+            cache.systems.push(System::new(
+                system.symbol.clone(),
+                system.sector_symbol.clone(),
+                system.r#type,
+                system.x,
+                system.y,
+                Vec::new(),
+                Vec::new(),
+            ));
+            added += 1;
+        }
+    }
+    added
+}
+
+/// Updates cached waypoints with scanned traits, orbitals and charts, and
+/// adds the ones not cached yet. Returns how many were new. Systems that
+/// aren't cached are left alone: a scan only sees part of a system, and a
+/// partial list would stop `search::cache_waypoints` fetching the rest.
+pub fn merge_waypoints(cache: &mut UniverseCache, scanned: &[ScannedWaypoint]) -> usize {
+    let mut added = 0;
+    for waypoint in scanned {
+        let Some(waypoints) = cache.waypoints.get_mut(&waypoint.system_symbol) else {
+            continue;
+        };
+        match waypoints.iter_mut().find(|w| w.symbol == waypoint.symbol) {
+            Some(cached) => {
+                cached.traits = waypoint.traits.clone();
+                cached.orbitals = waypoint.orbitals.clone();
+                cached.faction = waypoint.faction.clone();
+                cached.chart = waypoint.chart.clone();
+            }
+            None => {
+                let mut new = Waypoint::new(
+                    waypoint.symbol.clone(),
+                    waypoint.r#type,
+                    waypoint.system_symbol.clone(),
+                    waypoint.x,
+                    waypoint.y,
+                    waypoint.orbitals.clone(),
+                    waypoint.traits.clone(),
+                    false,
+                );
+                new.faction = waypoint.faction.clone();
+                new.chart = waypoint.chart.clone();
+                waypoints.push(new);
+                added += 1;
+            }
+        }
+    }
+    added
+}
+
+/// Another agent's ship as one of ours last saw it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sighting {
+    pub time: DateTime<Utc>,
+    pub seen_by: String,
+    pub ship: ScannedShip,
+}
+
+/// Latest sighting of each ship, keyed by ship symbol.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sightings {
+    pub ships: BTreeMap<String, Sighting>,
+}
+
+impl Sightings {
+    fn path() -> PathBuf {
+        cache::data_dir().join("sightings.json")
+    }
+
+    /// Loads saved sightings, starting empty if they are missing or unreadable.
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        fs::create_dir_all(cache::data_dir())?;
+        fs::write(Self::path(), serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Sightings in a system, most recent first.
+    pub fn in_system(&self, system: &str) -> Vec<&Sighting> {
+        let mut sightings: Vec<&Sighting> = self
+            .ships
+            .values()
+            .filter(|sighting| sighting.ship.nav.system_symbol == system)
+            .collect();
+        sightings.sort_by_key(|sighting| std::cmp::Reverse(sighting.time));
+        sightings
+    }
+}
+
+/// What a scan found, for reporting back.
+#[derive(Serialize)]
+pub struct ScanResult {
+    pub cooldown: Cooldown,
+    pub summary: String,
+    /// Ships found by a ship scan.
+    pub ships: Vec<ScannedShip>,
+}
+
+/// Runs a scan and stores what it finds: systems and waypoints in the
+/// universe cache, ships in the sightings.
+pub async fn scan(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    kind: ScanKind,
+    cache: &mut UniverseCache,
+) -> Result<ScanResult> {
+    check(ship)?;
+    let result = match kind {
+        ScanKind::Systems => {
+            let data = client.scan_systems(&ship.symbol).await?;
+            let added = merge_systems(cache, &data.systems);
+            cache.save()?;
+            ScanResult {
+                cooldown: *data.cooldown,
+                summary: format!("{} systems in range, {} new", data.systems.len(), added),
+                ships: Vec::new(),
+            }
+        }
+        ScanKind::Waypoints => {
+            search::cache_waypoints(client, cache, &ship.nav.system_symbol).await?;
+            let data = client.scan_waypoints(&ship.symbol).await?;
+            let added = merge_waypoints(cache, &data.waypoints);
+            cache.save()?;
+            ScanResult {
+                cooldown: *data.cooldown,
+                summary: format!("{} waypoints in range, {} new", data.waypoints.len(), added),
+                ships: Vec::new(),
+            }
+        }
+        ScanKind::Ships => {
+            let data = client.scan_ships(&ship.symbol).await?;
+            let mut sightings = Sightings::load();
+            let time = Utc::now();
+            for scanned in &data.ships {
+                sightings.ships.insert(
+                    scanned.symbol.clone(),
+                    Sighting {
+                        time,
+                        seen_by: ship.symbol.clone(),
+                        ship: scanned.clone(),
+                    },
+                );
+            }
+            sightings.save()?;
+            ScanResult {
+                cooldown: *data.cooldown,
+                summary: format!("{} ships in range", data.ships.len()),
+                ships: data.ships,
+            }
+        }
+    };
+    Ok(result)
+}
@@ -0,0 +1,111 @@
+use anyhow::Result;
+use serde::Serialize;
+use spacetraders_api::models::{
+    Ship, Waypoint, WaypointModifierSymbol, WaypointTraitSymbol, WaypointType,
+};
+
+use crate::cache::UniverseCache;
+use crate::client::SpaceTradersClient;
+use crate::navigation::distance;
+
+/// What a waypoint must have to match. Every criterion given must hold.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WaypointQuery {
+    pub waypoint_type: Option<WaypointType>,
+    pub traits: Vec<WaypointTraitSymbol>,
+    pub modifiers: Vec<WaypointModifierSymbol>,
+}
+
+impl WaypointQuery {
+    pub fn matches(&self, waypoint: &Waypoint) -> bool {
+        let modifiers = waypoint.modifiers.as_deref().unwrap_or_default();
+        self.waypoint_type.is_none_or(|t| waypoint.r#type == t)
+            && self
+                .traits
+                .iter()
+                .all(|symbol| waypoint.traits.iter().any(|t| t.symbol == *symbol))
+            && self
+                .modifiers
+                .iter()
+                .all(|symbol| modifiers.iter().any(|m| m.symbol == *symbol))
+    }
+}
+
+/// A matching waypoint and how far it is from the ship searched from.
+#[derive(Clone, Debug, Serialize)]
+pub struct WaypointMatch {
+    pub waypoint: Waypoint,
+    /// Distance between the ship's system and the waypoint's; zero when
+    /// they share a system.
+    pub system_distance: Option<f64>,
+    /// Distance from the ship, only known within its own system.
+    pub distance: Option<f64>,
+}
+
+/// Fetches a system's waypoints with their traits into the cache, unless
+/// they are already there.
+pub async fn cache_waypoints(
+    client: &SpaceTradersClient,
+    cache: &mut UniverseCache,
+    system: &str,
+) -> Result<()> {
+    if !cache.waypoints.contains_key(system) {
+        let waypoints = client.get_system_waypoints(system, None, None).await?;
+        cache.waypoints.insert(system.to_string(), waypoints);
+        cache.save()?;
+    }
+    Ok(())
+}
+
+/// Cached waypoints matching `query` in `system`, or in every cached system
+/// when `None`. Closest to `ship` first: its own system by distance, then
+/// other systems by how far away they are.
+pub fn search(
+    cache: &UniverseCache,
+    system: Option<&str>,
+    query: &WaypointQuery,
+    ship: Option<&Ship>,
+) -> Vec<WaypointMatch> {
+    let origin = ship.map(|ship| {
+        let position = &ship.nav.route.destination;
+        (ship.nav.system_symbol.as_str(), (position.x, position.y))
+    });
+    let system_position = |symbol: &str| cache.system(symbol).map(|s| (s.x, s.y));
+
+    let mut matches: Vec<WaypointMatch> = cache
+        .waypoints
+        .iter()
+        .filter(|(symbol, _)| system.is_none_or(|system| system == symbol.as_str()))
+        .flat_map(|(_, waypoints)| waypoints)
+        .filter(|waypoint| query.matches(waypoint))
+        .map(|waypoint| {
+            let (system_distance, distance) = match origin {
+                Some((home, position)) if home == waypoint.system_symbol => (
+                    Some(0.0),
+                    Some(self::distance(position, (waypoint.x, waypoint.y))),
+                ),
+                Some((home, _)) => (
+                    system_position(home)
+                        .zip(system_position(&waypoint.system_symbol))
+                        .map(|(a, b)| self::distance(a, b)),
+                    None,
+                ),
+                None => (None, None),
+            };
+            WaypointMatch {
+                waypoint: waypoint.clone(),
+                system_distance,
+                distance,
+            }
+        })
+        .collect();
+
+    let key = |d: Option<f64>| d.unwrap_or(f64::INFINITY);
+    matches.sort_by(|a, b| {
+        key(a.system_distance)
+            .total_cmp(&key(b.system_distance))
+            .then_with(|| key(a.distance).total_cmp(&key(b.distance)))
+            .then_with(|| a.waypoint.symbol.cmp(&b.waypoint.symbol))
+    });
+    matches
+}
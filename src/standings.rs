@@ -0,0 +1,92 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use spacetraders_api::models::GetStatus200Response;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::cache;
+
+/// The server's leaderboards at one time, best first.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub time: DateTime<Utc>,
+    pub most_credits: Vec<(String, i64)>,
+    pub most_submitted_charts: Vec<(String, i32)>,
+}
+
+impl Snapshot {
+    pub fn of(status: &GetStatus200Response) -> Self {
+        let boards = &status.leaderboards;
+        Self {
+            time: Utc::now(),
+            most_credits: boards
+                .most_credits
+                .iter()
+                .map(|entry| (entry.agent_symbol.clone(), entry.credits))
+                .collect(),
+            most_submitted_charts: boards
+                .most_submitted_charts
+                .iter()
+                .map(|entry| (entry.agent_symbol.clone(), entry.chart_count))
+                .collect(),
+        }
+    }
+
+    /// Position of an agent on the credits board, counting from 1.
+    pub fn credits_rank(&self, agent: &str) -> Option<usize> {
+        rank(&self.most_credits, agent)
+    }
+
+    /// Position of an agent on the charts board, counting from 1.
+    pub fn charts_rank(&self, agent: &str) -> Option<usize> {
+        rank(&self.most_submitted_charts, agent)
+    }
+
+    fn same_standings(&self, other: &Self) -> bool {
+        self.most_credits == other.most_credits
+            && self.most_submitted_charts == other.most_submitted_charts
+    }
+}
+
+fn rank<T>(board: &[(String, T)], agent: &str) -> Option<usize> {
+    board
+        .iter()
+        .position(|(symbol, _)| symbol == agent)
+        .map(|i| i + 1)
+}
+
+fn path() -> PathBuf {
+    cache::data_dir().join("standings.jsonl")
+}
+
+/// Every snapshot, oldest first. Unreadable lines are skipped.
+pub fn load() -> Vec<Snapshot> {
+    fs::read_to_string(path())
+        .map(|contents| {
+            contents
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Appends the leaderboards to the history unless they match the last
+/// snapshot, and returns the history.
+pub fn record(status: &GetStatus200Response) -> Result<Vec<Snapshot>> {
+    let mut history = load();
+    let snapshot = Snapshot::of(status);
+    if history
+        .last()
+        .is_some_and(|last| last.same_standings(&snapshot))
+    {
+        return Ok(history);
+    }
+    fs::create_dir_all(cache::data_dir())?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path())?;
+    writeln!(file, "{}", serde_json::to_string(&snapshot)?)?;
+    history.push(snapshot);
+    Ok(history)
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use serde::Serialize;
+use spacetraders_api::models::{ActivityLevel, Market, SupplyLevel};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use crate::cache::UniverseCache;
+use crate::client::SpaceTradersClient;
+
+/// How a market trades a good.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Role {
+    Export,
+    Import,
+    Exchange,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Export => write!(f, "EXPORT"),
+            Self::Import => write!(f, "IMPORT"),
+            Self::Exchange => write!(f, "EXCHANGE"),
+        }
+    }
+}
+
+/// A cached market that trades a good. Supply, activity and prices are only
+/// known for markets one of our ships has visited.
+#[derive(Clone, Debug, Serialize)]
+pub struct Listing {
+    pub waypoint: String,
+    pub role: Role,
+    pub supply: Option<SupplyLevel>,
+    pub activity: Option<ActivityLevel>,
+    pub purchase_price: Option<i32>,
+    pub sell_price: Option<i32>,
+}
+
+/// An input an exporting market is short of, holding back its production.
+#[derive(Clone, Debug, Serialize)]
+pub struct Bottleneck {
+    pub waypoint: String,
+    pub input: String,
+    pub supply: SupplyLevel,
+}
+
+/// Where a good sits in the supply chain and who trades it.
+#[derive(Clone, Debug, Serialize)]
+pub struct GoodReport {
+    pub good: String,
+    /// Goods it is made from.
+    pub inputs: Vec<String>,
+    /// Goods it is made into.
+    pub feeds: Vec<String>,
+    pub markets: Vec<Listing>,
+    pub bottlenecks: Vec<Bottleneck>,
+}
+
+/// Fetches the supply chain into the cache unless it is already there. It
+/// only changes on a server reset.
+pub async fn cache_supply_chain(
+    client: &SpaceTradersClient,
+    cache: &mut UniverseCache,
+) -> Result<()> {
+    if cache.supply_chain.is_empty() {
+        cache.supply_chain = client.get_supply_chain().await?;
+        cache.save()?;
+    }
+    Ok(())
+}
+
+/// Every good in the supply chain, sorted.
+pub fn goods(chain: &BTreeMap<String, Vec<String>>) -> Vec<String> {
+    let goods: BTreeSet<&String> = chain
+        .iter()
+        .flat_map(|(good, inputs)| std::iter::once(good).chain(inputs))
+        .collect();
+    goods.into_iter().cloned().collect()
+}
+
+/// Cached markets trading `good`, exporters first.
+pub fn listings(markets: &HashMap<String, Market>, good: &str) -> Vec<Listing> {
+    let mut listings: Vec<Listing> = markets
+        .values()
+        .flat_map(|market| {
+            let roles = [
+                (Role::Export, &market.exports),
+                (Role::Import, &market.imports),
+                (Role::Exchange, &market.exchange),
+            ];
+            roles.into_iter().filter_map(move |(role, goods)| {
+                goods.iter().find(|g| g.symbol.to_string() == good)?;
+                let trade = market
+                    .trade_goods
+                    .iter()
+                    .flatten()
+                    .find(|g| g.symbol.to_string() == good);
+                Some(Listing {
+                    waypoint: market.symbol.clone(),
+                    role,
+                    supply: trade.map(|t| t.supply),
+                    activity: trade.and_then(|t| t.activity),
+                    purchase_price: trade.map(|t| t.purchase_price),
+                    sell_price: trade.map(|t| t.sell_price),
+                })
+            })
+        })
+        .collect();
+    listings.sort_by(|a, b| {
+        a.role
+            .cmp(&b.role)
+            .then_with(|| a.waypoint.cmp(&b.waypoint))
+    });
+    listings
+}
+
+pub fn report(
+    chain: &BTreeMap<String, Vec<String>>,
+    markets: &HashMap<String, Market>,
+    good: &str,
+) -> GoodReport {
+    let inputs = chain.get(good).cloned().unwrap_or_default();
+    let feeds = chain
+        .iter()
+        .filter(|(_, inputs)| inputs.iter().any(|input| input == good))
+        .map(|(output, _)| output.clone())
+        .collect();
+    let markets_listed = listings(markets, good);
+
+    // Exporters produce faster the better supplied their imports are, so a
+    // scarce input at an exporting market is worth hauling in.
+    let mut bottlenecks = Vec::new();
+    for exporter in markets_listed.iter().filter(|l| l.role == Role::Export) {
+        for input in &inputs {
+            let supply = listings(markets, input)
+                .into_iter()
+                .find(|l| l.waypoint == exporter.waypoint && l.role == Role::Import)
+                .and_then(|l| l.supply);
+            if let Some(supply @ (SupplyLevel::Scarce | SupplyLevel::Limited)) = supply {
+                bottlenecks.push(Bottleneck {
+                    waypoint: exporter.waypoint.clone(),
+                    input: input.clone(),
+                    supply,
+                });
+            }
+        }
+    }
+
+    GoodReport {
+        good: good.to_string(),
+        inputs,
+        feeds,
+        markets: markets_listed,
+        bottlenecks,
+    }
+}
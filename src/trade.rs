@@ -0,0 +1,618 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use spacetraders_api::models::{MarketTradeGood, Ship, ShipNavStatus, TradeSymbol};
+use std::collections::HashMap;
+
+use crate::cache::{self, UniverseCache};
+use crate::client::SpaceTradersClient;
+use crate::events::{self, EventKind};
+use crate::impact::{ImpactModel, Side};
+use crate::navigation::fly_to_for;
+use crate::prices;
+
+/// Minutes between looks at a price we are waiting on to recover.
+const RECHECK_MINUTES: i64 = 5;
+
+/// Half-lives to wait for a price to recover before buying less.
+const PATIENCE_HALF_LIVES: f64 = 2.0;
+
+/// Goods bought at one stop of a route and sold at a later one.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Lot {
+    pub good: TradeSymbol,
+    pub units: i32,
+    /// Indices into the route.
+    pub buy_at: usize,
+    pub sell_at: usize,
+    /// Expected totals, allowing for the prices moving batch by batch.
+    pub cost: i64,
+    pub revenue: i64,
+    /// Units bought and sold so far.
+    #[serde(default)]
+    pub bought: i32,
+    #[serde(default)]
+    pub sold: i32,
+}
+
+impl Lot {
+    /// Expected sale price per unit, the most worth paying.
+    fn break_even(&self) -> f64 {
+        self.revenue as f64 / self.units.max(1) as f64
+    }
+}
+
+/// What to buy and sell at each stop of a trade run.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TradePlan {
+    pub route: Vec<String>,
+    pub lots: Vec<Lot>,
+    /// When buying stopped to let a price recover, and when to look again.
+    #[serde(default)]
+    pub paused: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub recheck: Option<DateTime<Utc>>,
+}
+
+impl TradePlan {
+    pub fn cost(&self) -> i64 {
+        self.lots.iter().map(|lot| lot.cost).sum()
+    }
+
+    pub fn revenue(&self) -> i64 {
+        self.lots.iter().map(|lot| lot.revenue).sum()
+    }
+
+    pub fn profit(&self) -> i64 {
+        self.revenue() - self.cost()
+    }
+}
+
+/// Picks the goods and quantities to buy at each stop of `route` so they
+/// sell at a later stop for the most profit. Batches of `trade_volume`
+/// are added greedily, best margin first, while the hold has room on
+/// every leg they ride and the credits in hand at each stop cover them;
+/// each batch moves its market's price as the impact model predicts.
+/// Stops without cached prices are passed through.
+pub fn plan(
+    cache: &UniverseCache,
+    model: &ImpactModel,
+    route: &[String],
+    free: i32,
+    credits: i64,
+) -> TradePlan {
+    let markets: Vec<&[MarketTradeGood]> = route
+        .iter()
+        .map(|waypoint| {
+            cache
+                .markets
+                .get(waypoint)
+                .and_then(|market| market.trade_goods.as_deref())
+                .unwrap_or_default()
+        })
+        .collect();
+    let stops = route.len();
+    // Hold used on the leg leaving each stop, and credits left at each stop
+    let mut hold = vec![0; stops];
+    let mut cash = vec![credits; stops];
+    let mut bought: HashMap<(usize, TradeSymbol), i32> = HashMap::new();
+    let mut sold: HashMap<(usize, TradeSymbol), i32> = HashMap::new();
+    let mut lots: Vec<Lot> = Vec::new();
+
+    loop {
+        let mut best: Option<(f64, Lot)> = None;
+        for (i, goods) in markets.iter().enumerate() {
+            for buy in goods.iter() {
+                let traded = bought.get(&(i, buy.symbol)).copied().unwrap_or_default();
+                let unit_cost = model.price(buy, traded, Side::Buy);
+                for (j, sell_goods) in markets.iter().enumerate().skip(i + 1) {
+                    let Some(sell) = sell_goods.iter().find(|g| g.symbol == buy.symbol) else {
+                        continue;
+                    };
+                    let sales = sold.get(&(j, sell.symbol)).copied().unwrap_or_default();
+                    let unit_revenue = model.price(sell, sales, Side::Sell);
+                    let margin = unit_revenue - unit_cost;
+                    if margin <= 0.0 || best.as_ref().is_some_and(|(m, _)| *m >= margin) {
+                        continue;
+                    }
+                    let room = free - hold[i..j].iter().max().copied().unwrap_or_default();
+                    let affordable = cash[i..j].iter().min().copied().unwrap_or_default() as f64
+                        / unit_cost.max(1.0);
+                    let units = buy
+                        .trade_volume
+                        .min(sell.trade_volume)
+                        .min(room)
+                        .min(affordable as i32);
+                    if units <= 0 {
+                        continue;
+                    }
+                    let lot = Lot {
+                        good: buy.symbol,
+                        units,
+                        buy_at: i,
+                        sell_at: j,
+                        cost: (unit_cost * units as f64).round() as i64,
+                        revenue: (unit_revenue * units as f64).round() as i64,
+                        bought: 0,
+                        sold: 0,
+                    };
+                    best = Some((margin, lot));
+                }
+            }
+        }
+        let Some((_, lot)) = best else {
+            break;
+        };
+
+        for used in &mut hold[lot.buy_at..lot.sell_at] {
+            *used += lot.units;
+        }
+        for (stop, left) in cash.iter_mut().enumerate().skip(lot.buy_at) {
+            *left -= lot.cost;
+            if stop >= lot.sell_at {
+                *left += lot.revenue;
+            }
+        }
+        *bought.entry((lot.buy_at, lot.good)).or_default() += lot.units;
+        *sold.entry((lot.sell_at, lot.good)).or_default() += lot.units;
+        match lots
+            .iter_mut()
+            .find(|l| (l.good, l.buy_at, l.sell_at) == (lot.good, lot.buy_at, lot.sell_at))
+        {
+            Some(existing) => {
+                existing.units += lot.units;
+                existing.cost += lot.cost;
+                existing.revenue += lot.revenue;
+            }
+            None => lots.push(lot),
+        }
+    }
+
+    lots.sort_by_key(|lot| (lot.buy_at, lot.sell_at, lot.good.to_string()));
+    TradePlan {
+        route: route.to_vec(),
+        lots,
+        ..TradePlan::default()
+    }
+}
+
+/// The most profitable two-stop routes between cached markets in `system`,
+/// best first, each planned with the impact model so big lots are priced
+/// as the market will move under them.
+pub fn find_routes(
+    cache: &UniverseCache,
+    model: &ImpactModel,
+    system: &str,
+    free: i32,
+    credits: i64,
+    limit: usize,
+) -> Vec<TradePlan> {
+    let mut markets: Vec<&String> = cache
+        .markets
+        .iter()
+        .filter(|(symbol, market)| {
+            cache::system_symbol(symbol) == system && market.trade_goods.is_some()
+        })
+        .map(|(symbol, _)| symbol)
+        .collect();
+    markets.sort();
+    let mut plans: Vec<TradePlan> = markets
+        .iter()
+        .flat_map(|from| markets.iter().map(move |to| (*from, *to)))
+        .filter(|(from, to)| from != to)
+        .map(|(from, to)| plan(cache, model, &[from.clone(), to.clone()], free, credits))
+        .filter(|plan| plan.profit() > 0)
+        .collect();
+    plans.sort_by_key(|plan| std::cmp::Reverse(plan.profit()));
+    plans.truncate(limit);
+    plans
+}
+
+/// Trades `units` of a good the market lists in lots no larger than its
+/// trade volume, returning the credits that changed hands.
+async fn trade(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    good: &MarketTradeGood,
+    units: i32,
+    buying: bool,
+) -> Result<i64> {
+    let volume = good.trade_volume.max(1);
+    let mut total = 0;
+    let mut remaining = units;
+    while remaining > 0 {
+        let batch = remaining.min(volume);
+        let data = if buying {
+            client
+                .purchase_cargo(&ship.symbol, good.symbol, batch)
+                .await?
+        } else {
+            client.sell_cargo(&ship.symbol, good.symbol, batch).await?
+        };
+        total += data.transaction.total_price as i64;
+        remaining -= batch;
+    }
+    Ok(total)
+}
+
+/// Buys what the plan buys at stop `stop`, best paying lots first, one
+/// batch at a time. Stops at the first batch the impact model expects to
+/// cost more than its goods will sell for, returning that good.
+async fn buy(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    model: &ImpactModel,
+    goods: &[MarketTradeGood],
+    plan: &mut TradePlan,
+    stop: usize,
+) -> Result<(i64, Option<MarketTradeGood>)> {
+    let mut order: Vec<usize> = (0..plan.lots.len())
+        .filter(|i| plan.lots[*i].buy_at == stop && plan.lots[*i].bought < plan.lots[*i].units)
+        .collect();
+    order.sort_by(|a, b| {
+        plan.lots[*b]
+            .break_even()
+            .total_cmp(&plan.lots[*a].break_even())
+    });
+    let mut spent = 0;
+    let mut traded: Vec<(TradeSymbol, i32)> = Vec::new();
+    for i in order {
+        let lot = &mut plan.lots[i];
+        let Some(good) = goods.iter().find(|g| g.symbol == lot.good) else {
+            continue;
+        };
+        let mut bought_here = traded
+            .iter()
+            .find(|(symbol, _)| *symbol == lot.good)
+            .map_or(0, |(_, units)| *units);
+        while lot.bought < lot.units {
+            if model.price(good, bought_here, Side::Buy) >= lot.break_even() {
+                return Ok((spent, Some(good.clone())));
+            }
+            let batch = (lot.units - lot.bought).min(good.trade_volume.max(1));
+            spent += trade(client, ship, good, batch, true).await?;
+            lot.bought += batch;
+            bought_here += batch;
+        }
+        match traded.iter_mut().find(|(symbol, _)| *symbol == lot.good) {
+            Some((_, units)) => *units = bought_here,
+            None => traded.push((lot.good, bought_here)),
+        }
+    }
+    Ok((spent, None))
+}
+
+/// Carries out the plan one stop at a time: flies to stop `next`, sells
+/// what was bought for it, buys what the plan buys there and refuels.
+/// When a price has run up past what its goods will sell for, buying
+/// waits for it to recover, for up to a couple of the impact model's
+/// half-lives, then goes on with less. Returns whether the route is done.
+pub async fn step(
+    client: &SpaceTradersClient,
+    ship: &Ship,
+    cache: &mut UniverseCache,
+    model: &ImpactModel,
+    plan: &mut TradePlan,
+    next: &mut usize,
+) -> Result<bool> {
+    let Some(waypoint) = plan.route.get(*next).cloned() else {
+        return Ok(true);
+    };
+    if ship.nav.waypoint_symbol != waypoint {
+        fly_to_for(client, ship, &waypoint, "to trade").await?;
+        return Ok(false);
+    }
+    let now = Utc::now();
+    if plan.recheck.is_some_and(|recheck| now < recheck) {
+        return Ok(false);
+    }
+
+    if ship.nav.status == ShipNavStatus::InOrbit {
+        client.dock_ship(&ship.symbol).await?;
+    }
+    let market = prices::before_trading(client, &waypoint).await?;
+    let goods = market.trade_goods.clone().unwrap_or_default();
+    let mut held: Vec<(TradeSymbol, i32)> = ship
+        .cargo
+        .inventory
+        .iter()
+        .map(|item| (item.symbol, item.units))
+        .collect();
+    let mut earned = 0;
+    for lot in plan.lots.iter_mut().filter(|lot| lot.sell_at == *next) {
+        let Some((_, left)) = held.iter_mut().find(|(good, _)| *good == lot.good) else {
+            continue;
+        };
+        let units = (lot.bought - lot.sold).min(*left);
+        if units <= 0 {
+            continue;
+        }
+        let Some(good) = goods.iter().find(|g| g.symbol == lot.good) else {
+            // The market stopped trading it, so sell at a later stop that does
+            let later = (*next + 1..plan.route.len()).find(|stop| {
+                cache
+                    .markets
+                    .get(&plan.route[*stop])
+                    .and_then(|market| market.trade_goods.as_ref())
+                    .is_some_and(|goods| goods.iter().any(|g| g.symbol == lot.good))
+            });
+            events::record(
+                log::Level::Warn,
+                EventKind::Log,
+                match later {
+                    Some(stop) => format!(
+                        "{} no longer trades {}, {} will sell it at {} instead",
+                        waypoint, lot.good, ship.symbol, plan.route[stop]
+                    ),
+                    None => format!(
+                        "{} no longer trades {} and no later stop does, {} keeps {} units",
+                        waypoint, lot.good, ship.symbol, units
+                    ),
+                },
+            );
+            if let Some(stop) = later {
+                lot.sell_at = stop;
+            }
+            continue;
+        };
+        earned += trade(client, ship, good, units, false).await?;
+        lot.sold += units;
+        *left -= units;
+    }
+    let (spent, waiting) = buy(client, ship, model, &goods, plan, *next).await?;
+    if ship.fuel.current < ship.fuel.capacity
+        && goods.iter().any(|good| good.symbol == TradeSymbol::Fuel)
+    {
+        client.refuel_ship(&ship.symbol, None, false).await?;
+    }
+    if earned > 0 || spent > 0 {
+        events::record(
+            log::Level::Info,
+            EventKind::Log,
+            format!(
+                "{} traded at {}: sold for {}, bought for {}",
+                ship.symbol, waypoint, earned, spent
+            ),
+        );
+    }
+
+    // Our trades moved the prices, so keep the cache current
+    let market = client
+        .get_market(cache::system_symbol(&waypoint), &waypoint)
+        .await?;
+    cache.markets.insert(waypoint.clone(), market);
+    cache.save()?;
+
+    if let Some(good) = waiting {
+        let half_life = model.half_life(Side::Buy, good.supply, good.activity);
+        let patience = Duration::seconds((half_life * PATIENCE_HALF_LIVES * 60.0) as i64);
+        let since = *plan.paused.get_or_insert(now);
+        if now - since < patience {
+            if since == now {
+                events::record(
+                    log::Level::Info,
+                    EventKind::Log,
+                    format!(
+                        "{} waiting for {} at {} to come down from {} (half-life {:.0} min)",
+                        ship.symbol, good.symbol, waypoint, good.purchase_price, half_life
+                    ),
+                );
+            }
+            plan.recheck = Some(now + Duration::minutes(RECHECK_MINUTES));
+            return Ok(false);
+        }
+        events::record(
+            log::Level::Info,
+            EventKind::Log,
+            format!(
+                "{} gave up waiting at {} and leaves with less cargo",
+                ship.symbol, waypoint
+            ),
+        );
+        for lot in plan.lots.iter_mut().filter(|lot| lot.buy_at == *next) {
+            let share = lot.bought as f64 / lot.units.max(1) as f64;
+            lot.cost = (lot.cost as f64 * share).round() as i64;
+            lot.revenue = (lot.revenue as f64 * share).round() as i64;
+            lot.units = lot.bought;
+        }
+    }
+    plan.paused = None;
+    plan.recheck = None;
+    *next += 1;
+    Ok(*next >= plan.route.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetraders_api::models::{market_trade_good, Market, SupplyLevel};
+
+    /// A listed good: symbol, purchase price and sell price.
+    type Listing = (TradeSymbol, i32, i32);
+
+    /// A cache holding one market per stop, each listing goods with a
+    /// trade volume of 10.
+    fn cache(stops: &[(&str, &[Listing])]) -> UniverseCache {
+        let mut cache = UniverseCache::default();
+        for (symbol, goods) in stops {
+            let mut market = Market::new(symbol.to_string(), vec![], vec![], vec![]);
+            market.trade_goods = Some(
+                goods
+                    .iter()
+                    .map(|(good, purchase, sell)| {
+                        MarketTradeGood::new(
+                            *good,
+                            market_trade_good::Type::Exchange,
+                            10,
+                            SupplyLevel::Moderate,
+                            *purchase,
+                            *sell,
+                        )
+                    })
+                    .collect(),
+            );
+            cache.markets.insert(symbol.to_string(), market);
+        }
+        cache
+    }
+
+    fn route(stops: &[&str]) -> Vec<String> {
+        stops.iter().map(|stop| stop.to_string()).collect()
+    }
+
+    /// Units riding the leg out of each stop.
+    fn carried(plan: &TradePlan) -> Vec<i32> {
+        (0..plan.route.len())
+            .map(|leg| {
+                plan.lots
+                    .iter()
+                    .filter(|lot| lot.buy_at <= leg && leg < lot.sell_at)
+                    .map(|lot| lot.units)
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fills_the_hold_but_no_more() {
+        let cache = cache(&[
+            ("A", &[(TradeSymbol::Iron, 10, 8)]),
+            ("B", &[(TradeSymbol::Iron, 120, 100)]),
+        ]);
+        let plan = plan(
+            &cache,
+            &ImpactModel::default(),
+            &route(&["A", "B"]),
+            25,
+            1_000_000,
+        );
+        assert_eq!(carried(&plan), vec![25, 0]);
+        assert_eq!(plan.lots.len(), 1);
+        assert!(plan.profit() > 0);
+    }
+
+    #[test]
+    fn reuses_the_hold_on_each_leg() {
+        let cache = cache(&[
+            ("A", &[(TradeSymbol::Iron, 10, 8)]),
+            (
+                "B",
+                &[(TradeSymbol::Iron, 60, 50), (TradeSymbol::Copper, 10, 8)],
+            ),
+            ("C", &[(TradeSymbol::Copper, 60, 50)]),
+        ]);
+        let plan = plan(
+            &cache,
+            &ImpactModel::default(),
+            &route(&["A", "B", "C"]),
+            10,
+            1_000_000,
+        );
+        let lots: Vec<_> = plan
+            .lots
+            .iter()
+            .map(|lot| (lot.good, lot.buy_at, lot.sell_at, lot.units))
+            .collect();
+        assert_eq!(
+            lots,
+            vec![
+                (TradeSymbol::Iron, 0, 1, 10),
+                (TradeSymbol::Copper, 1, 2, 10)
+            ]
+        );
+    }
+
+    #[test]
+    fn spends_no_more_than_the_credits_in_hand() {
+        let cache = cache(&[
+            ("A", &[(TradeSymbol::Iron, 10, 8)]),
+            ("B", &[(TradeSymbol::Iron, 60, 50)]),
+        ]);
+        let plan = plan(
+            &cache,
+            &ImpactModel::default(),
+            &route(&["A", "B"]),
+            100,
+            55,
+        );
+        assert_eq!(carried(&plan), vec![5, 0]);
+        assert!(plan.cost() <= 55);
+    }
+
+    #[test]
+    fn spends_sales_at_later_stops() {
+        let cache = cache(&[
+            ("A", &[(TradeSymbol::Iron, 10, 8)]),
+            (
+                "B",
+                &[(TradeSymbol::Iron, 60, 50), (TradeSymbol::Copper, 40, 30)],
+            ),
+            ("C", &[(TradeSymbol::Copper, 200, 150)]),
+        ]);
+        // Only enough to buy the iron; the copper is paid for by selling it
+        let plan = plan(
+            &cache,
+            &ImpactModel::default(),
+            &route(&["A", "B", "C"]),
+            10,
+            100,
+        );
+        assert_eq!(carried(&plan), vec![10, 10, 0]);
+        assert!(plan.lots.iter().all(|lot| lot.units == 10));
+    }
+
+    #[test]
+    fn stops_once_prices_meet() {
+        let cache = cache(&[
+            ("A", &[(TradeSymbol::Iron, 10, 8)]),
+            ("B", &[(TradeSymbol::Iron, 60, 50)]),
+        ]);
+        // Unlimited hold and credits, so only the prices moving can end it
+        let plan = plan(
+            &cache,
+            &ImpactModel::default(),
+            &route(&["A", "B"]),
+            i32::MAX,
+            i64::MAX / 2,
+        );
+        let lot = &plan.lots[0];
+        assert!(lot.units > 10 && lot.units < 1000);
+        assert!(lot.revenue > lot.cost);
+    }
+
+    #[test]
+    fn finds_the_best_route_in_the_system() {
+        let cache = cache(&[
+            ("X1-A-1", &[(TradeSymbol::Iron, 10, 8)]),
+            ("X1-A-2", &[(TradeSymbol::Iron, 30, 25)]),
+            ("X1-A-3", &[(TradeSymbol::Iron, 60, 50)]),
+            ("X1-B-1", &[(TradeSymbol::Iron, 500, 400)]),
+        ]);
+        let routes = find_routes(&cache, &ImpactModel::default(), "X1-A", 10, 1_000_000, 2);
+        let stops: Vec<_> = routes.iter().map(|plan| plan.route.clone()).collect();
+        assert_eq!(
+            stops,
+            vec![route(&["X1-A-1", "X1-A-3"]), route(&["X1-A-2", "X1-A-3"])]
+        );
+    }
+
+    #[test]
+    fn plans_nothing_without_a_margin() {
+        let cache = cache(&[
+            ("A", &[(TradeSymbol::Iron, 50, 40)]),
+            (
+                "B",
+                &[(TradeSymbol::Iron, 45, 40), (TradeSymbol::Copper, 10, 8)],
+            ),
+            ("C", &[]),
+        ]);
+        let plan = plan(
+            &cache,
+            &ImpactModel::default(),
+            &route(&["A", "B", "C"]),
+            100,
+            1_000_000,
+        );
+        assert!(plan.lots.is_empty());
+    }
+}
@@ -1,24 +1,56 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use log::Level;
 use ratatui::{
     prelude::*,
     style::{Color, Modifier, Style},
+    symbols::Marker,
     text::{Line, Span, Text},
     widgets::{
-        Axis, Block, Borders, Chart, Dataset, GraphType, List, ListItem, ListState, Paragraph,
-        Tabs, Wrap,
+        canvas::{Canvas, Line as CanvasLine, Points},
+        Axis, Block, Borders, Chart, Clear, Dataset, GraphType, List, ListItem, ListState,
+        Paragraph, Tabs, Wrap,
     },
     Frame, Terminal,
 };
-use std::{io, time::Duration};
-use tui_input::Input;
-use unicode_width::UnicodeWidthStr;
-
-use crate::client::SpaceTradersClient;
+use spacetraders_api::models::{
+    FactionSymbol, ShipNavStatus, ShipRole, SystemType, WaypointModifierSymbol,
+    WaypointTraitSymbol, WaypointType,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future::Future,
+    io,
+    time::Duration,
+};
+use tokio::task::JoinHandle;
+use tui_input::backend::crossterm::EventHandler;
+
+use crate::behaviour::Behaviour;
+use crate::cache::{self, UniverseCache};
+use crate::client::{Progress, SpaceTradersClient};
+use crate::condition::{self, Advice, Assessment, WearLog, CONDITION_CRITICAL, CONDITION_WARNING};
+use crate::construction::{self, SiteReport};
+use crate::contract;
+use crate::events::{self, EventMonitor};
+use crate::fitting::{self, Loadout, Plan};
+use crate::fleet::{self, FleetCommand};
+use crate::ledger::{self, Grouping, LedgerEntry, Period};
+use crate::palette::{self, Palette, PaletteCommand, PaletteTarget};
+use crate::rescue;
+use crate::rivals::{self, AgentSort, RivalSample, Rivals};
+use crate::roster::{self, FilterField, GroupBy, Roster, ShipFilter, ShipSort};
+use crate::scan::{self, ScanKind, Sighting, Sightings};
+use crate::search::{self, WaypointMatch, WaypointQuery};
+use crate::standings::{self, Snapshot};
+use crate::supply::{self, Role};
 
 #[derive(Clone, Copy)]
 pub enum AppState {
@@ -29,17 +61,158 @@ pub enum AppState {
     SystemDetail,
     Markets,
     WaypointDetail,
+    Shipyard,
+    Galaxy,
+    Fitting,
+    WaypointSearch,
+    Construction,
+    Agents,
+    Factions,
+    NearbyShips,
+    Contracts,
+}
+
+/// Viewport and filters of the galaxy map.
+pub struct GalaxyMap {
+    /// Centre of the view in universe coordinates; `None` centres on all systems.
+    pub center: Option<(f64, f64)>,
+    pub zoom: f64,
+    pub type_filter: Option<SystemType>,
+    pub faction_filter: Option<FactionSymbol>,
+    /// Index into our ships' systems for cycling focus with `o`.
+    pub ship_focus: usize,
+}
+
+impl Default for GalaxyMap {
+    fn default() -> Self {
+        Self {
+            center: None,
+            zoom: 1.0,
+            type_filter: None,
+            faction_filter: None,
+            ship_focus: 0,
+        }
+    }
+}
+
+/// Filters of the waypoint search view.
+pub struct WaypointSearch {
+    pub query: WaypointQuery,
+    /// Search every cached system instead of only the ship's.
+    pub all_systems: bool,
+    /// Index into our ships of the one distances are measured from.
+    pub ship: usize,
+    pub results_state: ListState,
+}
+
+impl Default for WaypointSearch {
+    fn default() -> Self {
+        let mut results_state = ListState::default();
+        results_state.select(Some(0));
+        Self {
+            query: WaypointQuery::default(),
+            all_systems: false,
+            ship: 0,
+            results_state,
+        }
+    }
+}
+
+/// A paged download running beside the input loop, so the UI keeps
+/// drawing while it works through thousands of entries.
+pub struct Download<T> {
+    pub progress: Progress,
+    task: JoinHandle<Result<T>>,
+}
+
+impl<T: Send + 'static> Download<T> {
+    fn start<F>(
+        client: &SpaceTradersClient,
+        fetch: impl FnOnce(SpaceTradersClient, Progress) -> F,
+    ) -> Self
+    where
+        F: Future<Output = Result<T>> + Send + 'static,
+    {
+        let progress = Progress::default();
+        let task = tokio::spawn(fetch(client.clone(), progress.clone()));
+        Self { progress, task }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Waits for the result; only blocks if the task is still running.
+    async fn result(self) -> Result<T> {
+        self.task.await?
+    }
+
+    /// How far the download has got, e.g. `page 3/120`.
+    pub fn describe(&self) -> String {
+        match self.progress.get() {
+            (pages, Some(total)) => format!("page {}/{}", pages, total),
+            (pages, None) => format!("page {}", pages),
+        }
+    }
+}
+
+/// Loadout being planned for the selected ship.
+pub struct FittingScreen {
+    pub planned: Loadout,
+    pub market: Option<spacetraders_api::models::Market>,
+    pub shipyard: Option<spacetraders_api::models::Shipyard>,
+    /// Whether the parts for sale have focus rather than the planned fit.
+    pub focus_available: bool,
+    pub planned_state: ListState,
+    pub available_state: ListState,
+    pub confirm_pending: bool,
+}
+
+impl FittingScreen {
+    fn new(planned: Loadout) -> Self {
+        let mut planned_state = ListState::default();
+        planned_state.select(Some(0));
+        let mut available_state = ListState::default();
+        available_state.select(Some(0));
+        Self {
+            planned,
+            market: None,
+            shipyard: None,
+            focus_available: false,
+            planned_state,
+            available_state,
+            confirm_pending: false,
+        }
+    }
+
+    /// Mounts and modules on sale at the market, with their prices.
+    fn available(&self) -> Vec<(String, i64)> {
+        self.market
+            .as_ref()
+            .and_then(|market| market.trade_goods.as_ref())
+            .map(|goods| {
+                goods
+                    .iter()
+                    .map(|good| (good.symbol.to_string(), good.purchase_price as i64))
+                    .filter(|(symbol, _)| fitting::PartKind::of(symbol).is_some())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
 
 pub struct App {
     pub state: AppState,
     pub client: SpaceTradersClient,
     pub should_quit: bool,
-    pub input: Input,
     pub agent: Option<spacetraders_api::models::Agent>,
     pub ships: Option<Vec<spacetraders_api::models::Ship>>,
     pub systems: Option<Vec<spacetraders_api::models::System>>,
-    pub status_message: String,
+    pub status: Option<spacetraders_api::models::GetStatus200Response>,
+    /// Leaderboard snapshots, oldest first.
+    pub standings: Vec<Snapshot>,
+    /// Lines the announcements pane is scrolled down by.
+    pub announcement_scroll: u16,
     pub ships_state: ListState,
     pub systems_state: ListState,
     pub help_visible: bool,
@@ -47,6 +220,59 @@ pub struct App {
     pub selected_system_index: Option<usize>,
     pub waypoints_state: ListState,
     pub selected_waypoint_index: Option<usize>,
+    pub waypoint: Option<spacetraders_api::models::Waypoint>,
+    pub shipyard: Option<spacetraders_api::models::Shipyard>,
+    pub shipyard_state: ListState,
+    pub purchase_pending: bool,
+    pub notice: Option<String>,
+    pub cache: UniverseCache,
+    pub galaxy: GalaxyMap,
+    pub contracts: Option<Vec<spacetraders_api::models::Contract>>,
+    pub event_monitor: EventMonitor,
+    pub log_visible: bool,
+    /// Least severe level shown in the event log pane.
+    pub log_level: Level,
+    /// Open command palette, if any.
+    pub palette: Option<Palette>,
+    /// Daemon behaviours by ship symbol; only loaded when attached.
+    pub behaviours: BTreeMap<String, Behaviour>,
+    pub ledger: Vec<LedgerEntry>,
+    /// Period the dashboard's profit and loss report is grouped by.
+    pub pnl_period: Period,
+    pub wear: WearLog,
+    /// Latest repair advice, for the ship it was asked for.
+    pub assessment: Option<Assessment>,
+    pub fitting: Option<FittingScreen>,
+    pub search: WaypointSearch,
+    /// Selected good in the supply chain explorer on the Markets tab.
+    pub supply_state: ListState,
+    /// Selected site in the construction tracker.
+    pub construction_state: ListState,
+    /// Agent directory and watch list for the agent browser.
+    pub rivals: Rivals,
+    pub rival_history: Vec<RivalSample>,
+    pub agent_sort: AgentSort,
+    pub agents_state: ListState,
+    pub factions: Option<Vec<spacetraders_api::models::Faction>>,
+    pub factions_state: ListState,
+    /// Other agents' ships seen by our scans.
+    pub sightings: Sightings,
+    pub sightings_state: ListState,
+    /// Selected contract in the contracts view.
+    pub contracts_state: ListState,
+    /// Local nicknames, groups, tags and notes for our ships.
+    pub roster: Roster,
+    pub ship_filter: ShipFilter,
+    /// Filter field the Ships tab's `t` key steps through.
+    pub ship_filter_field: FilterField,
+    pub ship_sort: ShipSort,
+    pub ship_group_by: GroupBy,
+    /// Ships picked with Space for the palette's fleet commands.
+    pub marked_ships: BTreeSet<String>,
+    /// Galaxy download started with `u`, while it runs.
+    pub galaxy_download: Option<Download<Vec<spacetraders_api::models::System>>>,
+    /// Agent directory download, while it runs.
+    pub agents_download: Option<Download<Vec<spacetraders_api::models::Agent>>>,
 }
 
 impl App {
@@ -57,16 +283,31 @@ impl App {
         systems_state.select(Some(0));
         let mut waypoints_state = ListState::default();
         waypoints_state.select(Some(0));
+        let mut shipyard_state = ListState::default();
+        shipyard_state.select(Some(0));
+        let mut supply_state = ListState::default();
+        supply_state.select(Some(0));
+        let mut construction_state = ListState::default();
+        construction_state.select(Some(0));
+        let mut agents_state = ListState::default();
+        agents_state.select(Some(0));
+        let mut factions_state = ListState::default();
+        factions_state.select(Some(0));
+        let mut sightings_state = ListState::default();
+        sightings_state.select(Some(0));
+        let mut contracts_state = ListState::default();
+        contracts_state.select(Some(0));
 
         Self {
             state: AppState::Dashboard,
             client,
             should_quit: false,
-            input: Input::default(),
             agent: None,
             ships: None,
             systems: None,
-            status_message: String::from("Welcome to Space Traders"),
+            status: None,
+            standings: Vec::new(),
+            announcement_scroll: 0,
             ships_state,
             systems_state,
             help_visible: false,
@@ -74,1006 +315,3464 @@ impl App {
             selected_system_index: None,
             waypoints_state,
             selected_waypoint_index: None,
+            waypoint: None,
+            shipyard: None,
+            shipyard_state,
+            purchase_pending: false,
+            notice: None,
+            cache: UniverseCache::load(),
+            galaxy: GalaxyMap::default(),
+            contracts: None,
+            event_monitor: EventMonitor::new(),
+            log_visible: false,
+            log_level: Level::Info,
+            palette: None,
+            behaviours: BTreeMap::new(),
+            ledger: Vec::new(),
+            pnl_period: Period::Day,
+            wear: WearLog::load(),
+            assessment: None,
+            fitting: None,
+            search: WaypointSearch::default(),
+            supply_state,
+            construction_state,
+            rivals: Rivals::load(),
+            rival_history: rivals::history(),
+            agent_sort: AgentSort::default(),
+            agents_state,
+            factions: None,
+            factions_state,
+            sightings: Sightings::load(),
+            sightings_state,
+            contracts_state,
+            roster: Roster::load(),
+            ship_filter: ShipFilter::default(),
+            ship_filter_field: FilterField::default(),
+            ship_sort: ShipSort::default(),
+            ship_group_by: GroupBy::default(),
+            marked_ships: BTreeSet::new(),
+            galaxy_download: None,
+            agents_download: None,
         }
     }
 
     pub async fn refresh_data(&mut self) -> Result<()> {
+        // The daemon keeps polling markets and scanning, so pick up what it saved
+        if self.client.is_attached() {
+            self.cache.reload();
+        }
         match self.state {
             AppState::Dashboard => {
                 self.agent = Some(self.client.get_my_agent().await?);
+                self.ledger = ledger::load();
+                self.contracts = Some(self.client.get_contracts().await?);
                 let status = self.client.get_status().await?;
-                self.status_message = format!(
-                    "Status: {}\nVersion: {}\nReset Date: {}",
-                    status.status, status.version, status.reset_date
-                );
+                self.standings = standings::record(&status).unwrap_or_else(|e| {
+                    events::record_error("Recording standings failed", &e);
+                    standings::load()
+                });
+                self.status = Some(status);
             }
             AppState::Ships => {
-                self.ships = Some(self.client.get_my_ships().await?);
+                let ships = self.client.get_my_ships().await?;
+                self.wear.record(&ships)?;
+                self.ships = Some(ships);
+                self.roster = Roster::load();
+                if self.client.is_attached() {
+                    self.behaviours = self.client.get_behaviours().await?;
+                }
+            }
+            AppState::ShipDetail if self.client.is_attached() => {
+                self.behaviours = self.client.get_behaviours().await?;
             }
             AppState::Systems => {
                 self.systems = Some(self.client.get_systems(Some(1), Some(20)).await?);
             }
+            AppState::SystemDetail => {
+                if self.ships.is_none() {
+                    self.ships = Some(self.client.get_my_ships().await?);
+                }
+                if self.client.is_attached() {
+                    self.behaviours = self.client.get_behaviours().await?;
+                }
+                let system_symbol = self
+                    .systems
+                    .as_ref()
+                    .and_then(|systems| systems.get(self.selected_system_index?))
+                    .map(|system| system.symbol.clone());
+                if let Some(system_symbol) = system_symbol {
+                    if !self.cache.waypoints.contains_key(&system_symbol) {
+                        let waypoints = self
+                            .client
+                            .get_system_waypoints(&system_symbol, None, None)
+                            .await?;
+                        self.cache.waypoints.insert(system_symbol, waypoints);
+                        self.cache.save()?;
+                    }
+                }
+                let gates: Vec<(String, String)> = self
+                    .systems
+                    .as_ref()
+                    .and_then(|systems| systems.get(self.selected_system_index?))
+                    .map(|system| {
+                        system
+                            .waypoints
+                            .iter()
+                            .filter(|w| w.r#type == WaypointType::JumpGate)
+                            .map(|w| (system.symbol.clone(), w.symbol.clone()))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                self.cache_jump_gates(gates).await?;
+            }
+            AppState::Galaxy if self.ships.is_none() => {
+                self.ships = Some(self.client.get_my_ships().await?);
+            }
+            AppState::WaypointDetail => {
+                if let Some((system_symbol, waypoint_symbol)) = self.selected_waypoint_symbols() {
+                    let waypoint = self
+                        .client
+                        .get_waypoint(&system_symbol, &waypoint_symbol)
+                        .await?;
+                    // Keep cached traits current, e.g. after the waypoint is charted
+                    if let Some(cached) =
+                        self.cache
+                            .waypoints
+                            .get_mut(&system_symbol)
+                            .and_then(|waypoints| {
+                                waypoints.iter_mut().find(|w| w.symbol == waypoint.symbol)
+                            })
+                    {
+                        *cached = waypoint.clone();
+                        self.cache.save()?;
+                    }
+                    if waypoint
+                        .traits
+                        .iter()
+                        .any(|t| t.symbol == WaypointTraitSymbol::Marketplace)
+                    {
+                        let market = self
+                            .client
+                            .get_market(&system_symbol, &waypoint_symbol)
+                            .await?;
+                        self.cache.markets.insert(waypoint_symbol, market);
+                        self.cache.save()?;
+                    }
+                    self.waypoint = Some(waypoint);
+                }
+            }
+            AppState::Markets => {
+                supply::cache_supply_chain(&self.client, &mut self.cache).await?;
+            }
+            AppState::WaypointSearch => {
+                if self.ships.is_none() {
+                    self.ships = Some(self.client.get_my_ships().await?);
+                }
+                if let Some(system) = self.search_ship().map(|s| s.nav.system_symbol.clone()) {
+                    search::cache_waypoints(&self.client, &mut self.cache, &system).await?;
+                }
+            }
+            AppState::Construction => {
+                if self.agent.is_none() {
+                    self.agent = Some(self.client.get_my_agent().await?);
+                }
+                if self.ships.is_none() {
+                    self.ships = Some(self.client.get_my_ships().await?);
+                }
+                let systems = self.construction_systems();
+                construction::cache_constructions(&self.client, &mut self.cache, &systems).await?;
+            }
+            AppState::Agents => {
+                if Rivals::load().agents.is_empty() && self.agents_download.is_none() {
+                    self.sync_agents();
+                }
+                rivals::poll_watched(&self.client).await?;
+                self.rivals = Rivals::load();
+                self.rival_history = rivals::history();
+            }
+            AppState::Factions => {
+                self.factions = Some(self.client.get_factions().await?);
+            }
+            AppState::Contracts => {
+                self.contracts = Some(self.client.get_contracts().await?);
+                let ships = self.client.get_my_ships().await?;
+                self.wear.record(&ships)?;
+                self.ships = Some(ships);
+            }
+            AppState::Fitting => {
+                let Some(waypoint) = self.selected_ship().map(|s| s.nav.waypoint_symbol.clone())
+                else {
+                    return Ok(());
+                };
+                let system = cache::system_symbol(&waypoint);
+                self.agent = Some(self.client.get_my_agent().await?);
+                // Most waypoints have neither, so a failure here isn't an error
+                let shipyard = self.client.get_shipyard(system, &waypoint).await.ok();
+                let market = self.client.get_market(system, &waypoint).await.ok();
+                if let Some(shipyard) = &shipyard {
+                    self.cache.shipyards.insert(waypoint, shipyard.clone());
+                    self.cache.save()?;
+                }
+                if let Some(screen) = &mut self.fitting {
+                    screen.shipyard = shipyard;
+                    screen.market = market;
+                }
+            }
+            AppState::Shipyard => {
+                if let Some((system_symbol, waypoint_symbol)) = self.selected_waypoint_symbols() {
+                    self.agent = Some(self.client.get_my_agent().await?);
+                    self.shipyard = Some(
+                        self.client
+                            .get_shipyard(&system_symbol, &waypoint_symbol)
+                            .await?,
+                    );
+                }
+            }
             _ => {}
         }
         Ok(())
     }
 
-    pub fn next_tab(&mut self) {
-        self.state = match self.state {
-            AppState::Dashboard => AppState::Ships,
-            AppState::Ships => AppState::Systems,
-            AppState::Systems => AppState::Markets,
-            AppState::Markets => AppState::Dashboard,
-            AppState::ShipDetail => AppState::Ships,
-            AppState::SystemDetail => AppState::Systems,
-            AppState::WaypointDetail => AppState::SystemDetail,
-        };
+    /// Refreshes the current view, sending any failure to the event log.
+    pub async fn refresh(&mut self) {
+        if let Err(e) = self.refresh_data().await {
+            events::record_error("Refresh failed", &e);
+        }
     }
 
-    pub fn previous_tab(&mut self) {
-        self.state = match self.state {
-            AppState::Dashboard => AppState::Markets,
-            AppState::Ships => AppState::Dashboard,
-            AppState::Systems => AppState::Ships,
-            AppState::Markets => AppState::Systems,
-            AppState::ShipDetail => AppState::Ships,
-            AppState::SystemDetail => AppState::Systems,
-            AppState::WaypointDetail => AppState::SystemDetail,
+    /// Feeds the latest fleet and contract data to the event monitor.
+    pub fn check_events(&mut self) {
+        self.event_monitor.check(
+            self.ships.as_deref().unwrap_or_default(),
+            self.contracts.as_deref().unwrap_or_default(),
+        );
+    }
+
+    /// Asks the repair advisor about the ship in the detail view.
+    pub async fn assess_selected_ship(&mut self) {
+        let Some(ship) = self
+            .ships
+            .as_ref()
+            .and_then(|ships| ships.get(self.selected_ship_index?))
+        else {
+            return;
         };
+        let assessment = condition::assess_ship(&self.client, ship).await;
+        self.notice = Some(format!(
+            "{}: {} ({})",
+            assessment.ship, assessment.advice, assessment.reason
+        ));
+        self.assessment = Some(assessment);
     }
 
-    pub fn next_item(&mut self) {
-        match self.state {
-            AppState::Ships => {
-                if let Some(ships) = &self.ships {
-                    if ships.is_empty() {
-                        return;
-                    }
+    pub fn open_search(&mut self) {
+        self.search.ship = self.selected_ship_index.unwrap_or(self.search.ship);
+        self.search.results_state.select(Some(0));
+        self.state = AppState::WaypointSearch;
+    }
 
-                    let i = match self.ships_state.selected() {
-                        Some(i) => {
-                            if i >= ships.len() - 1 {
-                                0
-                            } else {
-                                i + 1
-                            }
-                        }
-                        None => 0,
-                    };
-                    self.ships_state.select(Some(i));
-                }
-            }
-            AppState::Systems => {
-                if let Some(systems) = &self.systems {
-                    if systems.is_empty() {
-                        return;
-                    }
+    /// Ship the search measures distances from.
+    pub fn search_ship(&self) -> Option<&spacetraders_api::models::Ship> {
+        let ships = self.ships.as_ref()?;
+        ships.get(self.search.ship % ships.len().max(1))
+    }
 
-                    let i = match self.systems_state.selected() {
-                        Some(i) => {
-                            if i >= systems.len() - 1 {
-                                0
-                            } else {
-                                i + 1
-                            }
-                        }
-                        None => 0,
-                    };
-                    self.systems_state.select(Some(i));
-                }
-            }
-            AppState::SystemDetail => {
-                if let Some(systems) = &self.systems {
-                    if let Some(system_index) = self.selected_system_index {
-                        if system_index < systems.len() {
-                            let system = &systems[system_index];
-                            if system.waypoints.is_empty() {
-                                return;
-                            }
+    pub fn search_results(&self) -> Vec<WaypointMatch> {
+        let ship = self.search_ship();
+        let system = match ship {
+            Some(ship) if !self.search.all_systems => Some(ship.nav.system_symbol.as_str()),
+            _ => None,
+        };
+        search::search(&self.cache, system, &self.search.query, ship)
+    }
 
-                            let i = match self.waypoints_state.selected() {
-                                Some(i) => {
-                                    if i >= system.waypoints.len() - 1 {
-                                        0
-                                    } else {
-                                        i + 1
-                                    }
-                                }
-                                None => 0,
-                            };
-                            self.waypoints_state.select(Some(i));
-                        }
-                    }
-                }
-            }
-            _ => {}
-        }
+    fn cached_waypoints(&self) -> impl Iterator<Item = &spacetraders_api::models::Waypoint> {
+        self.cache.waypoints.values().flatten()
     }
 
-    pub fn previous_item(&mut self) {
-        match self.state {
-            AppState::Ships => {
-                if let Some(ships) = &self.ships {
-                    if ships.is_empty() {
-                        return;
-                    }
+    pub fn cycle_search_type(&mut self) {
+        let mut types: Vec<WaypointType> = self.cached_waypoints().map(|w| w.r#type).collect();
+        types.sort();
+        types.dedup();
+        self.search.query.waypoint_type = next_filter(&types, self.search.query.waypoint_type);
+        self.search.results_state.select(Some(0));
+    }
 
-                    let i = match self.ships_state.selected() {
-                        Some(i) => {
-                            if i == 0 {
-                                ships.len() - 1
-                            } else {
-                                i - 1
-                            }
-                        }
-                        None => 0,
-                    };
-                    self.ships_state.select(Some(i));
-                }
-            }
-            AppState::Systems => {
-                if let Some(systems) = &self.systems {
-                    if systems.is_empty() {
-                        return;
-                    }
+    pub fn cycle_search_trait(&mut self) {
+        let mut traits: Vec<WaypointTraitSymbol> = self
+            .cached_waypoints()
+            .flat_map(|w| w.traits.iter().map(|t| t.symbol))
+            .collect();
+        traits.sort();
+        traits.dedup();
+        let current = self.search.query.traits.first().copied();
+        self.search.query.traits = next_filter(&traits, current).into_iter().collect();
+        self.search.results_state.select(Some(0));
+    }
 
-                    let i = match self.systems_state.selected() {
-                        Some(i) => {
-                            if i == 0 {
-                                systems.len() - 1
-                            } else {
-                                i - 1
-                            }
-                        }
-                        None => 0,
-                    };
-                    self.systems_state.select(Some(i));
-                }
-            }
-            AppState::SystemDetail => {
-                if let Some(systems) = &self.systems {
-                    if let Some(system_index) = self.selected_system_index {
-                        if system_index < systems.len() {
-                            let system = &systems[system_index];
-                            if system.waypoints.is_empty() {
-                                return;
-                            }
+    pub fn cycle_search_modifier(&mut self) {
+        let mut modifiers: Vec<WaypointModifierSymbol> = self
+            .cached_waypoints()
+            .flat_map(|w| w.modifiers.iter().flatten().map(|m| m.symbol))
+            .collect();
+        modifiers.sort();
+        modifiers.dedup();
+        let current = self.search.query.modifiers.first().copied();
+        self.search.query.modifiers = next_filter(&modifiers, current).into_iter().collect();
+        self.search.results_state.select(Some(0));
+    }
 
-                            let i = match self.waypoints_state.selected() {
-                                Some(i) => {
-                                    if i == 0 {
-                                        system.waypoints.len() - 1
-                                    } else {
-                                        i - 1
-                                    }
-                                }
-                                None => 0,
-                            };
-                            self.waypoints_state.select(Some(i));
-                        }
-                    }
-                }
-            }
-            _ => {}
-        }
+    pub fn toggle_search_scope(&mut self) {
+        self.search.all_systems = !self.search.all_systems;
+        self.search.results_state.select(Some(0));
     }
 
-    pub fn toggle_help(&mut self) {
-        self.help_visible = !self.help_visible;
+    pub fn next_search_ship(&mut self) {
+        self.search.ship += 1;
+        self.search.results_state.select(Some(0));
     }
 
-    pub fn view_selected_ship_detail(&mut self) {
-        if let Some(ships) = &self.ships {
-            if !ships.is_empty() {
-                if let Some(i) = self.ships_state.selected() {
-                    self.selected_ship_index = Some(i);
-                    self.state = AppState::ShipDetail;
-                }
-            }
+    fn selected_ship(&self) -> Option<&spacetraders_api::models::Ship> {
+        self.ships.as_ref()?.get(self.selected_ship_index?)
+    }
+
+    pub fn open_fitting(&mut self) {
+        if let Some(ship) = self.selected_ship() {
+            self.fitting = Some(FittingScreen::new(Loadout::of(ship)));
+            self.state = AppState::Fitting;
         }
     }
 
-    pub fn view_selected_system_detail(&mut self) {
-        if let Some(systems) = &self.systems {
-            if !systems.is_empty() {
-                if let Some(i) = self.systems_state.selected() {
-                    self.selected_system_index = Some(i);
-                    self.state = AppState::SystemDetail;
-                }
-            }
+    /// Budget, changes and cost of the planned loadout.
+    pub fn fitting_plan(&self) -> Option<Plan> {
+        let ship = self.selected_ship()?;
+        let screen = self.fitting.as_ref()?;
+        let catalog = fitting::catalog(
+            self.ships.as_deref().unwrap_or_default(),
+            self.cache
+                .shipyards
+                .values()
+                .chain(screen.shipyard.as_ref()),
+        );
+        Some(fitting::plan(
+            ship,
+            screen.planned.clone(),
+            &catalog,
+            screen.market.as_ref(),
+            screen.shipyard.as_ref(),
+        ))
+    }
+
+    pub fn toggle_fitting_focus(&mut self) {
+        if let Some(screen) = &mut self.fitting {
+            screen.focus_available = !screen.focus_available;
         }
     }
 
-    pub fn view_selected_waypoint_detail(&mut self) {
-        if let Some(systems) = &self.systems {
-            if let Some(system_index) = self.selected_system_index {
-                if system_index < systems.len() {
-                    let system = &systems[system_index];
-                    if !system.waypoints.is_empty() {
-                        if let Some(i) = self.waypoints_state.selected() {
-                            if i < system.waypoints.len() {
-                                self.selected_waypoint_index = Some(i);
-                                self.state = AppState::WaypointDetail;
-                            }
-                        }
-                    }
+    /// Adds the selected part for sale to the plan, or drops the selected
+    /// planned part, depending on which list has focus.
+    pub fn toggle_fitting_part(&mut self) {
+        let Some(screen) = &mut self.fitting else {
+            return;
+        };
+        if screen.focus_available {
+            let available = screen.available();
+            if let Some((symbol, _)) = screen
+                .available_state
+                .selected()
+                .and_then(|i| available.get(i))
+            {
+                screen.planned.add(symbol);
+            }
+        } else {
+            let planned: Vec<String> = screen.planned.parts().cloned().collect();
+            if let Some(symbol) = screen.planned_state.selected().and_then(|i| planned.get(i)) {
+                screen.planned.remove(symbol);
+                if screen.planned_state.selected() >= Some(planned.len() - 1) {
+                    screen
+                        .planned_state
+                        .select(Some(planned.len().saturating_sub(2)));
                 }
             }
         }
     }
 
-    pub fn back_from_detail(&mut self) {
-        self.state = match self.state {
-            AppState::ShipDetail => AppState::Ships,
-            AppState::SystemDetail => AppState::Systems,
-            AppState::WaypointDetail => AppState::SystemDetail,
-            _ => self.state,
+    fn move_fitting_selection(&mut self, forward: bool) {
+        let Some(screen) = &mut self.fitting else {
+            return;
+        };
+        let (len, state) = if screen.focus_available {
+            (screen.available().len(), &mut screen.available_state)
+        } else {
+            (screen.planned.parts().count(), &mut screen.planned_state)
         };
+        if len == 0 {
+            return;
+        }
+        let i = match state.selected() {
+            Some(i) if forward => (i + 1) % len,
+            Some(i) => (i + len - 1) % len,
+            None => 0,
+        };
+        state.select(Some(i));
     }
-}
 
-pub async fn run_app(app: &mut App) -> Result<()> {
-    // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    pub fn request_fitting(&mut self) {
+        let Some(plan) = self.fitting_plan() else {
+            return;
+        };
+        if let Some(problem) = plan.problems.first() {
+            self.notice = Some(format!("Can't refit: {}", problem));
+        } else if plan.changes.is_empty() {
+            self.notice = Some(String::from("The planned loadout matches the current one"));
+        } else if let Some(screen) = &mut self.fitting {
+            screen.confirm_pending = true;
+        }
+    }
 
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    pub fn cancel_fitting(&mut self) {
+        if let Some(screen) = &mut self.fitting {
+            screen.confirm_pending = false;
+        }
+    }
 
-    // Refresh initial data
-    app.refresh_data().await?;
+    pub async fn confirm_fitting(&mut self) {
+        self.cancel_fitting();
+        let (Some(ship), Some(plan)) = (self.selected_ship().cloned(), self.fitting_plan()) else {
+            return;
+        };
+        match fitting::apply(&self.client, &ship, &plan.changes).await {
+            Ok(()) => {
+                self.notice = Some(format!(
+                    "Refitted {} with {} changes",
+                    ship.symbol,
+                    plan.changes.len()
+                ))
+            }
+            Err(e) => {
+                self.notice = Some(format!("Refit failed: {}", e));
+                events::record_error("Refit failed", &e);
+            }
+        }
+        // Partial refits leave the ship somewhere between the two loadouts
+        match self.client.get_my_ships().await {
+            Ok(ships) => {
+                self.ships = Some(ships);
+                self.open_fitting();
+                self.refresh().await;
+            }
+            Err(e) => events::record_error("Refresh failed", &e),
+        }
+    }
 
-    // Main loop
-    while !app.should_quit {
-        terminal.draw(|f| ui(f, app))?;
+    pub fn toggle_pnl_period(&mut self) {
+        self.pnl_period = match self.pnl_period {
+            Period::Hour => Period::Day,
+            Period::Day => Period::Hour,
+        };
+    }
 
-        if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press {
-                    match key.code {
-                        KeyCode::Char('q') => app.should_quit = true,
-                        KeyCode::Char('r') => {
-                            let _ = app.refresh_data().await;
-                        }
-                        KeyCode::Char('h') => app.toggle_help(),
-                        KeyCode::Tab => {
-                            app.next_tab();
-                            let _ = app.refresh_data().await;
-                        }
-                        KeyCode::BackTab => {
-                            app.previous_tab();
-                            let _ = app.refresh_data().await;
-                        }
-                        KeyCode::Enter => match app.state {
-                            AppState::Ships => app.view_selected_ship_detail(),
-                            AppState::Systems => app.view_selected_system_detail(),
-                            AppState::SystemDetail => app.view_selected_waypoint_detail(),
-                            AppState::ShipDetail => app.back_from_detail(),
-                            AppState::WaypointDetail => app.back_from_detail(),
-                            AppState::Dashboard => {}
-                            AppState::Markets => {}
-                        },
-                        KeyCode::Esc => match app.state {
-                            AppState::ShipDetail => app.back_from_detail(),
-                            AppState::SystemDetail => app.back_from_detail(),
-                            AppState::WaypointDetail => app.back_from_detail(),
-                            _ => {}
-                        },
-                        KeyCode::Down | KeyCode::Char('j') => app.next_item(),
-                        KeyCode::Up | KeyCode::Char('k') => app.previous_item(),
-                        KeyCode::Char('1') => {
-                            app.state = AppState::Dashboard;
-                            let _ = app.refresh_data().await;
-                        }
-                        KeyCode::Char('2') => {
-                            app.state = AppState::Ships;
-                            let _ = app.refresh_data().await;
-                        }
-                        KeyCode::Char('3') => {
-                            app.state = AppState::Systems;
-                            let _ = app.refresh_data().await;
-                        }
-                        KeyCode::Char('4') => {
-                            app.state = AppState::Markets;
-                            let _ = app.refresh_data().await;
-                        }
-                        _ => {}
-                    }
-                }
+    pub fn toggle_log(&mut self) {
+        self.log_visible = !self.log_visible;
+    }
+
+    /// Cycles the event log pane through severity thresholds.
+    pub fn cycle_log_level(&mut self) {
+        self.log_level = match self.log_level {
+            Level::Error => Level::Warn,
+            Level::Warn => Level::Info,
+            Level::Info => Level::Debug,
+            Level::Debug => Level::Trace,
+            Level::Trace => Level::Error,
+        };
+    }
+
+    /// Fetches connections for jump gates missing from the cache. Uncharted
+    /// gates return an error and are skipped.
+    async fn cache_jump_gates(&mut self, gates: Vec<(String, String)>) -> Result<()> {
+        let mut updated = false;
+        for (system_symbol, waypoint_symbol) in gates {
+            if self.cache.jump_gates.contains_key(&waypoint_symbol) {
+                continue;
+            }
+            if let Ok(gate) = self
+                .client
+                .get_jump_gate(&system_symbol, &waypoint_symbol)
+                .await
+            {
+                self.cache.jump_gates.insert(gate.symbol, gate.connections);
+                updated = true;
             }
         }
+        if updated {
+            self.cache.save()?;
+        }
+        Ok(())
     }
 
-    // Restore terminal
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    /// Starts downloading every system in the background. `check_downloads`
+    /// stores them once the last page is in.
+    pub fn sync_galaxy(&mut self) {
+        if let Some(download) = &self.galaxy_download {
+            self.notice = Some(format!(
+                "Galaxy download already running, {}",
+                download.describe()
+            ));
+            return;
+        }
+        self.galaxy_download = Some(Download::start(
+            &self.client,
+            |client, progress| async move { client.get_all_systems(&progress).await },
+        ));
+        self.notice = Some(String::from("Downloading the galaxy in the background"));
+    }
 
-    Ok(())
-}
+    /// Stores downloaded systems in the universe cache, along with the jump
+    /// gate connections of the systems our ships are in.
+    async fn store_galaxy(&mut self, systems: Vec<spacetraders_api::models::System>) -> Result<()> {
+        self.cache.systems = systems;
+        self.cache.systems_synced = true;
+        self.cache.save()?;
+
+        let gates: Vec<(String, String)> = self
+            .ship_systems()
+            .iter()
+            .filter_map(|symbol| self.cache.system(symbol))
+            .flat_map(|system| {
+                system
+                    .waypoints
+                    .iter()
+                    .filter(|w| w.r#type == WaypointType::JumpGate)
+                    .map(|w| (system.symbol.clone(), w.symbol.clone()))
+            })
+            .collect();
+        self.cache_jump_gates(gates).await?;
+
+        self.notice = Some(format!(
+            "Galaxy synced: {} systems",
+            self.cache.systems.len()
+        ));
+        Ok(())
+    }
 
-fn ui(f: &mut Frame, app: &mut App) {
-    // Create layout
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3),                                    // Tabs
-            Constraint::Min(0),                                       // Content
-            Constraint::Length(if app.help_visible { 9 } else { 3 }), // Status bar
-        ])
-        .split(f.size());
+    /// Picks up background downloads that have finished.
+    pub async fn check_downloads(&mut self) {
+        if let Some(download) = self.galaxy_download.take_if(|d| d.is_finished()) {
+            let stored = match download.result().await {
+                Ok(systems) => self.store_galaxy(systems).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = stored {
+                self.notice = Some(format!("Galaxy sync failed: {}", e));
+                events::record_error("Galaxy sync failed", &e);
+            }
+        }
+        if let Some(download) = self.agents_download.take_if(|d| d.is_finished()) {
+            if let Err(e) = download
+                .result()
+                .await
+                .and_then(|agents| self.store_agents(agents))
+            {
+                self.notice = Some(format!("Agent sync failed: {}", e));
+                events::record_error("Agent sync failed", &e);
+            }
+        }
+    }
 
-    // Tabs
-    let titles = vec!["Dashboard", "Ships", "Systems", "Markets"];
-    let titles = titles
-        .iter()
-        .map(|t| {
-            let (first, rest) = t.split_at(1);
-            Line::from(vec![
-                Span::styled(first, Style::default().fg(Color::Yellow)),
-                Span::styled(rest, Style::default()),
-            ])
-        })
-        .collect();
+    /// Systems containing at least one of our ships, in fleet order.
+    pub fn ship_systems(&self) -> Vec<String> {
+        let mut systems: Vec<String> = Vec::new();
+        for ship in self.ships.iter().flatten() {
+            if !systems.contains(&ship.nav.system_symbol) {
+                systems.push(ship.nav.system_symbol.clone());
+            }
+        }
+        systems
+    }
 
-    let tabs = Tabs::new(titles)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Space Traders"),
+    /// Systems the construction tracker watches.
+    fn construction_systems(&self) -> Vec<String> {
+        construction::our_systems(
+            self.agent.as_ref(),
+            self.ships.as_deref().unwrap_or_default(),
         )
-        .select(match app.state {
-            AppState::Dashboard => 0,
-            AppState::Ships | AppState::ShipDetail => 1,
-            AppState::Systems | AppState::SystemDetail | AppState::WaypointDetail => 2,
-            AppState::Markets => 3,
-        })
-        .style(Style::default().fg(Color::White))
-        .highlight_style(Style::default().fg(Color::Yellow));
-
-    f.render_widget(tabs, chunks[0]);
+    }
 
-    // Content
-    match app.state {
-        AppState::Dashboard => render_dashboard(f, app, chunks[1]),
-        AppState::Ships => render_ships(f, app, chunks[1]),
-        AppState::Systems => render_systems(f, app, chunks[1]),
-        AppState::Markets => render_markets(f, app, chunks[1]),
-        AppState::ShipDetail => render_ship_detail(f, app, chunks[1]),
-        AppState::SystemDetail => render_system_detail(f, app, chunks[1]),
-        AppState::WaypointDetail => render_waypoint_detail(f, app, chunks[1]),
+    pub fn construction_reports(&self) -> Vec<SiteReport> {
+        construction::reports(&self.cache, &self.construction_systems())
     }
 
-    // Status bar / Help screen with updated instructions
-    if app.help_visible {
-        let help_text = vec![
-            Line::from(Span::styled(
-                "Keyboard Navigation",
-                Style::default()
-                    .fg(Color::Yellow)
-                    .add_modifier(Modifier::BOLD),
-            )),
-            Line::from(""),
-            Line::from(vec![
-                Span::styled("q", Style::default().fg(Color::Cyan)),
-                Span::raw(": Quit  "),
-                Span::styled("r", Style::default().fg(Color::Cyan)),
-                Span::raw(": Refresh  "),
-                Span::styled("h", Style::default().fg(Color::Cyan)),
-                Span::raw(": Toggle Help  "),
-            ]),
-            Line::from(vec![
-                Span::styled("Tab", Style::default().fg(Color::Cyan)),
-                Span::raw(": Next tab  "),
-                Span::styled("Shift+Tab", Style::default().fg(Color::Cyan)),
-                Span::raw(": Previous tab  "),
-            ]),
-            Line::from(vec![
-                Span::styled("Enter", Style::default().fg(Color::Cyan)),
-                Span::raw(": View details  "),
-                Span::styled("Esc", Style::default().fg(Color::Cyan)),
-                Span::raw(": Back from details  "),
-            ]),
-            Line::from(vec![
-                Span::styled("1-4", Style::default().fg(Color::Cyan)),
-                Span::raw(": Switch tabs directly (1=Dashboard, 2=Ships, etc.)"),
-            ]),
-        ];
+    /// Starts downloading the whole agent directory in the background.
+    /// `check_downloads` stores it once the last page is in.
+    pub fn sync_agents(&mut self) {
+        if let Some(download) = &self.agents_download {
+            self.notice = Some(format!(
+                "Agent download already running, {}",
+                download.describe()
+            ));
+            return;
+        }
+        self.agents_download = Some(Download::start(
+            &self.client,
+            |client, progress| async move { client.get_all_agents(&progress).await },
+        ));
+        self.notice = Some(String::from(
+            "Downloading the agent directory in the background",
+        ));
+    }
 
-        let help = Paragraph::new(help_text)
-            .block(Block::default().borders(Borders::ALL).title("Help"))
-            .alignment(Alignment::Left)
-            .wrap(Wrap { trim: true });
+    /// Replaces the agent directory with a finished download.
+    fn store_agents(&mut self, agents: Vec<spacetraders_api::models::Agent>) -> Result<()> {
+        let mut rivals = Rivals::load();
+        rivals.agents = agents;
+        rivals.save()?;
+        self.notice = Some(format!(
+            "Agent directory synced: {} agents",
+            rivals.agents.len()
+        ));
+        self.rivals = rivals;
+        Ok(())
+    }
 
-        f.render_widget(help, chunks[2]);
-    } else {
-        let status_text = "Press h for help | q to quit | r to refresh | Enter to view details";
-        let status = Paragraph::new(status_text)
-            .block(Block::default().borders(Borders::ALL))
-            .wrap(Wrap { trim: true });
+    pub fn open_agents(&mut self) {
+        self.agents_state.select(Some(0));
+        self.state = AppState::Agents;
+    }
 
-        f.render_widget(status, chunks[2]);
+    pub fn open_factions(&mut self) {
+        self.factions_state.select(Some(0));
+        self.state = AppState::Factions;
     }
-}
 
-fn render_dashboard(f: &mut Frame, app: &mut App, area: Rect) {
-    let chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-        .split(area);
+    pub fn sorted_agents(&self) -> Vec<&spacetraders_api::models::Agent> {
+        rivals::sorted(&self.rivals.agents, self.agent_sort)
+    }
 
-    // Agent info
-    let agent_block = Block::default().borders(Borders::ALL).title("Agent Info");
+    pub fn cycle_agent_sort(&mut self) {
+        self.agent_sort = self.agent_sort.next();
+        self.agents_state.select(Some(0));
+    }
 
-    if let Some(agent) = &app.agent {
-        // Create styled lines for the agent info
-        let lines = vec![
-            Line::from(vec![
-                Span::raw("Agent: "),
-                Span::styled(
-                    &agent.symbol,
-                    Style::default()
-                        .fg(Color::Green)
-                        .add_modifier(Modifier::BOLD),
-                ),
-            ]),
-            Line::from(vec![
-                Span::raw("Credits: "),
-                Span::styled(
-                    agent.credits.to_string(),
-                    Style::default().fg(Color::Yellow),
-                ),
-            ]),
-            Line::from(vec![
-                Span::raw("HQ: "),
-                Span::styled(&agent.headquarters, Style::default().fg(Color::Cyan)),
-            ]),
-            Line::from(vec![
-                Span::raw("Faction: "),
-                Span::styled(&agent.starting_faction, Style::default().fg(Color::Magenta)),
-            ]),
-            Line::from(vec![
-                Span::raw("Ships: "),
-                Span::styled(
-                    agent.ship_count.to_string(),
-                    Style::default().fg(Color::Blue),
-                ),
-            ]),
-        ];
+    /// Adds the selected agent to the watch list, or takes it off.
+    pub fn toggle_watch_selected_agent(&mut self) {
+        let Some(agent) = self.agents_state.selected().and_then(|i| {
+            self.sorted_agents()
+                .get(i)
+                .map(|agent| agent.symbol.clone())
+        }) else {
+            return;
+        };
+        let watched = !self.rivals.is_watched(&agent);
+        let mut rivals = Rivals::load();
+        match rivals.set_watched(&agent, watched) {
+            Ok(()) => {
+                self.rivals.watched = rivals.watched;
+                self.notice = Some(if watched {
+                    format!("Watching {}", agent)
+                } else {
+                    format!("Stopped watching {}", agent)
+                });
+            }
+            Err(e) => events::record_error("Saving watch list failed", &e),
+        }
+    }
 
-        let agent_info = Paragraph::new(lines)
-            .block(agent_block)
-            .wrap(Wrap { trim: true });
-
-        f.render_widget(agent_info, chunks[0]);
-    } else {
-        let loading = Paragraph::new("Loading agent data...")
-            .block(agent_block)
-            .wrap(Wrap { trim: true });
-        f.render_widget(loading, chunks[0]);
+    /// Opens the galaxy map filtered to the selected faction and centred on
+    /// its headquarters.
+    pub fn view_faction_on_galaxy(&mut self) {
+        let Some(faction) = self
+            .factions
+            .as_ref()
+            .and_then(|factions| factions.get(self.factions_state.selected()?))
+        else {
+            return;
+        };
+        self.galaxy.faction_filter = Some(faction.symbol);
+        let headquarters = faction
+            .headquarters
+            .as_deref()
+            .and_then(|hq| self.cache.system(cache::system_symbol(hq)));
+        if let Some(system) = headquarters {
+            self.galaxy.center = Some((system.x as f64, system.y as f64));
+            self.galaxy.zoom = self.galaxy.zoom.max(32.0);
+        }
+        self.open_galaxy();
     }
 
-    // Status info
-    let status_block = Block::default().borders(Borders::ALL).title("Game Status");
-
-    // Parse the status message to apply colors
-    let status_lines = app
-        .status_message
-        .split('\n')
-        .map(|line| {
-            if line.starts_with("Status:") {
-                Line::from(vec![
-                    Span::raw("Status: "),
-                    Span::styled(
-                        line.trim_start_matches("Status: "),
-                        Style::default().fg(Color::Green),
-                    ),
-                ])
-            } else if line.starts_with("Version:") {
-                Line::from(vec![
-                    Span::raw("Version: "),
-                    Span::styled(
-                        line.trim_start_matches("Version: "),
-                        Style::default().fg(Color::Blue),
-                    ),
-                ])
-            } else if line.starts_with("Reset Date:") {
-                Line::from(vec![
-                    Span::raw("Reset Date: "),
-                    Span::styled(
-                        line.trim_start_matches("Reset Date: "),
-                        Style::default().fg(Color::Yellow),
-                    ),
-                ])
-            } else {
-                Line::from(line)
+    /// Runs a sensor scan from the ship in the detail view. A ship scan
+    /// opens the list of nearby ships.
+    pub async fn scan_selected_ship(&mut self, kind: ScanKind) {
+        let Some(ship) = self.selected_ship().cloned() else {
+            return;
+        };
+        match scan::scan(&self.client, &ship, kind, &mut self.cache).await {
+            Ok(result) => {
+                self.notice = Some(format!(
+                    "{} {} scan: {} (cooldown {}s)",
+                    ship.symbol, kind, result.summary, result.cooldown.remaining_seconds
+                ));
+                if let Some(scanned) = self
+                    .ships
+                    .as_mut()
+                    .and_then(|ships| ships.iter_mut().find(|s| s.symbol == ship.symbol))
+                {
+                    *scanned.cooldown = result.cooldown;
+                }
+                if kind == ScanKind::Ships {
+                    self.sightings = Sightings::load();
+                    self.sightings_state.select(Some(0));
+                    self.state = AppState::NearbyShips;
+                }
             }
-        })
-        .collect::<Vec<Line>>();
-
-    let status = Paragraph::new(status_lines)
-        .block(status_block)
-        .wrap(Wrap { trim: true });
-    f.render_widget(status, chunks[1]);
-}
-
-fn get_ship_role_color(ship_role: &str) -> Color {
-    match ship_role {
-        "COMMAND" => Color::Yellow,
-        "EXCAVATOR" => Color::LightRed,
-        "HAULER" => Color::Green,
-        "INTERCEPTOR" => Color::Red,
-        "EXPLORER" => Color::Blue,
-        "TRANSPORT" => Color::Cyan,
-        "CARRIER" => Color::Magenta,
-        "PATROL" => Color::LightBlue,
-        "SATELLITE" => Color::LightCyan,
-        "SURVEYOR" => Color::LightGreen,
-        _ => Color::Gray,
+            Err(e) => {
+                self.notice = Some(format!("Scan failed: {}", e));
+                events::record_error(&format!("{} {} scan", ship.symbol, kind), &e);
+            }
+        }
     }
-}
-
-fn render_ships(f: &mut Frame, app: &mut App, area: Rect) {
-    let ships_block = Block::default().borders(Borders::ALL).title("Ships");
-
-    if let Some(ships) = &app.ships {
-        if ships.is_empty() {
-            let no_ships = Paragraph::new("No ships found")
-                .block(ships_block)
-                .wrap(Wrap { trim: true });
-            f.render_widget(no_ships, area);
-        } else {
-            let items: Vec<ListItem> = ships
-                .iter()
-                .map(|ship| {
-                    let ship_role = ship.registration.role.to_string();
-                    let role_color = get_ship_role_color(&ship_role);
-
-                    // Create a simple location string - nav might be a complex type
-                    let location = "Current Location";
-
-                    // Create styled lines for the ship info
-                    let lines = vec![
-                        Line::from(vec![
-                            Span::raw("Ship: "),
-                            Span::styled(&ship.symbol, Style::default().fg(Color::Blue)),
-                            Span::raw(" - "),
-                            Span::styled(ship_role, Style::default().fg(role_color)),
-                        ]),
-                        Line::from(vec![
-                            Span::raw("Location: "),
-                            Span::styled(location, Style::default().fg(Color::Yellow)),
-                        ]),
-                        Line::from(""),
-                    ];
-
-                    ListItem::new(lines)
-                })
-                .collect();
-
-            let list = List::new(items)
-                .block(ships_block)
-                .highlight_style(Style::default().bg(Color::DarkGray))
-                .highlight_symbol(">> ");
 
-            f.render_stateful_widget(list, area, &mut app.ships_state);
+    /// Sightings in the system of the ship in the detail view.
+    pub fn nearby_ships(&self) -> Vec<&Sighting> {
+        match self.selected_ship() {
+            Some(ship) => self.sightings.in_system(&ship.nav.system_symbol),
+            None => Vec::new(),
         }
-    } else {
-        let loading = Paragraph::new("Loading ships data...")
-            .block(ships_block)
-            .wrap(Wrap { trim: true });
-        f.render_widget(loading, area);
     }
-}
 
-fn get_system_type_color(system_type: &str) -> Color {
-    match system_type {
-        "NEUTRON_STAR" => Color::Cyan,
-        "RED_STAR" => Color::Red,
-        "ORANGE_STAR" => Color::LightRed,
-        "BLUE_STAR" => Color::Blue,
-        "YOUNG_STAR" => Color::Yellow,
-        "WHITE_DWARF" => Color::White,
-        "BLACK_HOLE" => Color::DarkGray,
-        "HYPERGIANT" => Color::Magenta,
-        "NEBULA" => Color::LightMagenta,
-        "UNSTABLE" => Color::LightYellow,
-        _ => Color::Gray,
+    pub fn open_contracts(&mut self) {
+        self.contracts_state.select(Some(0));
+        self.state = AppState::Contracts;
     }
-}
-
-fn render_systems(f: &mut Frame, app: &mut App, area: Rect) {
-    // Split the area into two chunks: top for systems list, bottom for systems plot
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-        .split(area);
-
-    let systems_list_area = chunks[0];
-    let systems_plot_area = chunks[1];
 
-    // Render systems list
-    let systems_block = Block::default().borders(Borders::ALL).title("Systems");
-
-    match app.systems.as_ref() {
-        None => {
-            let loading_text = Text::from("Loading systems...");
-            let loading_paragraph = Paragraph::new(loading_text)
-                .block(systems_block)
-                .wrap(Wrap { trim: true });
-            f.render_widget(loading_paragraph, systems_list_area);
+    /// Accepts the selected contract if it is still open.
+    pub async fn accept_selected_contract(&mut self) {
+        let Some(selected) = self
+            .contracts_state
+            .selected()
+            .and_then(|i| self.contracts.as_ref()?.get(i))
+            .cloned()
+        else {
+            return;
+        };
+        if !contract::is_open(&selected, Utc::now()) {
+            self.notice = Some(format!("{} can no longer be accepted", selected.id));
+            return;
         }
-        Some(systems) if systems.is_empty() => {
-            let empty_text = Text::from("No systems found.");
-            let empty_paragraph = Paragraph::new(empty_text)
-                .block(systems_block)
-                .wrap(Wrap { trim: true });
-            f.render_widget(empty_paragraph, systems_list_area);
+        match self.client.accept_contract(&selected.id).await {
+            Ok(data) => {
+                self.notice = Some(format!(
+                    "Accepted {}, received {} credits",
+                    selected.id, data.contract.terms.payment.on_accepted
+                ));
+                self.refresh().await;
+            }
+            Err(e) => {
+                self.notice = Some(format!("Accepting {} failed: {}", selected.id, e));
+                events::record_error("Accepting contract failed", &e);
+            }
         }
-        Some(systems) => {
-            let systems_items: Vec<ListItem> = systems
-                .iter()
-                .map(|s| {
-                    let system_type = s.r#type.to_string();
-                    let color = get_system_type_color(&system_type);
+    }
 
-                    ListItem::new(Line::from(vec![
-                        Span::styled(
-                            format!("[{:^12}] ", system_type),
-                            Style::default().fg(color),
-                        ),
-                        Span::raw(format!("{} ", s.symbol)),
-                        Span::styled(
-                            format!("(x:{}, y:{})", s.x, s.y),
-                            Style::default().fg(Color::DarkGray),
-                        ),
-                    ]))
-                })
-                .collect();
+    pub fn open_construction(&mut self) {
+        self.construction_state.select(Some(0));
+        self.state = AppState::Construction;
+    }
 
-            let systems_list = List::new(systems_items)
-                .block(systems_block)
-                .highlight_style(
-                    Style::default()
-                        .bg(Color::DarkGray)
-                        .add_modifier(Modifier::BOLD),
-                )
-                .highlight_symbol("> ");
+    pub fn open_galaxy(&mut self) {
+        self.state = AppState::Galaxy;
+        if !self.cache.systems_synced && self.galaxy_download.is_none() {
+            self.notice = Some(String::from(
+                "Galaxy not downloaded yet, press u to download it",
+            ));
+        }
+    }
 
-            f.render_stateful_widget(systems_list, systems_list_area, &mut app.systems_state);
+    /// Cached systems that pass the galaxy map's type and faction filters.
+    pub fn galaxy_systems(&self) -> Vec<&spacetraders_api::models::System> {
+        self.cache
+            .systems
+            .iter()
+            .filter(|system| {
+                self.galaxy
+                    .type_filter
+                    .is_none_or(|system_type| system.r#type == system_type)
+            })
+            .filter(|system| {
+                self.galaxy
+                    .faction_filter
+                    .is_none_or(|faction| system.factions.iter().any(|f| f.symbol == faction))
+            })
+            .collect()
+    }
 
-            // Render systems plot
-            render_system_plot(f, app, systems_plot_area, systems);
+    /// Full extent of the cached galaxy as (min_x, max_x, min_y, max_y).
+    fn galaxy_bounds(&self) -> (f64, f64, f64, f64) {
+        if self.cache.systems.is_empty() {
+            return (-1.0, 1.0, -1.0, 1.0);
         }
+        self.cache.systems.iter().fold(
+            (f64::MAX, f64::MIN, f64::MAX, f64::MIN),
+            |(min_x, max_x, min_y, max_y), system| {
+                (
+                    min_x.min(system.x as f64),
+                    max_x.max(system.x as f64),
+                    min_y.min(system.y as f64),
+                    max_y.max(system.y as f64),
+                )
+            },
+        )
     }
-}
 
-fn render_system_plot(
-    f: &mut Frame,
-    app: &App,
-    area: Rect,
-    systems: &[spacetraders_api::models::System],
-) {
-    if systems.is_empty() {
-        return;
+    /// Returns the centre of the view and the half-width visible at the current zoom.
+    pub fn galaxy_viewport(&self) -> ((f64, f64), f64) {
+        let (min_x, max_x, min_y, max_y) = self.galaxy_bounds();
+        let center = self
+            .galaxy
+            .center
+            .unwrap_or(((min_x + max_x) / 2.0, (min_y + max_y) / 2.0));
+        let half_width = ((max_x - min_x).max(max_y - min_y) / 2.0).max(1.0) / self.galaxy.zoom;
+        (center, half_width)
     }
 
-    // Find the min and max coordinates to set the boundaries
-    let (min_x, max_x, min_y, max_y) = systems.iter().fold(
-        (i32::MAX, i32::MIN, i32::MAX, i32::MIN),
-        |(min_x, max_x, min_y, max_y), system| {
-            (
-                min_x.min(system.x),
-                max_x.max(system.x),
-                min_y.min(system.y),
-                max_y.max(system.y),
-            )
-        },
-    );
+    /// Moves the view by a fraction of the visible width.
+    pub fn pan_galaxy(&mut self, dx: f64, dy: f64) {
+        let ((x, y), half_width) = self.galaxy_viewport();
+        let step = half_width * 0.2;
+        self.galaxy.center = Some((x + dx * step, y + dy * step));
+    }
 
-    // Create the plot block
-    let block = Block::default().borders(Borders::ALL).title("System Map");
+    pub fn zoom_galaxy(&mut self, factor: f64) {
+        self.galaxy.zoom = (self.galaxy.zoom * factor).clamp(1.0, 512.0);
+    }
 
-    let inner_area = block.inner(area);
-    f.render_widget(block, area);
+    pub fn cycle_galaxy_type_filter(&mut self) {
+        let mut types: Vec<SystemType> = self.cache.systems.iter().map(|s| s.r#type).collect();
+        types.sort();
+        types.dedup();
+        self.galaxy.type_filter = next_filter(&types, self.galaxy.type_filter);
+    }
 
-    let plot_width = inner_area.width as usize;
-    let plot_height = inner_area.height as usize;
+    pub fn cycle_galaxy_faction_filter(&mut self) {
+        let mut factions: Vec<FactionSymbol> = self
+            .cache
+            .systems
+            .iter()
+            .flat_map(|s| s.factions.iter().map(|f| f.symbol))
+            .collect();
+        factions.sort();
+        factions.dedup();
+        self.galaxy.faction_filter = next_filter(&factions, self.galaxy.faction_filter);
+    }
 
-    // Add buffer space for plotting points
-    let x_range = (max_x - min_x + 1) as f64;
-    let y_range = (max_y - min_y + 1) as f64;
+    /// Centres the view on the next system that holds one of our ships.
+    pub fn focus_next_ship_system(&mut self) {
+        let systems = self.ship_systems();
+        if systems.is_empty() {
+            self.notice = Some(String::from("No ship locations known"));
+            return;
+        }
+        let symbol = &systems[self.galaxy.ship_focus % systems.len()];
+        self.galaxy.ship_focus = (self.galaxy.ship_focus + 1) % systems.len();
+        if let Some(system) = self.cache.system(symbol) {
+            self.galaxy.center = Some((system.x as f64, system.y as f64));
+            self.galaxy.zoom = self.galaxy.zoom.max(32.0);
+        }
+    }
 
-    // Create a canvas to plot points on
-    let mut canvas = vec![vec![(' ', Color::Reset); plot_width]; plot_height];
+    /// The filtered system closest to the centre of the galaxy view.
+    pub fn galaxy_cursor_system(&self) -> Option<&spacetraders_api::models::System> {
+        let ((cx, cy), _) = self.galaxy_viewport();
+        self.galaxy_systems().into_iter().min_by(|a, b| {
+            let da = (a.x as f64 - cx).powi(2) + (a.y as f64 - cy).powi(2);
+            let db = (b.x as f64 - cx).powi(2) + (b.y as f64 - cy).powi(2);
+            da.total_cmp(&db)
+        })
+    }
 
-    // Plot systems
-    for system in systems {
-        // Map system coordinates to plot coordinates
-        let plot_x = ((system.x - min_x) as f64 / x_range * (plot_width as f64 - 1.0)) as usize;
-        let plot_y = ((max_y - system.y) as f64 / y_range * (plot_height as f64 - 1.0)) as usize;
+    /// Opens SystemDetail for the system under the galaxy cursor. Systems that
+    /// are not on the current Systems page are appended to it.
+    pub fn view_galaxy_system_detail(&mut self) {
+        if let Some(system) = self.galaxy_cursor_system() {
+            let symbol = system.symbol.clone();
+            self.view_system(&symbol);
+        }
+    }
 
-        if plot_x < plot_width && plot_y < plot_height {
-            let system_type = system.r#type.to_string();
-            let color = get_system_type_color(&system_type);
-            canvas[plot_y][plot_x] = ('●', color);
+    /// Opens SystemDetail for a system by symbol. Systems that are not on the
+    /// current Systems page are taken from the cache and appended to it.
+    pub fn view_system(&mut self, symbol: &str) -> bool {
+        let systems = self.systems.get_or_insert_with(Vec::new);
+        let index = match systems.iter().position(|s| s.symbol == symbol) {
+            Some(index) => index,
+            None => match self.cache.system(symbol) {
+                Some(system) => {
+                    systems.push(system.clone());
+                    systems.len() - 1
+                }
+                None => return false,
+            },
+        };
+        self.systems_state.select(Some(index));
+        self.selected_system_index = Some(index);
+        self.waypoints_state.select(Some(0));
+        self.state = AppState::SystemDetail;
+        true
+    }
+
+    /// Opens WaypointDetail for a waypoint by symbol.
+    pub fn view_waypoint(&mut self, symbol: &str) -> bool {
+        if !self.view_system(cache::system_symbol(symbol)) {
+            return false;
         }
+        let index = self
+            .systems
+            .as_ref()
+            .and_then(|systems| systems.get(self.selected_system_index?))
+            .and_then(|system| system.waypoints.iter().position(|w| w.symbol == symbol));
+        let Some(index) = index else {
+            return false;
+        };
+        self.waypoints_state.select(Some(index));
+        self.selected_waypoint_index = Some(index);
+        self.waypoint = None;
+        self.state = AppState::WaypointDetail;
+        true
     }
 
-    // Highlight selected system
-    if let Some(selected_idx) = app.systems_state.selected() {
-        if selected_idx < systems.len() {
-            let system = &systems[selected_idx];
-            let plot_x = ((system.x - min_x) as f64 / x_range * (plot_width as f64 - 1.0)) as usize;
-            let plot_y =
-                ((max_y - system.y) as f64 / y_range * (plot_height as f64 - 1.0)) as usize;
+    pub fn view_ship(&mut self, symbol: &str) -> bool {
+        let index = self
+            .ships
+            .as_ref()
+            .and_then(|ships| ships.iter().position(|ship| ship.symbol == symbol));
+        let Some(index) = index else {
+            return false;
+        };
+        let position = self.visible_ships().iter().position(|i| *i == index);
+        self.ships_state.select(position.or(Some(0)));
+        self.selected_ship_index = Some(index);
+        self.state = AppState::ShipDetail;
+        true
+    }
 
-            if plot_x < plot_width && plot_y < plot_height {
-                canvas[plot_y][plot_x] = ('★', Color::White);
+    pub async fn open_palette(&mut self) {
+        // Ship symbols are searchable, so make sure the fleet is loaded
+        if self.ships.is_none() {
+            match self.client.get_my_ships().await {
+                Ok(ships) => self.ships = Some(ships),
+                Err(e) => events::record_error("Loading ships failed", &e),
             }
         }
+        self.palette = Some(Palette::default());
     }
 
-    // Convert canvas to text
-    let mut lines = Vec::new();
-    for row in canvas {
-        let mut spans = Vec::new();
-        for (ch, color) in row {
-            spans.push(Span::styled(ch.to_string(), Style::default().fg(color)));
+    /// Passes a key event to the open palette.
+    pub async fn handle_palette_event(&mut self, event: &Event) {
+        let Event::Key(key) = event else {
+            return;
+        };
+        let Some(palette) = &mut self.palette else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.palette = None,
+            KeyCode::Down => palette.next(),
+            KeyCode::Up => palette.previous(),
+            KeyCode::Enter => self.activate_palette_item().await,
+            _ => {
+                if palette.input.handle_event(event).is_some() {
+                    palette.update(self.ships.as_deref().unwrap_or_default(), &self.cache);
+                }
+            }
         }
-        lines.push(Line::from(spans));
     }
 
-    let text = Text::from(lines);
-    let paragraph = Paragraph::new(text);
-    f.render_widget(paragraph, inner_area);
-}
-
-fn render_markets(f: &mut Frame, _app: &mut App, area: Rect) {
-    let markets_block = Block::default().borders(Borders::ALL).title("Markets");
+    async fn activate_palette_item(&mut self) {
+        let Some(palette) = &mut self.palette else {
+            return;
+        };
+        let Some(item) = palette.selected_item() else {
+            return;
+        };
 
-    // Create a more visually appealing "coming soon" message
-    let lines = vec![
-        Line::from(""),
-        Line::from(vec![
-            Span::styled("✨ ", Style::default().fg(Color::Yellow)),
-            Span::styled(
-                "COMING SOON",
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            ),
-            Span::styled(" ✨", Style::default().fg(Color::Yellow)),
-        ]),
-        Line::from(""),
-        Line::from(vec![Span::raw(
-            "The Markets feature is under development and will be available in a future update.",
-        )]),
-        Line::from(""),
-        Line::from(vec![Span::styled(
-            "Stay tuned for trading capabilities!",
-            Style::default().fg(Color::Green),
-        )]),
-    ];
+        match item.target.clone() {
+            PaletteTarget::WaypointTrait(symbol) => {
+                palette.set_items(palette::waypoints_with_trait(&self.cache, symbol));
+                palette.hint = Some(format!("Cached waypoints with {}", symbol));
+            }
+            PaletteTarget::TradeGood(symbol) => {
+                palette.set_items(palette::markets_trading(&self.cache, symbol));
+                palette.hint = Some(format!("Cached markets trading {}", symbol));
+            }
+            PaletteTarget::Ship(symbol) => {
+                self.palette = None;
+                self.view_ship(&symbol);
+            }
+            PaletteTarget::System(symbol) => {
+                self.palette = None;
+                if self.view_system(&symbol) {
+                    self.refresh().await;
+                }
+            }
+            PaletteTarget::Waypoint(symbol) => {
+                self.palette = None;
+                if self.view_waypoint(&symbol) {
+                    self.refresh().await;
+                }
+            }
+            PaletteTarget::Command(command) => {
+                self.palette = None;
+                self.run_command(command).await;
+            }
+        }
+    }
 
-    let coming_soon = Paragraph::new(lines)
-        .block(markets_block)
-        .alignment(Alignment::Center)
-        .wrap(Wrap { trim: true });
+    /// Sends a palette command to the API and applies the result to the fleet.
+    pub async fn run_command(&mut self, command: PaletteCommand) {
+        let result = match &command {
+            PaletteCommand::Navigate { ship, waypoint } => {
+                self.client.navigate_ship(ship, waypoint).await.map(|data| {
+                    let arrival = data.nav.route.arrival.clone();
+                    self.update_ship(ship, |s| {
+                        s.nav = data.nav;
+                        s.fuel = data.fuel;
+                    });
+                    format!("{} navigating to {}, arriving {}", ship, waypoint, arrival)
+                })
+            }
+            PaletteCommand::Dock { ship } => self.client.dock_ship(ship).await.map(|nav| {
+                self.update_ship(ship, |s| *s.nav = nav);
+                format!("{} docked", ship)
+            }),
+            PaletteCommand::Orbit { ship } => self.client.orbit_ship(ship).await.map(|nav| {
+                self.update_ship(ship, |s| *s.nav = nav);
+                format!("{} in orbit", ship)
+            }),
+            PaletteCommand::Nickname { ship, name } => self
+                .roster
+                .edit(ship, |notes| notes.nickname = name.clone())
+                .map(|_| match name {
+                    Some(name) => format!("{} is now called {}", ship, name),
+                    None => format!("{} has no nickname", ship),
+                }),
+            PaletteCommand::Group { ship, group } => self
+                .roster
+                .edit(ship, |notes| notes.group = group.clone())
+                .map(|_| match group {
+                    Some(group) => format!("{} is in {}", ship, group),
+                    None => format!("{} is ungrouped", ship),
+                }),
+            PaletteCommand::Tag { ship, tag } => self
+                .roster
+                .edit(ship, |notes| {
+                    if !notes.tags.contains(tag) {
+                        notes.tags.push(tag.clone());
+                    }
+                })
+                .map(|_| format!("{} tagged {}", ship, tag)),
+            PaletteCommand::Untag { ship, tag } => self
+                .roster
+                .edit(ship, |notes| notes.tags.retain(|t| t != tag))
+                .map(|_| format!("{} untagged {}", ship, tag)),
+            PaletteCommand::Note { ship, text } => self
+                .roster
+                .edit(ship, |notes| notes.notes = text.clone())
+                .map(|_| format!("Notes on {} saved", ship)),
+            PaletteCommand::Fleet(order) => self.run_fleet(order).await,
+        };
 
-    f.render_widget(coming_soon, area);
-}
+        match result {
+            Ok(message) => {
+                log::info!("{}", message);
+                self.notice = Some(message);
+            }
+            Err(e) => {
+                self.notice = Some(format!("{} failed: {}", command, e));
+                events::record_error(&command.to_string(), &e);
+            }
+        }
+    }
 
-fn render_ship_detail(f: &mut Frame, app: &mut App, area: Rect) {
-    let ship_index = match app.selected_ship_index {
-        Some(index) => index,
-        None => {
-            // If no ship is selected, display an error message
-            let message = Paragraph::new("No ship selected")
-                .block(Block::default().borders(Borders::ALL).title("Error"))
-                .wrap(Wrap { trim: true });
-            f.render_widget(message, area);
-            return;
+    /// Gives an order to the marked ships and reloads the fleet.
+    async fn run_fleet(&mut self, order: &FleetCommand) -> Result<String> {
+        let ships: Vec<spacetraders_api::models::Ship> = self
+            .ships
+            .iter()
+            .flatten()
+            .filter(|ship| self.marked_ships.contains(&ship.symbol))
+            .cloned()
+            .collect();
+        if ships.is_empty() {
+            anyhow::bail!("mark ships with Space on the Ships tab first");
         }
-    };
+        let outcomes = fleet::run(&self.client, &ships, order).await;
+        let failed = outcomes.iter().filter(|outcome| !outcome.ok).count();
+        self.ships = Some(self.client.get_my_ships().await?);
+        Ok(format!(
+            "Fleet {} on {} ships: {} ok, {} failed (see event log)",
+            order,
+            outcomes.len(),
+            outcomes.len() - failed,
+            failed
+        ))
+    }
 
-    if let Some(ships) = &app.ships {
-        if ship_index >= ships.len() {
-            // If the selected index is out of bounds, display an error message
-            let message = Paragraph::new("Invalid ship selection")
-                .block(Block::default().borders(Borders::ALL).title("Error"))
-                .wrap(Wrap { trim: true });
-            f.render_widget(message, area);
+    /// Marks or unmarks the ship selected on the Ships tab.
+    pub fn toggle_ship_mark(&mut self) {
+        let ships = self.visible_ships();
+        let Some(&i) = self.ships_state.selected().and_then(|i| ships.get(i)) else {
             return;
+        };
+        let symbol = &self.ships.as_deref().unwrap_or_default()[i].symbol;
+        if !self.marked_ships.remove(symbol) {
+            self.marked_ships.insert(symbol.clone());
         }
+    }
 
-        let ship = &ships[ship_index];
-        let ship_role = ship.registration.role.to_string();
-        let role_color = get_ship_role_color(&ship_role);
-
-        // Split the screen into sections
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(3), // Header
-                Constraint::Length(8), // Basic info
-                Constraint::Min(0),    // Additional info
-            ])
-            .split(area);
+    /// Marks every ship the Ships tab lists, or clears the marks if they
+    /// are all marked already.
+    pub fn toggle_all_ship_marks(&mut self) {
+        let ships = self.ships.as_deref().unwrap_or_default();
+        let visible: Vec<String> = self
+            .visible_ships()
+            .into_iter()
+            .map(|i| ships[i].symbol.clone())
+            .collect();
+        if visible
+            .iter()
+            .all(|symbol| self.marked_ships.contains(symbol))
+        {
+            self.marked_ships.clear();
+        } else {
+            self.marked_ships.extend(visible);
+        }
+    }
 
-        // Render header
-        let header = Paragraph::new(vec![Line::from(vec![Span::styled(
-            &ship.symbol,
-            Style::default()
-                .fg(Color::Blue)
-                .add_modifier(Modifier::BOLD),
-        )])])
-        .block(Block::default().borders(Borders::ALL).title("Ship Detail"))
-        .alignment(Alignment::Center);
-        f.render_widget(header, chunks[0]);
+    /// Applies `update` to the loaded ship with the given symbol.
+    fn update_ship(
+        &mut self,
+        symbol: &str,
+        update: impl FnOnce(&mut spacetraders_api::models::Ship),
+    ) {
+        if let Some(ship) = self
+            .ships
+            .iter_mut()
+            .flatten()
+            .find(|ship| ship.symbol == symbol)
+        {
+            update(ship);
+        }
+    }
 
-        // Render basic info
-        let basic_info = vec![
-            Line::from(vec![
-                Span::styled("Role: ", Style::default().add_modifier(Modifier::BOLD)),
-                Span::styled(&ship_role, Style::default().fg(role_color)),
-            ]),
-            Line::from(vec![
-                Span::styled(
-                    "Registration: ",
-                    Style::default().add_modifier(Modifier::BOLD),
-                ),
-                Span::raw(format!(
-                    "{} ({})",
-                    ship.registration.name, ship.registration.faction_symbol
-                )),
-            ]),
-            Line::from(vec![
-                Span::styled("Frame: ", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(format!("{:?}", ship.frame.symbol)),
-            ]),
-            Line::from(vec![
-                Span::styled("Engine: ", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(format!("{:?}", ship.engine.symbol)),
-            ]),
-            Line::from(vec![
-                Span::styled("Fuel: ", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(format!("{}/{}", ship.fuel.current, ship.fuel.capacity)),
-            ]),
-        ];
+    /// Returns the system and waypoint symbols of the waypoint picked in SystemDetail.
+    fn selected_waypoint_symbols(&self) -> Option<(String, String)> {
+        let system = self.systems.as_ref()?.get(self.selected_system_index?)?;
+        let waypoint = system.waypoints.get(self.selected_waypoint_index?)?;
+        Some((system.symbol.clone(), waypoint.symbol.clone()))
+    }
 
-        let basic_info_widget = Paragraph::new(basic_info)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title("Ship Information"),
-            )
-            .wrap(Wrap { trim: true });
-        f.render_widget(basic_info_widget, chunks[1]);
+    /// Number of rows in the shipyard listing: priced ships when a ship is
+    /// present at the waypoint, otherwise just the ship types on offer.
+    fn shipyard_listing_len(&self) -> usize {
+        match &self.shipyard {
+            Some(shipyard) => match &shipyard.ships {
+                Some(ships) => ships.len(),
+                None => shipyard.ship_types.len(),
+            },
+            None => 0,
+        }
+    }
 
-        // Additional ship details: cargo, nav, etc.
-        let additonal_chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-            .split(chunks[2]);
+    pub fn waypoint_has_shipyard(&self) -> bool {
+        let Some((_, waypoint_symbol)) = self.selected_waypoint_symbols() else {
+            return false;
+        };
+        self.waypoint.as_ref().is_some_and(|waypoint| {
+            waypoint.symbol == waypoint_symbol
+                && waypoint
+                    .traits
+                    .iter()
+                    .any(|t| t.symbol == WaypointTraitSymbol::Shipyard)
+        })
+    }
 
-        // Left side: Cargo and modules
-        let cargo_capacity = ship.cargo.capacity;
-        let cargo_units = ship.cargo.units;
-        let cargo_info = vec![
-            Line::from(vec![
-                Span::styled("Cargo: ", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(format!("{}/{}", cargo_units, cargo_capacity)),
-            ]),
-            Line::from(""),
-            Line::from(vec![Span::styled(
-                "Modules: ",
-                Style::default().add_modifier(Modifier::BOLD),
-            )]),
-        ];
-        let mut modules_info = cargo_info;
-        for module in &ship.modules {
-            modules_info.push(Line::from(vec![
-                Span::raw(" - "),
-                Span::styled(
-                    format!("{:?}", module.symbol),
-                    Style::default().fg(Color::Cyan),
-                ),
-            ]));
+    pub fn open_shipyard(&mut self) {
+        if self.waypoint_has_shipyard() {
+            self.shipyard = None;
+            self.shipyard_state.select(Some(0));
+            self.state = AppState::Shipyard;
+        } else {
+            self.notice = Some(String::from("No shipyard at this waypoint"));
         }
+    }
 
-        let cargo_widget = Paragraph::new(modules_info)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title("Cargo & Modules"),
-            )
-            .wrap(Wrap { trim: true });
-        f.render_widget(cargo_widget, additonal_chunks[0]);
-
-        // Right side: Navigation
-        let mut nav_info = vec![
-            Line::from(vec![
-                Span::styled("Status: ", Style::default().add_modifier(Modifier::BOLD)),
-                {
-                    let status_str = ship.nav.status.to_string();
-                    let status_color = match status_str.as_str() {
-                        "IN_TRANSIT" => Color::Yellow,
-                        "DOCKED" => Color::Green,
-                        "IN_ORBIT" => Color::Blue,
-                        _ => Color::White,
-                    };
-                    Span::styled(status_str, Style::default().fg(status_color))
-                },
-            ]),
-            Line::from(vec![
-                Span::styled("Location: ", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(&ship.nav.waypoint_symbol),
-            ]),
-            Line::from(vec![Span::styled(
-                "Route: ",
-                Style::default().add_modifier(Modifier::BOLD),
-            )]),
-        ];
+    pub fn selected_shipyard_ship(&self) -> Option<&spacetraders_api::models::ShipyardShip> {
+        let ships = self.shipyard.as_ref()?.ships.as_ref()?;
+        ships.get(self.shipyard_state.selected()?)
+    }
 
-        // Add route information
-        nav_info.push(Line::from(vec![
-            Span::raw("  From: "),
-            Span::styled(
-                &ship.nav.route.origin.symbol,
-                Style::default().fg(Color::Blue),
-            ),
-        ]));
-        nav_info.push(Line::from(vec![
-            Span::raw("  To: "),
-            Span::styled(
-                &ship.nav.route.destination.symbol,
-                Style::default().fg(Color::Green),
-            ),
-        ]));
-        nav_info.push(Line::from(vec![
-            Span::raw("  Arrival: "),
-            Span::styled(&ship.nav.route.arrival, Style::default().fg(Color::Yellow)),
-        ]));
+    pub fn request_purchase(&mut self) {
+        if self.selected_shipyard_ship().is_some() {
+            self.purchase_pending = true;
+        } else {
+            self.notice = Some(String::from(
+                "Ship prices are only visible with a ship present at the shipyard",
+            ));
+        }
+    }
 
-        let nav_widget = Paragraph::new(nav_info)
-            .block(Block::default().borders(Borders::ALL).title("Navigation"))
-            .wrap(Wrap { trim: true });
-        f.render_widget(nav_widget, additonal_chunks[1]);
-    } else {
-        // If ships data is not loaded, display a loading message
-        let message = Paragraph::new("Loading ship data...")
-            .block(Block::default().borders(Borders::ALL).title("Loading"))
-            .wrap(Wrap { trim: true });
-        f.render_widget(message, area);
+    pub fn cancel_purchase(&mut self) {
+        self.purchase_pending = false;
     }
-}
 
-fn render_system_detail(f: &mut Frame, app: &mut App, area: Rect) {
-    let system_index = match app.selected_system_index {
-        Some(index) => index,
-        None => {
-            // If no system is selected, display an error message
-            let message = Paragraph::new("No system selected")
-                .block(Block::default().borders(Borders::ALL).title("Error"))
-                .wrap(Wrap { trim: true });
-            f.render_widget(message, area);
+    pub async fn confirm_purchase(&mut self) {
+        self.purchase_pending = false;
+        let Some(shipyard) = &self.shipyard else {
             return;
-        }
-    };
-
-    if let Some(systems) = &app.systems {
-        if system_index >= systems.len() {
-            // If the selected index is out of bounds, display an error message
-            let message = Paragraph::new("Invalid system selection")
-                .block(Block::default().borders(Borders::ALL).title("Error"))
-                .wrap(Wrap { trim: true });
-            f.render_widget(message, area);
+        };
+        let Some(ship) = self.selected_shipyard_ship() else {
+            return;
+        };
+        let ship_type = ship.r#type;
+        let waypoint_symbol = shipyard.symbol.clone();
+
+        match self.client.purchase_ship(ship_type, &waypoint_symbol).await {
+            Ok(purchase) => {
+                self.notice = Some(format!(
+                    "Purchased {} ({}) for {} credits",
+                    purchase.ship.symbol, ship_type, purchase.transaction.price
+                ));
+                self.agent = Some(*purchase.agent);
+                if let Some(ships) = &mut self.ships {
+                    ships.push(*purchase.ship);
+                }
+                if let Some(shipyard) = &mut self.shipyard {
+                    shipyard
+                        .transactions
+                        .get_or_insert_with(Vec::new)
+                        .insert(0, *purchase.transaction);
+                }
+            }
+            Err(e) => {
+                self.notice = Some(format!("Purchase failed: {}", e));
+                events::record_error("Purchase failed", &e);
+            }
+        }
+    }
+
+    pub fn next_tab(&mut self) {
+        self.state = match self.state {
+            AppState::Dashboard => AppState::Ships,
+            AppState::Ships => AppState::Systems,
+            AppState::Systems => AppState::Markets,
+            AppState::Markets => AppState::Dashboard,
+            AppState::ShipDetail => AppState::Ships,
+            AppState::SystemDetail => AppState::Systems,
+            AppState::WaypointDetail => AppState::SystemDetail,
+            AppState::Shipyard => AppState::WaypointDetail,
+            AppState::Galaxy => AppState::Systems,
+            AppState::Fitting => AppState::Ships,
+            AppState::WaypointSearch => AppState::Markets,
+            AppState::Construction => AppState::Markets,
+            AppState::Agents | AppState::Factions => AppState::Ships,
+            AppState::NearbyShips => AppState::Ships,
+            AppState::Contracts => AppState::Ships,
+        };
+    }
+
+    pub fn previous_tab(&mut self) {
+        self.state = match self.state {
+            AppState::Dashboard => AppState::Markets,
+            AppState::Ships => AppState::Dashboard,
+            AppState::Systems => AppState::Ships,
+            AppState::Markets => AppState::Systems,
+            AppState::ShipDetail => AppState::Ships,
+            AppState::SystemDetail => AppState::Systems,
+            AppState::WaypointDetail => AppState::SystemDetail,
+            AppState::Shipyard => AppState::WaypointDetail,
+            AppState::Galaxy => AppState::Systems,
+            AppState::Fitting => AppState::Ships,
+            AppState::WaypointSearch => AppState::Ships,
+            AppState::Construction => AppState::Ships,
+            AppState::Agents | AppState::Factions => AppState::Markets,
+            AppState::NearbyShips => AppState::Dashboard,
+            AppState::Contracts => AppState::Markets,
+        };
+    }
+
+    pub fn next_item(&mut self) {
+        match self.state {
+            AppState::Dashboard => {
+                self.announcement_scroll = self.announcement_scroll.saturating_add(1);
+            }
+            AppState::Ships => {
+                let ships = self.visible_ships();
+                if ships.is_empty() {
+                    return;
+                }
+
+                let i = match self.ships_state.selected() {
+                    Some(i) => {
+                        if i >= ships.len() - 1 {
+                            0
+                        } else {
+                            i + 1
+                        }
+                    }
+                    None => 0,
+                };
+                self.ships_state.select(Some(i));
+            }
+            AppState::Systems => {
+                if let Some(systems) = &self.systems {
+                    if systems.is_empty() {
+                        return;
+                    }
+
+                    let i = match self.systems_state.selected() {
+                        Some(i) => {
+                            if i >= systems.len() - 1 {
+                                0
+                            } else {
+                                i + 1
+                            }
+                        }
+                        None => 0,
+                    };
+                    self.systems_state.select(Some(i));
+                }
+            }
+            AppState::SystemDetail => {
+                if let Some(systems) = &self.systems {
+                    if let Some(system_index) = self.selected_system_index {
+                        if system_index < systems.len() {
+                            let system = &systems[system_index];
+                            if system.waypoints.is_empty() {
+                                return;
+                            }
+
+                            let i = match self.waypoints_state.selected() {
+                                Some(i) => {
+                                    if i >= system.waypoints.len() - 1 {
+                                        0
+                                    } else {
+                                        i + 1
+                                    }
+                                }
+                                None => 0,
+                            };
+                            self.waypoints_state.select(Some(i));
+                        }
+                    }
+                }
+            }
+            AppState::Shipyard => {
+                let len = self.shipyard_listing_len();
+                if len == 0 {
+                    return;
+                }
+
+                let i = match self.shipyard_state.selected() {
+                    Some(i) => {
+                        if i >= len - 1 {
+                            0
+                        } else {
+                            i + 1
+                        }
+                    }
+                    None => 0,
+                };
+                self.shipyard_state.select(Some(i));
+            }
+            AppState::Markets => {
+                let len = supply::goods(&self.cache.supply_chain).len();
+                if len > 0 {
+                    let i = self.supply_state.selected().map_or(0, |i| (i + 1) % len);
+                    self.supply_state.select(Some(i));
+                }
+            }
+            AppState::Fitting => self.move_fitting_selection(true),
+            AppState::WaypointSearch => {
+                let len = self.search_results().len();
+                if len > 0 {
+                    let i = self
+                        .search
+                        .results_state
+                        .selected()
+                        .map_or(0, |i| (i + 1) % len);
+                    self.search.results_state.select(Some(i));
+                }
+            }
+            AppState::Contracts => {
+                let len = self
+                    .contracts
+                    .as_ref()
+                    .map_or(0, |contracts| contracts.len());
+                if len > 0 {
+                    let i = self.contracts_state.selected().map_or(0, |i| (i + 1) % len);
+                    self.contracts_state.select(Some(i));
+                }
+            }
+            AppState::NearbyShips => {
+                let len = self.nearby_ships().len();
+                if len > 0 {
+                    let i = self.sightings_state.selected().map_or(0, |i| (i + 1) % len);
+                    self.sightings_state.select(Some(i));
+                }
+            }
+            AppState::Agents => {
+                let len = self.rivals.agents.len();
+                if len > 0 {
+                    let i = self.agents_state.selected().map_or(0, |i| (i + 1) % len);
+                    self.agents_state.select(Some(i));
+                }
+            }
+            AppState::Factions => {
+                let len = self.factions.as_ref().map_or(0, |factions| factions.len());
+                if len > 0 {
+                    let i = self.factions_state.selected().map_or(0, |i| (i + 1) % len);
+                    self.factions_state.select(Some(i));
+                }
+            }
+            AppState::Construction => {
+                let len = self.construction_reports().len();
+                if len > 0 {
+                    let i = self
+                        .construction_state
+                        .selected()
+                        .map_or(0, |i| (i + 1) % len);
+                    self.construction_state.select(Some(i));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn previous_item(&mut self) {
+        match self.state {
+            AppState::Dashboard => {
+                self.announcement_scroll = self.announcement_scroll.saturating_sub(1);
+            }
+            AppState::Ships => {
+                let ships = self.visible_ships();
+                if ships.is_empty() {
+                    return;
+                }
+
+                let i = match self.ships_state.selected() {
+                    Some(i) => {
+                        if i == 0 {
+                            ships.len() - 1
+                        } else {
+                            i - 1
+                        }
+                    }
+                    None => 0,
+                };
+                self.ships_state.select(Some(i));
+            }
+            AppState::Systems => {
+                if let Some(systems) = &self.systems {
+                    if systems.is_empty() {
+                        return;
+                    }
+
+                    let i = match self.systems_state.selected() {
+                        Some(i) => {
+                            if i == 0 {
+                                systems.len() - 1
+                            } else {
+                                i - 1
+                            }
+                        }
+                        None => 0,
+                    };
+                    self.systems_state.select(Some(i));
+                }
+            }
+            AppState::SystemDetail => {
+                if let Some(systems) = &self.systems {
+                    if let Some(system_index) = self.selected_system_index {
+                        if system_index < systems.len() {
+                            let system = &systems[system_index];
+                            if system.waypoints.is_empty() {
+                                return;
+                            }
+
+                            let i = match self.waypoints_state.selected() {
+                                Some(i) => {
+                                    if i == 0 {
+                                        system.waypoints.len() - 1
+                                    } else {
+                                        i - 1
+                                    }
+                                }
+                                None => 0,
+                            };
+                            self.waypoints_state.select(Some(i));
+                        }
+                    }
+                }
+            }
+            AppState::Shipyard => {
+                let len = self.shipyard_listing_len();
+                if len == 0 {
+                    return;
+                }
+
+                let i = match self.shipyard_state.selected() {
+                    Some(i) => {
+                        if i == 0 {
+                            len - 1
+                        } else {
+                            i - 1
+                        }
+                    }
+                    None => 0,
+                };
+                self.shipyard_state.select(Some(i));
+            }
+            AppState::Markets => {
+                let len = supply::goods(&self.cache.supply_chain).len();
+                if len > 0 {
+                    let i = self
+                        .supply_state
+                        .selected()
+                        .map_or(0, |i| (i + len - 1) % len);
+                    self.supply_state.select(Some(i));
+                }
+            }
+            AppState::Fitting => self.move_fitting_selection(false),
+            AppState::WaypointSearch => {
+                let len = self.search_results().len();
+                if len > 0 {
+                    let i = self
+                        .search
+                        .results_state
+                        .selected()
+                        .map_or(0, |i| (i + len - 1) % len);
+                    self.search.results_state.select(Some(i));
+                }
+            }
+            AppState::Contracts => {
+                let len = self
+                    .contracts
+                    .as_ref()
+                    .map_or(0, |contracts| contracts.len());
+                if len > 0 {
+                    let i = self
+                        .contracts_state
+                        .selected()
+                        .map_or(0, |i| (i + len - 1) % len);
+                    self.contracts_state.select(Some(i));
+                }
+            }
+            AppState::NearbyShips => {
+                let len = self.nearby_ships().len();
+                if len > 0 {
+                    let i = self
+                        .sightings_state
+                        .selected()
+                        .map_or(0, |i| (i + len - 1) % len);
+                    self.sightings_state.select(Some(i));
+                }
+            }
+            AppState::Agents => {
+                let len = self.rivals.agents.len();
+                if len > 0 {
+                    let i = self
+                        .agents_state
+                        .selected()
+                        .map_or(0, |i| (i + len - 1) % len);
+                    self.agents_state.select(Some(i));
+                }
+            }
+            AppState::Factions => {
+                let len = self.factions.as_ref().map_or(0, |factions| factions.len());
+                if len > 0 {
+                    let i = self
+                        .factions_state
+                        .selected()
+                        .map_or(0, |i| (i + len - 1) % len);
+                    self.factions_state.select(Some(i));
+                }
+            }
+            AppState::Construction => {
+                let len = self.construction_reports().len();
+                if len > 0 {
+                    let i = self
+                        .construction_state
+                        .selected()
+                        .map_or(0, |i| (i + len - 1) % len);
+                    self.construction_state.select(Some(i));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn toggle_help(&mut self) {
+        self.help_visible = !self.help_visible;
+    }
+
+    pub fn view_selected_ship_detail(&mut self) {
+        let ships = self.visible_ships();
+        if let Some(&i) = self.ships_state.selected().and_then(|i| ships.get(i)) {
+            self.selected_ship_index = Some(i);
+            self.state = AppState::ShipDetail;
+        }
+    }
+
+    /// Indices into `ships` in the order the Ships tab lists them.
+    pub fn visible_ships(&self) -> Vec<usize> {
+        self.ship_sections()
+            .into_iter()
+            .flat_map(|(_, ships)| ships)
+            .collect()
+    }
+
+    /// The Ships tab's sections after filtering, grouping and sorting.
+    pub fn ship_sections(&self) -> Vec<(String, Vec<usize>)> {
+        let ships = self.ships.as_deref().unwrap_or_default();
+        roster::arrange(
+            ships,
+            &self.roster,
+            &self.ship_filter,
+            self.ship_sort,
+            self.ship_group_by,
+        )
+    }
+
+    pub fn cycle_ship_sort(&mut self) {
+        self.ship_sort = self.ship_sort.next();
+        self.ships_state.select(Some(0));
+    }
+
+    pub fn cycle_ship_grouping(&mut self) {
+        self.ship_group_by = self.ship_group_by.next();
+        self.ships_state.select(Some(0));
+    }
+
+    pub fn cycle_ship_filter_field(&mut self) {
+        self.ship_filter_field = self.ship_filter_field.next();
+    }
+
+    /// Steps the chosen filter field through the values found in the fleet
+    /// and the roster, then back to showing every ship.
+    pub fn cycle_ship_filter_value(&mut self) {
+        let ships = self.ships.as_deref().unwrap_or_default();
+        let filter = &mut self.ship_filter;
+        match self.ship_filter_field {
+            FilterField::Role => {
+                let mut roles: Vec<ShipRole> =
+                    ships.iter().map(|ship| ship.registration.role).collect();
+                roles.sort();
+                roles.dedup();
+                filter.role = next_filter(&roles, filter.role);
+            }
+            FilterField::Status => {
+                let mut statuses: Vec<ShipNavStatus> =
+                    ships.iter().map(|ship| ship.nav.status).collect();
+                statuses.sort();
+                statuses.dedup();
+                filter.status = next_filter(&statuses, filter.status);
+            }
+            FilterField::System => {
+                let mut systems: Vec<String> = ships
+                    .iter()
+                    .map(|ship| ship.nav.system_symbol.clone())
+                    .collect();
+                systems.sort();
+                systems.dedup();
+                filter.system = next_filter(&systems, filter.system.take());
+            }
+            FilterField::Group => {
+                filter.group = next_filter(&self.roster.groups(), filter.group.take());
+            }
+            FilterField::Tag => {
+                filter.tag = next_filter(&self.roster.tags(), filter.tag.take());
+            }
+        }
+        self.ships_state.select(Some(0));
+    }
+
+    pub fn clear_ship_filter(&mut self) {
+        self.ship_filter = ShipFilter::default();
+        self.ships_state.select(Some(0));
+    }
+
+    pub fn view_selected_system_detail(&mut self) {
+        if let Some(systems) = &self.systems {
+            if !systems.is_empty() {
+                if let Some(i) = self.systems_state.selected() {
+                    self.selected_system_index = Some(i);
+                    self.waypoints_state.select(Some(0));
+                    self.state = AppState::SystemDetail;
+                }
+            }
+        }
+    }
+
+    pub fn view_selected_waypoint_detail(&mut self) {
+        if let Some(systems) = &self.systems {
+            if let Some(system_index) = self.selected_system_index {
+                if system_index < systems.len() {
+                    let system = &systems[system_index];
+                    if !system.waypoints.is_empty() {
+                        if let Some(i) = self.waypoints_state.selected() {
+                            if i < system.waypoints.len() {
+                                self.selected_waypoint_index = Some(i);
+                                self.waypoint = None;
+                                self.state = AppState::WaypointDetail;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn back_from_detail(&mut self) {
+        self.state = match self.state {
+            AppState::ShipDetail => AppState::Ships,
+            AppState::SystemDetail => AppState::Systems,
+            AppState::WaypointDetail => AppState::SystemDetail,
+            AppState::Shipyard => AppState::WaypointDetail,
+            AppState::Galaxy => AppState::Systems,
+            AppState::Fitting => AppState::ShipDetail,
+            AppState::WaypointSearch => AppState::Systems,
+            AppState::Construction => AppState::Systems,
+            AppState::Agents | AppState::Factions | AppState::Contracts => AppState::Dashboard,
+            AppState::NearbyShips => AppState::ShipDetail,
+            _ => self.state,
+        };
+    }
+}
+
+pub async fn run_app(app: &mut App) -> Result<()> {
+    // Setup terminal
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    // Refresh initial data
+    app.refresh_data().await?;
+
+    // Main loop
+    while !app.should_quit {
+        app.check_events();
+        app.check_downloads().await;
+        terminal.draw(|f| ui(f, app))?;
+
+        if event::poll(Duration::from_millis(50))? {
+            let event = event::read()?;
+            if let Event::Key(key) = event {
+                if key.kind == KeyEventKind::Press {
+                    if app.purchase_pending {
+                        match key.code {
+                            KeyCode::Char('y') => app.confirm_purchase().await,
+                            KeyCode::Char('n') | KeyCode::Esc => app.cancel_purchase(),
+                            _ => {}
+                        }
+                        continue;
+                    }
+
+                    if app.fitting.as_ref().is_some_and(|f| f.confirm_pending) {
+                        match key.code {
+                            KeyCode::Char('y') => app.confirm_fitting().await,
+                            KeyCode::Char('n') | KeyCode::Esc => app.cancel_fitting(),
+                            _ => {}
+                        }
+                        continue;
+                    }
+
+                    if app.palette.is_some() {
+                        app.handle_palette_event(&event).await;
+                        continue;
+                    }
+
+                    app.notice = None;
+                    match key.code {
+                        KeyCode::Char('q') => app.should_quit = true,
+                        KeyCode::Char('/') => app.open_palette().await,
+                        KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            app.open_palette().await
+                        }
+                        KeyCode::Char('r') => {
+                            app.refresh().await;
+                        }
+                        KeyCode::Char('h') => app.toggle_help(),
+                        KeyCode::Char('l') => app.toggle_log(),
+                        KeyCode::Char('v') if app.log_visible => app.cycle_log_level(),
+                        KeyCode::Tab => {
+                            app.next_tab();
+                            app.refresh().await;
+                        }
+                        KeyCode::BackTab => {
+                            app.previous_tab();
+                            app.refresh().await;
+                        }
+                        KeyCode::Enter => match app.state {
+                            AppState::Ships => {
+                                app.view_selected_ship_detail();
+                                app.refresh().await;
+                            }
+                            AppState::Systems => {
+                                app.view_selected_system_detail();
+                                app.refresh().await;
+                            }
+                            AppState::Galaxy => {
+                                app.view_galaxy_system_detail();
+                                app.refresh().await;
+                            }
+                            AppState::SystemDetail => {
+                                app.view_selected_waypoint_detail();
+                                app.refresh().await;
+                            }
+                            AppState::ShipDetail => app.back_from_detail(),
+                            AppState::WaypointDetail => app.back_from_detail(),
+                            AppState::Shipyard => app.back_from_detail(),
+                            AppState::Fitting => app.toggle_fitting_part(),
+                            AppState::WaypointSearch => {}
+                            AppState::Construction => {}
+                            AppState::Agents => {}
+                            AppState::Factions => app.view_faction_on_galaxy(),
+                            AppState::NearbyShips => {}
+                            AppState::Contracts => app.accept_selected_contract().await,
+                            AppState::Dashboard => {}
+                            AppState::Markets => {}
+                        },
+                        KeyCode::Esc => match app.state {
+                            AppState::ShipDetail => app.back_from_detail(),
+                            AppState::SystemDetail => app.back_from_detail(),
+                            AppState::WaypointDetail => app.back_from_detail(),
+                            AppState::Shipyard => app.back_from_detail(),
+                            AppState::Galaxy => app.back_from_detail(),
+                            AppState::Fitting => app.back_from_detail(),
+                            AppState::WaypointSearch => app.back_from_detail(),
+                            AppState::Construction => app.back_from_detail(),
+                            AppState::Agents => app.back_from_detail(),
+                            AppState::Factions => app.back_from_detail(),
+                            AppState::NearbyShips => app.back_from_detail(),
+                            AppState::Contracts => app.back_from_detail(),
+                            _ => {}
+                        },
+                        KeyCode::Char('g') if matches!(app.state, AppState::Ships) => {
+                            app.cycle_ship_grouping()
+                        }
+                        KeyCode::Char('f') if matches!(app.state, AppState::Ships) => {
+                            app.cycle_ship_filter_field()
+                        }
+                        KeyCode::Char('t') if matches!(app.state, AppState::Ships) => {
+                            app.cycle_ship_filter_value()
+                        }
+                        KeyCode::Char('x') if matches!(app.state, AppState::Ships) => {
+                            app.clear_ship_filter()
+                        }
+                        KeyCode::Char(' ') if matches!(app.state, AppState::Ships) => {
+                            app.toggle_ship_mark()
+                        }
+                        KeyCode::Char('a') if matches!(app.state, AppState::Ships) => {
+                            app.toggle_all_ship_marks()
+                        }
+                        KeyCode::Char('g') => {
+                            if let AppState::Systems = app.state {
+                                app.open_galaxy();
+                                app.refresh().await;
+                            }
+                        }
+                        KeyCode::Left | KeyCode::Right
+                            if matches!(app.state, AppState::Fitting) =>
+                        {
+                            app.toggle_fitting_focus()
+                        }
+                        KeyCode::Left if matches!(app.state, AppState::Galaxy) => {
+                            app.pan_galaxy(-1.0, 0.0)
+                        }
+                        KeyCode::Right if matches!(app.state, AppState::Galaxy) => {
+                            app.pan_galaxy(1.0, 0.0)
+                        }
+                        KeyCode::Up if matches!(app.state, AppState::Galaxy) => {
+                            app.pan_galaxy(0.0, 1.0)
+                        }
+                        KeyCode::Down if matches!(app.state, AppState::Galaxy) => {
+                            app.pan_galaxy(0.0, -1.0)
+                        }
+                        KeyCode::Char('+') | KeyCode::Char('=')
+                            if matches!(app.state, AppState::Galaxy) =>
+                        {
+                            app.zoom_galaxy(2.0)
+                        }
+                        KeyCode::Char('-') if matches!(app.state, AppState::Galaxy) => {
+                            app.zoom_galaxy(0.5)
+                        }
+                        KeyCode::Char('w')
+                            if matches!(app.state, AppState::Systems | AppState::SystemDetail) =>
+                        {
+                            app.open_search();
+                            app.refresh().await;
+                        }
+                        KeyCode::Char('c')
+                            if matches!(app.state, AppState::Systems | AppState::SystemDetail) =>
+                        {
+                            app.open_construction();
+                            app.refresh().await;
+                        }
+                        KeyCode::Char('t') if matches!(app.state, AppState::WaypointSearch) => {
+                            app.cycle_search_type()
+                        }
+                        KeyCode::Char('a') if matches!(app.state, AppState::WaypointSearch) => {
+                            app.cycle_search_trait()
+                        }
+                        KeyCode::Char('m') if matches!(app.state, AppState::WaypointSearch) => {
+                            app.cycle_search_modifier()
+                        }
+                        KeyCode::Char('x') if matches!(app.state, AppState::WaypointSearch) => {
+                            app.toggle_search_scope()
+                        }
+                        KeyCode::Char('o') if matches!(app.state, AppState::WaypointSearch) => {
+                            app.next_search_ship();
+                            app.refresh().await;
+                        }
+                        KeyCode::Char('t') if matches!(app.state, AppState::Galaxy) => {
+                            app.cycle_galaxy_type_filter()
+                        }
+                        KeyCode::Char('f') if matches!(app.state, AppState::Galaxy) => {
+                            app.cycle_galaxy_faction_filter()
+                        }
+                        KeyCode::Char('o') if matches!(app.state, AppState::Galaxy) => {
+                            app.focus_next_ship_system()
+                        }
+                        KeyCode::Char('u') if matches!(app.state, AppState::Galaxy) => {
+                            app.sync_galaxy()
+                        }
+                        KeyCode::Char('s') if matches!(app.state, AppState::Agents) => {
+                            app.cycle_agent_sort()
+                        }
+                        KeyCode::Char('s') if matches!(app.state, AppState::Ships) => {
+                            app.cycle_ship_sort()
+                        }
+                        KeyCode::Char('s') => {
+                            if let AppState::WaypointDetail = app.state {
+                                app.open_shipyard();
+                                app.refresh().await;
+                            }
+                        }
+                        KeyCode::Char('c') if matches!(app.state, AppState::ShipDetail) => {
+                            app.assess_selected_ship().await
+                        }
+                        KeyCode::Char('f') if matches!(app.state, AppState::ShipDetail) => {
+                            app.open_fitting();
+                            app.refresh().await;
+                        }
+                        KeyCode::Char('x') if matches!(app.state, AppState::ShipDetail) => {
+                            app.scan_selected_ship(ScanKind::Systems).await
+                        }
+                        KeyCode::Char('z') if matches!(app.state, AppState::ShipDetail) => {
+                            app.scan_selected_ship(ScanKind::Waypoints).await
+                        }
+                        KeyCode::Char('n') if matches!(app.state, AppState::ShipDetail) => {
+                            app.scan_selected_ship(ScanKind::Ships).await
+                        }
+                        KeyCode::Char('i') if matches!(app.state, AppState::Fitting) => {
+                            app.request_fitting()
+                        }
+                        KeyCode::Char('a') if matches!(app.state, AppState::Dashboard) => {
+                            app.open_agents();
+                            app.refresh().await;
+                        }
+                        KeyCode::Char('c') if matches!(app.state, AppState::Dashboard) => {
+                            app.open_contracts();
+                            app.refresh().await;
+                        }
+                        KeyCode::Char('f') if matches!(app.state, AppState::Dashboard) => {
+                            app.open_factions();
+                            app.refresh().await;
+                        }
+                        KeyCode::Char('w') if matches!(app.state, AppState::Agents) => {
+                            app.toggle_watch_selected_agent()
+                        }
+                        KeyCode::Char('u') if matches!(app.state, AppState::Agents) => {
+                            app.sync_agents()
+                        }
+                        KeyCode::Char('p') if matches!(app.state, AppState::Dashboard) => {
+                            app.toggle_pnl_period()
+                        }
+                        KeyCode::Char('b') => {
+                            if let AppState::Shipyard = app.state {
+                                app.request_purchase();
+                            }
+                        }
+                        KeyCode::Down | KeyCode::Char('j') => app.next_item(),
+                        KeyCode::Up | KeyCode::Char('k') => app.previous_item(),
+                        KeyCode::Char('1') => {
+                            app.state = AppState::Dashboard;
+                            app.refresh().await;
+                        }
+                        KeyCode::Char('2') => {
+                            app.state = AppState::Ships;
+                            app.refresh().await;
+                        }
+                        KeyCode::Char('3') => {
+                            app.state = AppState::Systems;
+                            app.refresh().await;
+                        }
+                        KeyCode::Char('4') => {
+                            app.state = AppState::Markets;
+                            app.refresh().await;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    // Restore terminal
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+
+    Ok(())
+}
+
+fn ui(f: &mut Frame, app: &mut App) {
+    // Create layout
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),                                     // Tabs
+            Constraint::Min(0),                                        // Content
+            Constraint::Length(if app.log_visible { 10 } else { 0 }),  // Event log
+            Constraint::Length(if app.help_visible { 12 } else { 3 }), // Status bar
+        ])
+        .split(f.size());
+
+    // Tabs
+    let titles = ["Dashboard", "Ships", "Systems", "Markets"];
+    let titles = titles
+        .iter()
+        .map(|t| {
+            let (first, rest) = t.split_at(1);
+            Line::from(vec![
+                Span::styled(first, Style::default().fg(Color::Yellow)),
+                Span::styled(rest, Style::default()),
+            ])
+        })
+        .collect();
+
+    let tabs = Tabs::new(titles)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Space Traders"),
+        )
+        .select(match app.state {
+            AppState::Dashboard | AppState::Agents | AppState::Factions | AppState::Contracts => 0,
+            AppState::Ships | AppState::ShipDetail | AppState::Fitting | AppState::NearbyShips => 1,
+            AppState::Systems
+            | AppState::SystemDetail
+            | AppState::WaypointDetail
+            | AppState::Shipyard
+            | AppState::Galaxy
+            | AppState::WaypointSearch
+            | AppState::Construction => 2,
+            AppState::Markets => 3,
+        })
+        .style(Style::default().fg(Color::White))
+        .highlight_style(Style::default().fg(Color::Yellow));
+
+    f.render_widget(tabs, chunks[0]);
+
+    // Content
+    match app.state {
+        AppState::Dashboard => render_dashboard(f, app, chunks[1]),
+        AppState::Ships => render_ships(f, app, chunks[1]),
+        AppState::Systems => render_systems(f, app, chunks[1]),
+        AppState::Markets => render_markets(f, app, chunks[1]),
+        AppState::ShipDetail => render_ship_detail(f, app, chunks[1]),
+        AppState::SystemDetail => render_system_detail(f, app, chunks[1]),
+        AppState::WaypointDetail => render_waypoint_detail(f, app, chunks[1]),
+        AppState::Shipyard => render_shipyard(f, app, chunks[1]),
+        AppState::Galaxy => render_galaxy(f, app, chunks[1]),
+        AppState::Fitting => render_fitting(f, app, chunks[1]),
+        AppState::WaypointSearch => render_waypoint_search(f, app, chunks[1]),
+        AppState::Construction => render_construction(f, app, chunks[1]),
+        AppState::Agents => render_agents(f, app, chunks[1]),
+        AppState::Factions => render_factions(f, app, chunks[1]),
+        AppState::NearbyShips => render_nearby_ships(f, app, chunks[1]),
+        AppState::Contracts => render_contracts(f, app, chunks[1]),
+    }
+
+    if app.purchase_pending {
+        render_purchase_confirmation(f, app, chunks[1]);
+    }
+    if app.fitting.as_ref().is_some_and(|f| f.confirm_pending) {
+        render_fitting_confirmation(f, app, chunks[1]);
+    }
+
+    if app.log_visible {
+        render_event_log(f, app, chunks[2]);
+    }
+
+    if app.palette.is_some() {
+        render_palette(f, app, f.size());
+    }
+
+    // Status bar / Help screen with updated instructions
+    if app.help_visible {
+        let help_text = vec![
+            Line::from(Span::styled(
+                "Keyboard Navigation",
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("q", Style::default().fg(Color::Cyan)),
+                Span::raw(": Quit  "),
+                Span::styled("r", Style::default().fg(Color::Cyan)),
+                Span::raw(": Refresh  "),
+                Span::styled("h", Style::default().fg(Color::Cyan)),
+                Span::raw(": Toggle Help  "),
+            ]),
+            Line::from(vec![
+                Span::styled("Tab", Style::default().fg(Color::Cyan)),
+                Span::raw(": Next tab  "),
+                Span::styled("Shift+Tab", Style::default().fg(Color::Cyan)),
+                Span::raw(": Previous tab  "),
+            ]),
+            Line::from(vec![
+                Span::styled("Enter", Style::default().fg(Color::Cyan)),
+                Span::raw(": View details  "),
+                Span::styled("Esc", Style::default().fg(Color::Cyan)),
+                Span::raw(": Back from details  "),
+                Span::styled("s", Style::default().fg(Color::Cyan)),
+                Span::raw(": Open shipyard  "),
+                Span::styled("b", Style::default().fg(Color::Cyan)),
+                Span::raw(": Buy ship  "),
+                Span::styled("c", Style::default().fg(Color::Cyan)),
+                Span::raw(": Repair advice  "),
+                Span::styled("f", Style::default().fg(Color::Cyan)),
+                Span::raw(": Fitting (Enter add/remove, i install)  "),
+            ]),
+            Line::from(vec![
+                Span::styled("x/z/n", Style::default().fg(Color::Cyan)),
+                Span::raw(": Scan systems/waypoints/ships from the ship  "),
+            ]),
+            Line::from(vec![
+                Span::styled("f/t", Style::default().fg(Color::Cyan)),
+                Span::raw(": Ships filter field/value  "),
+                Span::styled("x", Style::default().fg(Color::Cyan)),
+                Span::raw(": Clear filter  "),
+                Span::styled("s", Style::default().fg(Color::Cyan)),
+                Span::raw(": Sort ships  "),
+                Span::styled("g", Style::default().fg(Color::Cyan)),
+                Span::raw(": Group ships  "),
+                Span::styled("Space/a", Style::default().fg(Color::Cyan)),
+                Span::raw(": Mark ship/all for fleet commands  "),
+            ]),
+            Line::from(vec![
+                Span::styled("g", Style::default().fg(Color::Cyan)),
+                Span::raw(": Galaxy map  "),
+                Span::styled("Arrows/+/-", Style::default().fg(Color::Cyan)),
+                Span::raw(": Pan/zoom  "),
+                Span::styled("t/f", Style::default().fg(Color::Cyan)),
+                Span::raw(": Type/faction filter  "),
+                Span::styled("o", Style::default().fg(Color::Cyan)),
+                Span::raw(": Our ships  "),
+                Span::styled("u", Style::default().fg(Color::Cyan)),
+                Span::raw(": Sync galaxy  "),
+            ]),
+            Line::from(vec![
+                Span::styled("w", Style::default().fg(Color::Cyan)),
+                Span::raw(": Waypoint search  "),
+                Span::styled("t/a/m", Style::default().fg(Color::Cyan)),
+                Span::raw(": Type/trait/modifier  "),
+                Span::styled("x", Style::default().fg(Color::Cyan)),
+                Span::raw(": Ship's system/all cached  "),
+                Span::styled("o", Style::default().fg(Color::Cyan)),
+                Span::raw(": Measure from next ship  "),
+                Span::styled("c", Style::default().fg(Color::Cyan)),
+                Span::raw(": Construction sites  "),
+            ]),
+            Line::from(vec![
+                Span::styled("/ or Ctrl+P", Style::default().fg(Color::Cyan)),
+                Span::raw(
+                    ": Find or run a command (navigate SHIP to WAYPOINT, dock SHIP, fleet dock)",
+                ),
+            ]),
+            Line::from(vec![
+                Span::styled("l", Style::default().fg(Color::Cyan)),
+                Span::raw(": Toggle event log  "),
+                Span::styled("v", Style::default().fg(Color::Cyan)),
+                Span::raw(": Event log severity  "),
+                Span::styled("p", Style::default().fg(Color::Cyan)),
+                Span::raw(": P&L by hour/day  "),
+            ]),
+            Line::from(vec![
+                Span::styled("a", Style::default().fg(Color::Cyan)),
+                Span::raw(": Agents (s sort, w watch, u sync)  "),
+                Span::styled("f", Style::default().fg(Color::Cyan)),
+                Span::raw(": Factions (Enter shows on galaxy map)  "),
+                Span::styled("c", Style::default().fg(Color::Cyan)),
+                Span::raw(": Contracts (Enter accepts)  "),
+            ]),
+            Line::from(vec![
+                Span::styled("1-4", Style::default().fg(Color::Cyan)),
+                Span::raw(": Switch tabs directly (1=Dashboard, 2=Ships, etc.)"),
+            ]),
+        ];
+
+        let help = Paragraph::new(help_text)
+            .block(Block::default().borders(Borders::ALL).title("Help"))
+            .alignment(Alignment::Left)
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(help, chunks[3]);
+    } else {
+        let status_text = match &app.notice {
+            Some(notice) => notice.as_str(),
+            None => "Press h for help | q to quit | r to refresh | Enter to view details",
+        };
+        let status = Paragraph::new(status_text)
+            .block(Block::default().borders(Borders::ALL))
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(status, chunks[3]);
+    }
+}
+
+fn render_dashboard(f: &mut Frame, app: &mut App, area: Rect) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(9),
+            Constraint::Percentage(50),
+            Constraint::Percentage(50),
+        ])
+        .split(area);
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(30),
+            Constraint::Percentage(30),
+            Constraint::Percentage(40),
+        ])
+        .split(rows[0]);
+    let standings_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(25),
+            Constraint::Percentage(25),
+            Constraint::Percentage(50),
+        ])
+        .split(rows[1]);
+    let ledger_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(rows[2]);
+    render_announcements(f, app, chunks[2]);
+    render_leaderboards(f, app, standings_chunks[0], standings_chunks[1]);
+    render_standings_chart(f, app, standings_chunks[2]);
+    render_profit_and_loss(f, app, ledger_chunks[0]);
+    render_credits_chart(f, app, ledger_chunks[1]);
+
+    // Agent info
+    let agent_block = Block::default().borders(Borders::ALL).title("Agent Info");
+
+    if let Some(agent) = &app.agent {
+        // Create styled lines for the agent info
+        let lines = vec![
+            Line::from(vec![
+                Span::raw("Agent: "),
+                Span::styled(
+                    &agent.symbol,
+                    Style::default()
+                        .fg(Color::Green)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            ]),
+            Line::from(vec![
+                Span::raw("Credits: "),
+                Span::styled(
+                    agent.credits.to_string(),
+                    Style::default().fg(Color::Yellow),
+                ),
+            ]),
+            Line::from(vec![
+                Span::raw("HQ: "),
+                Span::styled(&agent.headquarters, Style::default().fg(Color::Cyan)),
+            ]),
+            Line::from(vec![
+                Span::raw("Faction: "),
+                Span::styled(&agent.starting_faction, Style::default().fg(Color::Magenta)),
+            ]),
+            Line::from(vec![
+                Span::raw("Ships: "),
+                Span::styled(
+                    agent.ship_count.to_string(),
+                    Style::default().fg(Color::Blue),
+                ),
+            ]),
+        ];
+
+        let agent_info = Paragraph::new(lines)
+            .block(agent_block)
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(agent_info, chunks[0]);
+    } else {
+        let loading = Paragraph::new("Loading agent data...")
+            .block(agent_block)
+            .wrap(Wrap { trim: true });
+        f.render_widget(loading, chunks[0]);
+    }
+
+    // Server status and universe stats
+    let status_block = Block::default().borders(Borders::ALL).title("Server");
+    let Some(status) = &app.status else {
+        let loading = Paragraph::new("Loading server status...")
+            .block(status_block)
+            .wrap(Wrap { trim: true });
+        f.render_widget(loading, chunks[1]);
+        return;
+    };
+    let field = |label: &'static str, value: String, color: Color| {
+        Line::from(vec![
+            Span::raw(label),
+            Span::styled(value, Style::default().fg(color)),
+        ])
+    };
+    let stats = &status.stats;
+    let mut status_lines = vec![
+        field("Status: ", status.status.clone(), Color::Green),
+        field("Version: ", status.version.clone(), Color::Blue),
+        field("Reset: ", status.reset_date.clone(), Color::Yellow),
+        field(
+            "Next reset: ",
+            format!(
+                "{} ({})",
+                status.server_resets.next, status.server_resets.frequency
+            ),
+            Color::Yellow,
+        ),
+        field("Agents: ", stats.agents.to_string(), Color::Cyan),
+        field("Ships: ", stats.ships.to_string(), Color::Cyan),
+        field(
+            "Systems: ",
+            format!("{} ({} waypoints)", stats.systems, stats.waypoints),
+            Color::Cyan,
+        ),
+    ];
+    if let Some(accounts) = stats.accounts {
+        status_lines.insert(4, field("Accounts: ", accounts.to_string(), Color::Cyan));
+    }
+
+    let status = Paragraph::new(status_lines)
+        .block(status_block)
+        .wrap(Wrap { trim: true });
+    f.render_widget(status, chunks[1]);
+}
+
+/// Server announcements followed by its links, scrolled with Up/Down.
+fn render_announcements(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Announcements (↑/↓ scroll)");
+    let Some(status) = &app.status else {
+        f.render_widget(block, area);
+        return;
+    };
+    let mut lines = Vec::new();
+    for announcement in &status.announcements {
+        lines.push(Line::from(Span::styled(
+            announcement.title.clone(),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )));
+        lines.extend(
+            announcement
+                .body
+                .lines()
+                .map(|line| Line::from(line.to_string())),
+        );
+        lines.push(Line::from(""));
+    }
+    for link in &status.links {
+        lines.push(Line::from(vec![
+            Span::raw(format!("{}: ", link.name)),
+            Span::styled(link.url.clone(), Style::default().fg(Color::Blue)),
+        ]));
+    }
+    let announcements = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: true })
+        .scroll((app.announcement_scroll, 0));
+    f.render_widget(announcements, area);
+}
+
+/// The credits and charts leaderboards, with our agent highlighted.
+fn render_leaderboards(f: &mut Frame, app: &App, credits_area: Rect, charts_area: Rect) {
+    let ours = app.agent.as_ref().map(|agent| agent.symbol.as_str());
+    let board = |title: &'static str, entries: Vec<(String, String)>, rank: Option<usize>| {
+        let mut lines: Vec<Line> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, (agent, score))| {
+                let style = if Some(agent.as_str()) == ours {
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(Color::Green)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                Line::from(Span::styled(
+                    format!("{:>3}. {:<16} {:>14}", i + 1, agent, score),
+                    style,
+                ))
+            })
+            .collect();
+        if rank.is_none() {
+            if let Some(ours) = ours {
+                lines.push(Line::from(Span::styled(
+                    format!("     {} is not ranked", ours),
+                    Style::default().fg(Color::DarkGray),
+                )));
+            }
+        }
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title))
+    };
+    let Some(latest) = app.standings.last() else {
+        f.render_widget(
+            Block::default().borders(Borders::ALL).title("Most Credits"),
+            credits_area,
+        );
+        f.render_widget(
+            Block::default().borders(Borders::ALL).title("Most Charts"),
+            charts_area,
+        );
+        return;
+    };
+    let ours_symbol = ours.unwrap_or_default();
+    f.render_widget(
+        board(
+            "Most Credits",
+            latest
+                .most_credits
+                .iter()
+                .map(|(agent, credits)| (agent.clone(), credits.to_string()))
+                .collect(),
+            latest.credits_rank(ours_symbol),
+        ),
+        credits_area,
+    );
+    f.render_widget(
+        board(
+            "Most Charts",
+            latest
+                .most_submitted_charts
+                .iter()
+                .map(|(agent, charts)| (agent.clone(), charts.to_string()))
+                .collect(),
+            latest.charts_rank(ours_symbol),
+        ),
+        charts_area,
+    );
+}
+
+/// Credits leaderboard positions over time for the agents on the latest
+/// board and ours. Higher on the chart is a better rank.
+fn render_standings_chart(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Credits Rank over Time");
+    let (Some(first), Some(latest)) = (app.standings.first(), app.standings.last()) else {
+        let empty = Paragraph::new("No leaderboard snapshots yet")
+            .block(block)
+            .wrap(Wrap { trim: true });
+        f.render_widget(empty, area);
+        return;
+    };
+
+    let ours = app.agent.as_ref().map(|agent| agent.symbol.clone());
+    let mut agents: Vec<String> = latest
+        .most_credits
+        .iter()
+        .map(|(agent, _)| agent.clone())
+        .collect();
+    if let Some(ours) = &ours {
+        if !agents.contains(ours) {
+            agents.push(ours.clone());
+        }
+    }
+    let start = first.time;
+    let now_hours = (Utc::now() - start).num_seconds() as f64 / 3600.0;
+    let depth = app
+        .standings
+        .iter()
+        .map(|snapshot| snapshot.most_credits.len())
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    // Ranks are plotted negated so first place sits at the top
+    let series: Vec<(String, Vec<(f64, f64)>)> = agents
+        .iter()
+        .map(|agent| {
+            let points = app
+                .standings
+                .iter()
+                .filter_map(|snapshot| {
+                    let rank = snapshot.credits_rank(agent)?;
+                    Some((
+                        (snapshot.time - start).num_seconds() as f64 / 3600.0,
+                        -(rank as f64),
+                    ))
+                })
+                .collect();
+            (agent.clone(), points)
+        })
+        .collect();
+    let palette = [
+        Color::Cyan,
+        Color::Magenta,
+        Color::Blue,
+        Color::Yellow,
+        Color::LightRed,
+        Color::LightCyan,
+        Color::LightMagenta,
+        Color::LightBlue,
+    ];
+    let datasets: Vec<Dataset> = series
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, points))| !points.is_empty())
+        .map(|(i, (agent, points))| {
+            let color = if Some(agent) == ours.as_ref() {
+                Color::Green
+            } else {
+                palette[i % palette.len()]
+            };
+            Dataset::default()
+                .name(agent.clone())
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(color))
+                .data(points)
+        })
+        .collect();
+    let chart = Chart::new(datasets)
+        .block(block)
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds([0.0, now_hours.max(1.0 / 60.0)])
+                .labels(vec![
+                    Span::raw(
+                        start
+                            .with_timezone(&chrono::Local)
+                            .format("%m-%d %H:%M")
+                            .to_string(),
+                    ),
+                    Span::raw("now"),
+                ]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds([-(depth as f64) - 0.5, -0.5])
+                .labels(vec![Span::raw(format!("#{}", depth)), Span::raw("#1")]),
+        );
+    f.render_widget(chart, area);
+}
+
+fn render_profit_and_loss(f: &mut Frame, app: &App, area: Rect) {
+    let period = app.pnl_period;
+    let title = match period {
+        Period::Hour => "Profit & Loss per Hour (p: per day)",
+        Period::Day => "Profit & Loss per Day (p: per hour)",
+    };
+    let block = Block::default().borders(Borders::ALL).title(title);
+
+    let report = ledger::profit_and_loss(&app.ledger, period, Grouping::Ship);
+    if report.is_empty() {
+        let empty = Paragraph::new("No transactions recorded yet")
+            .block(block)
+            .wrap(Wrap { trim: true });
+        f.render_widget(empty, area);
+        return;
+    }
+
+    let credits_color = |amount: i64| {
+        if amount >= 0 {
+            Color::Green
+        } else {
+            Color::Red
+        }
+    };
+    let mut lines = vec![Line::from(Span::styled(
+        format!(
+            "{:<16} {:<20} {:>10} {:>10} {:>10}",
+            "Period", "Ship", "Income", "Expenses", "Net"
+        ),
+        Style::default().add_modifier(Modifier::BOLD),
+    ))];
+    for row in &report {
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{:<16} ", period.label(row.period)),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Span::styled(
+                format!("{:<20} ", row.group.as_deref().unwrap_or("(agent)")),
+                Style::default().fg(Color::Cyan),
+            ),
+            Span::raw(format!("{:>10} {:>10} ", row.income, row.expenses)),
+            Span::styled(
+                format!("{:>10}", row.net()),
+                Style::default().fg(credits_color(row.net())),
+            ),
+        ]));
+    }
+
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn render_credits_chart(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Credits over Time");
+
+    let history = ledger::credit_history(&app.ledger);
+    let (Some(first), Some(last)) = (history.first(), history.last()) else {
+        let empty = Paragraph::new("No balance history yet")
+            .block(block)
+            .wrap(Wrap { trim: true });
+        f.render_widget(empty, area);
+        return;
+    };
+
+    // Hours since the first known balance against credits
+    let start = first.0;
+    let mut points: Vec<(f64, f64)> = history
+        .iter()
+        .map(|(time, credits)| {
+            (
+                (*time - start).num_seconds() as f64 / 3600.0,
+                *credits as f64,
+            )
+        })
+        .collect();
+    // Extend the last balance to now so a quiet period still shows as flat
+    let now_hours = (Utc::now() - start).num_seconds() as f64 / 3600.0;
+    points.push((now_hours, last.1 as f64));
+
+    let min_credits = points.iter().map(|p| p.1).fold(f64::MAX, f64::min);
+    let max_credits = points.iter().map(|p| p.1).fold(f64::MIN, f64::max);
+    let padding = ((max_credits - min_credits) * 0.1).max(1.0);
+    let y_bounds = [(min_credits - padding).max(0.0), max_credits + padding];
+    let x_bounds = [0.0, now_hours.max(1.0 / 60.0)];
+
+    let datasets = vec![Dataset::default()
+        .name("credits")
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(Color::Yellow))
+        .data(&points)];
+    let chart = Chart::new(datasets)
+        .block(block)
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds(x_bounds)
+                .labels(vec![
+                    Span::raw(
+                        start
+                            .with_timezone(&chrono::Local)
+                            .format("%m-%d %H:%M")
+                            .to_string(),
+                    ),
+                    Span::raw("now"),
+                ]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds(y_bounds)
+                .labels(vec![
+                    Span::raw(format!("{:.0}", y_bounds[0])),
+                    Span::raw(format!("{:.0}", y_bounds[1])),
+                ]),
+        );
+    f.render_widget(chart, area);
+}
+
+fn get_ship_role_color(ship_role: &str) -> Color {
+    match ship_role {
+        "COMMAND" => Color::Yellow,
+        "EXCAVATOR" => Color::LightRed,
+        "HAULER" => Color::Green,
+        "INTERCEPTOR" => Color::Red,
+        "EXPLORER" => Color::Blue,
+        "TRANSPORT" => Color::Cyan,
+        "CARRIER" => Color::Magenta,
+        "PATROL" => Color::LightBlue,
+        "SATELLITE" => Color::LightCyan,
+        "SURVEYOR" => Color::LightGreen,
+        _ => Color::Gray,
+    }
+}
+
+fn render_ships(f: &mut Frame, app: &mut App, area: Rect) {
+    let title = format!(
+        "Ships [filter: {}] [sort: {}] [group: {}] [marked: {}] (f/t filter {}, x clear, s sort, g group, Space/a mark)",
+        app.ship_filter,
+        app.ship_sort,
+        app.ship_group_by,
+        app.marked_ships.len(),
+        app.ship_filter_field
+    );
+    let ships_block = Block::default().borders(Borders::ALL).title(title);
+
+    if let Some(ships) = &app.ships {
+        let sections = app.ship_sections();
+        if ships.is_empty() || sections.is_empty() {
+            let message = if ships.is_empty() {
+                "No ships found"
+            } else {
+                "No ships match the filter, press x to clear it"
+            };
+            let no_ships = Paragraph::new(message)
+                .block(ships_block)
+                .wrap(Wrap { trim: true });
+            f.render_widget(no_ships, area);
+        } else {
+            let now = Utc::now();
+            let mut items: Vec<ListItem> = Vec::new();
+            for (section, indices) in &sections {
+                for (n, &i) in indices.iter().enumerate() {
+                    let ship = &ships[i];
+                    let notes = app.roster.notes(&ship.symbol);
+                    let ship_role = ship.registration.role.to_string();
+                    let role_color = get_ship_role_color(&ship_role);
+
+                    let mut lines = Vec::new();
+                    // Each section is headed by its first ship
+                    if n == 0 && !section.is_empty() {
+                        lines.push(Line::from(Span::styled(
+                            format!("── {} ({}) ──", section, indices.len()),
+                            Style::default()
+                                .fg(Color::Magenta)
+                                .add_modifier(Modifier::BOLD),
+                        )));
+                    }
+                    let mark = if app.marked_ships.contains(&ship.symbol) {
+                        "[x] "
+                    } else {
+                        "[ ] "
+                    };
+                    let mut name = vec![
+                        Span::styled(mark, Style::default().fg(Color::Green)),
+                        Span::raw("Ship: "),
+                        Span::styled(&ship.symbol, Style::default().fg(Color::Blue)),
+                    ];
+                    if let Some(nickname) = &notes.nickname {
+                        name.push(Span::styled(
+                            format!(" \"{}\"", nickname),
+                            Style::default().fg(Color::White),
+                        ));
+                    }
+                    name.push(Span::raw(" - "));
+                    name.push(Span::styled(ship_role, Style::default().fg(role_color)));
+                    if !notes.tags.is_empty() {
+                        name.push(Span::styled(
+                            format!(" [{}]", notes.tags.join(", ")),
+                            Style::default().fg(Color::Cyan),
+                        ));
+                    }
+                    lines.push(Line::from(name));
+
+                    let mut status = vec![
+                        Span::raw("Location: "),
+                        Span::styled(
+                            format!("{} {}", ship.nav.status, ship.nav.waypoint_symbol),
+                            Style::default().fg(Color::Yellow),
+                        ),
+                        Span::styled(
+                            format!(
+                                "  fuel {}/{}  cargo {}/{}",
+                                ship.fuel.current,
+                                ship.fuel.capacity,
+                                ship.cargo.units,
+                                ship.cargo.capacity
+                            ),
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                    ];
+                    if ship.nav.status == ShipNavStatus::InTransit {
+                        status.push(Span::styled(
+                            format!("  arriving {}", ship.nav.route.arrival),
+                            Style::default().fg(Color::DarkGray),
+                        ));
+                    }
+                    if let Some(remaining) = scan::cooldown_remaining(ship, now) {
+                        status.push(Span::styled(
+                            format!("  cooldown {}s", remaining),
+                            Style::default().fg(Color::DarkGray),
+                        ));
+                    }
+                    if let Some(behaviour) = app.behaviours.get(&ship.symbol) {
+                        status.push(Span::styled(
+                            format!("  {}", behaviour),
+                            Style::default().fg(Color::Magenta),
+                        ));
+                    }
+                    lines.push(Line::from(status));
+                    lines.push(Line::from(""));
+
+                    items.push(ListItem::new(lines));
+                }
+            }
+
+            let list = List::new(items)
+                .block(ships_block)
+                .highlight_style(Style::default().bg(Color::DarkGray))
+                .highlight_symbol(">> ");
+
+            f.render_stateful_widget(list, area, &mut app.ships_state);
+        }
+    } else {
+        let loading = Paragraph::new("Loading ships data...")
+            .block(ships_block)
+            .wrap(Wrap { trim: true });
+        f.render_widget(loading, area);
+    }
+}
+
+fn get_system_type_color(system_type: &str) -> Color {
+    match system_type {
+        "NEUTRON_STAR" => Color::Cyan,
+        "RED_STAR" => Color::Red,
+        "ORANGE_STAR" => Color::LightRed,
+        "BLUE_STAR" => Color::Blue,
+        "YOUNG_STAR" => Color::Yellow,
+        "WHITE_DWARF" => Color::White,
+        "BLACK_HOLE" => Color::DarkGray,
+        "HYPERGIANT" => Color::Magenta,
+        "NEBULA" => Color::LightMagenta,
+        "UNSTABLE" => Color::LightYellow,
+        _ => Color::Gray,
+    }
+}
+
+fn render_systems(f: &mut Frame, app: &mut App, area: Rect) {
+    // Split the area into two chunks: top for systems list, bottom for systems plot
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(area);
+
+    let systems_list_area = chunks[0];
+    let systems_plot_area = chunks[1];
+
+    // Render systems list
+    let systems_block = Block::default()
+        .borders(Borders::ALL)
+        .title("Systems (press g for galaxy map, w to search waypoints)");
+
+    match app.systems.as_ref() {
+        None => {
+            let loading_text = Text::from("Loading systems...");
+            let loading_paragraph = Paragraph::new(loading_text)
+                .block(systems_block)
+                .wrap(Wrap { trim: true });
+            f.render_widget(loading_paragraph, systems_list_area);
+        }
+        Some(systems) if systems.is_empty() => {
+            let empty_text = Text::from("No systems found.");
+            let empty_paragraph = Paragraph::new(empty_text)
+                .block(systems_block)
+                .wrap(Wrap { trim: true });
+            f.render_widget(empty_paragraph, systems_list_area);
+        }
+        Some(systems) => {
+            let systems_items: Vec<ListItem> = systems
+                .iter()
+                .map(|s| {
+                    let system_type = s.r#type.to_string();
+                    let color = get_system_type_color(&system_type);
+
+                    ListItem::new(Line::from(vec![
+                        Span::styled(
+                            format!("[{:^12}] ", system_type),
+                            Style::default().fg(color),
+                        ),
+                        Span::raw(format!("{} ", s.symbol)),
+                        Span::styled(
+                            format!("(x:{}, y:{})", s.x, s.y),
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                    ]))
+                })
+                .collect();
+
+            let systems_list = List::new(systems_items)
+                .block(systems_block)
+                .highlight_style(
+                    Style::default()
+                        .bg(Color::DarkGray)
+                        .add_modifier(Modifier::BOLD),
+                )
+                .highlight_symbol("> ");
+
+            f.render_stateful_widget(systems_list, systems_list_area, &mut app.systems_state);
+
+            // Render systems plot
+            render_system_plot(f, app, systems_plot_area, systems);
+        }
+    }
+}
+
+fn render_system_plot(
+    f: &mut Frame,
+    app: &App,
+    area: Rect,
+    systems: &[spacetraders_api::models::System],
+) {
+    if systems.is_empty() {
+        return;
+    }
+
+    // Find the min and max coordinates to set the boundaries
+    let (min_x, max_x, min_y, max_y) = systems.iter().fold(
+        (i32::MAX, i32::MIN, i32::MAX, i32::MIN),
+        |(min_x, max_x, min_y, max_y), system| {
+            (
+                min_x.min(system.x),
+                max_x.max(system.x),
+                min_y.min(system.y),
+                max_y.max(system.y),
+            )
+        },
+    );
+
+    // Create the plot block
+    let block = Block::default().borders(Borders::ALL).title("System Map");
+
+    let inner_area = block.inner(area);
+    f.render_widget(block, area);
+
+    let plot_width = inner_area.width as usize;
+    let plot_height = inner_area.height as usize;
+
+    // Add buffer space for plotting points
+    let x_range = (max_x - min_x + 1) as f64;
+    let y_range = (max_y - min_y + 1) as f64;
+
+    // Create a canvas to plot points on
+    let mut canvas = vec![vec![(' ', Color::Reset); plot_width]; plot_height];
+
+    // Plot systems
+    for system in systems {
+        // Map system coordinates to plot coordinates
+        let plot_x = ((system.x - min_x) as f64 / x_range * (plot_width as f64 - 1.0)) as usize;
+        let plot_y = ((max_y - system.y) as f64 / y_range * (plot_height as f64 - 1.0)) as usize;
+
+        if plot_x < plot_width && plot_y < plot_height {
+            let system_type = system.r#type.to_string();
+            let color = get_system_type_color(&system_type);
+            canvas[plot_y][plot_x] = ('●', color);
+        }
+    }
+
+    // Highlight selected system
+    if let Some(selected_idx) = app.systems_state.selected() {
+        if selected_idx < systems.len() {
+            let system = &systems[selected_idx];
+            let plot_x = ((system.x - min_x) as f64 / x_range * (plot_width as f64 - 1.0)) as usize;
+            let plot_y =
+                ((max_y - system.y) as f64 / y_range * (plot_height as f64 - 1.0)) as usize;
+
+            if plot_x < plot_width && plot_y < plot_height {
+                canvas[plot_y][plot_x] = ('★', Color::White);
+            }
+        }
+    }
+
+    // Convert canvas to text
+    let mut lines = Vec::new();
+    for row in canvas {
+        let mut spans = Vec::new();
+        for (ch, color) in row {
+            spans.push(Span::styled(ch.to_string(), Style::default().fg(color)));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    let text = Text::from(lines);
+    let paragraph = Paragraph::new(text);
+    f.render_widget(paragraph, inner_area);
+}
+
+/// The supply chain explorer: every good, what it is made from and feeds,
+/// and the cached markets trading it.
+fn render_markets(f: &mut Frame, app: &mut App, area: Rect) {
+    let goods = supply::goods(&app.cache.supply_chain);
+    if goods.is_empty() {
+        let message = Paragraph::new("Loading supply chain... press r to retry")
+            .block(Block::default().borders(Borders::ALL).title("Supply Chain"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(message, area);
+        return;
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .split(area);
+
+    let items: Vec<ListItem> = goods
+        .iter()
+        .map(|good| ListItem::new(Span::styled(good.clone(), Style::default().fg(Color::Blue))))
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Goods"))
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(list, chunks[0], &mut app.supply_state);
+
+    let Some(good) = app.supply_state.selected().and_then(|i| goods.get(i)) else {
+        return;
+    };
+    let report = supply::report(&app.cache.supply_chain, &app.cache.markets, good);
+    let goods_line = |label: &'static str, goods: &[String]| {
+        Line::from(vec![
+            Span::styled(label, Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(
+                if goods.is_empty() {
+                    String::from("nothing")
+                } else {
+                    goods.join(", ")
+                },
+                Style::default().fg(Color::Cyan),
+            ),
+        ])
+    };
+    let mut lines = vec![
+        goods_line("Made from: ", &report.inputs),
+        goods_line("Feeds: ", &report.feeds),
+        Line::from(""),
+    ];
+
+    if report.markets.is_empty() {
+        lines.push(Line::from("No cached market trades this good"));
+    }
+    for listing in &report.markets {
+        let role_color = match listing.role {
+            Role::Export => Color::Green,
+            Role::Import => Color::Yellow,
+            Role::Exchange => Color::Magenta,
+        };
+        let mut spans = vec![
+            Span::styled(
+                format!("{:<9}", listing.role.to_string()),
+                Style::default().fg(role_color),
+            ),
+            Span::raw(format!("{:<16}", listing.waypoint)),
+        ];
+        match (listing.supply, listing.activity) {
+            (Some(supply), activity) => {
+                spans.push(Span::raw(format!(
+                    " {} / {}",
+                    supply,
+                    activity.map(|a| a.to_string()).unwrap_or_default()
+                )));
+                spans.push(Span::styled(
+                    format!(
+                        "  buy {} sell {}",
+                        listing.purchase_price.unwrap_or_default(),
+                        listing.sell_price.unwrap_or_default()
+                    ),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            (None, _) => spans.push(Span::styled(
+                " not visited",
+                Style::default().fg(Color::DarkGray),
+            )),
+        }
+        lines.push(Line::from(spans));
+    }
+
+    if !report.bottlenecks.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "Bottlenecks",
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+    }
+    for bottleneck in &report.bottlenecks {
+        lines.push(Line::from(Span::styled(
+            format!(
+                "{} is short of {} ({})",
+                bottleneck.waypoint, bottleneck.input, bottleneck.supply
+            ),
+            Style::default().fg(Color::Red),
+        )));
+    }
+
+    let details = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Supply Chain: {}", report.good)),
+        )
+        .wrap(Wrap { trim: true });
+    f.render_widget(details, chunks[1]);
+}
+
+fn render_ship_detail(f: &mut Frame, app: &mut App, area: Rect) {
+    let ship_index = match app.selected_ship_index {
+        Some(index) => index,
+        None => {
+            // If no ship is selected, display an error message
+            let message = Paragraph::new("No ship selected")
+                .block(Block::default().borders(Borders::ALL).title("Error"))
+                .wrap(Wrap { trim: true });
+            f.render_widget(message, area);
+            return;
+        }
+    };
+
+    if let Some(ships) = &app.ships {
+        if ship_index >= ships.len() {
+            // If the selected index is out of bounds, display an error message
+            let message = Paragraph::new("Invalid ship selection")
+                .block(Block::default().borders(Borders::ALL).title("Error"))
+                .wrap(Wrap { trim: true });
+            f.render_widget(message, area);
+            return;
+        }
+
+        let ship = &ships[ship_index];
+        let ship_role = ship.registration.role.to_string();
+        let role_color = get_ship_role_color(&ship_role);
+
+        // Split the screen into sections
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3), // Header
+                Constraint::Length(8), // Basic info
+                Constraint::Length(7), // Condition
+                Constraint::Min(0),    // Additional info
+            ])
+            .split(area);
+
+        // Render header
+        let header = Paragraph::new(vec![Line::from(vec![Span::styled(
+            &ship.symbol,
+            Style::default()
+                .fg(Color::Blue)
+                .add_modifier(Modifier::BOLD),
+        )])])
+        .block(Block::default().borders(Borders::ALL).title("Ship Detail"))
+        .alignment(Alignment::Center);
+        f.render_widget(header, chunks[0]);
+
+        // Render basic info
+        let mut basic_info = vec![
+            Line::from(vec![
+                Span::styled("Role: ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::styled(&ship_role, Style::default().fg(role_color)),
+            ]),
+            Line::from(vec![
+                Span::styled(
+                    "Registration: ",
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(format!(
+                    "{} ({})",
+                    ship.registration.name, ship.registration.faction_symbol
+                )),
+            ]),
+            Line::from(vec![
+                Span::styled("Frame: ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(format!("{:?}", ship.frame.symbol)),
+            ]),
+            Line::from(vec![
+                Span::styled("Engine: ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(format!("{:?}", ship.engine.symbol)),
+            ]),
+            Line::from(vec![
+                Span::styled("Fuel: ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(format!("{}/{}", ship.fuel.current, ship.fuel.capacity)),
+                match rescue::assess(&app.cache, ships, ship, &[]) {
+                    Some(stranding) => Span::styled(
+                        format!(
+                            "  stranded, {:.0} from fuel at {} (ship rescue {})",
+                            stranding.distance, stranding.market, ship.symbol
+                        ),
+                        Style::default().fg(Color::Red),
+                    ),
+                    None => Span::raw(""),
+                },
+            ]),
+            Line::from(vec![
+                Span::styled("Cooldown: ", Style::default().add_modifier(Modifier::BOLD)),
+                match scan::cooldown_remaining(ship, Utc::now()) {
+                    Some(remaining) => Span::styled(
+                        format!("{}s", remaining),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                    None => Span::styled("ready", Style::default().fg(Color::Green)),
+                },
+            ]),
+        ];
+        if app.client.is_attached() {
+            basic_info.push(Line::from(vec![
+                Span::styled("Behaviour: ", Style::default().add_modifier(Modifier::BOLD)),
+                match app.behaviours.get(&ship.symbol) {
+                    Some(behaviour) => {
+                        Span::styled(behaviour.to_string(), Style::default().fg(Color::Magenta))
+                    }
+                    None => Span::styled("none", Style::default().fg(Color::DarkGray)),
+                },
+            ]));
+        }
+        let notes = app.roster.notes(&ship.symbol);
+        for (label, value) in [
+            ("Nickname: ", notes.nickname.clone()),
+            ("Group: ", notes.group.clone()),
+            (
+                "Tags: ",
+                (!notes.tags.is_empty()).then(|| notes.tags.join(", ")),
+            ),
+            ("Notes: ", (!notes.notes.is_empty()).then_some(notes.notes)),
+        ] {
+            if let Some(value) = value {
+                basic_info.push(Line::from(vec![
+                    Span::styled(label, Style::default().add_modifier(Modifier::BOLD)),
+                    Span::styled(value, Style::default().fg(Color::Cyan)),
+                ]));
+            }
+        }
+
+        let basic_info_widget = Paragraph::new(basic_info)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Ship Information"),
+            )
+            .wrap(Wrap { trim: true });
+        f.render_widget(basic_info_widget, chunks[1]);
+
+        render_ship_condition(f, app, ship, chunks[2]);
+
+        // Additional ship details: cargo, nav, etc.
+        let additonal_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(chunks[3]);
+
+        // Left side: Cargo and modules
+        let cargo_capacity = ship.cargo.capacity;
+        let cargo_units = ship.cargo.units;
+        let cargo_info = vec![
+            Line::from(vec![
+                Span::styled("Cargo: ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(format!("{}/{}", cargo_units, cargo_capacity)),
+            ]),
+            Line::from(""),
+            Line::from(vec![Span::styled(
+                "Modules: ",
+                Style::default().add_modifier(Modifier::BOLD),
+            )]),
+        ];
+        let mut modules_info = cargo_info;
+        for module in &ship.modules {
+            modules_info.push(Line::from(vec![
+                Span::raw(" - "),
+                Span::styled(
+                    format!("{:?}", module.symbol),
+                    Style::default().fg(Color::Cyan),
+                ),
+            ]));
+        }
+
+        let cargo_widget = Paragraph::new(modules_info)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Cargo & Modules (press f to refit)"),
+            )
+            .wrap(Wrap { trim: true });
+        f.render_widget(cargo_widget, additonal_chunks[0]);
+
+        // Right side: Navigation
+        let mut nav_info = vec![
+            Line::from(vec![
+                Span::styled("Status: ", Style::default().add_modifier(Modifier::BOLD)),
+                {
+                    let status_str = ship.nav.status.to_string();
+                    let status_color = match status_str.as_str() {
+                        "IN_TRANSIT" => Color::Yellow,
+                        "DOCKED" => Color::Green,
+                        "IN_ORBIT" => Color::Blue,
+                        _ => Color::White,
+                    };
+                    Span::styled(status_str, Style::default().fg(status_color))
+                },
+            ]),
+            Line::from(vec![
+                Span::styled("Location: ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(&ship.nav.waypoint_symbol),
+            ]),
+            Line::from(vec![Span::styled(
+                "Route: ",
+                Style::default().add_modifier(Modifier::BOLD),
+            )]),
+        ];
+
+        // Add route information
+        nav_info.push(Line::from(vec![
+            Span::raw("  From: "),
+            Span::styled(
+                &ship.nav.route.origin.symbol,
+                Style::default().fg(Color::Blue),
+            ),
+        ]));
+        nav_info.push(Line::from(vec![
+            Span::raw("  To: "),
+            Span::styled(
+                &ship.nav.route.destination.symbol,
+                Style::default().fg(Color::Green),
+            ),
+        ]));
+        nav_info.push(Line::from(vec![
+            Span::raw("  Arrival: "),
+            Span::styled(&ship.nav.route.arrival, Style::default().fg(Color::Yellow)),
+        ]));
+
+        let nav_widget = Paragraph::new(nav_info)
+            .block(Block::default().borders(Borders::ALL).title("Navigation"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(nav_widget, additonal_chunks[1]);
+    } else {
+        // If ships data is not loaded, display a loading message
+        let message = Paragraph::new("Loading ship data...")
+            .block(Block::default().borders(Borders::ALL).title("Loading"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(message, area);
+    }
+}
+
+fn get_condition_color(condition: f64) -> Color {
+    if condition < CONDITION_CRITICAL {
+        Color::Red
+    } else if condition < CONDITION_WARNING {
+        Color::Yellow
+    } else {
+        Color::Green
+    }
+}
+
+fn render_ship_condition(
+    f: &mut Frame,
+    app: &App,
+    ship: &spacetraders_api::models::Ship,
+    area: Rect,
+) {
+    let mut lines: Vec<Line> = condition::components(ship)
+        .iter()
+        .map(|(component, condition, integrity)| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{:<9}", component),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    format!("{:>4.0}%", condition * 100.0),
+                    Style::default().fg(get_condition_color(*condition)),
+                ),
+                Span::styled(
+                    format!("  integrity {:.0}%", integrity * 100.0),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ])
+        })
+        .collect();
+
+    lines.push(Line::from(vec![
+        Span::styled("Wear: ", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(match app.wear.wear_per_day(&ship.symbol) {
+            Some(wear) => format!("{:.1}% per day", wear * 100.0),
+            None => String::from("not enough history yet"),
+        }),
+    ]));
+
+    let advice = match &app.assessment {
+        Some(assessment) if assessment.ship == ship.symbol => {
+            let color = match assessment.advice {
+                Advice::KeepRunning => Color::Green,
+                Advice::Repair => Color::Yellow,
+                Advice::Scrap => Color::Red,
+            };
+            vec![
+                Span::styled(
+                    assessment.advice.to_string(),
+                    Style::default().fg(color).add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(format!(" - {}", assessment.reason)),
+            ]
+        }
+        _ => vec![Span::styled(
+            "press c to check",
+            Style::default().fg(Color::DarkGray),
+        )],
+    };
+    let mut advice_line = vec![Span::styled(
+        "Advisor: ",
+        Style::default().add_modifier(Modifier::BOLD),
+    )];
+    advice_line.extend(advice);
+    lines.push(Line::from(advice_line));
+
+    let widget = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title("Condition"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(widget, area);
+}
+
+fn render_system_detail(f: &mut Frame, app: &mut App, area: Rect) {
+    let system_index = match app.selected_system_index {
+        Some(index) => index,
+        None => {
+            // If no system is selected, display an error message
+            let message = Paragraph::new("No system selected")
+                .block(Block::default().borders(Borders::ALL).title("Error"))
+                .wrap(Wrap { trim: true });
+            f.render_widget(message, area);
+            return;
+        }
+    };
+
+    if let Some(systems) = &app.systems {
+        if system_index >= systems.len() {
+            // If the selected index is out of bounds, display an error message
+            let message = Paragraph::new("Invalid system selection")
+                .block(Block::default().borders(Borders::ALL).title("Error"))
+                .wrap(Wrap { trim: true });
+            f.render_widget(message, area);
             return;
         }
 
@@ -1081,443 +3780,2132 @@ fn render_system_detail(f: &mut Frame, app: &mut App, area: Rect) {
         let system_type = system.r#type.to_string();
         let system_color = get_system_type_color(&system_type);
 
-        // Split the screen into sections
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(3), // Header
-                Constraint::Length(7), // Basic info
-                Constraint::Min(0),    // Waypoints section
-            ])
-            .split(area);
+        // Split the screen into sections
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3), // Header
+                Constraint::Length(7), // Basic info
+                Constraint::Min(0),    // Waypoints section
+            ])
+            .split(area);
+
+        // Render system header
+        let header_text = Text::from(vec![Line::from(vec![
+            Span::raw("System: "),
+            Span::styled(&system.symbol, Style::default().fg(Color::Green)),
+            Span::raw(" - Type: "),
+            Span::styled(&system_type, Style::default().fg(system_color)),
+        ])]);
+        let header = Paragraph::new(header_text)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("System Detail"),
+            )
+            .wrap(Wrap { trim: true });
+        f.render_widget(header, chunks[0]);
+
+        // Render basic info
+        let basic_info = vec![
+            Line::from(vec![
+                Span::styled("Type: ", Style::default().add_modifier(Modifier::BOLD)),
+                {
+                    let system_type_str = system.r#type.to_string();
+                    let type_color = get_system_type_color(&system_type_str);
+                    Span::styled(system_type_str, Style::default().fg(type_color))
+                },
+            ]),
+            Line::from(vec![
+                Span::styled("Position: ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(format!("X: {}, Y: {}", system.x, system.y)),
+            ]),
+            Line::from(vec![
+                Span::styled("Waypoints: ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(system.waypoints.len().to_string()),
+            ]),
+            Line::from(vec![
+                Span::styled("Factions: ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(system.factions.len().to_string()),
+            ]),
+        ];
+
+        let basic_info_widget = Paragraph::new(basic_info)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("System Information"),
+            )
+            .wrap(Wrap { trim: true });
+        f.render_widget(basic_info_widget, chunks[1]);
+
+        // Split waypoints section: list on top, map on bottom
+        let waypoints_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(chunks[2]);
+
+        let waypoints_list_area = waypoints_chunks[0];
+        let waypoints_plot_area = waypoints_chunks[1];
+
+        // Render waypoints list
+        if system.waypoints.is_empty() {
+            let no_waypoints = Paragraph::new("No waypoints in this system")
+                .block(Block::default().borders(Borders::ALL).title("Waypoints"))
+                .wrap(Wrap { trim: true });
+            f.render_widget(no_waypoints, waypoints_list_area);
+        } else {
+            let waypoint_items: Vec<ListItem> = system
+                .waypoints
+                .iter()
+                .map(|waypoint| {
+                    let waypoint_type = waypoint.r#type.to_string();
+                    let waypoint_color = match waypoint_type.as_str() {
+                        "PLANET" => Color::Green,
+                        "GAS_GIANT" => Color::LightRed,
+                        "MOON" => Color::White,
+                        "ORBITAL_STATION" => Color::Blue,
+                        "JUMP_GATE" => Color::Magenta,
+                        "ASTEROID_FIELD" => Color::Yellow,
+                        "NEBULA" => Color::LightMagenta,
+                        "DEBRIS_FIELD" => Color::DarkGray,
+                        _ => Color::Gray,
+                    };
+
+                    let lines = vec![Line::from(vec![
+                        Span::styled(&waypoint.symbol, Style::default().fg(Color::Cyan)),
+                        Span::raw(" - "),
+                        Span::styled(waypoint_type.clone(), Style::default().fg(waypoint_color)),
+                        Span::raw(format!(" ({}, {})", waypoint.x, waypoint.y)),
+                    ])];
+
+                    ListItem::new(lines)
+                })
+                .collect();
+
+            let waypoints_list = List::new(waypoint_items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Waypoints (press Enter for details)"),
+                )
+                .highlight_style(Style::default().bg(Color::DarkGray))
+                .highlight_symbol(">> ");
+
+            // Using app's waypoints_state for selection
+            f.render_stateful_widget(
+                waypoints_list,
+                waypoints_list_area,
+                &mut app.waypoints_state,
+            );
+
+            // Render waypoints plot
+            render_waypoints_plot(f, app, waypoints_plot_area, system);
+        }
+    } else {
+        // If systems data is not loaded, display a loading message
+        let message = Paragraph::new("Loading system data...")
+            .block(Block::default().borders(Borders::ALL).title("Loading"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(message, area);
+    }
+}
+
+fn get_waypoint_type_color(waypoint_type: &str) -> Color {
+    match waypoint_type {
+        "PLANET" => Color::Green,
+        "GAS_GIANT" => Color::LightRed,
+        "MOON" => Color::White,
+        "ORBITAL_STATION" => Color::Blue,
+        "JUMP_GATE" => Color::Magenta,
+        "ASTEROID_FIELD" => Color::Yellow,
+        "NEBULA" => Color::LightMagenta,
+        "DEBRIS_FIELD" => Color::DarkGray,
+        _ => Color::Gray,
+    }
+}
+
+fn get_waypoint_type_char(waypoint_type: &str) -> char {
+    match waypoint_type {
+        "PLANET" => 'P',
+        "GAS_GIANT" => 'G',
+        "MOON" => 'm',
+        "ORBITAL_STATION" => 'S',
+        "JUMP_GATE" => 'J',
+        "ASTEROID_FIELD" => '∗',
+        "NEBULA" => '≈',
+        "DEBRIS_FIELD" => '⦿',
+        _ => '•',
+    }
+}
+
+/// Map marker for waypoints whose traits matter more than their type:
+/// shipyards, fuel stations, markets and minable deposits.
+fn get_waypoint_feature_marker(
+    waypoint: &spacetraders_api::models::Waypoint,
+) -> Option<(char, Color)> {
+    let has_trait =
+        |symbol: WaypointTraitSymbol| waypoint.traits.iter().any(|t| t.symbol == symbol);
+
+    if has_trait(WaypointTraitSymbol::Shipyard) {
+        Some(('Y', Color::LightBlue))
+    } else if waypoint.r#type == WaypointType::FuelStation {
+        Some(('F', Color::LightRed))
+    } else if has_trait(WaypointTraitSymbol::Marketplace) {
+        Some(('$', Color::LightGreen))
+    } else if has_trait(WaypointTraitSymbol::CommonMetalDeposits)
+        || has_trait(WaypointTraitSymbol::PreciousMetalDeposits)
+        || has_trait(WaypointTraitSymbol::RareMetalDeposits)
+        || has_trait(WaypointTraitSymbol::MineralDeposits)
+    {
+        Some(('∗', Color::LightYellow))
+    } else {
+        None
+    }
+}
+
+/// Position of a ship inside its system. Ships in transit are placed along
+/// the line from origin to destination by the fraction of flight time elapsed.
+fn ship_position(ship: &spacetraders_api::models::Ship, now: DateTime<Utc>) -> (f64, f64) {
+    let route = &ship.nav.route;
+    let destination = (route.destination.x as f64, route.destination.y as f64);
+    if ship.nav.status != ShipNavStatus::InTransit {
+        return destination;
+    }
+
+    let origin = (route.origin.x as f64, route.origin.y as f64);
+    let (Ok(departure), Ok(arrival)) = (
+        DateTime::parse_from_rfc3339(&route.departure_time),
+        DateTime::parse_from_rfc3339(&route.arrival),
+    ) else {
+        return origin;
+    };
+    let total = (arrival - departure).num_milliseconds();
+    if total <= 0 {
+        return destination;
+    }
+    let progress = ((now - departure.with_timezone(&Utc)).num_milliseconds() as f64 / total as f64)
+        .clamp(0.0, 1.0);
+    (
+        origin.0 + (destination.0 - origin.0) * progress,
+        origin.1 + (destination.1 - origin.1) * progress,
+    )
+}
+
+fn render_waypoints_plot(
+    f: &mut Frame,
+    app: &App,
+    area: Rect,
+    system: &spacetraders_api::models::System,
+) {
+    let waypoints = &system.waypoints;
+    if waypoints.is_empty() {
+        return;
+    }
+
+    // Find the min and max coordinates to set the boundaries
+    let (min_x, max_x, min_y, max_y) = waypoints.iter().fold(
+        (i32::MAX, i32::MIN, i32::MAX, i32::MIN),
+        |(min_x, max_x, min_y, max_y), waypoint| {
+            (
+                min_x.min(waypoint.x),
+                max_x.max(waypoint.x),
+                min_y.min(waypoint.y),
+                max_y.max(waypoint.y),
+            )
+        },
+    );
+
+    // Create the plot block
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Waypoints Map (Y shipyard, F fuel, $ market, ∗ deposits, ▲ ship, ∙ planned)");
+
+    let inner_area = block.inner(area);
+    f.render_widget(block, area);
+
+    let plot_width = inner_area.width as usize;
+    let plot_height = inner_area.height as usize;
+
+    // Add buffer space for plotting points
+    let x_range = (max_x - min_x + 1).max(2) as f64; // Ensure range is at least 2
+    let y_range = (max_y - min_y + 1).max(2) as f64; // Ensure range is at least 2
+
+    // Map system coordinates to plot coordinates
+    let to_plot = |x: f64, y: f64| -> Option<(usize, usize)> {
+        let plot_x = (x - min_x as f64) / x_range * (plot_width as f64 - 1.0);
+        let plot_y = (max_y as f64 - y) / y_range * (plot_height as f64 - 1.0);
+        if plot_x < 0.0 || plot_y < 0.0 {
+            return None;
+        }
+        let (plot_x, plot_y) = (plot_x as usize, plot_y as usize);
+        (plot_x < plot_width && plot_y < plot_height).then_some((plot_x, plot_y))
+    };
+
+    // Create a canvas to plot points on
+    let mut canvas = vec![vec![(' ', Color::Reset); plot_width]; plot_height];
+
+    let ships: Vec<&spacetraders_api::models::Ship> = app
+        .ships
+        .iter()
+        .flatten()
+        .filter(|ship| ship.nav.system_symbol == system.symbol)
+        .collect();
+
+    let steps = plot_width.max(plot_height);
+    let mut plot_leg = |(x1, y1): (f64, f64), (x2, y2): (f64, f64), marker: (char, Color)| {
+        for step in 0..=steps {
+            let t = step as f64 / steps.max(1) as f64;
+            if let Some((px, py)) = to_plot(x1 + (x2 - x1) * t, y1 + (y2 - y1) * t) {
+                canvas[py][px] = marker;
+            }
+        }
+    };
+
+    // Plot the stops assigned behaviours have planned, then the legs of
+    // ships in transit, underneath everything else
+    for ship in &ships {
+        let Some(behaviour) = app.behaviours.get(&ship.symbol) else {
+            continue;
+        };
+        let route = &ship.nav.route;
+        let mut from = (route.destination.x as f64, route.destination.y as f64);
+        for stop in behaviour.planned_stops(ship, &app.cache) {
+            let Some(waypoint) = waypoints.iter().find(|w| w.symbol == stop) else {
+                break;
+            };
+            let to = (waypoint.x as f64, waypoint.y as f64);
+            plot_leg(from, to, ('∙', Color::Blue));
+            from = to;
+        }
+    }
+    for ship in ships
+        .iter()
+        .filter(|ship| ship.nav.status == ShipNavStatus::InTransit)
+    {
+        let route = &ship.nav.route;
+        plot_leg(
+            (route.origin.x as f64, route.origin.y as f64),
+            (route.destination.x as f64, route.destination.y as f64),
+            ('·', Color::DarkGray),
+        );
+    }
+
+    // Plot the star at center (0,0)
+    if let Some((star_x, star_y)) = to_plot(0.0, 0.0) {
+        canvas[star_y][star_x] = ('★', Color::Yellow);
+    }
+
+    // Plot waypoints, preferring trait markers once traits have been fetched
+    let details = app.cache.waypoints.get(&system.symbol);
+    for waypoint in waypoints {
+        if let Some((plot_x, plot_y)) = to_plot(waypoint.x as f64, waypoint.y as f64) {
+            let waypoint_type = waypoint.r#type.to_string();
+            let feature = details
+                .and_then(|details| details.iter().find(|w| w.symbol == waypoint.symbol))
+                .and_then(get_waypoint_feature_marker);
+            canvas[plot_y][plot_x] = feature.unwrap_or((
+                get_waypoint_type_char(&waypoint_type),
+                get_waypoint_type_color(&waypoint_type),
+            ));
+        }
+    }
+
+    // Highlight selected waypoint
+    if let Some(selected_idx) = app.waypoints_state.selected() {
+        if let Some(waypoint) = waypoints.get(selected_idx) {
+            if let Some((plot_x, plot_y)) = to_plot(waypoint.x as f64, waypoint.y as f64) {
+                // Keep the waypoint's character but make it bright white
+                canvas[plot_y][plot_x].1 = Color::White;
+            }
+        }
+    }
+
+    // Plot our ships on top
+    let now = Utc::now();
+    for ship in &ships {
+        let (x, y) = ship_position(ship, now);
+        if let Some((plot_x, plot_y)) = to_plot(x, y) {
+            let color = if ship.nav.status == ShipNavStatus::InTransit {
+                Color::LightCyan
+            } else {
+                Color::LightGreen
+            };
+            canvas[plot_y][plot_x] = ('▲', color);
+        }
+    }
+
+    // Convert canvas to text
+    let mut lines = Vec::new();
+    for row in canvas {
+        let mut spans = Vec::new();
+        for (ch, color) in row {
+            spans.push(Span::styled(ch.to_string(), Style::default().fg(color)));
+        }
+        lines.push(Line::from(spans));
+    }
+
+    let text = Text::from(lines);
+    let paragraph = Paragraph::new(text);
+    f.render_widget(paragraph, inner_area);
+}
 
-        // Render system header
-        let header_text = Text::from(vec![Line::from(vec![
-            Span::raw("System: "),
-            Span::styled(&system.symbol, Style::default().fg(Color::Green)),
-            Span::raw(" - Type: "),
-            Span::styled(&system_type, Style::default().fg(system_color)),
-        ])]);
-        let header = Paragraph::new(header_text)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title("System Detail"),
-            )
-            .wrap(Wrap { trim: true });
-        f.render_widget(header, chunks[0]);
+fn render_waypoint_detail(f: &mut Frame, app: &mut App, area: Rect) {
+    let waypoint_index = match app.selected_waypoint_index {
+        Some(index) => index,
+        None => {
+            // If no waypoint is selected, display an error message
+            let message = Paragraph::new("No waypoint selected")
+                .block(Block::default().borders(Borders::ALL).title("Error"))
+                .wrap(Wrap { trim: true });
+            f.render_widget(message, area);
+            return;
+        }
+    };
 
-        // Render basic info
-        let basic_info = vec![
-            Line::from(vec![
-                Span::styled("Type: ", Style::default().add_modifier(Modifier::BOLD)),
-                {
-                    let system_type_str = system.r#type.to_string();
-                    let type_color = get_system_type_color(&system_type_str);
-                    Span::styled(system_type_str, Style::default().fg(type_color))
-                },
-            ]),
-            Line::from(vec![
-                Span::styled("Position: ", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(format!("X: {}, Y: {}", system.x, system.y)),
-            ]),
-            Line::from(vec![
-                Span::styled("Waypoints: ", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(system.waypoints.len().to_string()),
-            ]),
-            Line::from(vec![
-                Span::styled("Factions: ", Style::default().add_modifier(Modifier::BOLD)),
-                Span::raw(system.factions.len().to_string()),
-            ]),
-        ];
+    if let Some(systems) = &app.systems {
+        if let Some(system_index) = app.selected_system_index {
+            if system_index < systems.len() {
+                let system = &systems[system_index];
+                if waypoint_index >= system.waypoints.len() {
+                    // If the selected index is out of bounds, display an error message
+                    let message = Paragraph::new("Invalid waypoint selection")
+                        .block(Block::default().borders(Borders::ALL).title("Error"))
+                        .wrap(Wrap { trim: true });
+                    f.render_widget(message, area);
+                    return;
+                }
 
-        let basic_info_widget = Paragraph::new(basic_info)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title("System Information"),
-            )
+                let waypoint = &system.waypoints[waypoint_index];
+                let waypoint_type = waypoint.r#type.to_string();
+                let waypoint_color = match waypoint_type.as_str() {
+                    "PLANET" => Color::Green,
+                    "GAS_GIANT" => Color::LightRed,
+                    "MOON" => Color::White,
+                    "ORBITAL_STATION" => Color::Blue,
+                    "JUMP_GATE" => Color::Magenta,
+                    "ASTEROID_FIELD" => Color::Yellow,
+                    "NEBULA" => Color::LightMagenta,
+                    "DEBRIS_FIELD" => Color::DarkGray,
+                    _ => Color::Gray,
+                };
+
+                // Split the screen into sections
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([
+                        Constraint::Length(3), // Header
+                        Constraint::Length(8), // Basic info
+                        Constraint::Min(0),    // Traits and other details
+                    ])
+                    .split(area);
+
+                // Render header
+                let header = Paragraph::new(vec![
+                    Line::from(vec![Span::styled(
+                        &waypoint.symbol,
+                        Style::default()
+                            .fg(Color::Cyan)
+                            .add_modifier(Modifier::BOLD),
+                    )]),
+                    Line::from(vec![Span::styled(
+                        format!("System: {}", system.symbol),
+                        Style::default().fg(Color::Gray),
+                    )]),
+                ])
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Waypoint Detail"),
+                )
+                .alignment(Alignment::Center);
+                f.render_widget(header, chunks[0]);
+
+                // Render basic info
+                let basic_info = vec![
+                    Line::from(vec![
+                        Span::styled("Type: ", Style::default().add_modifier(Modifier::BOLD)),
+                        Span::styled(&waypoint_type, Style::default().fg(waypoint_color)),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("Position: ", Style::default().add_modifier(Modifier::BOLD)),
+                        Span::raw(format!("X: {}, Y: {}", waypoint.x, waypoint.y)),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("Orbits: ", Style::default().add_modifier(Modifier::BOLD)),
+                        Span::raw(match &waypoint.orbits {
+                            Some(orbits) => orbits,
+                            None => "None",
+                        }),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("Orbitals: ", Style::default().add_modifier(Modifier::BOLD)),
+                        Span::raw(format!("{} objects", waypoint.orbitals.len())),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("Traits: ", Style::default().add_modifier(Modifier::BOLD)),
+                        Span::raw(match &app.waypoint {
+                            Some(details) if details.symbol == waypoint.symbol => details
+                                .traits
+                                .iter()
+                                .map(|t| t.symbol.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                            _ => String::from("Loading..."),
+                        }),
+                    ]),
+                ];
+
+                let basic_info_widget = Paragraph::new(basic_info)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title("Waypoint Information"),
+                    )
+                    .wrap(Wrap { trim: true });
+                f.render_widget(basic_info_widget, chunks[1]);
+
+                // Split bottom area for orbitals and system details (top/bottom)
+                let bottom_chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .split(chunks[2]);
+
+                // Render orbitals on top
+                let orbitals_info = if waypoint.orbitals.is_empty() {
+                    vec![Line::from("No orbital bodies")]
+                } else {
+                    waypoint
+                        .orbitals
+                        .iter()
+                        .map(|orbital| {
+                            Line::from(vec![
+                                Span::styled("• ", Style::default().fg(Color::Blue)),
+                                Span::styled(
+                                    &orbital.symbol,
+                                    Style::default()
+                                        .fg(Color::Cyan)
+                                        .add_modifier(Modifier::BOLD),
+                                ),
+                            ])
+                        })
+                        .collect()
+                };
+
+                let orbitals_widget = Paragraph::new(orbitals_info)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title("Orbital Bodies"),
+                    )
+                    .wrap(Wrap { trim: true });
+                f.render_widget(orbitals_widget, bottom_chunks[0]);
+
+                // Render system information below orbitals
+                let system_info = vec![
+                    Line::from(vec![
+                        Span::styled("System: ", Style::default().add_modifier(Modifier::BOLD)),
+                        Span::styled(system.symbol.clone(), Style::default().fg(Color::Green)),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("Type: ", Style::default().add_modifier(Modifier::BOLD)),
+                        {
+                            let system_type_str = system.r#type.to_string();
+                            let type_color = get_system_type_color(&system_type_str);
+                            Span::styled(system_type_str, Style::default().fg(type_color))
+                        },
+                    ]),
+                    Line::from(vec![
+                        Span::styled("Position: ", Style::default().add_modifier(Modifier::BOLD)),
+                        Span::raw(format!("X: {}, Y: {}", system.x, system.y)),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("Waypoints: ", Style::default().add_modifier(Modifier::BOLD)),
+                        Span::raw(format!("{} total", system.waypoints.len())),
+                    ]),
+                ];
+
+                let system_widget = Paragraph::new(system_info)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title("System Information"),
+                    )
+                    .wrap(Wrap { trim: true });
+                f.render_widget(system_widget, bottom_chunks[1]);
+            }
+        }
+    } else {
+        // If systems data is not loaded, display a loading message
+        let message = Paragraph::new("Loading system data...")
+            .block(Block::default().borders(Borders::ALL).title("Loading"))
             .wrap(Wrap { trim: true });
-        f.render_widget(basic_info_widget, chunks[1]);
+        f.render_widget(message, area);
+    }
+}
 
-        // Split waypoints section: list on top, map on bottom
-        let waypoints_chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-            .split(chunks[2]);
+fn render_shipyard(f: &mut Frame, app: &mut App, area: Rect) {
+    let Some(shipyard) = &app.shipyard else {
+        let message = Paragraph::new("Loading shipyard data...")
+            .block(Block::default().borders(Borders::ALL).title("Loading"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(message, area);
+        return;
+    };
+
+    // Split the screen into sections
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3),      // Header
+            Constraint::Min(0),         // Ships for sale
+            Constraint::Percentage(30), // Transactions
+        ])
+        .split(area);
 
-        let waypoints_list_area = waypoints_chunks[0];
-        let waypoints_plot_area = waypoints_chunks[1];
+    // Render header
+    let credits = app
+        .agent
+        .as_ref()
+        .map(|agent| agent.credits.to_string())
+        .unwrap_or_else(|| String::from("?"));
+    let header = Paragraph::new(Line::from(vec![
+        Span::styled(
+            &shipyard.symbol,
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::raw("  Modifications fee: "),
+        Span::styled(
+            shipyard.modifications_fee.to_string(),
+            Style::default().fg(Color::Yellow),
+        ),
+        Span::raw("  Credits: "),
+        Span::styled(credits, Style::default().fg(Color::Yellow)),
+    ]))
+    .block(Block::default().borders(Borders::ALL).title("Shipyard"))
+    .alignment(Alignment::Center);
+    f.render_widget(header, chunks[0]);
+
+    let ships_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(chunks[1]);
 
-        // Render waypoints list
-        if system.waypoints.is_empty() {
-            let no_waypoints = Paragraph::new("No waypoints in this system")
-                .block(Block::default().borders(Borders::ALL).title("Waypoints"))
-                .wrap(Wrap { trim: true });
-            f.render_widget(no_waypoints, waypoints_list_area);
-        } else {
-            let waypoint_items: Vec<ListItem> = system
-                .waypoints
+    match &shipyard.ships {
+        Some(ships) => {
+            let items: Vec<ListItem> = ships
                 .iter()
-                .map(|waypoint| {
-                    let waypoint_type = waypoint.r#type.to_string();
-                    let waypoint_color = match waypoint_type.as_str() {
-                        "PLANET" => Color::Green,
-                        "GAS_GIANT" => Color::LightRed,
-                        "MOON" => Color::White,
-                        "ORBITAL_STATION" => Color::Blue,
-                        "JUMP_GATE" => Color::Magenta,
-                        "ASTEROID_FIELD" => Color::Yellow,
-                        "NEBULA" => Color::LightMagenta,
-                        "DEBRIS_FIELD" => Color::DarkGray,
-                        _ => Color::Gray,
-                    };
-
-                    let lines = vec![Line::from(vec![
-                        Span::styled(&waypoint.symbol, Style::default().fg(Color::Cyan)),
+                .map(|ship| {
+                    ListItem::new(Line::from(vec![
+                        Span::styled(&ship.name, Style::default().fg(Color::Blue)),
                         Span::raw(" - "),
-                        Span::styled(waypoint_type.clone(), Style::default().fg(waypoint_color)),
-                        Span::raw(format!(" ({}, {})", waypoint.x, waypoint.y)),
-                    ])];
-
-                    ListItem::new(lines)
+                        Span::styled(
+                            ship.purchase_price.to_string(),
+                            Style::default().fg(Color::Yellow),
+                        ),
+                        Span::styled(
+                            format!(" ({})", ship.supply),
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                    ]))
                 })
                 .collect();
 
-            let waypoints_list = List::new(waypoint_items)
+            let list = List::new(items)
                 .block(
                     Block::default()
                         .borders(Borders::ALL)
-                        .title("Waypoints (press Enter for details)"),
+                        .title("Ships for Sale (press b to buy)"),
                 )
                 .highlight_style(Style::default().bg(Color::DarkGray))
                 .highlight_symbol(">> ");
+            f.render_stateful_widget(list, ships_chunks[0], &mut app.shipyard_state);
 
-            // Using app's waypoints_state for selection
-            f.render_stateful_widget(
-                waypoints_list,
-                waypoints_list_area,
-                &mut app.waypoints_state,
-            );
+            if let Some(ship) = app.shipyard_state.selected().and_then(|i| ships.get(i)) {
+                render_shipyard_ship(f, ship, ships_chunks[1]);
+            }
+        }
+        None => {
+            // Without a ship at the waypoint only the available types are listed
+            let items: Vec<ListItem> = shipyard
+                .ship_types
+                .iter()
+                .map(|ship_type| {
+                    ListItem::new(Span::styled(
+                        ship_type.r#type.to_string(),
+                        Style::default().fg(Color::Blue),
+                    ))
+                })
+                .collect();
+
+            let list = List::new(items)
+                .block(Block::default().borders(Borders::ALL).title("Ship Types"))
+                .highlight_style(Style::default().bg(Color::DarkGray))
+                .highlight_symbol(">> ");
+            f.render_stateful_widget(list, ships_chunks[0], &mut app.shipyard_state);
+
+            let message = Paragraph::new(
+                "Send a ship to this waypoint to see prices and ship specifications.",
+            )
+            .block(Block::default().borders(Borders::ALL).title("Ship Details"))
+            .wrap(Wrap { trim: true });
+            f.render_widget(message, ships_chunks[1]);
+        }
+    }
+
+    // Render transaction history
+    let transactions = shipyard.transactions.as_deref().unwrap_or_default();
+    let transaction_lines = if transactions.is_empty() {
+        vec![Line::from("No recent transactions")]
+    } else {
+        transactions
+            .iter()
+            .map(|transaction| {
+                Line::from(vec![
+                    Span::styled(&transaction.timestamp, Style::default().fg(Color::DarkGray)),
+                    Span::raw(" "),
+                    Span::styled(&transaction.agent_symbol, Style::default().fg(Color::Green)),
+                    Span::raw(format!(" bought {} for ", transaction.ship_type)),
+                    Span::styled(
+                        transaction.price.to_string(),
+                        Style::default().fg(Color::Yellow),
+                    ),
+                ])
+            })
+            .collect()
+    };
+
+    let transactions_widget = Paragraph::new(transaction_lines)
+        .block(Block::default().borders(Borders::ALL).title("Transactions"))
+        .wrap(Wrap { trim: true });
+    f.render_widget(transactions_widget, chunks[2]);
+}
+
+fn render_shipyard_ship(f: &mut Frame, ship: &spacetraders_api::models::ShipyardShip, area: Rect) {
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled("Type: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(ship.r#type.to_string(), Style::default().fg(Color::Cyan)),
+        ]),
+        Line::from(vec![
+            Span::styled("Price: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(
+                ship.purchase_price.to_string(),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
+        Line::from(vec![
+            Span::styled("Frame: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!(
+                "{} ({} module slots, {} mounting points, fuel {})",
+                ship.frame.name,
+                ship.frame.module_slots,
+                ship.frame.mounting_points,
+                ship.frame.fuel_capacity
+            )),
+        ]),
+        Line::from(vec![
+            Span::styled("Engine: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!(
+                "{} (speed {})",
+                ship.engine.name, ship.engine.speed
+            )),
+        ]),
+        Line::from(vec![
+            Span::styled("Reactor: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!(
+                "{} (power {})",
+                ship.reactor.name, ship.reactor.power_output
+            )),
+        ]),
+        Line::from(vec![
+            Span::styled("Crew: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!(
+                "{} required, {} capacity",
+                ship.crew.required, ship.crew.capacity
+            )),
+        ]),
+        Line::from(""),
+        Line::from(vec![Span::styled(
+            "Modules: ",
+            Style::default().add_modifier(Modifier::BOLD),
+        )]),
+    ];
+    for module in &ship.modules {
+        lines.push(Line::from(vec![
+            Span::raw(" - "),
+            Span::styled(&module.name, Style::default().fg(Color::Cyan)),
+        ]));
+    }
+    lines.push(Line::from(vec![Span::styled(
+        "Mounts: ",
+        Style::default().add_modifier(Modifier::BOLD),
+    )]));
+    for mount in &ship.mounts {
+        lines.push(Line::from(vec![
+            Span::raw(" - "),
+            Span::styled(&mount.name, Style::default().fg(Color::Magenta)),
+        ]));
+    }
+
+    let widget = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(ship.name.as_str()),
+        )
+        .wrap(Wrap { trim: true });
+    f.render_widget(widget, area);
+}
+
+fn render_purchase_confirmation(f: &mut Frame, app: &App, area: Rect) {
+    let Some(ship) = app.selected_shipyard_ship() else {
+        return;
+    };
+    let credits = app.agent.as_ref().map_or(0, |agent| agent.credits);
+    let remaining = credits - ship.purchase_price as i64;
+    let remaining_color = if remaining < 0 {
+        Color::Red
+    } else {
+        Color::Green
+    };
+
+    let lines = vec![
+        Line::from(vec![
+            Span::raw("Buy "),
+            Span::styled(&ship.name, Style::default().fg(Color::Blue)),
+            Span::raw(format!(" ({})?", ship.r#type)),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("Price: "),
+            Span::styled(
+                ship.purchase_price.to_string(),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]),
+        Line::from(vec![
+            Span::raw("Credits: "),
+            Span::styled(credits.to_string(), Style::default().fg(Color::Yellow)),
+            Span::raw(" -> "),
+            Span::styled(remaining.to_string(), Style::default().fg(remaining_color)),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y", Style::default().fg(Color::Cyan)),
+            Span::raw(": Confirm  "),
+            Span::styled("n", Style::default().fg(Color::Cyan)),
+            Span::raw(": Cancel"),
+        ]),
+    ];
+
+    let popup_area = centered_rect(50, 40, area);
+    let popup = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Confirm Purchase"),
+        )
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    f.render_widget(Clear, popup_area);
+    f.render_widget(popup, popup_area);
+}
+
+fn render_fitting(f: &mut Frame, app: &mut App, area: Rect) {
+    let plan = app.fitting_plan();
+    let (Some(screen), Some(plan)) = (&mut app.fitting, plan) else {
+        let message = Paragraph::new("No ship selected")
+            .block(Block::default().borders(Borders::ALL).title("Fitting"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(message, area);
+        return;
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+    let left = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(12)])
+        .split(chunks[0]);
+    let focused =
+        |focus: bool| Style::default().fg(if focus { Color::Yellow } else { Color::White });
+
+    let current = app
+        .ships
+        .as_ref()
+        .and_then(|ships| ships.get(app.selected_ship_index?))
+        .map(Loadout::of)
+        .unwrap_or_default();
+    let mut installed = current.clone();
+    let planned: Vec<ListItem> = screen
+        .planned
+        .parts()
+        .map(|symbol| {
+            // Parts not on the ship yet are marked as new
+            let color = if installed.remove(symbol) {
+                Color::Blue
+            } else {
+                Color::Green
+            };
+            ListItem::new(Span::styled(symbol.clone(), Style::default().fg(color)))
+        })
+        .collect();
+    let planned_list = List::new(planned)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(focused(!screen.focus_available))
+                .title("Planned Fit (Enter to remove)"),
+        )
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(planned_list, left[0], &mut screen.planned_state);
+
+    let budget = &plan.budget;
+    let usage = |label: &str, used: i32, available: i32| {
+        let color = if used > available {
+            Color::Red
+        } else {
+            Color::Green
+        };
+        Line::from(vec![
+            Span::raw(format!("{}: ", label)),
+            Span::styled(
+                format!("{}/{}", used, available),
+                Style::default().fg(color),
+            ),
+        ])
+    };
+    let mut lines = vec![
+        usage("Power", budget.power_used, budget.power_output),
+        usage("Crew", budget.crew_required, budget.crew_capacity),
+        usage("Module slots", budget.slots_used, budget.module_slots),
+        usage(
+            "Mounting points",
+            budget.mounts_used,
+            budget.mounting_points,
+        ),
+    ];
+    if let Some(quote) = &plan.quote {
+        let credits = app.agent.as_ref().map_or(0, |agent| agent.credits);
+        lines.push(Line::from(vec![
+            Span::raw("Cost: "),
+            Span::styled(quote.total.to_string(), Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!(
+                    " ({} parts + {} fees, {} credits)",
+                    quote.parts, quote.fees, credits
+                ),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ]));
+    }
+    for change in &plan.changes {
+        lines.push(Line::from(Span::raw(change.to_string())));
+    }
+    for problem in &plan.problems {
+        lines.push(Line::from(Span::styled(
+            problem.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    let summary = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Budget (i to install)"),
+        )
+        .wrap(Wrap { trim: true });
+    f.render_widget(summary, left[1]);
+
+    let available: Vec<ListItem> = screen
+        .available()
+        .into_iter()
+        .map(|(symbol, price)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(symbol, Style::default().fg(Color::Blue)),
+                Span::raw(" - "),
+                Span::styled(price.to_string(), Style::default().fg(Color::Yellow)),
+            ]))
+        })
+        .collect();
+    let title = match (&screen.market, &screen.shipyard) {
+        (_, None) => "For Sale (no shipyard here)",
+        (None, _) => "For Sale (no market here)",
+        _ => "For Sale (Enter to add)",
+    };
+    let available_list = List::new(available)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(focused(screen.focus_available))
+                .title(title),
+        )
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(available_list, chunks[1], &mut screen.available_state);
+}
+
+fn render_waypoint_search(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .split(area);
+
+    let filter = |label: &'static str, value: Option<String>| {
+        vec![
+            Span::raw(label),
+            Span::styled(
+                value.unwrap_or_else(|| String::from("any")),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]
+    };
+    let query = &app.search.query;
+    let ship = app.search_ship();
+    let scope = match ship {
+        Some(ship) if !app.search.all_systems => ship.nav.system_symbol.clone(),
+        _ => String::from("all cached systems"),
+    };
+    let mut spans = filter("Type: ", query.waypoint_type.map(|t| t.to_string()));
+    spans.extend(filter(
+        "  Trait: ",
+        query.traits.first().map(|t| t.to_string()),
+    ));
+    spans.extend(filter(
+        "  Modifier: ",
+        query.modifiers.first().map(|m| m.to_string()),
+    ));
+    spans.extend([
+        Span::raw("  In: "),
+        Span::styled(scope, Style::default().fg(Color::Cyan)),
+        Span::raw("  From: "),
+        Span::styled(
+            ship.map_or("no ship", |ship| ship.symbol.as_str())
+                .to_string(),
+            Style::default().fg(Color::Green),
+        ),
+    ]);
+    let header = Paragraph::new(Line::from(spans))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Waypoint Search"),
+        )
+        .alignment(Alignment::Center);
+    f.render_widget(header, chunks[0]);
+
+    let results = app.search_results();
+    let items: Vec<ListItem> = results
+        .iter()
+        .map(|found| {
+            let waypoint = &found.waypoint;
+            let distance = match (found.distance, found.system_distance) {
+                (Some(distance), _) => format!("{:.0}", distance),
+                (None, Some(distance)) => format!("{:.0} (system)", distance),
+                (None, None) => String::from("?"),
+            };
+            let traits = waypoint
+                .traits
+                .iter()
+                .map(|t| t.symbol.to_string())
+                .chain(
+                    waypoint
+                        .modifiers
+                        .iter()
+                        .flatten()
+                        .map(|m| m.symbol.to_string()),
+                )
+                .collect::<Vec<_>>()
+                .join(", ");
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:<16}", waypoint.symbol),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::styled(
+                    format!(" {:<22}", waypoint.r#type.to_string()),
+                    Style::default().fg(get_waypoint_type_color(&waypoint.r#type.to_string())),
+                ),
+                Span::styled(
+                    format!(" {:>14} ", distance),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::styled(traits, Style::default().fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("{} Matches", results.len())),
+        )
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(list, chunks[1], &mut app.search.results_state);
+}
+
+/// Construction sites in our systems with per-material progress and where
+/// to buy what is still missing.
+fn render_construction(f: &mut Frame, app: &mut App, area: Rect) {
+    let reports = app.construction_reports();
+    if reports.is_empty() {
+        let message = Paragraph::new("Nothing under construction in our systems")
+            .block(Block::default().borders(Borders::ALL).title("Construction"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(message, area);
+        return;
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .split(area);
+
+    let percent = |fulfilled: i32, required: i32| {
+        if required > 0 {
+            fulfilled as f64 / required as f64 * 100.0
+        } else {
+            100.0
+        }
+    };
+    let progress_color = |done: f64| {
+        if done >= 100.0 {
+            Color::Green
+        } else if done >= 50.0 {
+            Color::Yellow
+        } else {
+            Color::Red
+        }
+    };
+    let items: Vec<ListItem> = reports
+        .iter()
+        .map(|report| {
+            let site = &report.construction;
+            let (fulfilled, required) = site
+                .materials
+                .iter()
+                .fold((0, 0), |(f, r), m| (f + m.fulfilled, r + m.required));
+            let done = if site.is_complete {
+                100.0
+            } else {
+                percent(fulfilled, required)
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:<16}", site.symbol),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::styled(
+                    format!(" {:>5.1}%", done),
+                    Style::default().fg(progress_color(done)),
+                ),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Sites"))
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(list, chunks[0], &mut app.construction_state);
+
+    let Some(report) = app
+        .construction_state
+        .selected()
+        .and_then(|i| reports.get(i))
+    else {
+        return;
+    };
+    let mut lines = Vec::new();
+    for material in &report.construction.materials {
+        let done = percent(material.fulfilled, material.required);
+        let source = report
+            .sources
+            .iter()
+            .find(|source| source.good == material.trade_symbol);
+        let sourcing = match source {
+            None => String::new(),
+            Some(source) => match (&source.market, source.price, source.cost) {
+                (Some(market), Some(price), Some(cost)) => {
+                    format!("buy at {} for {} each, {} total", market, price, cost)
+                }
+                (Some(market), _, _) => format!("buy at {}, price unknown", market),
+                _ => String::from("no known seller"),
+            },
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{:<24}", material.trade_symbol.to_string()),
+                Style::default().fg(Color::Blue),
+            ),
+            Span::styled(
+                format!(
+                    "{:>6}/{:<6} {:>5.1}%  ",
+                    material.fulfilled, material.required, done
+                ),
+                Style::default().fg(progress_color(done)),
+            ),
+            Span::styled(sourcing, Style::default().fg(Color::DarkGray)),
+        ]));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::styled(
+            "Known cost to finish: ",
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(
+            report.known_cost().to_string(),
+            Style::default().fg(Color::Yellow),
+        ),
+    ]));
+    let details = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Materials for {}", report.construction.symbol)),
+        )
+        .wrap(Wrap { trim: true });
+    f.render_widget(details, chunks[1]);
+}
 
-            // Render waypoints plot
-            render_waypoints_plot(f, app, waypoints_plot_area, &system.waypoints);
-        }
-    } else {
-        // If systems data is not loaded, display a loading message
-        let message = Paragraph::new("Loading system data...")
-            .block(Block::default().borders(Borders::ALL).title("Loading"))
+/// Our contracts with the evaluator's estimate for each.
+fn render_contracts(f: &mut Frame, app: &mut App, area: Rect) {
+    let contracts = app.contracts.clone().unwrap_or_default();
+    if contracts.is_empty() {
+        let message = Paragraph::new("No contracts, negotiate one at a faction waypoint")
+            .block(Block::default().borders(Borders::ALL).title("Contracts"))
             .wrap(Wrap { trim: true });
         f.render_widget(message, area);
+        return;
     }
-}
 
-fn render_waypoints_plot(
-    f: &mut Frame,
-    app: &App,
-    area: Rect,
-    waypoints: &[spacetraders_api::models::SystemWaypoint],
-) {
-    if waypoints.is_empty() {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(area);
+
+    // Ships already running a behaviour aren't free to take a contract on
+    let ships: Vec<_> = app
+        .ships
+        .iter()
+        .flatten()
+        .filter(|ship| {
+            app.behaviours
+                .get(&ship.symbol)
+                .is_none_or(Behaviour::is_finished)
+        })
+        .cloned()
+        .collect();
+    let now = Utc::now();
+    let verdict_color = |verdict: &str| match verdict {
+        "ACCEPT" => Color::Green,
+        "DECLINE" => Color::Red,
+        _ => Color::Yellow,
+    };
+    let items: Vec<ListItem> = contracts
+        .iter()
+        .map(|c| {
+            let (status, color) = if c.fulfilled {
+                ("FULFILLED", Color::DarkGray)
+            } else if c.accepted {
+                ("ACCEPTED", Color::Green)
+            } else if contract::is_open(c, now) {
+                ("OPEN", Color::Yellow)
+            } else {
+                ("EXPIRED", Color::DarkGray)
+            };
+            let mut spans = vec![
+                Span::styled(format!("{:<26}", c.id), Style::default().fg(Color::Cyan)),
+                Span::styled(format!(" {:<10}", status), Style::default().fg(color)),
+            ];
+            if !c.fulfilled {
+                let estimate = contract::evaluate(&app.cache, &ships, c);
+                spans.push(Span::styled(
+                    format!(" {:+}", estimate.profit()),
+                    Style::default().fg(verdict_color(estimate.verdict())),
+                ));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Contracts"))
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(list, chunks[0], &mut app.contracts_state);
+
+    let Some(selected) = app
+        .contracts_state
+        .selected()
+        .and_then(|i| contracts.get(i))
+    else {
         return;
+    };
+    let estimate = contract::evaluate(&app.cache, &ships, selected);
+    let field = |label: &'static str, value: String| {
+        Line::from(vec![
+            Span::styled(label, Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(value),
+        ])
+    };
+    let payment = &selected.terms.payment;
+    let mut lines = vec![
+        field(
+            "Faction: ",
+            format!("{} ({:?})", selected.faction_symbol, selected.r#type),
+        ),
+        field(
+            "Payment: ",
+            format!(
+                "{} on accepting, {} on fulfilling",
+                payment.on_accepted, payment.on_fulfilled
+            ),
+        ),
+        field("Deadline: ", selected.terms.deadline.clone()),
+        Line::from(""),
+    ];
+    for good in selected.terms.deliver.iter().flatten() {
+        let sourcing = match estimate
+            .deliveries
+            .iter()
+            .find(|d| d.good == good.trade_symbol)
+        {
+            None => String::from("done"),
+            Some(delivery) => match (&delivery.market, delivery.price, delivery.cost) {
+                (Some(market), Some(price), Some(cost)) => {
+                    format!("buy at {} for {} each, {} total", market, price, cost)
+                }
+                (Some(market), _, _) => format!("buy at {}, price unknown", market),
+                _ => String::from("no known seller"),
+            },
+        };
+        lines.push(Line::from(vec![
+            Span::styled(
+                format!("{:<20}", good.trade_symbol),
+                Style::default().fg(Color::Blue),
+            ),
+            Span::raw(format!(
+                "{:>5}/{:<5} to {}  ",
+                good.units_fulfilled, good.units_required, good.destination_symbol
+            )),
+            Span::styled(sourcing, Style::default().fg(Color::DarkGray)),
+        ]));
+    }
+    if !selected.fulfilled {
+        lines.push(Line::from(""));
+        lines.push(field(
+            "Cost: ",
+            format!(
+                "{}{}",
+                estimate.cost,
+                if estimate.priced {
+                    ""
+                } else {
+                    " (some goods unpriced)"
+                }
+            ),
+        ));
+        lines.push(Line::from(vec![
+            Span::styled("Profit: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(
+                format!("{} ({})", estimate.profit(), estimate.verdict()),
+                Style::default().fg(verdict_color(estimate.verdict())),
+            ),
+        ]));
+        lines.push(field(
+            "Travel: ",
+            match (&estimate.ship, estimate.travel_seconds) {
+                (Some(ship), Some(seconds)) => format!(
+                    "{} min with {} in {} trip(s), {}",
+                    seconds / 60,
+                    ship,
+                    estimate.trips,
+                    if estimate.achievable == Some(true) {
+                        "before the deadline"
+                    } else {
+                        "past the deadline"
+                    }
+                ),
+                _ => String::from("no hauler in the destination's system"),
+            },
+        ));
     }
+    let details = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(selected.id.clone()),
+        )
+        .wrap(Wrap { trim: true });
+    f.render_widget(details, chunks[1]);
+}
 
-    // Find the min and max coordinates to set the boundaries
-    let (min_x, max_x, min_y, max_y) = waypoints.iter().fold(
-        (i32::MAX, i32::MIN, i32::MAX, i32::MIN),
-        |(min_x, max_x, min_y, max_y), waypoint| {
-            (
-                min_x.min(waypoint.x),
-                max_x.max(waypoint.x),
-                min_y.min(waypoint.y),
-                max_y.max(waypoint.y),
-            )
-        },
-    );
+/// Every agent in the last synced directory, with the credits of watched
+/// agents over time.
+fn render_agents(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+        .split(area);
+
+    let ours = app.agent.as_ref().map(|agent| agent.symbol.clone());
+    let items: Vec<ListItem> = app
+        .sorted_agents()
+        .iter()
+        .map(|agent| {
+            let watched = app.rivals.is_watched(&agent.symbol);
+            let symbol_style = if Some(&agent.symbol) == ours.as_ref() {
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::Cyan)
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    if watched { "★ " } else { "  " },
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::styled(format!("{:<16}", agent.symbol), symbol_style),
+                Span::styled(
+                    format!(" {:>14}", agent.credits),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::styled(
+                    format!(" {:>4} ships", agent.ship_count),
+                    Style::default().fg(Color::Blue),
+                ),
+                Span::styled(
+                    format!("  {:<12}", agent.headquarters),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    agent.starting_faction.clone(),
+                    Style::default().fg(Color::Magenta),
+                ),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "{} Agents by {} (s sort, w watch, u sync){}",
+            app.rivals.agents.len(),
+            app.agent_sort,
+            app.agents_download
+                .as_ref()
+                .map(|download| format!(" downloading {}", download.describe()))
+                .unwrap_or_default()
+        )))
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(list, chunks[0], &mut app.agents_state);
 
-    // Create the plot block
     let block = Block::default()
         .borders(Borders::ALL)
-        .title("Waypoints Map");
+        .title("Watched Credits over Time");
+    let Some(start) = app.rival_history.first().map(|sample| sample.time) else {
+        let empty = Paragraph::new("Press w on an agent to record their credits")
+            .block(block)
+            .wrap(Wrap { trim: true });
+        f.render_widget(empty, chunks[1]);
+        return;
+    };
+    let hours = |time: DateTime<Utc>| (time - start).num_seconds() as f64 / 3600.0;
+    let now_hours = hours(Utc::now());
+    let palette = [
+        Color::Cyan,
+        Color::Magenta,
+        Color::Yellow,
+        Color::Blue,
+        Color::LightRed,
+        Color::LightGreen,
+    ];
+    let series: Vec<(String, Vec<(f64, f64)>)> = app
+        .rivals
+        .watched
+        .iter()
+        .map(|agent| {
+            let mut points: Vec<(f64, f64)> = app
+                .rival_history
+                .iter()
+                .filter(|sample| sample.agent == *agent)
+                .map(|sample| (hours(sample.time), sample.credits as f64))
+                .collect();
+            // Extend the last sample to now so a quiet rival still shows
+            if let Some(&(_, credits)) = points.last() {
+                points.push((now_hours, credits));
+            }
+            (agent.clone(), points)
+        })
+        .collect();
+    let (min_credits, max_credits) = series
+        .iter()
+        .flat_map(|(_, points)| points.iter().map(|p| p.1))
+        .fold((f64::MAX, f64::MIN), |(min, max), credits| {
+            (min.min(credits), max.max(credits))
+        });
+    if min_credits > max_credits {
+        let empty = Paragraph::new("No samples for watched agents yet")
+            .block(block)
+            .wrap(Wrap { trim: true });
+        f.render_widget(empty, chunks[1]);
+        return;
+    }
+    let padding = ((max_credits - min_credits) * 0.1).max(1.0);
+    let y_bounds = [(min_credits - padding).max(0.0), max_credits + padding];
+    let datasets: Vec<Dataset> = series
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, points))| !points.is_empty())
+        .map(|(i, (agent, points))| {
+            Dataset::default()
+                .name(agent.clone())
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(palette[i % palette.len()]))
+                .data(points)
+        })
+        .collect();
+    let chart = Chart::new(datasets)
+        .block(block)
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds([0.0, now_hours.max(1.0 / 60.0)])
+                .labels(vec![
+                    Span::raw(
+                        start
+                            .with_timezone(&chrono::Local)
+                            .format("%m-%d %H:%M")
+                            .to_string(),
+                    ),
+                    Span::raw("now"),
+                ]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::DarkGray))
+                .bounds(y_bounds)
+                .labels(vec![
+                    Span::raw(format!("{:.0}", y_bounds[0])),
+                    Span::raw(format!("{:.0}", y_bounds[1])),
+                ]),
+        );
+    f.render_widget(chart, chunks[1]);
+}
 
-    let inner_area = block.inner(area);
-    f.render_widget(block, area);
+/// Factions with their traits, headquarters and whether they recruit.
+fn render_factions(f: &mut Frame, app: &mut App, area: Rect) {
+    let Some(factions) = &app.factions else {
+        let loading = Paragraph::new("Loading factions...")
+            .block(Block::default().borders(Borders::ALL).title("Factions"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(loading, area);
+        return;
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .split(area);
 
-    let plot_width = inner_area.width as usize;
-    let plot_height = inner_area.height as usize;
+    let items: Vec<ListItem> = factions
+        .iter()
+        .map(|faction| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:<10}", faction.symbol.to_string()),
+                    Style::default().fg(Color::Magenta),
+                ),
+                if faction.is_recruiting {
+                    Span::styled(" recruiting", Style::default().fg(Color::Green))
+                } else {
+                    Span::styled(" closed", Style::default().fg(Color::DarkGray))
+                },
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Factions"))
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol(">> ");
+    let selected = app
+        .factions_state
+        .selected()
+        .and_then(|i| factions.get(i))
+        .cloned();
+    f.render_stateful_widget(list, chunks[0], &mut app.factions_state);
+
+    let Some(faction) = selected else {
+        return;
+    };
+    let mut lines = vec![
+        Line::from(Span::styled(
+            faction.name.clone(),
+            Style::default()
+                .fg(Color::Magenta)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(faction.description.clone()),
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("HQ: "),
+            Span::styled(
+                faction
+                    .headquarters
+                    .clone()
+                    .unwrap_or_else(|| String::from("none")),
+                Style::default().fg(Color::Cyan),
+            ),
+        ]),
+        Line::from(vec![
+            Span::raw("Recruiting: "),
+            if faction.is_recruiting {
+                Span::styled("yes", Style::default().fg(Color::Green))
+            } else {
+                Span::styled("no", Style::default().fg(Color::Red))
+            },
+        ]),
+        Line::from(""),
+    ];
+    for faction_trait in &faction.traits {
+        lines.push(Line::from(Span::styled(
+            faction_trait.name.clone(),
+            Style::default().fg(Color::Yellow),
+        )));
+        lines.push(Line::from(Span::styled(
+            faction_trait.description.clone(),
+            Style::default().fg(Color::DarkGray),
+        )));
+    }
+    let details = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Enter: show on galaxy map"),
+        )
+        .wrap(Wrap { trim: true });
+    f.render_widget(details, chunks[1]);
+}
 
-    // Add buffer space for plotting points
-    let x_range = (max_x - min_x + 1).max(2) as f64; // Ensure range is at least 2
-    let y_range = (max_y - min_y + 1).max(2) as f64; // Ensure range is at least 2
+/// Other agents' ships our scans have seen in the selected ship's system.
+fn render_nearby_ships(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
 
-    // Create a canvas to plot points on
-    let mut canvas = vec![vec![(' ', Color::Reset); plot_width]; plot_height];
+    let now = Utc::now();
+    let scanner = app.selected_ship();
+    let cooldown = match scanner.and_then(|ship| scan::cooldown_remaining(ship, now)) {
+        Some(remaining) => format!("cooldown {}s", remaining),
+        None => String::from("ready, n to scan again"),
+    };
+    let system = scanner
+        .map(|ship| ship.nav.system_symbol.clone())
+        .unwrap_or_default();
+    let sightings = app.nearby_ships();
+    let items: Vec<ListItem> = sightings
+        .iter()
+        .map(|sighting| {
+            let scanned = &sighting.ship;
+            let age = (now - sighting.time).num_minutes();
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:<20}", scanned.symbol),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::styled(
+                    format!(" {:<12}", scanned.registration.role.to_string()),
+                    Style::default()
+                        .fg(get_ship_role_color(&scanned.registration.role.to_string())),
+                ),
+                Span::styled(
+                    format!(" {:<14}", scanned.nav.waypoint_symbol),
+                    Style::default().fg(Color::Yellow),
+                ),
+                Span::styled(
+                    format!(" {}m ago", age),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+        })
+        .collect();
+    let selected = app
+        .sightings_state
+        .selected()
+        .and_then(|i| sightings.get(i))
+        .map(|sighting| (*sighting).clone());
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Ships seen in {} ({})", system, cooldown)),
+        )
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(list, chunks[0], &mut app.sightings_state);
 
-    // Plot the star at center (0,0)
-    let star_x = ((0 - min_x) as f64 / x_range * (plot_width as f64 - 1.0)) as usize;
-    let star_y = ((max_y - 0) as f64 / y_range * (plot_height as f64 - 1.0)) as usize;
+    let Some(sighting) = selected else {
+        return;
+    };
+    let scanned = &sighting.ship;
+    let field = |label: &'static str, value: String| {
+        Line::from(vec![
+            Span::styled(label, Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(value),
+        ])
+    };
+    let part = |symbol: Option<&String>| symbol.cloned().unwrap_or_else(|| String::from("unknown"));
+    let mut lines = vec![
+        field("Agent: ", scanned.registration.name.clone()),
+        field("Faction: ", scanned.registration.faction_symbol.clone()),
+        field("Role: ", scanned.registration.role.to_string()),
+        Line::from(""),
+        field(
+            "Nav: ",
+            format!("{} at {}", scanned.nav.status, scanned.nav.waypoint_symbol),
+        ),
+        field(
+            "Route: ",
+            format!(
+                "{} → {}, arriving {}",
+                scanned.nav.route.origin.symbol,
+                scanned.nav.route.destination.symbol,
+                scanned.nav.route.arrival
+            ),
+        ),
+        field("Flight mode: ", scanned.nav.flight_mode.to_string()),
+        Line::from(""),
+        field(
+            "Frame: ",
+            part(scanned.frame.as_ref().map(|frame| &frame.symbol)),
+        ),
+        field(
+            "Reactor: ",
+            part(scanned.reactor.as_ref().map(|reactor| &reactor.symbol)),
+        ),
+        field("Engine: ", scanned.engine.symbol.clone()),
+        field(
+            "Mounts: ",
+            match &scanned.mounts {
+                Some(mounts) if !mounts.is_empty() => mounts
+                    .iter()
+                    .map(|mount| mount.symbol.clone())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                Some(_) => String::from("none"),
+                None => String::from("unknown"),
+            },
+        ),
+        Line::from(""),
+    ];
+    lines.push(Line::from(Span::styled(
+        format!(
+            "Seen by {} at {}",
+            sighting.seen_by,
+            sighting
+                .time
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+        ),
+        Style::default().fg(Color::DarkGray),
+    )));
+    let details = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(scanned.symbol.clone()),
+        )
+        .wrap(Wrap { trim: true });
+    f.render_widget(details, chunks[1]);
+}
 
-    if star_x < plot_width && star_y < plot_height {
-        canvas[star_y][star_x] = ('★', Color::Yellow);
-    }
+fn render_fitting_confirmation(f: &mut Frame, app: &App, area: Rect) {
+    let Some(plan) = app.fitting_plan() else {
+        return;
+    };
+    let credits = app.agent.as_ref().map_or(0, |agent| agent.credits);
+    let total = plan.quote.as_ref().map_or(0, |quote| quote.total);
+    let remaining = credits - total;
+    let remaining_color = if remaining < 0 {
+        Color::Red
+    } else {
+        Color::Green
+    };
 
-    // Plot waypoints
-    for waypoint in waypoints {
-        // Map waypoint coordinates to plot coordinates
-        let plot_x = ((waypoint.x - min_x) as f64 / x_range * (plot_width as f64 - 1.0)) as usize;
-        let plot_y = ((max_y - waypoint.y) as f64 / y_range * (plot_height as f64 - 1.0)) as usize;
+    let mut lines: Vec<Line> = plan
+        .changes
+        .iter()
+        .map(|change| Line::from(change.to_string()))
+        .collect();
+    lines.extend([
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("Credits: "),
+            Span::styled(credits.to_string(), Style::default().fg(Color::Yellow)),
+            Span::raw(" -> "),
+            Span::styled(remaining.to_string(), Style::default().fg(remaining_color)),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y", Style::default().fg(Color::Cyan)),
+            Span::raw(": Confirm  "),
+            Span::styled("n", Style::default().fg(Color::Cyan)),
+            Span::raw(": Cancel"),
+        ]),
+    ]);
 
-        if plot_x < plot_width && plot_y < plot_height {
-            let waypoint_type = waypoint.r#type.to_string();
-            let waypoint_char = match waypoint_type.as_str() {
-                "PLANET" => 'P',
-                "GAS_GIANT" => 'G',
-                "MOON" => 'm',
-                "ORBITAL_STATION" => 'S',
-                "JUMP_GATE" => 'J',
-                "ASTEROID_FIELD" => '∗',
-                "NEBULA" => '≈',
-                "DEBRIS_FIELD" => '⦿',
-                _ => '•',
-            };
+    let popup_area = centered_rect(50, 40, area);
+    let popup = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Confirm Refit"),
+        )
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+    f.render_widget(Clear, popup_area);
+    f.render_widget(popup, popup_area);
+}
 
-            let waypoint_color = match waypoint_type.as_str() {
-                "PLANET" => Color::Green,
-                "GAS_GIANT" => Color::LightRed,
-                "MOON" => Color::White,
-                "ORBITAL_STATION" => Color::Blue,
-                "JUMP_GATE" => Color::Magenta,
-                "ASTEROID_FIELD" => Color::Yellow,
-                "NEBULA" => Color::LightMagenta,
-                "DEBRIS_FIELD" => Color::DarkGray,
-                _ => Color::Gray,
-            };
+/// Returns a rectangle of the given percentage size centered inside `area`.
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
 
-            canvas[plot_y][plot_x] = (waypoint_char, waypoint_color);
-        }
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+/// Steps a filter through `values`, wrapping back round to no filter.
+fn next_filter<T: Clone + PartialEq>(values: &[T], current: Option<T>) -> Option<T> {
+    match current {
+        None => values.first().cloned(),
+        Some(value) => values
+            .iter()
+            .position(|v| *v == value)
+            .and_then(|i| values.get(i + 1).cloned()),
     }
+}
+
+fn render_galaxy(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(75), Constraint::Percentage(25)])
+        .split(area);
 
-    // Highlight selected waypoint
-    if let Some(selected_idx) = app.waypoints_state.selected() {
-        if selected_idx < waypoints.len() {
-            let waypoint = &waypoints[selected_idx];
-            let plot_x =
-                ((waypoint.x - min_x) as f64 / x_range * (plot_width as f64 - 1.0)) as usize;
-            let plot_y =
-                ((max_y - waypoint.y) as f64 / y_range * (plot_height as f64 - 1.0)) as usize;
+    let systems = app.galaxy_systems();
+    let ((cx, cy), half_width) = app.galaxy_viewport();
+
+    let title = format!(
+        "Galaxy Map ({} systems | type: {} | faction: {} | zoom: {}x)",
+        systems.len(),
+        app.galaxy
+            .type_filter
+            .map_or(String::from("all"), |t| t.to_string()),
+        app.galaxy
+            .faction_filter
+            .map_or(String::from("all"), |f| f.to_string()),
+        app.galaxy.zoom
+    );
+    let title = match &app.galaxy_download {
+        Some(download) => format!("{} downloading {}", title, download.describe()),
+        None => title,
+    };
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(chunks[0]);
+
+    // Terminal cells are about twice as tall as they are wide
+    let half_height = half_width * (inner.height as f64 * 2.0) / (inner.width.max(1) as f64);
+    let x_bounds = [cx - half_width, cx + half_width];
+    let y_bounds = [cy - half_height, cy + half_height];
+    let in_view = |x: f64, y: f64| {
+        x >= x_bounds[0] && x <= x_bounds[1] && y >= y_bounds[0] && y <= y_bounds[1]
+    };
 
-            if plot_x < plot_width && plot_y < plot_height {
-                // Keep the waypoint type character but make it bright white and boldface
-                let waypoint_type = waypoint.r#type.to_string();
-                let waypoint_char = match waypoint_type.as_str() {
-                    "PLANET" => 'P',
-                    "GAS_GIANT" => 'G',
-                    "MOON" => 'm',
-                    "ORBITAL_STATION" => 'S',
-                    "JUMP_GATE" => 'J',
-                    "ASTEROID_FIELD" => '∗',
-                    "NEBULA" => '≈',
-                    "DEBRIS_FIELD" => '⦿',
-                    _ => '•',
-                };
-                canvas[plot_y][plot_x] = (waypoint_char, Color::White);
-            }
+    // Group points by colour so each system type is drawn in one pass
+    let mut points: Vec<(Color, Vec<(f64, f64)>)> = Vec::new();
+    for system in &systems {
+        let color = get_system_type_color(&system.r#type.to_string());
+        let coords = (system.x as f64, system.y as f64);
+        match points.iter_mut().find(|(c, _)| *c == color) {
+            Some((_, group)) => group.push(coords),
+            None => points.push((color, vec![coords])),
         }
     }
 
-    // Convert canvas to text
-    let mut lines = Vec::new();
-    for row in canvas {
-        let mut spans = Vec::new();
-        for (ch, color) in row {
-            spans.push(Span::styled(ch.to_string(), Style::default().fg(color)));
-        }
-        lines.push(Line::from(spans));
-    }
+    let edges: Vec<((f64, f64), (f64, f64))> = app
+        .cache
+        .jump_gate_edges()
+        .into_iter()
+        .filter_map(|(from, to)| {
+            let from = systems.iter().find(|s| s.symbol == from)?;
+            let to = systems.iter().find(|s| s.symbol == to)?;
+            Some(((from.x as f64, from.y as f64), (to.x as f64, to.y as f64)))
+        })
+        .collect();
 
-    let text = Text::from(lines);
-    let paragraph = Paragraph::new(text);
-    f.render_widget(paragraph, inner_area);
-}
+    let ship_systems: Vec<(f64, f64)> = app
+        .ship_systems()
+        .iter()
+        .filter_map(|symbol| app.cache.system(symbol))
+        .map(|system| (system.x as f64, system.y as f64))
+        .collect();
 
-fn render_waypoint_detail(f: &mut Frame, app: &mut App, area: Rect) {
-    let waypoint_index = match app.selected_waypoint_index {
-        Some(index) => index,
-        None => {
-            // If no waypoint is selected, display an error message
-            let message = Paragraph::new("No waypoint selected")
-                .block(Block::default().borders(Borders::ALL).title("Error"))
-                .wrap(Wrap { trim: true });
-            f.render_widget(message, area);
-            return;
+    // Only label systems once zoomed in far enough for the names to fit
+    let labels: Vec<(f64, f64, String)> = {
+        let visible: Vec<_> = systems
+            .iter()
+            .filter(|s| in_view(s.x as f64, s.y as f64))
+            .collect();
+        if visible.len() <= 30 {
+            visible
+                .iter()
+                .map(|s| (s.x as f64, s.y as f64, s.symbol.clone()))
+                .collect()
+        } else {
+            Vec::new()
         }
     };
 
-    if let Some(systems) = &app.systems {
-        if let Some(system_index) = app.selected_system_index {
-            if system_index < systems.len() {
-                let system = &systems[system_index];
-                if waypoint_index >= system.waypoints.len() {
-                    // If the selected index is out of bounds, display an error message
-                    let message = Paragraph::new("Invalid waypoint selection")
-                        .block(Block::default().borders(Borders::ALL).title("Error"))
-                        .wrap(Wrap { trim: true });
-                    f.render_widget(message, area);
-                    return;
-                }
+    let canvas = Canvas::default()
+        .block(block)
+        .marker(Marker::Braille)
+        .x_bounds(x_bounds)
+        .y_bounds(y_bounds)
+        .paint(|ctx| {
+            for ((x1, y1), (x2, y2)) in &edges {
+                ctx.draw(&CanvasLine::new(*x1, *y1, *x2, *y2, Color::DarkGray));
+            }
+            for (color, coords) in &points {
+                ctx.draw(&Points {
+                    coords,
+                    color: *color,
+                });
+            }
+            ctx.layer();
+            for (x, y, symbol) in &labels {
+                ctx.print(
+                    *x,
+                    *y,
+                    Span::styled(format!(" {}", symbol), Style::default().fg(Color::Gray)),
+                );
+            }
+            for (x, y) in &ship_systems {
+                ctx.print(
+                    *x,
+                    *y,
+                    Span::styled("▲", Style::default().fg(Color::LightGreen)),
+                );
+            }
+            ctx.print(cx, cy, Span::styled("+", Style::default().fg(Color::White)));
+        });
+    f.render_widget(canvas, chunks[0]);
 
-                let waypoint = &system.waypoints[waypoint_index];
-                let waypoint_type = waypoint.r#type.to_string();
-                let waypoint_color = match waypoint_type.as_str() {
-                    "PLANET" => Color::Green,
-                    "GAS_GIANT" => Color::LightRed,
-                    "MOON" => Color::White,
-                    "ORBITAL_STATION" => Color::Blue,
-                    "JUMP_GATE" => Color::Magenta,
-                    "ASTEROID_FIELD" => Color::Yellow,
-                    "NEBULA" => Color::LightMagenta,
-                    "DEBRIS_FIELD" => Color::DarkGray,
-                    _ => Color::Gray,
-                };
+    // Details of the system under the cursor
+    let info_block = Block::default()
+        .borders(Borders::ALL)
+        .title("Cursor (Enter to open)");
+    let info = match app.galaxy_cursor_system() {
+        Some(system) => {
+            let system_type = system.r#type.to_string();
+            let ships_here = app
+                .ships
+                .iter()
+                .flatten()
+                .filter(|ship| ship.nav.system_symbol == system.symbol)
+                .count();
+            let gates: usize = app
+                .cache
+                .jump_gates
+                .iter()
+                .filter(|(gate, _)| cache::system_symbol(gate) == system.symbol)
+                .map(|(_, connections)| connections.len())
+                .sum();
+            vec![
+                Line::from(Span::styled(
+                    &system.symbol,
+                    Style::default()
+                        .fg(Color::Green)
+                        .add_modifier(Modifier::BOLD),
+                )),
+                Line::from(vec![
+                    Span::styled("Type: ", Style::default().add_modifier(Modifier::BOLD)),
+                    Span::styled(
+                        system_type.clone(),
+                        Style::default().fg(get_system_type_color(&system_type)),
+                    ),
+                ]),
+                Line::from(vec![
+                    Span::styled("Position: ", Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(format!("X: {}, Y: {}", system.x, system.y)),
+                ]),
+                Line::from(vec![
+                    Span::styled("Factions: ", Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(
+                        system
+                            .factions
+                            .iter()
+                            .map(|f| f.symbol.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ),
+                ]),
+                Line::from(vec![
+                    Span::styled("Waypoints: ", Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(system.waypoints.len().to_string()),
+                ]),
+                Line::from(vec![
+                    Span::styled(
+                        "Gate links: ",
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(gates.to_string()),
+                ]),
+                Line::from(vec![
+                    Span::styled("Our ships: ", Style::default().add_modifier(Modifier::BOLD)),
+                    Span::styled(
+                        ships_here.to_string(),
+                        Style::default().fg(Color::LightGreen),
+                    ),
+                ]),
+            ]
+        }
+        None => vec![Line::from("No systems cached. Press u to sync the galaxy.")],
+    };
+    let info_widget = Paragraph::new(info)
+        .block(info_block)
+        .wrap(Wrap { trim: true });
+    f.render_widget(info_widget, chunks[1]);
+}
 
-                // Split the screen into sections
-                let chunks = Layout::default()
-                    .direction(Direction::Vertical)
-                    .constraints([
-                        Constraint::Length(3), // Header
-                        Constraint::Length(8), // Basic info
-                        Constraint::Min(0),    // Traits and other details
-                    ])
-                    .split(area);
+fn get_log_level_color(level: Level) -> Color {
+    match level {
+        Level::Error => Color::Red,
+        Level::Warn => Color::Yellow,
+        Level::Info => Color::Green,
+        Level::Debug => Color::Blue,
+        Level::Trace => Color::DarkGray,
+    }
+}
 
-                // Render header
-                let header = Paragraph::new(vec![
-                    Line::from(vec![Span::styled(
-                        &waypoint.symbol,
-                        Style::default()
-                            .fg(Color::Cyan)
-                            .add_modifier(Modifier::BOLD),
-                    )]),
-                    Line::from(vec![Span::styled(
-                        format!("System: {}", system.symbol),
-                        Style::default().fg(Color::Gray),
-                    )]),
-                ])
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title("Waypoint Detail"),
-                )
-                .alignment(Alignment::Center);
-                f.render_widget(header, chunks[0]);
+fn render_event_log(f: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title(format!(
+        "Event Log ({} and above, v to change)",
+        app.log_level
+    ));
+    let visible_rows = block.inner(area).height as usize;
 
-                // Render basic info
-                let basic_info = vec![
-                    Line::from(vec![
-                        Span::styled("Type: ", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::styled(&waypoint_type, Style::default().fg(waypoint_color)),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Position: ", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(format!("X: {}, Y: {}", waypoint.x, waypoint.y)),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Orbits: ", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(match &waypoint.orbits {
-                            Some(orbits) => orbits,
-                            None => "None",
-                        }),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Orbitals: ", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(format!("{} objects", waypoint.orbitals.len())),
-                    ]),
-                ];
+    // Show the newest events that fit, oldest at the top
+    let events = events::recent(app.log_level);
+    let lines: Vec<Line> = events
+        .iter()
+        .skip(events.len().saturating_sub(visible_rows))
+        .map(|event| {
+            Line::from(vec![
+                Span::styled(
+                    event.time.format("%H:%M:%S ").to_string(),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!("{:<5} ", event.level),
+                    Style::default().fg(get_log_level_color(event.level)),
+                ),
+                Span::styled(
+                    format!("[{}] ", event.kind),
+                    Style::default().fg(Color::Cyan),
+                ),
+                Span::raw(event.message.clone()),
+            ])
+        })
+        .collect();
 
-                let basic_info_widget = Paragraph::new(basic_info)
-                    .block(
-                        Block::default()
-                            .borders(Borders::ALL)
-                            .title("Waypoint Information"),
-                    )
-                    .wrap(Wrap { trim: true });
-                f.render_widget(basic_info_widget, chunks[1]);
+    let log = Paragraph::new(lines).block(block);
+    f.render_widget(log, area);
+}
 
-                // Split bottom area for orbitals and system details (top/bottom)
-                let bottom_chunks = Layout::default()
-                    .direction(Direction::Vertical)
-                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-                    .split(chunks[2]);
+fn render_palette(f: &mut Frame, app: &mut App, area: Rect) {
+    let Some(palette) = &mut app.palette else {
+        return;
+    };
 
-                // Render orbitals on top
-                let orbitals_info = if waypoint.orbitals.is_empty() {
-                    vec![Line::from("No orbital bodies")]
-                } else {
-                    waypoint
-                        .orbitals
-                        .iter()
-                        .map(|orbital| {
-                            Line::from(vec![
-                                Span::styled("• ", Style::default().fg(Color::Blue)),
-                                Span::styled(
-                                    &orbital.symbol,
-                                    Style::default()
-                                        .fg(Color::Cyan)
-                                        .add_modifier(Modifier::BOLD),
-                                ),
-                            ])
-                        })
-                        .collect()
-                };
+    let popup_area = centered_rect(60, 60, area);
+    f.render_widget(Clear, popup_area);
 
-                let orbitals_widget = Paragraph::new(orbitals_info)
-                    .block(
-                        Block::default()
-                            .borders(Borders::ALL)
-                            .title("Orbital Bodies"),
-                    )
-                    .wrap(Wrap { trim: true });
-                f.render_widget(orbitals_widget, bottom_chunks[0]);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)])
+        .split(popup_area);
 
-                // Render system information below orbitals
-                let system_info = vec![
-                    Line::from(vec![
-                        Span::styled("System: ", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::styled(system.symbol.clone(), Style::default().fg(Color::Green)),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Type: ", Style::default().add_modifier(Modifier::BOLD)),
-                        {
-                            let system_type_str = system.r#type.to_string();
-                            let type_color = get_system_type_color(&system_type_str);
-                            Span::styled(system_type_str, Style::default().fg(type_color))
-                        },
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Position: ", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(format!("X: {}, Y: {}", system.x, system.y)),
-                    ]),
-                    Line::from(vec![
-                        Span::styled("Waypoints: ", Style::default().add_modifier(Modifier::BOLD)),
-                        Span::raw(format!("{} total", system.waypoints.len())),
-                    ]),
-                ];
+    // Render the query line with a visible cursor
+    let input_block = Block::default()
+        .borders(Borders::ALL)
+        .title("Find ships, systems, waypoints, traits, goods or run a command");
+    let input_width = input_block.inner(chunks[0]).width.max(1) as usize;
+    let scroll = palette.input.visual_scroll(input_width - 1);
+    let input = Paragraph::new(palette.input.value())
+        .scroll((0, scroll as u16))
+        .block(input_block);
+    f.render_widget(input, chunks[0]);
+    f.set_cursor(
+        chunks[0].x + 1 + (palette.input.visual_cursor().saturating_sub(scroll)) as u16,
+        chunks[0].y + 1,
+    );
 
-                let system_widget = Paragraph::new(system_info)
-                    .block(
-                        Block::default()
-                            .borders(Borders::ALL)
-                            .title("System Information"),
-                    )
-                    .wrap(Wrap { trim: true });
-                f.render_widget(system_widget, bottom_chunks[1]);
-            }
-        }
-    } else {
-        // If systems data is not loaded, display a loading message
-        let message = Paragraph::new("Loading system data...")
-            .block(Block::default().borders(Borders::ALL).title("Loading"))
+    let results_block = Block::default().borders(Borders::ALL).title(
+        palette
+            .hint
+            .clone()
+            .unwrap_or_else(|| String::from("Matches")),
+    );
+    if palette.items.is_empty() {
+        let message = if palette.input.value().trim().is_empty() {
+            "Type to search"
+        } else {
+            "No matches"
+        };
+        let empty = Paragraph::new(message)
+            .block(results_block)
             .wrap(Wrap { trim: true });
-        f.render_widget(message, area);
+        f.render_widget(empty, chunks[1]);
+        return;
     }
+
+    let items: Vec<ListItem> = palette
+        .items
+        .iter()
+        .map(|item| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:<9}", item.kind),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(&item.label, Style::default().fg(Color::Cyan)),
+            ]))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(results_block)
+        .highlight_style(Style::default().bg(Color::DarkGray))
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(list, chunks[1], &mut palette.state);
 }
@@ -0,0 +1,69 @@
+use spacetraders_api::models::{Market, Ship};
+use std::collections::BTreeMap;
+
+use crate::cache::{self, UniverseCache};
+use crate::navigation::position;
+
+/// Credits that change hands at a market per round of trades, from its last
+/// known prices: every good's trade volume at its purchase price.
+fn trade_value(market: &Market) -> Option<f64> {
+    let goods = market.trade_goods.as_ref()?;
+    Some(
+        goods
+            .iter()
+            .map(|good| good.trade_volume as f64 * good.purchase_price as f64)
+            .sum(),
+    )
+}
+
+fn listed_goods(market: &Market) -> usize {
+    market.exports.len() + market.imports.len() + market.exchange.len()
+}
+
+/// How much fresh prices at each cached market are worth. Markets nobody
+/// has seen prices at are valued at the average per good of those we have.
+pub fn market_values(cache: &UniverseCache) -> BTreeMap<String, f64> {
+    let (total, goods) = cache
+        .markets
+        .values()
+        .filter_map(|market| Some((trade_value(market)?, listed_goods(market))))
+        .fold((0.0, 0), |(total, goods), (value, count)| {
+            (total + value, goods + count)
+        });
+    let per_good = if goods > 0 { total / goods as f64 } else { 1.0 };
+    cache
+        .markets
+        .values()
+        .map(|market| {
+            let value =
+                trade_value(market).unwrap_or_else(|| per_good * listed_goods(market) as f64);
+            (market.symbol.clone(), value)
+        })
+        .collect()
+}
+
+/// Parks each probe at a market in its own system, filling the most
+/// valuable markets first and sending the closest free probe to each.
+/// Returns the waypoint chosen for every probe that has one.
+pub fn plan_stations(probes: &[&Ship], cache: &UniverseCache) -> BTreeMap<String, String> {
+    let mut markets: Vec<(String, f64)> = market_values(cache).into_iter().collect();
+    markets.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut stations = BTreeMap::new();
+    for (waypoint, _) in markets {
+        let system = cache::system_symbol(&waypoint);
+        let target = position(cache, &waypoint).unwrap_or_default();
+        let closest = probes
+            .iter()
+            .filter(|probe| probe.nav.system_symbol == system)
+            .filter(|probe| !stations.contains_key(&probe.symbol))
+            .min_by_key(|probe| {
+                let at = &probe.nav.route.destination;
+                (at.x - target.0).pow(2) + (at.y - target.1).pow(2)
+            });
+        if let Some(probe) = closest {
+            stations.insert(probe.symbol.clone(), waypoint);
+        }
+    }
+    stations
+}